@@ -0,0 +1,81 @@
+//! 统计与查询性能基准
+//!
+//! 构造一个包含 100 万条系统指标的数据库，验证 `get_statistics` 在聚合查询下
+//! 远低于 1 秒返回。运行: `cargo bench --bench statistics`
+
+use blackbox::{BlackBox, establish_connection_with_url};
+use criterion::{Criterion, criterion_group, criterion_main};
+use diesel::connection::SimpleConnection;
+use std::time::Duration;
+
+const SERVER_COUNT: i64 = 20;
+const METRICS_PER_SERVER: i64 = 50_000;
+const PROCESSES_PER_SERVER: i64 = 200;
+const TRENDS_PER_PROCESS: i64 = 10;
+const CRASHES_PER_SERVER: i64 = 100;
+
+/// 用递归 CTE 批量生成数据，避免逐行插入
+fn populate(db_path: &str) {
+    let mut conn = establish_connection_with_url(Some(db_path)).expect("打开基准数据库失败");
+    conn.batch_execute(&format!(
+        "BEGIN;
+         WITH RECURSIVE s(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM s WHERE n < {servers})
+         INSERT INTO servers (server_id, server_name, server_ip, server_os, server_status)
+         SELECT 'srv-' || n, 'server-' || n, '10.0.0.' || n, 'Linux', 'online' FROM s;
+
+         WITH RECURSIVE m(n) AS (SELECT 0 UNION ALL SELECT n + 1 FROM m WHERE n < {metrics} - 1)
+         INSERT INTO system_metrics (server_id, timestamp, cpu_usage, memory_usage, disk_usage,
+                                     io_read, io_write, network_in, network_out)
+         SELECT 'srv-' || (n % {servers} + 1), 1700000000000 + n, 10.0, 20.0, 30.0, 1.0, 1.0, 1.0, 1.0 FROM m;
+
+         WITH RECURSIVE p(n) AS (SELECT 0 UNION ALL SELECT n + 1 FROM p WHERE n < {processes} - 1)
+         INSERT INTO processes (server_id, pid, name, user_name, status)
+         SELECT 'srv-' || (n % {servers} + 1), n, 'proc-' || n, 'root', 'S' FROM p;
+
+         WITH RECURSIVE t(n) AS (SELECT 0 UNION ALL SELECT n + 1 FROM t WHERE n < {trends} - 1)
+         INSERT INTO process_trends (server_id, pid, timestamp, cpu_usage, memory_usage, thread_count)
+         SELECT 'srv-' || ((n / {trends_per_process}) % {servers} + 1), n / {trends_per_process},
+                1700000000000 + n, 1.0, 1.0, 4 FROM t;
+
+         WITH RECURSIVE c(n) AS (SELECT 0 UNION ALL SELECT n + 1 FROM c WHERE n < {crashes} - 1)
+         INSERT INTO crash_logs (server_id, log_id, timestamp, crash_type, severity, title, message, resolved)
+         SELECT 'srv-' || (n % {servers} + 1), n, 1700000000000 + n, 'kernel', 'high', 'oops', 'oops', 0 FROM c;
+
+         INSERT INTO ai_recommendations (crash_log_id, priority, action, command)
+         SELECT id, 1, 'restart', 'systemctl restart' FROM crash_logs;
+         COMMIT;",
+        servers = SERVER_COUNT,
+        metrics = SERVER_COUNT * METRICS_PER_SERVER,
+        processes = SERVER_COUNT * PROCESSES_PER_SERVER,
+        trends = SERVER_COUNT * PROCESSES_PER_SERVER * TRENDS_PER_PROCESS,
+        trends_per_process = TRENDS_PER_PROCESS,
+        crashes = SERVER_COUNT * CRASHES_PER_SERVER,
+    ))
+    .expect("生成基准数据失败");
+}
+
+fn bench_statistics(c: &mut Criterion) {
+    let dir = tempfile::tempdir().expect("创建临时目录失败");
+    let db_path = dir.path().join("bench.db").to_string_lossy().into_owned();
+
+    let blackbox = BlackBox::new(Some(db_path.clone()));
+    blackbox.init_database(true).expect("初始化数据库失败");
+    populate(&db_path);
+
+    let mut group = c.benchmark_group("million_metrics");
+    group
+        .sample_size(10)
+        .measurement_time(Duration::from_secs(10));
+
+    group.bench_function("get_statistics", |b| {
+        b.iter(|| blackbox.get_statistics().expect("统计失败"))
+    });
+    group.bench_function("query_servers", |b| {
+        b.iter(|| blackbox.query_servers(None, Some(5)).expect("查询失败"))
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_statistics);
+criterion_main!(benches);
@@ -0,0 +1,589 @@
+//! 崩溃日志分析
+//!
+//! [`Analyzer`] 根据崩溃日志和所在服务器的上下文生成摘要、分析和处理建议：
+//!
+//! - [`RuleAnalyzer`]：内置规则，按崩溃类型和日志中的关键字给出建议，智能插入检测到线程数异常时使用
+//! - [`CommandAnalyzer`]：把崩溃日志和上下文以 JSON 写入外部命令的标准输入，从标准输出读取结果，
+//!   用于接入 LLM 等外部服务 (配置文件的 `[analyzer]`)
+//!
+//! 由 dmesg 生成的内核异常日志先以占位文本 [`PENDING_TEXT`] 写入，
+//! 之后由 `blackbox crash analyze --pending` 补全。
+
+use crate::error::{BlackBoxError, Result};
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::config::AnalyzerConfig;
+use crate::domain::*;
+use crate::models::*;
+use crate::store::Store;
+
+/// 等待分析的崩溃日志中摘要、分析和消息的占位文本
+pub const PENDING_TEXT: &str = "正在等待 AI 生成";
+
+/// 上下文中包含的最近系统指标条数
+const CONTEXT_METRICS: i64 = 10;
+
+/// 崩溃日志所在服务器的上下文
+#[derive(Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalysisContext {
+    pub server: Option<Server>,
+    /// 最近的系统指标，按时间戳倒序
+    pub recent_metrics: Vec<SystemMetric>,
+    pub processes: Vec<Process>,
+}
+
+impl AnalysisContext {
+    pub fn load<S: Store + ?Sized>(store: &mut S, server_id: &str) -> Result<Self> {
+        Ok(Self {
+            server: store.get_server_by_id(server_id)?,
+            recent_metrics: store.get_metrics_by_server(server_id, Some(CONTEXT_METRICS))?,
+            processes: store.get_processes_by_server(server_id)?,
+        })
+    }
+}
+
+/// 分析结果，建议的 `crash_log_id` 为被分析的崩溃日志
+#[derive(Serialize, Debug)]
+pub struct Analysis {
+    pub summary: String,
+    /// Markdown 格式的详细分析
+    pub analysis: String,
+    pub recommendations: Vec<NewAiRecommendation>,
+}
+
+/// 崩溃日志分析器
+pub trait Analyzer {
+    /// 分析器名称，显示在命令输出中
+    fn name(&self) -> &str;
+
+    fn analyze(&self, crash_log: &CrashLog, context: &AnalysisContext) -> Result<Analysis>;
+}
+
+/// 按配置选择分析器：配置了外部命令时使用命令，否则使用内置规则
+pub fn from_config(config: &AnalyzerConfig) -> Box<dyn Analyzer> {
+    match &config.program {
+        Some(program) => Box::new(CommandAnalyzer {
+            program: program.clone(),
+            args: config.args.clone(),
+            timeout: Duration::from_secs(config.timeout_secs),
+        }),
+        None => Box::new(RuleAnalyzer),
+    }
+}
+
+/// 摘要、分析或消息仍是占位文本
+pub fn is_pending(crash_log: &CrashLog) -> bool {
+    crash_log.message == PENDING_TEXT
+        || crash_log.ai_summary.as_deref() == Some(PENDING_TEXT)
+        || crash_log.ai_analysis.as_deref() == Some(PENDING_TEXT)
+}
+
+/// 分析字段没有内容或仍是占位文本
+pub fn is_missing_analysis(value: Option<&str>) -> bool {
+    value.is_none_or(|value| value.trim().is_empty() || value == PENDING_TEXT)
+}
+
+/// 已有的崩溃日志再次收到 (重复上报或合并) 时实际写入的内容
+///
+/// 解决状态只能通过 crash resolve / reopen 修改并记入处理历史，保留已有的值；
+/// 收到的消息是占位文本、或没有给出分析时，保留已有的消息和知识库或 crash analyze 生成的分析。
+pub fn keep_existing(existing: &CrashLog, incoming: NewCrashLog) -> NewCrashLog {
+    fn keep(incoming: Option<String>, existing: &Option<String>) -> Option<String> {
+        if is_missing_analysis(incoming.as_deref()) && !is_missing_analysis(existing.as_deref()) {
+            existing.clone()
+        } else {
+            incoming
+        }
+    }
+
+    NewCrashLog {
+        resolved: existing.resolved,
+        message: if incoming.message == PENDING_TEXT {
+            existing.message.clone()
+        } else {
+            incoming.message
+        },
+        ai_summary: keep(incoming.ai_summary, &existing.ai_summary),
+        ai_analysis: keep(incoming.ai_analysis, &existing.ai_analysis),
+        ..incoming
+    }
+}
+
+/// 等待分析的崩溃日志，按 id 升序
+pub fn pending(conn: &mut SqliteConnection) -> Result<Vec<CrashLog>> {
+    use crate::schema::crash_logs::dsl::*;
+
+    Ok(crash_logs
+        .filter(
+            message
+                .eq(PENDING_TEXT)
+                .or(ai_summary.eq(PENDING_TEXT))
+                .or(ai_analysis.eq(PENDING_TEXT)),
+        )
+        .order(id.asc())
+        .load(conn)?)
+}
+
+/// 写入分析结果，调用方负责事务
+///
+/// 消息仍是占位文本时用摘要替换；已有 AI 建议的日志保留原建议，不重复添加。
+pub fn apply<S: Store + ?Sized>(
+    store: &mut S,
+    crash_log: &CrashLog,
+    analysis: &Analysis,
+) -> Result<()> {
+    let message = if crash_log.message == PENDING_TEXT {
+        analysis.summary.clone()
+    } else {
+        crash_log.message.clone()
+    };
+
+    store.update_crash_log(
+        crash_log.id,
+        &NewCrashLog {
+            server_id: crash_log.server_id.clone(),
+            log_id: crash_log.log_id,
+            timestamp: crash_log.timestamp,
+            crash_type: crash_log.crash_type.clone(),
+            severity: crash_log.severity.clone(),
+            title: crash_log.title.clone(),
+            message,
+            stack_trace: crash_log.stack_trace.clone(),
+            resolved: crash_log.resolved,
+            ai_summary: Some(analysis.summary.clone()),
+            ai_analysis: Some(analysis.analysis.clone()),
+        },
+    )?;
+
+    if store
+        .get_recommendations_by_crash_log(crash_log.id)?
+        .is_empty()
+    {
+        for recommendation in &analysis.recommendations {
+            store.create_ai_recommendation(&NewAiRecommendation {
+                crash_log_id: crash_log.id,
+                priority: recommendation.priority,
+                action: recommendation.action.clone(),
+                command: recommendation.command.clone(),
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+/// 一条规则：崩溃类型匹配或日志中出现任一关键字 (不区分大小写) 时生效
+struct Rule {
+    crash_types: &'static [&'static str],
+    keywords: &'static [&'static str],
+    finding: &'static str,
+    detail: &'static str,
+    /// (操作, 命令)
+    recommendations: &'static [(&'static str, &'static str)],
+}
+
+const RULES: &[Rule] = &[
+    Rule {
+        crash_types: &["oom"],
+        keywords: &[
+            "out of memory",
+            "oom-killer",
+            "oom_reaper",
+            "killed process",
+        ],
+        finding: "内存耗尽，进程被 OOM killer 终止",
+        detail: "系统可用内存不足，内核选择并终止了占用内存最多的进程。",
+        recommendations: &[
+            (
+                "查看被终止的进程和当时的内存状态",
+                "dmesg -T | grep -iE 'out of memory|killed process' | tail -n 20",
+            ),
+            ("找出占用内存最多的进程", "ps aux --sort=-rss | head -n 15"),
+        ],
+    },
+    Rule {
+        crash_types: &[],
+        keywords: &["kernel panic"],
+        finding: "内核 panic",
+        detail: "内核遇到无法恢复的错误并停止运行，通常需要结合上一次启动的内核日志和 kdump 转储定位。",
+        recommendations: &[
+            ("查看上一次启动的内核错误日志", "journalctl -k -b -1 -p err"),
+            ("检查是否生成了 kdump 转储", "ls -lt /var/crash | head"),
+        ],
+    },
+    Rule {
+        crash_types: &[],
+        keywords: &[
+            "kernel bug at",
+            "internal error: oops",
+            "bug:",
+            "call trace:",
+        ],
+        finding: "内核 BUG / Oops",
+        detail: "内核代码触发了 BUG 或 Oops，调用栈通常指向出错的驱动或子系统。",
+        recommendations: &[
+            (
+                "查看完整的 Oops 和调用栈",
+                "dmesg -T | grep -A 40 -iE 'kernel BUG|Oops|Call trace'",
+            ),
+            ("记录内核版本，检查是否有包含修复的更新", "uname -r"),
+        ],
+    },
+    Rule {
+        crash_types: &["segmentation_fault"],
+        keywords: &["segfault", "segmentation fault"],
+        finding: "用户态进程段错误",
+        detail: "进程访问了非法内存地址，核心转储可以定位出错的代码位置。",
+        recommendations: &[
+            ("列出最近的核心转储", "coredumpctl list --since today"),
+            (
+                "查看内核记录的段错误地址",
+                "dmesg -T | grep -i segfault | tail -n 20",
+            ),
+        ],
+    },
+    Rule {
+        crash_types: &[],
+        keywords: &["blocked for more than", "hung_task"],
+        finding: "任务长时间阻塞",
+        detail: "有进程在不可中断睡眠中阻塞超过阈值，通常由 IO 卡顿或锁竞争引起。",
+        recommendations: &[
+            (
+                "查看处于 D 状态的进程",
+                "ps -eo pid,stat,wchan:32,comm | awk '$2 ~ /D/'",
+            ),
+            ("观察磁盘 IO 延迟", "iostat -x 1 5"),
+        ],
+    },
+    Rule {
+        crash_types: &[],
+        keywords: &["i/o error", "ext4-fs error", "xfs: "],
+        finding: "磁盘或文件系统错误",
+        detail: "内核报告了块设备 IO 错误或文件系统错误，可能是磁盘故障。",
+        recommendations: &[
+            (
+                "查看磁盘相关的错误日志",
+                "dmesg -T | grep -iE 'i/o error|ext4-fs|xfs' | tail -n 20",
+            ),
+            ("检查磁盘健康状态", "smartctl -a /dev/sda"),
+        ],
+    },
+];
+
+/// 内置的规则分析器
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RuleAnalyzer;
+
+impl Analyzer for RuleAnalyzer {
+    fn name(&self) -> &str {
+        "rules"
+    }
+
+    fn analyze(&self, crash_log: &CrashLog, context: &AnalysisContext) -> Result<Analysis> {
+        let mut findings: Vec<(String, String)> = Vec::new();
+        let mut steps: Vec<(String, String)> = Vec::new();
+
+        if crash_log.crash_type == CrashType::ThreadException {
+            let (finding, detail, recommendations) = thread_exception(crash_log);
+            findings.push((finding, detail));
+            steps.extend(recommendations);
+        }
+
+        let text = format!(
+            "{}\n{}",
+            crash_log.message,
+            crash_log.stack_trace.as_deref().unwrap_or_default()
+        )
+        .to_lowercase();
+        for rule in RULES {
+            if rule.crash_types.contains(&crash_log.crash_type.as_str())
+                || rule.keywords.iter().any(|keyword| text.contains(keyword))
+            {
+                findings.push((rule.finding.to_string(), rule.detail.to_string()));
+                steps.extend(
+                    rule.recommendations
+                        .iter()
+                        .map(|(action, command)| (action.to_string(), command.to_string())),
+                );
+            }
+        }
+
+        if findings.is_empty() {
+            findings.push((
+                format!("{} 类型的崩溃", crash_log.crash_type),
+                "没有匹配的内置规则，需要结合系统日志人工分析。".to_string(),
+            ));
+            steps.push((
+                "查看崩溃前后的系统日志".to_string(),
+                "journalctl -p warning --since '-1h'".to_string(),
+            ));
+            steps.push((
+                "查看最近的内核日志".to_string(),
+                "dmesg -T | tail -n 200".to_string(),
+            ));
+        }
+
+        let mut analysis = String::from("## 🔍 问题分析\n\n");
+        for (finding, detail) in &findings {
+            analysis.push_str(&format!("- **{}**：{}\n", finding, detail));
+        }
+
+        analysis.push_str("\n### 📊 服务器状态\n\n");
+        match &context.server {
+            Some(server) => analysis.push_str(&format!(
+                "- **服务器**: {} ({}，{}，状态 {})\n",
+                server.server_name, server.server_ip, server.server_os, server.server_status
+            )),
+            None => analysis.push_str(&format!("- **服务器**: {} (未登记)\n", crash_log.server_id)),
+        }
+        if let Some(metric) = context.recent_metrics.first() {
+            analysis.push_str(&format!(
+                "- **最近一次指标**: CPU {:.1}% | 内存 {:.1}% | 磁盘 {:.1}%\n",
+                metric.cpu_usage, metric.memory_usage, metric.disk_usage
+            ));
+            if metric.memory_usage >= 90.0
+                && !findings.iter().any(|(finding, _)| finding.contains("内存"))
+            {
+                steps.push((
+                    "内存使用率偏高，找出占用内存最多的进程".to_string(),
+                    "ps aux --sort=-rss | head -n 15".to_string(),
+                ));
+            }
+            if metric.disk_usage >= 90.0 {
+                steps.push((
+                    "磁盘使用率偏高，清理大文件".to_string(),
+                    "df -h && du -xh / --max-depth=2 2>/dev/null | sort -rh | head -n 20"
+                        .to_string(),
+                ));
+            }
+        }
+        analysis.push_str(&format!(
+            "- **已记录进程**: {} 个\n",
+            context.processes.len()
+        ));
+
+        let mut seen = HashSet::new();
+        steps.retain(|(_, command)| seen.insert(command.clone()));
+        analysis.push_str("\n---\n\n## 💡 处理建议\n");
+        for (index, (action, command)) in steps.iter().enumerate() {
+            analysis.push_str(&format!(
+                "\n### {}. {} `优先级: P{}`\n\n```bash\n{}\n```\n",
+                index + 1,
+                action,
+                index + 1,
+                command
+            ));
+        }
+
+        Ok(Analysis {
+            summary: findings
+                .iter()
+                .map(|(finding, _)| finding.as_str())
+                .collect::<Vec<_>>()
+                .join("；"),
+            analysis,
+            recommendations: steps
+                .into_iter()
+                .enumerate()
+                .map(|(index, (action, command))| NewAiRecommendation {
+                    crash_log_id: crash_log.id,
+                    priority: index as i32 + 1,
+                    action,
+                    command,
+                })
+                .collect(),
+        })
+    }
+}
+
+/// 从线程异常日志的消息和堆栈中取出进程信息，给出针对该进程的建议
+fn thread_exception(crash_log: &CrashLog) -> (String, String, Vec<(String, String)>) {
+    let trace = crash_log.stack_trace.as_deref().unwrap_or_default();
+    let name = trace
+        .lines()
+        .find_map(|line| line.strip_prefix("PROCESS_NAME: "))
+        .or_else(|| field(&crash_log.message, "NAME="))
+        .unwrap_or("未知进程");
+    // 进程名和 PID 来自上报的数据，只有合法的 PID 才写入命令，进程名只保留文件名安全的字符
+    let pid = field(trace, "PID=")
+        .or_else(|| field(&crash_log.message, "PID="))
+        .and_then(|pid| pid.parse::<u32>().ok())
+        .map_or_else(|| "<pid>".to_string(), |pid| pid.to_string());
+    let file_name: String = name
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+        .collect();
+    let file_name = if file_name.trim_matches('.').is_empty() {
+        "process".to_string()
+    } else {
+        file_name
+    };
+    let count = field(&crash_log.message, "Count=").unwrap_or("未知");
+
+    let finding = format!("进程 {} (PID {}) 线程数异常", name, pid);
+    let detail = format!(
+        "线程数达到 {}，超过检测阈值，可能存在线程泄漏或线程池配置过大，继续增长会耗尽系统线程上限。",
+        count
+    );
+    let recommendations = vec![
+        (
+            "查看进程当前线程数和线程名分布".to_string(),
+            format!(
+                "ps -o pid,nlwp,comm -p {pid}; cat /proc/{pid}/task/*/comm | sort | uniq -c | sort -rn | head"
+            ),
+        ),
+        (
+            "确认进程和系统的线程上限".to_string(),
+            format!("grep -i processes /proc/{pid}/limits; cat /proc/sys/kernel/threads-max"),
+        ),
+        (
+            "采集所有线程的调用栈，定位创建线程的代码".to_string(),
+            format!("gdb -p {pid} -batch -ex 'thread apply all bt' > /tmp/{file_name}-threads.txt"),
+        ),
+    ];
+    (finding, detail, recommendations)
+}
+
+/// `KEY=value` 形式的字段值，值以逗号或空白结束
+fn field<'a>(text: &'a str, key: &str) -> Option<&'a str> {
+    let start = text.find(key)? + key.len();
+    let value = text[start..].split([',', ' ', '\n']).next()?;
+    (!value.is_empty()).then_some(value)
+}
+
+/// 调用外部命令的分析器
+///
+/// 标准输入为 `{"crashLog": ..., "context": ...}`，命令需要在标准输出写出
+/// `{"summary": ..., "analysis": ..., "recommendations": [{"priority": 1, "action": ..., "command": ...}]}`，
+/// 非零退出或超时视为失败。
+#[derive(Debug, Clone)]
+pub struct CommandAnalyzer {
+    pub program: String,
+    pub args: Vec<String>,
+    pub timeout: Duration,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CommandInput<'a> {
+    crash_log: &'a CrashLog,
+    context: &'a AnalysisContext,
+}
+
+#[derive(Deserialize)]
+struct CommandOutput {
+    summary: String,
+    analysis: String,
+    #[serde(default)]
+    recommendations: Vec<CommandRecommendation>,
+}
+
+#[derive(Deserialize)]
+struct CommandRecommendation {
+    priority: i32,
+    action: String,
+    #[serde(default)]
+    command: String,
+}
+
+impl Analyzer for CommandAnalyzer {
+    fn name(&self) -> &str {
+        &self.program
+    }
+
+    fn analyze(&self, crash_log: &CrashLog, context: &AnalysisContext) -> Result<Analysis> {
+        let input = serde_json::to_vec(&CommandInput { crash_log, context })?;
+        let stdout = self.run(input)?;
+
+        let output: CommandOutput = serde_json::from_str(&stdout).map_err(|e| {
+            BlackBoxError::parse("分析结果 JSON", format!("{}: {}", self.program, e))
+        })?;
+        if output.summary.trim().is_empty() {
+            return Err(BlackBoxError::validation(format!(
+                "分析命令 {} 返回的 summary 为空",
+                self.program
+            )));
+        }
+
+        Ok(Analysis {
+            summary: output.summary,
+            analysis: output.analysis,
+            recommendations: output
+                .recommendations
+                .into_iter()
+                .map(|recommendation| NewAiRecommendation {
+                    crash_log_id: crash_log.id,
+                    priority: recommendation.priority,
+                    action: recommendation.action,
+                    command: recommendation.command,
+                })
+                .collect(),
+        })
+    }
+}
+
+impl CommandAnalyzer {
+    /// 运行命令并返回标准输出，超时时终止进程
+    fn run(&self, input: Vec<u8>) -> Result<String> {
+        let io_error = |e| BlackBoxError::io(&self.program, e);
+
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(io_error)?;
+
+        // 在单独的线程中读写管道，避免输出填满管道缓冲区时双方互相等待
+        let mut stdin = child.stdin.take().expect("stdin 已设置为管道");
+        std::thread::spawn(move || {
+            let _ = stdin.write_all(&input);
+        });
+        let read = |mut pipe: Box<dyn Read + Send>| {
+            std::thread::spawn(move || {
+                let mut content = String::new();
+                let _ = pipe.read_to_string(&mut content);
+                content
+            })
+        };
+        let stdout = read(Box::new(child.stdout.take().expect("stdout 已设置为管道")));
+        let stderr = read(Box::new(child.stderr.take().expect("stderr 已设置为管道")));
+
+        let started = Instant::now();
+        let status = loop {
+            if let Some(status) = child.try_wait().map_err(io_error)? {
+                break status;
+            }
+            if started.elapsed() >= self.timeout {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(BlackBoxError::io(
+                    &self.program,
+                    std::io::Error::other(format!(
+                        "分析命令执行超过 {} 秒，已终止",
+                        self.timeout.as_secs()
+                    )),
+                ));
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        };
+
+        let stdout = stdout.join().unwrap_or_default();
+        if !status.success() {
+            let stderr = stderr.join().unwrap_or_default();
+            return Err(BlackBoxError::io(
+                &self.program,
+                std::io::Error::other(format!("命令退出状态: {}: {}", status, stderr.trim())),
+            ));
+        }
+        Ok(stdout)
+    }
+}
@@ -0,0 +1,283 @@
+//! 基于游标的增量导出 (NDJSON 变更记录)
+//!
+//! 每张表记录一个高水位：已导出的最大 id，以及已导出的最大 `(updated_at, id)`。
+//! 再次导出时只输出 id 更大 (新增) 或 `(updated_at, id)` 更大 (原地修改) 的行，
+//! 最后输出新的游标。删除操作不会产生变更记录。
+//!
+//! updated_at 统一按 `strftime('%Y-%m-%d %H:%M:%f')` (毫秒精度) 比较，
+//! 不受 `CURRENT_TIMESTAMP` 默认值与程序写入的时间格式不同的影响；
+//! 同一毫秒内修改的多行按 id 区分先后。
+
+use crate::error::{BlackBoxError, Result};
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use crate::models::*;
+
+/// 写入变更记录失败时错误中的读写目标 (输出可能是文件或标准输出)
+const OUTPUT: &str = "变更记录输出";
+
+/// 单张表的高水位
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct TableCursor {
+    pub last_id: i32,
+    pub last_updated_at: Option<NaiveDateTime>,
+    /// updated_at 等于 `last_updated_at` 的行中已导出的最大 id
+    #[serde(default)]
+    pub last_updated_id: i32,
+}
+
+/// 增量导出游标，按表名记录高水位
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ExportCursor {
+    #[serde(default)]
+    pub tables: BTreeMap<String, TableCursor>,
+}
+
+impl ExportCursor {
+    /// 从文件读取游标，文件不存在时返回空游标 (即全量导出)
+    pub fn load(path: &str) -> Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|e| BlackBoxError::parse("游标文件", format!("{}: {}", path, e))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(BlackBoxError::io(path, e)),
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+            .map_err(|e| BlackBoxError::io(path, e))?;
+        Ok(())
+    }
+
+    fn table(&self, name: &str) -> TableCursor {
+        self.tables.get(name).cloned().unwrap_or_default()
+    }
+}
+
+/// 变更类型：id 超过上次高水位为新增，否则为修改
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeOp {
+    Insert,
+    Update,
+}
+
+#[derive(Serialize)]
+struct ChangeRecord<'a, T> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    table: &'a str,
+    op: ChangeOp,
+    id: i32,
+    row: &'a T,
+}
+
+#[derive(Serialize)]
+struct CursorRecord<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    cursor: &'a ExportCursor,
+}
+
+/// 增量导出结果
+#[derive(Debug, Clone)]
+pub struct ChangeExportSummary {
+    /// 每张表输出的变更记录数，按导出顺序排列
+    pub tables: Vec<(String, usize)>,
+    /// 新的游标
+    pub cursor: ExportCursor,
+}
+
+impl ChangeExportSummary {
+    pub fn total(&self) -> usize {
+        self.tables.iter().map(|(_, count)| count).sum()
+    }
+}
+
+struct ChangeWriter<'a, W: Write> {
+    writer: W,
+    since: &'a ExportCursor,
+    summary: ChangeExportSummary,
+}
+
+impl<W: Write> ChangeWriter<'_, W> {
+    fn write_rows<T: Serialize>(
+        &mut self,
+        table: &str,
+        rows: impl Iterator<Item = QueryResult<T>>,
+        id_of: fn(&T) -> i32,
+    ) -> Result<()> {
+        let previous = self.since.table(table);
+        let mut count = 0;
+
+        for row in rows {
+            let row = row?;
+            let id = id_of(&row);
+            let op = if id > previous.last_id {
+                ChangeOp::Insert
+            } else {
+                ChangeOp::Update
+            };
+            serde_json::to_writer(
+                &mut self.writer,
+                &ChangeRecord {
+                    kind: "change",
+                    table,
+                    op,
+                    id,
+                    row: &row,
+                },
+            )?;
+            self.writer
+                .write_all(b"\n")
+                .map_err(|e| BlackBoxError::io(OUTPUT, e))?;
+            count += 1;
+        }
+
+        self.summary.tables.push((table.to_string(), count));
+        Ok(())
+    }
+
+    /// 高水位只前进不后退，表被清空时保留旧值
+    fn advance(
+        &mut self,
+        table: &str,
+        max_id: Option<i32>,
+        max_updated: Option<(NaiveDateTime, i32)>,
+    ) {
+        let previous = self.since.table(table);
+        let previous_updated = previous
+            .last_updated_at
+            .map(|at| (at, previous.last_updated_id));
+        let (last_updated_at, last_updated_id) = match previous_updated.max(max_updated) {
+            Some((at, id)) => (Some(at), id),
+            None => (None, 0),
+        };
+        self.summary.cursor.tables.insert(
+            table.to_string(),
+            TableCursor {
+                last_id: previous.last_id.max(max_id.unwrap_or(0)),
+                last_updated_at,
+                last_updated_id,
+            },
+        );
+    }
+}
+
+/// 只按 id 追踪的表 (行只会新增和删除)
+macro_rules! export_inserts {
+    ($conn:expr, $out:expr, $table:ident, $model:ty) => {{
+        use crate::schema::$table::dsl as t;
+
+        let name = stringify!($table);
+        let previous = $out.since.table(name);
+
+        let max_id: Option<i32> = t::$table.select(diesel::dsl::max(t::id)).first($conn)?;
+        let rows = t::$table
+            .filter(t::id.gt(previous.last_id))
+            .order(t::id.asc())
+            .load_iter::<$model, _>($conn)?;
+        $out.write_rows(name, rows, |r: &$model| r.id)?;
+        $out.advance(name, max_id, None);
+    }};
+}
+
+/// 比较 updated_at 时使用的统一格式 (毫秒精度)
+const UPDATED_AT_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.3f";
+const UPDATED_AT_SQL: &str = "strftime('%Y-%m-%d %H:%M:%f', updated_at)";
+
+/// 同时按 id 和 `(updated_at, id)` 追踪的表 (行会被原地修改)
+macro_rules! export_upserts {
+    ($conn:expr, $out:expr, $table:ident, $model:ty) => {{
+        use crate::schema::$table::dsl as t;
+        use diesel::dsl::sql;
+        use diesel::sql_types::{Nullable, Text};
+
+        let name = stringify!($table);
+        let previous = $out.since.table(name);
+        let updated = || sql::<Nullable<Text>>(UPDATED_AT_SQL);
+
+        let max_id: Option<i32> = t::$table.select(diesel::dsl::max(t::id)).first($conn)?;
+        let max_updated_at: Option<String> = t::$table
+            .select(sql::<Nullable<Text>>(&format!("max({})", UPDATED_AT_SQL)))
+            .first($conn)?;
+        let max_updated = match max_updated_at {
+            Some(at) => {
+                let id: Option<i32> = t::$table
+                    .filter(updated().eq(&at))
+                    .select(diesel::dsl::max(t::id))
+                    .first($conn)?;
+                let at = NaiveDateTime::parse_from_str(&at, UPDATED_AT_FORMAT)
+                    .map_err(|e| BlackBoxError::parse("updated_at", format!("{}: {}", at, e)))?;
+                Some((at, id.unwrap_or(0)))
+            }
+            None => None,
+        };
+
+        let query = t::$table.order(t::id.asc()).into_boxed();
+        let query = match previous.last_updated_at {
+            Some(since) => {
+                let since = since.format(UPDATED_AT_FORMAT).to_string();
+                query.filter(
+                    t::id
+                        .gt(previous.last_id)
+                        .or(updated().gt(since.clone()))
+                        .or(updated().eq(since).and(t::id.gt(previous.last_updated_id))),
+                )
+            }
+            None => query.filter(t::id.gt(previous.last_id).or(t::updated_at.is_not_null())),
+        };
+        let rows = query.load_iter::<$model, _>($conn)?;
+        $out.write_rows(name, rows, |r: &$model| r.id)?;
+        $out.advance(name, max_id, max_updated);
+    }};
+}
+
+/// 按外键依赖顺序输出自 `since` 以来的变更记录，最后一行为新的游标
+///
+/// 整个导出在一个读事务中完成，保证各表高水位与输出的数据一致。
+pub fn export_changes<W: Write>(
+    conn: &mut SqliteConnection,
+    since: &ExportCursor,
+    writer: W,
+) -> Result<ChangeExportSummary> {
+    conn.transaction::<_, BlackBoxError, _>(|conn| {
+        let mut out = ChangeWriter {
+            writer,
+            since,
+            summary: ChangeExportSummary {
+                tables: Vec::new(),
+                cursor: since.clone(),
+            },
+        };
+
+        export_upserts!(conn, out, servers, Server);
+        export_upserts!(conn, out, server_labels, ServerLabel);
+        export_upserts!(conn, out, system_metrics, SystemMetric);
+        export_upserts!(conn, out, processes, Process);
+        export_upserts!(conn, out, process_trends, ProcessTrend);
+        export_inserts!(conn, out, threads, Thread);
+        export_upserts!(conn, out, crash_logs, CrashLog);
+        export_upserts!(conn, out, ai_recommendations, AiRecommendation);
+
+        serde_json::to_writer(
+            &mut out.writer,
+            &CursorRecord {
+                kind: "cursor",
+                cursor: &out.summary.cursor,
+            },
+        )?;
+        out.writer
+            .write_all(b"\n")
+            .and_then(|()| out.writer.flush())
+            .map_err(|e| BlackBoxError::io(OUTPUT, e))?;
+
+        Ok(out.summary)
+    })
+}
@@ -0,0 +1,328 @@
+//! BlackBox 配置
+//!
+//! 配置可以来自 TOML 文件 (`--config` 或环境变量 `BLACKBOX_CONFIG`)，
+//! 也可以在代码中通过 [`BlackBox::builder`](crate::BlackBox::builder) 设置。
+//! 文件中未出现的项使用默认值，命令行参数优先于配置文件。
+//!
+//! ```toml
+//! [database]
+//! path = "/var/lib/blackbox/monitoring.db"
+//!
+//! [connection]
+//! busy_timeout_ms = 10000
+//!
+//! [retention]
+//! hours = 48
+//!
+//! [detection]
+//! thread_exception_threshold = 4000
+//!
+//! [output]
+//! format = "json"
+//!
+//! [[notify.webhooks]]
+//! name = "ops"
+//! url = "http://10.0.0.5:8080/hooks/blackbox"
+//!
+//! [analyzer]
+//! program = "/usr/local/bin/crash-analyzer"
+//!
+//! [forward]
+//! enabled = true
+//! ```
+
+use crate::error::{BlackBoxError, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+use crate::database::ConnectionOptions;
+use crate::notify::NotifyEvent;
+
+/// 指定配置文件路径的环境变量
+pub const CONFIG_ENV: &str = "BLACKBOX_CONFIG";
+
+/// 未配置数据库路径时使用的文件
+pub const DEFAULT_DB_PATH: &str = "./database.db";
+
+/// `output.format` 的可选值 (不区分大小写)
+pub const OUTPUT_FORMATS: [&str; 4] = ["table", "json", "csv", "yaml"];
+
+/// 完整配置
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct BlackBoxConfig {
+    pub database: DatabaseConfig,
+    /// 连接参数 (PRAGMA 和忙等待重试)
+    pub connection: ConnectionOptions,
+    pub retention: RetentionConfig,
+    pub detection: DetectionConfig,
+    pub output: OutputConfig,
+    pub notify: NotifyConfig,
+    pub analyzer: AnalyzerConfig,
+    pub forward: ForwardConfig,
+}
+
+/// 数据库位置
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    /// 数据库文件路径，未设置时使用 [`DEFAULT_DB_PATH`]
+    pub path: Option<String>,
+}
+
+/// 数据保留
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetentionConfig {
+    /// 每次智能插入前删除早于该小时数的指标、趋势、崩溃日志和幂等台账
+    pub hours: i64,
+    /// `clean` 命令默认保留的天数
+    pub clean_days: i64,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            hours: 24,
+            clean_days: 30,
+        }
+    }
+}
+
+/// 崩溃检测
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct DetectionConfig {
+    /// dmesg 中出现任一字符串即视为系统崩溃，生成内核异常日志
+    pub crash_indicators: Vec<String>,
+    /// 进程线程数超过该值时生成线程异常日志
+    pub thread_exception_threshold: i32,
+}
+
+impl Default for DetectionConfig {
+    fn default() -> Self {
+        Self {
+            crash_indicators: [
+                "kernel BUG at",
+                "Internal error: Oops",
+                "segmentation fault",
+                "kernel panic",
+                "Call trace:",
+                "---[ end trace",
+                "BUG:",
+                "WARNING:",
+            ]
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
+            thread_exception_threshold: 2000,
+        }
+    }
+}
+
+/// 命令行输出
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputConfig {
+    /// query / stats 未指定 --output 时的格式 (table、json、csv、yaml)
+    pub format: String,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self {
+            format: "table".to_string(),
+        }
+    }
+}
+
+/// 崩溃和告警通知
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotifyConfig {
+    /// 同一目标对同一服务器的同类事件，在该秒数内只通知一次，为 0 时不限流
+    pub rate_limit_secs: u64,
+    /// 每次通知的最大尝试次数
+    pub max_attempts: u32,
+    /// 第一次失败后的等待毫秒数，之后每次翻倍
+    pub retry_backoff_ms: u64,
+    /// 单次 webhook 请求或命令执行的超时秒数
+    pub timeout_secs: u64,
+    pub webhooks: Vec<WebhookConfig>,
+    pub commands: Vec<CommandConfig>,
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        Self {
+            rate_limit_secs: 300,
+            max_attempts: 3,
+            retry_backoff_ms: 1000,
+            timeout_secs: 5,
+            webhooks: Vec::new(),
+            commands: Vec::new(),
+        }
+    }
+}
+
+impl NotifyConfig {
+    /// 是否配置了通知目标
+    pub fn is_enabled(&self) -> bool {
+        !self.webhooks.is_empty() || !self.commands.is_empty()
+    }
+}
+
+/// 以 JSON 请求体 POST 通知的 webhook
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookConfig {
+    /// 目标名称，记录在投递日志中
+    pub name: String,
+    pub url: String,
+    /// 订阅的事件 (crash、alert)，默认全部
+    #[serde(default = "NotifyEvent::all")]
+    pub events: Vec<NotifyEvent>,
+}
+
+/// 通过环境变量接收通知的本地命令
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct CommandConfig {
+    /// 目标名称，记录在投递日志中
+    pub name: String,
+    pub program: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// 订阅的事件 (crash、alert)，默认全部
+    #[serde(default = "NotifyEvent::all")]
+    pub events: Vec<NotifyEvent>,
+}
+
+/// 崩溃日志分析 (blackbox crash analyze)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct AnalyzerConfig {
+    /// 外部分析命令，未设置时使用内置规则
+    pub program: Option<String>,
+    pub args: Vec<String>,
+    /// 单条崩溃日志的分析超时秒数
+    pub timeout_secs: u64,
+    /// 崩溃特征知识库文件 (TOML 或 JSON)，新建崩溃日志时自动匹配
+    pub knowledge_base: Option<String>,
+}
+
+impl Default for AnalyzerConfig {
+    fn default() -> Self {
+        Self {
+            program: None,
+            args: Vec::new(),
+            timeout_secs: 60,
+            knowledge_base: None,
+        }
+    }
+}
+
+/// 边缘节点转发 (blackbox forward)
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ForwardConfig {
+    /// 边缘节点模式：所有写入 (JSON、CSV、行协议、导入、标签、崩溃日志处理、清理和合并) 都在同一事务中加入转发队列
+    pub enabled: bool,
+}
+
+impl BlackBoxConfig {
+    /// 从 TOML 文件读取配置
+    pub fn load(path: &str) -> Result<Self> {
+        let content = fs::read_to_string(path).map_err(|e| BlackBoxError::io(path, e))?;
+        Self::from_toml(&content)
+    }
+
+    pub fn from_toml(content: &str) -> Result<Self> {
+        let config: Self =
+            toml::from_str(content).map_err(|e| BlackBoxError::parse("TOML", e.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// 读取 `path` 指定的配置文件，未指定时读取 `BLACKBOX_CONFIG`，都没有时使用默认配置
+    ///
+    /// 配置文件没有设置数据库路径时，沿用 `DATABASE_URL` (可写在 .env 中)。
+    pub fn from_env(path: Option<&str>) -> Result<Self> {
+        dotenv::dotenv().ok();
+
+        let path = path
+            .map(str::to_string)
+            .or_else(|| std::env::var(CONFIG_ENV).ok());
+        let mut config = match path {
+            Some(path) => Self::load(&path)?,
+            None => Self::default(),
+        };
+
+        if config.database.path.is_none() {
+            config.database.path = std::env::var("DATABASE_URL").ok();
+        }
+
+        Ok(config)
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        toml::to_string_pretty(self).map_err(|e| BlackBoxError::parse("TOML", e.to_string()))
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.retention.hours <= 0 {
+            return Err(BlackBoxError::validation("retention.hours 必须大于 0"));
+        }
+        if self.retention.clean_days <= 0 {
+            return Err(BlackBoxError::validation("retention.clean_days 必须大于 0"));
+        }
+        if self.detection.thread_exception_threshold <= 0 {
+            return Err(BlackBoxError::validation(
+                "detection.thread_exception_threshold 必须大于 0",
+            ));
+        }
+        if self.notify.max_attempts == 0 {
+            return Err(BlackBoxError::validation("notify.max_attempts 必须大于 0"));
+        }
+        if self.analyzer.timeout_secs == 0 {
+            return Err(BlackBoxError::validation(
+                "analyzer.timeout_secs 必须大于 0",
+            ));
+        }
+        if !OUTPUT_FORMATS
+            .iter()
+            .any(|format| format.eq_ignore_ascii_case(&self.output.format))
+        {
+            return Err(BlackBoxError::validation(format!(
+                "output.format 的取值 {} 无效 (可选 {})",
+                self.output.format,
+                OUTPUT_FORMATS.join("、")
+            )));
+        }
+
+        let names: Vec<&str> = self
+            .notify
+            .webhooks
+            .iter()
+            .map(|webhook| webhook.name.as_str())
+            .chain(
+                self.notify
+                    .commands
+                    .iter()
+                    .map(|command| command.name.as_str()),
+            )
+            .collect();
+        for (index, name) in names.iter().enumerate() {
+            if name.trim().is_empty() {
+                return Err(BlackBoxError::validation("通知目标的 name 不能为空"));
+            }
+            if names[..index].contains(name) {
+                return Err(BlackBoxError::validation(format!(
+                    "通知目标名称重复: {}",
+                    name
+                )));
+            }
+        }
+        Ok(())
+    }
+}
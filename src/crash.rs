@@ -0,0 +1,354 @@
+//! 崩溃日志的处理流程 (blackbox crash)
+//!
+//! 值班人员可以把崩溃日志标记为已解决、重新打开或添加备注；每次操作都记入
+//! crash_events 表，保存操作人、时间和说明，与崩溃日志一起随级联删除。
+//!
+//! 崩溃日志的 AI 建议可以添加、整体替换、调整顺序和删除，优先级始终保持为从 1 开始的连续编号；
+//! 执行过的建议可以标记为已执行并记录结果。
+
+use crate::error::{BlackBoxError, Result};
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use serde::Serialize;
+
+use crate::database::{
+    create_ai_recommendation, db_now, delete_recommendations_by_crash_log,
+    get_recommendations_by_crash_log,
+};
+use crate::domain::*;
+use crate::models::*;
+
+/// 崩溃日志列表的筛选条件
+#[derive(Debug, Clone, Default)]
+pub struct CrashLogFilter {
+    pub server_id: Option<String>,
+    /// 只列出已解决 (true) 或未解决 (false) 的日志
+    pub resolved: Option<bool>,
+    pub limit: Option<i64>,
+}
+
+/// 崩溃日志及其 AI 建议和处理历史
+#[derive(Serialize, Debug, Clone)]
+pub struct CrashLogHistory {
+    pub crash_log: CrashLog,
+    pub recommendations: Vec<AiRecommendation>,
+    /// 按发生顺序排列
+    pub events: Vec<CrashEvent>,
+}
+
+/// 按时间戳倒序列出崩溃日志
+pub fn list(conn: &mut SqliteConnection, filter: &CrashLogFilter) -> Result<Vec<CrashLog>> {
+    use crate::schema::crash_logs::dsl::*;
+
+    let mut query = crash_logs.into_boxed();
+    if let Some(server) = &filter.server_id {
+        query = query.filter(server_id.eq(server));
+    }
+    if let Some(value) = filter.resolved {
+        query = query.filter(resolved.eq(value));
+    }
+    if let Some(value) = filter.limit {
+        query = query.limit(value);
+    }
+
+    Ok(query.order((timestamp.desc(), id.desc())).load(conn)?)
+}
+
+pub fn get(conn: &mut SqliteConnection, crash_log_id: i32) -> Result<CrashLog> {
+    use crate::schema::crash_logs::dsl::*;
+
+    crash_logs
+        .find(crash_log_id)
+        .first(conn)
+        .optional()?
+        .ok_or_else(|| BlackBoxError::not_found("崩溃日志", crash_log_id.to_string()))
+}
+
+pub fn history(conn: &mut SqliteConnection, crash_log_id: i32) -> Result<CrashLogHistory> {
+    use crate::schema::crash_events;
+
+    let crash_log = get(conn, crash_log_id)?;
+    let recommendations = get_recommendations_by_crash_log(conn, crash_log_id)?;
+    let events = crash_events::table
+        .filter(crash_events::crash_log_id.eq(crash_log_id))
+        .order(crash_events::id.asc())
+        .load(conn)?;
+
+    Ok(CrashLogHistory {
+        crash_log,
+        recommendations,
+        events,
+    })
+}
+
+/// 修改解决状态并记入历史，调用方负责事务
+///
+/// 状态没有变化时返回冲突错误，避免重复操作在历史中留下误导性的记录。
+pub fn set_resolved(
+    conn: &mut SqliteConnection,
+    crash_log_id: i32,
+    resolved_value: bool,
+    actor: &str,
+    note: Option<&str>,
+) -> Result<CrashLog> {
+    use crate::schema::crash_logs::dsl::*;
+
+    let crash_log = get(conn, crash_log_id)?;
+    if crash_log.resolved == resolved_value {
+        let state = if resolved_value {
+            "已解决"
+        } else {
+            "未解决"
+        };
+        return Err(BlackBoxError::conflict(format!(
+            "崩溃日志 {} 已经是{}状态",
+            crash_log_id, state
+        )));
+    }
+
+    diesel::update(crash_logs.find(crash_log_id))
+        .set((resolved.eq(resolved_value), updated_at.eq(db_now())))
+        .execute(conn)?;
+
+    let action = if resolved_value {
+        CrashAction::Resolved
+    } else {
+        CrashAction::Reopened
+    };
+    record(conn, crash_log_id, action, actor, note)?;
+
+    get(conn, crash_log_id)
+}
+
+/// 为崩溃日志添加备注，调用方负责事务
+pub fn annotate(
+    conn: &mut SqliteConnection,
+    crash_log_id: i32,
+    actor: &str,
+    note: &str,
+) -> Result<CrashEvent> {
+    if note.trim().is_empty() {
+        return Err(BlackBoxError::validation("备注内容不能为空"));
+    }
+
+    get(conn, crash_log_id)?;
+    record(conn, crash_log_id, CrashAction::Note, actor, Some(note))
+}
+
+fn record(
+    conn: &mut SqliteConnection,
+    crash_log_id: i32,
+    action: CrashAction,
+    actor: &str,
+    note: Option<&str>,
+) -> Result<CrashEvent> {
+    use crate::schema::crash_events;
+
+    if actor.trim().is_empty() {
+        return Err(BlackBoxError::validation("操作人不能为空"));
+    }
+
+    diesel::insert_into(crash_events::table)
+        .values(&NewCrashEvent {
+            crash_log_id,
+            action,
+            actor: actor.trim().to_string(),
+            note: note
+                .map(str::trim)
+                .filter(|n| !n.is_empty())
+                .map(str::to_string),
+        })
+        .execute(conn)?;
+
+    Ok(crash_events::table
+        .order(crash_events::id.desc())
+        .first(conn)?)
+}
+
+/// 按列表顺序编号，提供了优先级的按优先级排序 (相同优先级保持原顺序)
+pub fn numbered(
+    crash_log_id: i32,
+    recommendations: &[SmartRecommendation],
+) -> Result<Vec<NewAiRecommendation>> {
+    let mut numbered = Vec::with_capacity(recommendations.len());
+    for (index, recommendation) in recommendations.iter().enumerate() {
+        if recommendation.action.trim().is_empty() {
+            return Err(BlackBoxError::validation("建议的 action 不能为空"));
+        }
+        numbered.push(NewAiRecommendation {
+            crash_log_id,
+            priority: recommendation.priority.unwrap_or(index as i32 + 1),
+            action: recommendation.action.trim().to_string(),
+            command: recommendation.command.trim().to_string(),
+        });
+    }
+    numbered.sort_by_key(|recommendation| recommendation.priority);
+    Ok(numbered)
+}
+
+/// 崩溃日志的 AI 建议，按优先级排列
+pub fn recommendations(
+    conn: &mut SqliteConnection,
+    crash_log_id: i32,
+) -> Result<Vec<AiRecommendation>> {
+    get(conn, crash_log_id)?;
+    get_recommendations_by_crash_log(conn, crash_log_id)
+}
+
+pub fn get_recommendation(
+    conn: &mut SqliteConnection,
+    recommendation_id: i32,
+) -> Result<AiRecommendation> {
+    use crate::schema::ai_recommendations;
+
+    ai_recommendations::table
+        .find(recommendation_id)
+        .first(conn)
+        .optional()?
+        .ok_or_else(|| BlackBoxError::not_found("AI 建议", recommendation_id.to_string()))
+}
+
+/// 添加一条建议，调用方负责事务
+///
+/// 未指定优先级时排在最后；指定时插入到该位置，原有的建议依次后移。
+pub fn add_recommendation(
+    conn: &mut SqliteConnection,
+    crash_log_id: i32,
+    recommendation: &SmartRecommendation,
+) -> Result<AiRecommendation> {
+    use crate::schema::ai_recommendations;
+
+    let mut existing: Vec<i32> = recommendations(conn, crash_log_id)?
+        .iter()
+        .map(|r| r.id)
+        .collect();
+    let new = numbered(crash_log_id, std::slice::from_ref(recommendation))?.remove(0);
+    let position = match recommendation.priority {
+        Some(priority) => (priority.max(1) as usize - 1).min(existing.len()),
+        None => existing.len(),
+    };
+
+    diesel::insert_into(ai_recommendations::table)
+        .values(&new)
+        .execute(conn)?;
+    let id: i32 = ai_recommendations::table
+        .select(ai_recommendations::id)
+        .order(ai_recommendations::id.desc())
+        .first(conn)?;
+
+    existing.insert(position, id);
+    renumber(conn, &existing)?;
+    get_recommendation(conn, id)
+}
+
+/// 用新的列表替换崩溃日志的全部建议，调用方负责事务
+pub fn replace_recommendations(
+    conn: &mut SqliteConnection,
+    crash_log_id: i32,
+    new_recommendations: &[SmartRecommendation],
+) -> Result<Vec<AiRecommendation>> {
+    get(conn, crash_log_id)?;
+    let new = numbered(crash_log_id, new_recommendations)?;
+
+    delete_recommendations_by_crash_log(conn, crash_log_id)?;
+    for (index, recommendation) in new.into_iter().enumerate() {
+        create_ai_recommendation(
+            conn,
+            &NewAiRecommendation {
+                priority: index as i32 + 1,
+                ..recommendation
+            },
+        )?;
+    }
+
+    get_recommendations_by_crash_log(conn, crash_log_id)
+}
+
+/// 按给出的建议 id 顺序重新编号，必须列出该崩溃日志的全部建议，调用方负责事务
+pub fn reorder_recommendations(
+    conn: &mut SqliteConnection,
+    crash_log_id: i32,
+    ids: &[i32],
+) -> Result<Vec<AiRecommendation>> {
+    let mut existing: Vec<i32> = recommendations(conn, crash_log_id)?
+        .iter()
+        .map(|r| r.id)
+        .collect();
+    let mut requested = ids.to_vec();
+    existing.sort_unstable();
+    requested.sort_unstable();
+    if existing != requested {
+        return Err(BlackBoxError::validation(format!(
+            "需要按新顺序列出崩溃日志 {} 的全部建议 id: {:?}",
+            crash_log_id, existing
+        )));
+    }
+
+    renumber(conn, ids)?;
+    get_recommendations_by_crash_log(conn, crash_log_id)
+}
+
+/// 删除一条建议，其余建议重新编号，调用方负责事务
+pub fn remove_recommendation(
+    conn: &mut SqliteConnection,
+    recommendation_id: i32,
+) -> Result<AiRecommendation> {
+    use crate::schema::ai_recommendations;
+
+    let removed = get_recommendation(conn, recommendation_id)?;
+    diesel::delete(ai_recommendations::table.find(recommendation_id)).execute(conn)?;
+
+    let ids: Vec<i32> = get_recommendations_by_crash_log(conn, removed.crash_log_id)?
+        .iter()
+        .map(|r| r.id)
+        .collect();
+    renumber(conn, &ids)?;
+    Ok(removed)
+}
+
+/// 标记建议为已执行 (记录时间和结果) 或取消标记，调用方负责事务
+///
+/// 对已执行的建议再次标记时更新执行时间和结果。
+pub fn set_applied(
+    conn: &mut SqliteConnection,
+    recommendation_id: i32,
+    applied_value: bool,
+    outcome_value: Option<&str>,
+) -> Result<AiRecommendation> {
+    use crate::schema::ai_recommendations::dsl::*;
+
+    get_recommendation(conn, recommendation_id)?;
+    let now = db_now();
+    let outcome_value = outcome_value
+        .map(str::trim)
+        .filter(|o| !o.is_empty())
+        .filter(|_| applied_value);
+
+    diesel::update(ai_recommendations.find(recommendation_id))
+        .set((
+            applied.eq(applied_value),
+            applied_at.eq(applied_value.then_some(now)),
+            outcome.eq(outcome_value),
+            updated_at.eq(now),
+        ))
+        .execute(conn)?;
+
+    get_recommendation(conn, recommendation_id)
+}
+
+/// 按 id 顺序把优先级设为 1..n，只更新优先级变化的行
+fn renumber(conn: &mut SqliteConnection, ids: &[i32]) -> Result<()> {
+    use crate::schema::ai_recommendations::dsl::*;
+
+    let now = db_now();
+    for (index, recommendation_id) in ids.iter().enumerate() {
+        diesel::update(
+            ai_recommendations
+                .find(recommendation_id)
+                .filter(priority.ne(index as i32 + 1)),
+        )
+        .set((priority.eq(index as i32 + 1), updated_at.eq(now)))
+        .execute(conn)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,291 @@
+//! 按表导出 CSV，以及 CSV 到智能插入结构的映射
+//!
+//! 导出的列名与数据库列名一致 (snake_case)；导入时按表头映射列，
+//! 同时接受 snake_case 与 camelCase 表头，未知列会被忽略。
+
+use crate::error::{BlackBoxError, Result};
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+use crate::domain::{ProcessState, ServerStatus};
+use crate::models::*;
+
+/// 可以导出为 CSV 的数据表
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsvTable {
+    Servers,
+    SystemMetrics,
+    Processes,
+    ProcessTrends,
+    Threads,
+    CrashLogs,
+    AiRecommendations,
+}
+
+impl CsvTable {
+    /// 全部数据表，按外键依赖顺序排列
+    pub const ALL: [CsvTable; 7] = [
+        CsvTable::Servers,
+        CsvTable::SystemMetrics,
+        CsvTable::Processes,
+        CsvTable::ProcessTrends,
+        CsvTable::Threads,
+        CsvTable::CrashLogs,
+        CsvTable::AiRecommendations,
+    ];
+
+    /// 数据库中的表名，也用作目录导出时的文件名
+    pub fn table_name(self) -> &'static str {
+        match self {
+            CsvTable::Servers => "servers",
+            CsvTable::SystemMetrics => "system_metrics",
+            CsvTable::Processes => "processes",
+            CsvTable::ProcessTrends => "process_trends",
+            CsvTable::Threads => "threads",
+            CsvTable::CrashLogs => "crash_logs",
+            CsvTable::AiRecommendations => "ai_recommendations",
+        }
+    }
+}
+
+/// 将整张表按主键顺序写成 CSV，返回写入的行数
+pub fn export_table<W: Write>(
+    conn: &mut SqliteConnection,
+    table: CsvTable,
+    writer: W,
+) -> Result<usize> {
+    use crate::schema::*;
+
+    let mut csv_writer = csv::Writer::from_writer(writer);
+
+    let count = match table {
+        CsvTable::Servers => write_rows(
+            &mut csv_writer,
+            servers::table
+                .order(servers::id)
+                .load_iter::<Server, _>(conn)?,
+        )?,
+        CsvTable::SystemMetrics => write_rows(
+            &mut csv_writer,
+            system_metrics::table
+                .order(system_metrics::id)
+                .load_iter::<SystemMetric, _>(conn)?,
+        )?,
+        CsvTable::Processes => write_rows(
+            &mut csv_writer,
+            processes::table
+                .order(processes::id)
+                .load_iter::<Process, _>(conn)?,
+        )?,
+        CsvTable::ProcessTrends => write_rows(
+            &mut csv_writer,
+            process_trends::table
+                .order(process_trends::id)
+                .load_iter::<ProcessTrend, _>(conn)?,
+        )?,
+        CsvTable::Threads => write_rows(
+            &mut csv_writer,
+            threads::table
+                .order(threads::id)
+                .load_iter::<Thread, _>(conn)?,
+        )?,
+        CsvTable::CrashLogs => write_rows(
+            &mut csv_writer,
+            crash_logs::table
+                .order(crash_logs::id)
+                .load_iter::<CrashLog, _>(conn)?,
+        )?,
+        CsvTable::AiRecommendations => write_rows(
+            &mut csv_writer,
+            ai_recommendations::table
+                .order(ai_recommendations::id)
+                .load_iter::<AiRecommendation, _>(conn)?,
+        )?,
+    };
+
+    csv_writer
+        .flush()
+        .map_err(|e| BlackBoxError::io("CSV 输出", e))?;
+    Ok(count)
+}
+
+/// 逐行写出，避免大表一次性加载到内存
+fn write_rows<W: Write, T: Serialize>(
+    writer: &mut csv::Writer<W>,
+    rows: impl Iterator<Item = QueryResult<T>>,
+) -> Result<usize> {
+    let mut count = 0;
+    for row in rows {
+        writer.serialize(row?)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// CSV 解析结果：成功映射的记录，以及逐行的错误信息
+pub struct ParsedRows<T> {
+    pub rows: Vec<T>,
+    pub errors: Vec<String>,
+}
+
+/// 按表头解析 CSV，类型错误按行记录而不是中断整个文件
+pub fn parse_rows<T: DeserializeOwned>(content: &str) -> Result<ParsedRows<T>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(content.as_bytes());
+
+    let raw_headers = reader.headers()?.clone();
+    let headers: csv::StringRecord = raw_headers.iter().map(normalize_header).collect();
+
+    let mut parsed = ParsedRows {
+        rows: Vec::new(),
+        errors: Vec::new(),
+    };
+
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                parsed
+                    .errors
+                    .push(format!("第 {} 行: {}", error_line(&e), e));
+                continue;
+            }
+        };
+
+        match record.deserialize::<T>(Some(&headers)) {
+            Ok(row) => parsed.rows.push(row),
+            Err(e) => {
+                let line = record.position().map(|p| p.line()).unwrap_or_default();
+                let message = match e.kind() {
+                    csv::ErrorKind::Deserialize { err, .. } => {
+                        let column = err
+                            .field()
+                            .and_then(|index| raw_headers.get(index as usize))
+                            .map(|name| format!(" {} 列", name))
+                            .unwrap_or_default();
+                        format!("第 {} 行{}: {}", line, column, err.kind())
+                    }
+                    _ => format!("第 {} 行: {}", line, e),
+                };
+                parsed.errors.push(message);
+            }
+        }
+    }
+
+    Ok(parsed)
+}
+
+fn error_line(error: &csv::Error) -> u64 {
+    error.position().map(|p| p.line()).unwrap_or_default()
+}
+
+/// 将表头统一为 camelCase，以匹配智能插入结构的字段名
+fn normalize_header(header: &str) -> String {
+    let mut normalized = String::with_capacity(header.len());
+    let mut upper_next = false;
+
+    for c in header.trim().chars() {
+        if c == '_' || c == '-' || c == ' ' {
+            upper_next = !normalized.is_empty();
+        } else if upper_next {
+            normalized.extend(c.to_uppercase());
+            upper_next = false;
+        } else {
+            normalized.push(c);
+        }
+    }
+
+    normalized
+}
+
+/// 进程 CSV 行：每行一个进程，可附带一条趋势数据
+///
+/// 线程信息无法用单行表示，需要通过 JSON 插入。
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CsvProcessRow {
+    pub server_id: String,
+    pub pid: i32,
+    pub name: String,
+    pub user_name: String,
+    pub status: ProcessState,
+    pub timestamp: Option<i64>,
+    pub cpu_usage: Option<f32>,
+    pub memory_usage: Option<f32>,
+    pub thread_count: Option<i32>,
+    pub server_name: Option<String>,
+    pub server_ip: Option<String>,
+    pub server_os: Option<String>,
+    pub server_status: Option<ServerStatus>,
+}
+
+impl From<CsvProcessRow> for SmartProcessInsert {
+    fn from(row: CsvProcessRow) -> Self {
+        // 只有 CPU 和内存都给出时才生成趋势记录
+        let trend = match (row.cpu_usage, row.memory_usage) {
+            (Some(cpu_usage), Some(memory_usage)) => vec![SmartProcessTrend {
+                cpu_usage,
+                memory_usage,
+                thread_count: row.thread_count.unwrap_or(0),
+            }],
+            _ => Vec::new(),
+        };
+
+        SmartProcessInsert {
+            server_id: row.server_id,
+            pid: row.pid,
+            name: row.name,
+            user_name: row.user_name,
+            status: row.status,
+            timestamp: row
+                .timestamp
+                .unwrap_or_else(|| chrono::Utc::now().timestamp_millis()),
+            trend,
+            threads: Vec::new(),
+            server_name: row.server_name,
+            server_ip: row.server_ip,
+            server_os: row.server_os,
+            server_status: row.server_status,
+        }
+    }
+}
+
+/// 将解析结果交给智能插入逻辑，解析失败的行计入错误
+///
+/// 未开启 `continue_on_error` 时，只要有一行解析失败就整体拒绝并列出所有错误行。
+pub(crate) fn insert_parsed<T, U, S>(
+    store: &mut S,
+    parsed: ParsedRows<T>,
+    options: &crate::InsertOptions,
+    insert: fn(&mut S, Vec<U>, &crate::InsertOptions) -> Result<crate::InsertResult>,
+) -> Result<crate::InsertResult>
+where
+    U: From<T>,
+    S: crate::Store + ?Sized,
+{
+    if !parsed.errors.is_empty() && !options.continue_on_error {
+        return Err(BlackBoxError::parse(
+            "CSV",
+            format!(
+                "有 {} 行无法解析:\n{}",
+                parsed.errors.len(),
+                parsed.errors.join("\n")
+            ),
+        ));
+    }
+
+    let mut result = insert(
+        store,
+        parsed.rows.into_iter().map(U::from).collect(),
+        options,
+    )?;
+    for message in parsed.errors {
+        result.add_error_message(message);
+    }
+
+    Ok(result)
+}
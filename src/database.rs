@@ -1,17 +1,136 @@
+use crate::error::{BlackBoxError, Result};
+use diesel::connection::SimpleConnection;
 use diesel::prelude::*;
 use diesel::sqlite::SqliteConnection;
-use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::env;
 
+use crate::domain::*;
 use crate::models::*;
 
+/// 写入 created_at / updated_at 的当前时间 (UTC)，统一截断到毫秒
+///
+/// 与 SQLite `strftime('%Y-%m-%d %H:%M:%f')` 的精度一致，增量导出按该格式比较时间。
+pub fn db_now() -> chrono::NaiveDateTime {
+    use chrono::{DurationRound, TimeDelta};
+
+    let now = chrono::Utc::now().naive_utc();
+    now.duration_trunc(TimeDelta::milliseconds(1))
+        .unwrap_or(now)
+}
+
 pub fn establish_connection() -> Result<SqliteConnection> {
     establish_connection_with_url(None)
 }
 
+/// 连接参数，每次建立连接时以 PRAGMA 形式应用
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConnectionOptions {
+    /// 日志模式 (WAL、DELETE、TRUNCATE 等)，WAL 允许读写并发
+    pub journal_mode: String,
+    /// 同步级别 (OFF、NORMAL、FULL、EXTRA)
+    pub synchronous: String,
+    /// 遇到锁时 SQLite 内部等待的毫秒数
+    pub busy_timeout_ms: u32,
+    /// 页缓存大小，负数表示 KiB，正数表示页数
+    pub cache_size: i64,
+    /// 内存映射 I/O 的最大字节数，0 表示禁用
+    pub mmap_size: i64,
+    /// 连接池中最多保留的空闲连接数
+    pub max_idle_connections: usize,
+    /// 超过 busy_timeout 后仍然报 SQLITE_BUSY 时的重试次数
+    pub busy_retries: u32,
+    /// 首次重试前的等待毫秒数，之后每次翻倍
+    pub retry_backoff_ms: u64,
+}
+
+impl Default for ConnectionOptions {
+    fn default() -> Self {
+        Self {
+            journal_mode: "WAL".to_string(),
+            synchronous: "NORMAL".to_string(),
+            busy_timeout_ms: 5000,
+            cache_size: -8000,
+            mmap_size: 0,
+            max_idle_connections: 4,
+            busy_retries: 5,
+            retry_backoff_ms: 50,
+        }
+    }
+}
+
+pub fn establish_connection_with_options(
+    database_path: Option<&str>,
+    options: &ConnectionOptions,
+) -> Result<SqliteConnection> {
+    let mut connection = establish_connection_with_url(database_path)?;
+    apply_connection_options(&mut connection, options)?;
+    Ok(connection)
+}
+
+pub fn apply_connection_options(
+    conn: &mut SqliteConnection,
+    options: &ConnectionOptions,
+) -> Result<()> {
+    // busy_timeout 要最先设置，切换 WAL 本身也可能需要等待锁
+    conn.batch_execute(&format!(
+        "PRAGMA busy_timeout = {};
+         PRAGMA journal_mode = {};
+         PRAGMA synchronous = {};
+         PRAGMA cache_size = {};
+         PRAGMA mmap_size = {};",
+        options.busy_timeout_ms,
+        sanitize_pragma_value(&options.journal_mode)?,
+        sanitize_pragma_value(&options.synchronous)?,
+        options.cache_size,
+        options.mmap_size,
+    ))?;
+
+    Ok(())
+}
+
+/// PRAGMA 不支持参数绑定，只允许字母数字形式的取值
+fn sanitize_pragma_value(value: &str) -> Result<&str> {
+    if !value.is_empty() && value.chars().all(|c| c.is_ascii_alphanumeric()) {
+        Ok(value)
+    } else {
+        Err(BlackBoxError::validation(format!(
+            "无效的 PRAGMA 取值: {}",
+            value
+        )))
+    }
+}
+
+/// 判断错误是否由数据库被锁 (SQLITE_BUSY / SQLITE_LOCKED) 引起
+pub fn is_busy_error(error: &BlackBoxError) -> bool {
+    error.is_busy()
+}
+
+/// 在数据库被锁时按指数退避重试操作
+pub fn with_busy_retry<T>(
+    options: &ConnectionOptions,
+    mut op: impl FnMut() -> Result<T>,
+) -> Result<T> {
+    let mut attempt = 0;
+    let mut backoff = options.retry_backoff_ms;
+
+    loop {
+        match op() {
+            Err(e) if attempt < options.busy_retries && is_busy_error(&e) => {
+                attempt += 1;
+                std::thread::sleep(std::time::Duration::from_millis(backoff));
+                backoff = backoff.saturating_mul(2);
+            }
+            result => return result,
+        }
+    }
+}
+
 pub fn establish_connection_with_url(database_path: Option<&str>) -> Result<SqliteConnection> {
     dotenv::dotenv().ok();
-    
+
     let database_url = if let Some(path) = database_path {
         // 如果提供了路径，构造 SQLite URL
         if path.starts_with("sqlite://") {
@@ -22,291 +141,958 @@ pub fn establish_connection_with_url(database_path: Option<&str>) -> Result<Sqli
     } else {
         // 否则从环境变量获取，如果没有则使用默认值
         env::var("DATABASE_URL")
-            .unwrap_or_else(|_| "sqlite://./database.db".to_string())
+            .unwrap_or_else(|_| format!("sqlite://{}", crate::config::DEFAULT_DB_PATH))
     };
-    
-    let connection = SqliteConnection::establish(&database_url)?;
+
+    let mut connection = SqliteConnection::establish(&database_url)?;
+
+    // SQLite 默认不检查外键约束，需要在每个连接上单独开启
+    diesel::sql_query("PRAGMA foreign_keys = ON").execute(&mut connection)?;
+
     Ok(connection)
 }
 
+#[derive(QueryableByName)]
+struct UserVersionRow {
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    user_version: i32,
+}
+
+#[derive(QueryableByName)]
+struct NameRow {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    name: String,
+}
+
+// 数据库结构版本相关操作
+pub fn get_schema_version(conn: &mut SqliteConnection) -> Result<i32> {
+    let row = diesel::sql_query("PRAGMA user_version").get_result::<UserVersionRow>(conn)?;
+
+    Ok(row.user_version)
+}
+
+/// 读取附加数据库 (ATTACH) 的结构版本
+pub fn get_attached_schema_version(conn: &mut SqliteConnection, schema_name: &str) -> Result<i32> {
+    let row = diesel::sql_query(format!("PRAGMA {}.user_version", schema_name))
+        .get_result::<UserVersionRow>(conn)?;
+
+    Ok(row.user_version)
+}
+
+/// 按主键顺序读取附加数据库中的整张表
+pub fn load_attached_rows<T>(
+    conn: &mut SqliteConnection,
+    schema_name: &str,
+    table_name: &str,
+) -> Result<Vec<T>>
+where
+    T: diesel::QueryableByName<diesel::sqlite::Sqlite> + 'static,
+{
+    let rows = diesel::sql_query(format!(
+        "SELECT * FROM {}.{} ORDER BY id",
+        schema_name, table_name
+    ))
+    .load::<T>(conn)?;
+
+    Ok(rows)
+}
+
+pub fn set_schema_version(conn: &mut SqliteConnection, version: i32) -> Result<()> {
+    // PRAGMA 不支持参数绑定，version 为整数，直接拼接是安全的
+    diesel::sql_query(format!("PRAGMA user_version = {}", version)).execute(conn)?;
+
+    Ok(())
+}
+
+pub fn table_exists(conn: &mut SqliteConnection, table_name: &str) -> Result<bool> {
+    let rows =
+        diesel::sql_query("SELECT name FROM sqlite_master WHERE type = 'table' AND name = ?")
+            .bind::<diesel::sql_types::Text, _>(table_name)
+            .load::<NameRow>(conn)?;
+
+    Ok(!rows.is_empty())
+}
+
+pub fn get_table_names(conn: &mut SqliteConnection) -> Result<Vec<String>> {
+    let rows = diesel::sql_query(
+        "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name",
+    )
+    .load::<NameRow>(conn)?;
+
+    Ok(rows.into_iter().map(|r| r.name).collect())
+}
+
+pub fn get_table_columns(
+    conn: &mut SqliteConnection,
+    schema_name: &str,
+    table_name: &str,
+) -> Result<Vec<String>> {
+    // table_info 同时返回 cid、type 等列，这里只取列名
+    let rows = diesel::sql_query(format!("PRAGMA {}.table_info({})", schema_name, table_name))
+        .load::<NameRow>(conn)?;
+
+    Ok(rows.into_iter().map(|r| r.name).collect())
+}
+
+/// 外键：子表的 `column` 引用父表 `parent` 的 `parent_column`
+#[derive(Debug, Clone, PartialEq, QueryableByName)]
+pub struct ForeignKey {
+    #[diesel(sql_type = diesel::sql_types::Text, column_name = from)]
+    pub column: String,
+    #[diesel(sql_type = diesel::sql_types::Text, column_name = table)]
+    pub parent: String,
+    #[diesel(sql_type = diesel::sql_types::Text, column_name = to)]
+    pub parent_column: String,
+}
+
+pub fn get_foreign_keys(conn: &mut SqliteConnection, table_name: &str) -> Result<Vec<ForeignKey>> {
+    Ok(
+        diesel::sql_query(format!("PRAGMA foreign_key_list({})", table_name))
+            .load::<ForeignKey>(conn)?,
+    )
+}
+
+/// 全部表及其外键，按外键依赖顺序排列 (父表在前，同一层级按表名排序)
+pub fn get_tables_by_dependency(
+    conn: &mut SqliteConnection,
+) -> Result<Vec<(String, Vec<ForeignKey>)>> {
+    let mut remaining = Vec::new();
+    for table in get_table_names(conn)? {
+        let foreign_keys = get_foreign_keys(conn, &table)?;
+        remaining.push((table, foreign_keys));
+    }
+
+    let mut ordered: Vec<(String, Vec<ForeignKey>)> = Vec::with_capacity(remaining.len());
+    while !remaining.is_empty() {
+        let ready = remaining.iter().position(|(table, foreign_keys)| {
+            foreign_keys.iter().all(|fk| {
+                fk.parent == *table || ordered.iter().any(|(placed, _)| *placed == fk.parent)
+            })
+        });
+        // 外键成环 (或引用了不存在的表) 时按剩余顺序排在最后
+        let index = ready.unwrap_or(0);
+        ordered.push(remaining.remove(index));
+    }
+
+    Ok(ordered)
+}
+
+#[derive(QueryableByName)]
+struct MessageRow {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    message: String,
+}
+
+#[derive(QueryableByName)]
+struct CountRow {
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    count: i64,
+}
+
+#[derive(QueryableByName)]
+struct RowIdRow {
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    rowid: i64,
+}
+
+/// 执行 SQLite 物理完整性检查，全部正常时返回 `["ok"]`
+pub fn run_integrity_check(conn: &mut SqliteConnection, quick: bool) -> Result<Vec<String>> {
+    let sql = if quick {
+        "SELECT quick_check AS message FROM pragma_quick_check"
+    } else {
+        "SELECT integrity_check AS message FROM pragma_integrity_check"
+    };
+    let rows = diesel::sql_query(sql).load::<MessageRow>(conn)?;
+
+    Ok(rows.into_iter().map(|r| r.message).collect())
+}
+
+pub fn count_rows_where(
+    conn: &mut SqliteConnection,
+    table_name: &str,
+    predicate: &str,
+) -> Result<usize> {
+    let row = diesel::sql_query(format!(
+        "SELECT COUNT(*) AS count FROM {} WHERE {}",
+        table_name, predicate
+    ))
+    .get_result::<CountRow>(conn)?;
+
+    Ok(row.count as usize)
+}
+
+pub fn delete_rows_where(
+    conn: &mut SqliteConnection,
+    table_name: &str,
+    predicate: &str,
+) -> Result<usize> {
+    let deleted_count =
+        diesel::sql_query(format!("DELETE FROM {} WHERE {}", table_name, predicate))
+            .execute(conn)?;
+
+    Ok(deleted_count)
+}
+
+/// 把列中的每个不同取值改写为 `canonical` 返回的写法，返回改写的行数
+pub fn normalize_column(
+    conn: &mut SqliteConnection,
+    table_name: &str,
+    column: &str,
+    canonical: impl Fn(&str) -> String,
+) -> Result<usize> {
+    use diesel::sql_types::Text;
+
+    let values = diesel::sql_query(format!(
+        "SELECT DISTINCT {} AS name FROM {}",
+        column, table_name
+    ))
+    .load::<NameRow>(conn)?;
+
+    let mut updated = 0;
+    for value in values {
+        let normalized = canonical(&value.name);
+        if normalized != value.name {
+            updated += diesel::sql_query(format!(
+                "UPDATE {} SET {} = ? WHERE {} = ?",
+                table_name, column, column
+            ))
+            .bind::<Text, _>(&normalized)
+            .bind::<Text, _>(&value.name)
+            .execute(conn)?;
+        }
+    }
+
+    Ok(updated)
+}
+
+pub fn load_rowids(conn: &mut SqliteConnection, table_name: &str) -> Result<Vec<i64>> {
+    let rows =
+        diesel::sql_query(format!("SELECT rowid FROM {}", table_name)).load::<RowIdRow>(conn)?;
+
+    Ok(rows.into_iter().map(|r| r.rowid).collect())
+}
+
+pub fn get_max_rowid(conn: &mut SqliteConnection, table_name: &str) -> Result<i64> {
+    // MAX(rowid) 只读取 B 树最右侧的路径，页损坏时通常仍能成功
+    let row = diesel::sql_query(format!(
+        "SELECT COALESCE(MAX(rowid), 0) AS rowid FROM {}",
+        table_name
+    ))
+    .get_result::<RowIdRow>(conn)?;
+
+    Ok(row.rowid)
+}
+
+pub fn get_user_index_names(conn: &mut SqliteConnection) -> Result<Vec<String>> {
+    // sql 为 NULL 的是 SQLite 为 UNIQUE/PRIMARY KEY 自动创建的索引，不能手动删除
+    let rows = diesel::sql_query(
+        "SELECT name FROM sqlite_master WHERE type = 'index' AND sql IS NOT NULL",
+    )
+    .load::<NameRow>(conn)?;
+
+    Ok(rows.into_iter().map(|r| r.name).collect())
+}
+
 pub fn create_server(conn: &mut SqliteConnection, new_server: &NewServer) -> Result<Server> {
     use crate::schema::servers::dsl::*;
-    
+
+    let now = db_now();
     diesel::insert_into(servers)
-        .values(new_server)
+        .values((new_server, created_at.eq(now), updated_at.eq(now)))
         .execute(conn)?;
-    
+
     // SQLite 不支持 RETURNING，所以需要单独查询
     let server = servers
         .filter(server_id.eq(&new_server.server_id))
         .first::<Server>(conn)?;
-    
+
     Ok(server)
 }
 
-pub fn get_server_by_id(conn: &mut SqliteConnection, server_id_param: &str) -> Result<Option<Server>> {
+pub fn get_server_by_id(
+    conn: &mut SqliteConnection,
+    server_id_param: &str,
+) -> Result<Option<Server>> {
     use crate::schema::servers::dsl::*;
-    
+
     let server = servers
         .filter(server_id.eq(server_id_param))
         .first::<Server>(conn)
         .optional()?;
-    
+
     Ok(server)
 }
 
 pub fn get_all_servers(conn: &mut SqliteConnection) -> Result<Vec<Server>> {
     use crate::schema::servers::dsl::*;
-    
-    let results = servers
-        .load::<Server>(conn)?;
-    
+
+    let results = servers.load::<Server>(conn)?;
+
     Ok(results)
 }
 
-pub fn update_server_status(conn: &mut SqliteConnection, server_id_param: &str, new_status: &str) -> Result<Server> {
+pub fn update_server_status(
+    conn: &mut SqliteConnection,
+    server_id_param: &str,
+    new_status: &ServerStatus,
+) -> Result<Server> {
     use crate::schema::servers::dsl::*;
-    
+
     diesel::update(servers.filter(server_id.eq(server_id_param)))
-        .set((server_status.eq(new_status), updated_at.eq(chrono::Utc::now().naive_utc())))
+        .set((server_status.eq(new_status), updated_at.eq(db_now())))
         .execute(conn)?;
-    
+
     // SQLite 不支持 RETURNING，所以需要单独查询
     let server = servers
         .filter(server_id.eq(server_id_param))
         .first::<Server>(conn)?;
-    
+
     Ok(server)
 }
 
-pub fn create_system_metric(conn: &mut SqliteConnection, new_metric: &NewSystemMetric) -> Result<()> {
+pub fn create_system_metric(
+    conn: &mut SqliteConnection,
+    new_metric: &NewSystemMetric,
+) -> Result<()> {
     use crate::schema::system_metrics::dsl::*;
-    
+
     diesel::insert_into(system_metrics)
         .values(new_metric)
         .execute(conn)?;
-    
+
     Ok(())
 }
 
-pub fn get_metrics_by_server(conn: &mut SqliteConnection, server_id_param: &str, limit: Option<i64>) -> Result<Vec<SystemMetric>> {
+pub fn get_metrics_by_server(
+    conn: &mut SqliteConnection,
+    server_id_param: &str,
+    limit: Option<i64>,
+) -> Result<Vec<SystemMetric>> {
     use crate::schema::system_metrics::dsl::*;
-    
+
     let mut query = system_metrics
         .filter(server_id.eq(server_id_param))
         .order(timestamp.desc())
         .into_boxed();
-    
+
     if let Some(limit_val) = limit {
         query = query.limit(limit_val);
     }
-    
+
     let results = query.load::<SystemMetric>(conn)?;
     Ok(results)
 }
 
 pub fn get_metrics_by_time_range(
-    conn: &mut SqliteConnection, 
-    server_id_param: &str, 
-    start_time: i64, 
-    end_time: i64
+    conn: &mut SqliteConnection,
+    server_id_param: &str,
+    start_time: i64,
+    end_time: i64,
 ) -> Result<Vec<SystemMetric>> {
     use crate::schema::system_metrics::dsl::*;
-    
+
     let results = system_metrics
         .filter(server_id.eq(server_id_param))
         .filter(timestamp.between(start_time, end_time))
         .order(timestamp.asc())
         .load::<SystemMetric>(conn)?;
-    
+
     Ok(results)
 }
 
 pub fn delete_old_metrics(conn: &mut SqliteConnection, before_timestamp: i64) -> Result<usize> {
     use crate::schema::system_metrics::dsl::*;
-    
-    let deleted_count = diesel::delete(system_metrics.filter(timestamp.lt(before_timestamp)))
-        .execute(conn)?;
-    
+
+    let deleted_count =
+        diesel::delete(system_metrics.filter(timestamp.lt(before_timestamp))).execute(conn)?;
+
     Ok(deleted_count)
 }
 
 // 进程相关操作
 pub fn create_process(conn: &mut SqliteConnection, new_process: &NewProcess) -> Result<()> {
     use crate::schema::processes::dsl::*;
-    
+
     diesel::insert_into(processes)
         .values(new_process)
         .execute(conn)?;
-    
+
     Ok(())
 }
 
-pub fn get_processes_by_server(conn: &mut SqliteConnection, server_id_param: &str) -> Result<Vec<Process>> {
+pub fn get_processes_by_server(
+    conn: &mut SqliteConnection,
+    server_id_param: &str,
+) -> Result<Vec<Process>> {
     use crate::schema::processes::dsl::*;
-    
+
     let results = processes
         .filter(server_id.eq(server_id_param))
         .load::<Process>(conn)?;
-    
+
     Ok(results)
 }
 
 // 进程趋势相关操作
-pub fn create_process_trend(conn: &mut SqliteConnection, new_trend: &NewProcessTrend) -> Result<()> {
+pub fn create_process_trend(
+    conn: &mut SqliteConnection,
+    new_trend: &NewProcessTrend,
+) -> Result<()> {
     use crate::schema::process_trends::dsl::*;
-    
+
     diesel::insert_into(process_trends)
         .values(new_trend)
         .execute(conn)?;
-    
+
     Ok(())
 }
 
-pub fn get_process_trends(conn: &mut SqliteConnection, server_id_param: &str, pid_param: i32) -> Result<Vec<ProcessTrend>> {
+pub fn get_process_trends(
+    conn: &mut SqliteConnection,
+    server_id_param: &str,
+    pid_param: i32,
+) -> Result<Vec<ProcessTrend>> {
     use crate::schema::process_trends::dsl::*;
-    
+
     let results = process_trends
         .filter(server_id.eq(server_id_param))
         .filter(pid.eq(pid_param))
         .order(timestamp.desc())
         .load::<ProcessTrend>(conn)?;
-    
+
     Ok(results)
 }
 
+pub fn get_process_trend_by_timestamp(
+    conn: &mut SqliteConnection,
+    server_id_param: &str,
+    pid_param: i32,
+    timestamp_param: i64,
+) -> Result<Option<ProcessTrend>> {
+    use crate::schema::process_trends::dsl::*;
+
+    let result = process_trends
+        .filter(server_id.eq(server_id_param))
+        .filter(pid.eq(pid_param))
+        .filter(timestamp.eq(timestamp_param))
+        .first::<ProcessTrend>(conn)
+        .optional()?;
+
+    Ok(result)
+}
+
+pub fn update_process_trend(
+    conn: &mut SqliteConnection,
+    trend_id: i32,
+    new_trend: &NewProcessTrend,
+) -> Result<()> {
+    use crate::schema::process_trends::dsl::*;
+
+    diesel::update(process_trends.filter(id.eq(trend_id)))
+        .set((
+            cpu_usage.eq(new_trend.cpu_usage),
+            memory_usage.eq(new_trend.memory_usage),
+            thread_count.eq(new_trend.thread_count),
+            updated_at.eq(db_now()),
+        ))
+        .execute(conn)?;
+
+    Ok(())
+}
+
 // 线程相关操作
 pub fn create_thread(conn: &mut SqliteConnection, new_thread: &NewThread) -> Result<()> {
     use crate::schema::threads::dsl::*;
-    
+
     diesel::insert_into(threads)
         .values(new_thread)
         .execute(conn)?;
-    
+
     Ok(())
 }
 
-pub fn get_threads_by_process(conn: &mut SqliteConnection, server_id_param: &str, pid_param: i32) -> Result<Vec<Thread>> {
+pub fn get_threads_by_process(
+    conn: &mut SqliteConnection,
+    server_id_param: &str,
+    pid_param: i32,
+) -> Result<Vec<Thread>> {
     use crate::schema::threads::dsl::*;
-    
+
     let results = threads
         .filter(server_id.eq(server_id_param))
         .filter(pid.eq(pid_param))
         .load::<Thread>(conn)?;
-    
+
     Ok(results)
 }
 
 // 崩溃日志相关操作
 pub fn create_crash_log(conn: &mut SqliteConnection, new_log: &NewCrashLog) -> Result<i32> {
     use crate::schema::crash_logs::dsl::*;
-    
+
     diesel::insert_into(crash_logs)
         .values(new_log)
         .execute(conn)?;
-    
+
     // 获取插入的记录ID
     let log = crash_logs
         .filter(server_id.eq(&new_log.server_id))
         .filter(log_id.eq(new_log.log_id))
         .first::<CrashLog>(conn)?;
-    
+
     Ok(log.id)
 }
 
-pub fn get_crash_logs_by_server(conn: &mut SqliteConnection, server_id_param: &str) -> Result<Vec<CrashLog>> {
+pub fn get_crash_logs_by_server(
+    conn: &mut SqliteConnection,
+    server_id_param: &str,
+) -> Result<Vec<CrashLog>> {
     use crate::schema::crash_logs::dsl::*;
-    
+
     let results = crash_logs
         .filter(server_id.eq(server_id_param))
         .order(timestamp.desc())
         .load::<CrashLog>(conn)?;
-    
+
     Ok(results)
 }
 
 pub fn get_unresolved_crash_logs(conn: &mut SqliteConnection) -> Result<Vec<CrashLog>> {
     use crate::schema::crash_logs::dsl::*;
-    
+
     let results = crash_logs
         .filter(resolved.eq(false))
         .order(timestamp.desc())
         .load::<CrashLog>(conn)?;
-    
+
     Ok(results)
 }
 
 // AI 建议相关操作
-pub fn create_ai_recommendation(conn: &mut SqliteConnection, new_recommendation: &NewAiRecommendation) -> Result<()> {
+pub fn create_ai_recommendation(
+    conn: &mut SqliteConnection,
+    new_recommendation: &NewAiRecommendation,
+) -> Result<()> {
     use crate::schema::ai_recommendations::dsl::*;
-    
+
     diesel::insert_into(ai_recommendations)
         .values(new_recommendation)
         .execute(conn)?;
-    
+
     Ok(())
 }
 
-pub fn get_recommendations_by_crash_log(conn: &mut SqliteConnection, crash_log_id_param: i32) -> Result<Vec<AiRecommendation>> {
+pub fn get_recommendations_by_crash_log(
+    conn: &mut SqliteConnection,
+    crash_log_id_param: i32,
+) -> Result<Vec<AiRecommendation>> {
     use crate::schema::ai_recommendations::dsl::*;
-    
+
     let results = ai_recommendations
         .filter(crash_log_id.eq(crash_log_id_param))
-        .order(priority.asc())
+        .order((priority.asc(), id.asc()))
         .load::<AiRecommendation>(conn)?;
-    
+
     Ok(results)
 }
+
+pub fn delete_recommendations_by_crash_log(
+    conn: &mut SqliteConnection,
+    crash_log_id_param: i32,
+) -> Result<()> {
+    use crate::schema::ai_recommendations::dsl::*;
+
+    diesel::delete(ai_recommendations.filter(crash_log_id.eq(crash_log_id_param))).execute(conn)?;
+
+    Ok(())
+}
+
+// 聚合与批量查询
+
+/// 单次 IN (...) 查询绑定的最大参数数量，低于旧版 SQLite 的 999 个变量限制
+const MAX_IN_PARAMS: usize = 500;
+
+/// 按服务器聚合系统指标：(server_id, 记录数, 最新时间戳)
+pub fn get_metric_summary_by_server(
+    conn: &mut SqliteConnection,
+) -> Result<HashMap<String, (i64, Option<i64>)>> {
+    use crate::schema::system_metrics::dsl::*;
+    use diesel::dsl::count_star;
+
+    let rows = system_metrics
+        .group_by(server_id)
+        .select((server_id, count_star(), diesel::dsl::max(timestamp)))
+        .load::<(String, i64, Option<i64>)>(conn)?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(sid, count, latest)| (sid, (count, latest)))
+        .collect())
+}
+
+/// 按服务器统计进程数
+pub fn count_processes_by_server(conn: &mut SqliteConnection) -> Result<HashMap<String, i64>> {
+    use crate::schema::processes::dsl::*;
+    use diesel::dsl::count_star;
+
+    let rows = processes
+        .group_by(server_id)
+        .select((server_id, count_star()))
+        .load::<(String, i64)>(conn)?;
+
+    Ok(rows.into_iter().collect())
+}
+
+/// 按服务器统计崩溃日志数
+pub fn count_crash_logs_by_server(conn: &mut SqliteConnection) -> Result<HashMap<String, i64>> {
+    use crate::schema::crash_logs::dsl::*;
+    use diesel::dsl::count_star;
+
+    let rows = crash_logs
+        .group_by(server_id)
+        .select((server_id, count_star()))
+        .load::<(String, i64)>(conn)?;
+
+    Ok(rows.into_iter().collect())
+}
+
+/// 批量加载同一服务器下多个进程的趋势数据，按 pid 分组，组内按时间倒序
+pub fn get_process_trends_by_pids(
+    conn: &mut SqliteConnection,
+    server_id_param: &str,
+    pids: &[i32],
+) -> Result<HashMap<i32, Vec<ProcessTrend>>> {
+    use crate::schema::process_trends::dsl::*;
+
+    let mut grouped: HashMap<i32, Vec<ProcessTrend>> = HashMap::new();
+    for chunk in pids.chunks(MAX_IN_PARAMS) {
+        let rows = process_trends
+            .filter(server_id.eq(server_id_param))
+            .filter(pid.eq_any(chunk))
+            .order(timestamp.desc())
+            .load::<ProcessTrend>(conn)?;
+        for row in rows {
+            grouped.entry(row.pid).or_default().push(row);
+        }
+    }
+
+    Ok(grouped)
+}
+
+/// 批量加载同一服务器下多个进程的线程信息，按 pid 分组
+pub fn get_threads_by_pids(
+    conn: &mut SqliteConnection,
+    server_id_param: &str,
+    pids: &[i32],
+) -> Result<HashMap<i32, Vec<Thread>>> {
+    use crate::schema::threads::dsl::*;
+
+    let mut grouped: HashMap<i32, Vec<Thread>> = HashMap::new();
+    for chunk in pids.chunks(MAX_IN_PARAMS) {
+        let rows = threads
+            .filter(server_id.eq(server_id_param))
+            .filter(pid.eq_any(chunk))
+            .load::<Thread>(conn)?;
+        for row in rows {
+            grouped.entry(row.pid).or_default().push(row);
+        }
+    }
+
+    Ok(grouped)
+}
+
+/// 批量加载多条崩溃日志的 AI 建议，按 crash_log_id 分组，组内按优先级排序
+pub fn get_recommendations_by_crash_logs(
+    conn: &mut SqliteConnection,
+    crash_log_ids: &[i32],
+) -> Result<HashMap<i32, Vec<AiRecommendation>>> {
+    use crate::schema::ai_recommendations::dsl::*;
+
+    let mut grouped: HashMap<i32, Vec<AiRecommendation>> = HashMap::new();
+    for chunk in crash_log_ids.chunks(MAX_IN_PARAMS) {
+        let rows = ai_recommendations
+            .filter(crash_log_id.eq_any(chunk))
+            .order(priority.asc())
+            .load::<AiRecommendation>(conn)?;
+        for row in rows {
+            grouped.entry(row.crash_log_id).or_default().push(row);
+        }
+    }
+
+    Ok(grouped)
+}
+
+// 指标暴露相关查询
+
+/// 每个服务器最新的一条系统指标
+pub fn get_latest_metrics_per_server(
+    conn: &mut SqliteConnection,
+) -> Result<Vec<LatestServerMetric>> {
+    let results = diesel::sql_query(
+        "SELECT s.server_id, s.server_name, m.timestamp, m.cpu_usage, m.memory_usage, m.disk_usage,
+                m.io_read, m.io_write, m.network_in, m.network_out
+         FROM servers s
+         JOIN system_metrics m ON m.id = (
+             SELECT id FROM system_metrics
+             WHERE server_id = s.server_id
+             ORDER BY timestamp DESC, id DESC
+             LIMIT 1
+         )
+         ORDER BY s.server_id",
+    )
+    .load::<LatestServerMetric>(conn)?;
+
+    Ok(results)
+}
+
+/// 每个进程最新的一条趋势数据
+pub fn get_latest_trends_per_process(
+    conn: &mut SqliteConnection,
+) -> Result<Vec<LatestProcessTrend>> {
+    let results = diesel::sql_query(
+        "SELECT p.server_id, s.server_name, p.pid, p.name, p.user_name,
+                t.timestamp, t.cpu_usage, t.memory_usage, t.thread_count
+         FROM processes p
+         JOIN servers s ON s.server_id = p.server_id
+         JOIN process_trends t ON t.id = (
+             SELECT id FROM process_trends
+             WHERE server_id = p.server_id AND pid = p.pid
+             ORDER BY timestamp DESC, id DESC
+             LIMIT 1
+         )
+         ORDER BY p.server_id, p.pid, p.name",
+    )
+    .load::<LatestProcessTrend>(conn)?;
+
+    Ok(results)
+}
+
+/// 按服务器、崩溃类型、严重性和解决状态统计崩溃日志
+pub fn count_crash_logs_by_kind(conn: &mut SqliteConnection) -> Result<Vec<CrashLogCount>> {
+    let results = diesel::sql_query(
+        "SELECT c.server_id, s.server_name, c.crash_type, c.severity, c.resolved, COUNT(*) AS count
+         FROM crash_logs c
+         JOIN servers s ON s.server_id = c.server_id
+         GROUP BY c.server_id, s.server_name, c.crash_type, c.severity, c.resolved
+         ORDER BY c.server_id, c.crash_type, c.severity, c.resolved",
+    )
+    .load::<CrashLogCount>(conn)?;
+
+    Ok(results)
+}
+
+/// 全部进程趋势及其进程名、用户，按服务器、pid 和时间排序
+///
+/// 同一 pid 对应多个进程记录时取最新创建的一条。
+pub fn get_named_process_trends(conn: &mut SqliteConnection) -> Result<Vec<NamedProcessTrend>> {
+    let results = diesel::sql_query(
+        "SELECT t.server_id, t.pid, p.name, p.user_name,
+                t.timestamp, t.cpu_usage, t.memory_usage, t.thread_count
+         FROM process_trends t
+         JOIN processes p ON p.id = (
+             SELECT id FROM processes
+             WHERE server_id = t.server_id AND pid = t.pid
+             ORDER BY id DESC
+             LIMIT 1
+         )
+         ORDER BY t.server_id, t.pid, t.timestamp",
+    )
+    .load::<NamedProcessTrend>(conn)?;
+
+    Ok(results)
+}
+
+// 服务器标签
+pub fn set_server_label(
+    conn: &mut SqliteConnection,
+    server_id_param: &str,
+    key: &str,
+    value: &str,
+) -> Result<()> {
+    use crate::schema::server_labels::dsl::*;
+
+    let new_label = NewServerLabel {
+        server_id: server_id_param.to_string(),
+        label_key: key.to_string(),
+        label_value: value.to_string(),
+    };
+
+    let now = db_now();
+    diesel::insert_into(server_labels)
+        .values((&new_label, created_at.eq(now), updated_at.eq(now)))
+        .on_conflict((server_id, label_key))
+        .do_update()
+        .set((label_value.eq(value), updated_at.eq(now)))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+pub fn remove_server_label(
+    conn: &mut SqliteConnection,
+    server_id_param: &str,
+    key: &str,
+) -> Result<bool> {
+    use crate::schema::server_labels::dsl::*;
+
+    let deleted = diesel::delete(
+        server_labels
+            .filter(server_id.eq(server_id_param))
+            .filter(label_key.eq(key)),
+    )
+    .execute(conn)?;
+
+    Ok(deleted > 0)
+}
+
+/// 获取标签，按服务器和标签键排序；`server_id_param` 为 None 时返回全部服务器的标签
+pub fn get_server_labels(
+    conn: &mut SqliteConnection,
+    server_id_param: Option<&str>,
+) -> Result<Vec<ServerLabel>> {
+    use crate::schema::server_labels::dsl::*;
+
+    let mut query = server_labels
+        .order((server_id.asc(), label_key.asc()))
+        .into_boxed();
+    if let Some(server) = server_id_param {
+        query = query.filter(server_id.eq(server));
+    }
+
+    Ok(query.load::<ServerLabel>(conn)?)
+}
+
+/// 同时具有全部给定标签的服务器 ID
+pub fn get_server_ids_with_labels(
+    conn: &mut SqliteConnection,
+    labels: &[(String, String)],
+) -> Result<Vec<String>> {
+    use crate::schema::server_labels::dsl::*;
+
+    let mut matched: Option<Vec<String>> = None;
+    for (key, value) in labels {
+        let ids = server_labels
+            .filter(label_key.eq(key))
+            .filter(label_value.eq(value))
+            .select(server_id)
+            .load::<String>(conn)?;
+        matched = Some(match matched {
+            Some(previous) => previous
+                .into_iter()
+                .filter(|candidate| ids.contains(candidate))
+                .collect(),
+            None => ids,
+        });
+    }
+
+    Ok(matched.unwrap_or_default())
+}
+
 // 导出功能
 pub fn export_all_data(conn: &mut SqliteConnection) -> Result<ExportData> {
-    let servers = get_all_servers(conn)?;
+    export_filtered_data(conn, &ExportFilter::default())
+}
+
+/// 按服务器、标签、时间范围和数据类别导出
+///
+/// 时间范围作用于系统指标、进程趋势和崩溃日志；指定时间范围时，
+/// 范围内没有趋势数据的进程不会被导出。
+pub fn export_filtered_data(
+    conn: &mut SqliteConnection,
+    filter: &ExportFilter,
+) -> Result<ExportData> {
+    let mut servers = get_all_servers(conn)?;
+    if !filter.server_ids.is_empty() {
+        servers.retain(|s| filter.server_ids.contains(&s.server_id));
+    }
+    if !filter.labels.is_empty() {
+        let labelled = get_server_ids_with_labels(conn, &filter.labels)?;
+        servers.retain(|s| labelled.contains(&s.server_id));
+    }
+
+    let has_time_range = filter.from.is_some() || filter.to.is_some();
     let mut export_servers = Vec::new();
-    
+
     for server in servers {
+        let labels = get_server_labels(conn, Some(&server.server_id))?
+            .into_iter()
+            .map(|l| (l.label_key, l.label_value))
+            .collect();
+
         // 获取系统指标
-        let metrics = get_metrics_by_server(conn, &server.server_id, None)?;
-        let export_metrics: Vec<ExportSystemMetric> = metrics.into_iter().map(|m| ExportSystemMetric {
-            timestamp: m.timestamp,
-            cpu_usage: m.cpu_usage,
-            memory_usage: m.memory_usage,
-            disk_usage: m.disk_usage,
-            io_read: m.io_read,
-            io_write: m.io_write,
-            network_in: m.network_in,
-            network_out: m.network_out,
-        }).collect();
-        
+        let metrics = if !filter.includes(ExportEntity::Metrics) {
+            Vec::new()
+        } else if has_time_range {
+            get_metrics_by_time_range(
+                conn,
+                &server.server_id,
+                filter.from.unwrap_or(i64::MIN),
+                filter.to.unwrap_or(i64::MAX),
+            )?
+        } else {
+            get_metrics_by_server(conn, &server.server_id, None)?
+        };
+        let export_metrics: Vec<ExportSystemMetric> = metrics
+            .into_iter()
+            .map(|m| ExportSystemMetric {
+                timestamp: m.timestamp,
+                cpu_usage: m.cpu_usage,
+                memory_usage: m.memory_usage,
+                disk_usage: m.disk_usage,
+                io_read: m.io_read,
+                io_write: m.io_write,
+                network_in: m.network_in,
+                network_out: m.network_out,
+            })
+            .collect();
+
         // 获取进程信息
-        let processes = get_processes_by_server(conn, &server.server_id)?;
+        let processes = if filter.includes(ExportEntity::Processes) {
+            get_processes_by_server(conn, &server.server_id)?
+        } else {
+            Vec::new()
+        };
         let mut export_processes = Vec::new();
-        
+
         for process in processes {
             // 获取进程趋势
-            let trends = get_process_trends(conn, &server.server_id, process.pid)?;
-            let export_trends: Vec<ExportProcessTrend> = trends.into_iter().map(|t| ExportProcessTrend {
-                timestamp: t.timestamp,
-                cpu_usage: t.cpu_usage,
-                memory_usage: t.memory_usage,
-                thread_count: t.thread_count,
-            }).collect();
-            
+            let mut trends = get_process_trends(conn, &server.server_id, process.pid)?;
+            if has_time_range {
+                trends.retain(|t| filter.in_range(t.timestamp));
+                if trends.is_empty() {
+                    continue;
+                }
+            }
+            let export_trends: Vec<ExportProcessTrend> = trends
+                .into_iter()
+                .map(|t| ExportProcessTrend {
+                    timestamp: t.timestamp,
+                    cpu_usage: t.cpu_usage,
+                    memory_usage: t.memory_usage,
+                    thread_count: t.thread_count,
+                })
+                .collect();
+
             // 获取线程信息
             let threads = get_threads_by_process(conn, &server.server_id, process.pid)?;
-            let export_threads: Vec<ExportThread> = threads.into_iter().map(|t| ExportThread {
-                thread_id: t.thread_id,
-                user_name: t.user_name,
-                priority: t.priority,
-                nice_value: t.nice_value,
-                virtual_memory: t.virtual_memory,
-                resident_memory: t.resident_memory,
-                shared_memory: t.shared_memory,
-                status: t.status,
-                cpu_usage: t.cpu_usage,
-                memory_usage: t.memory_usage,
-                runtime: t.runtime,
-                command: t.command,
-            }).collect();
-            
+            let export_threads: Vec<ExportThread> = threads
+                .into_iter()
+                .map(|t| ExportThread {
+                    thread_id: t.thread_id,
+                    user_name: t.user_name,
+                    priority: t.priority,
+                    nice_value: t.nice_value,
+                    virtual_memory: t.virtual_memory,
+                    resident_memory: t.resident_memory,
+                    shared_memory: t.shared_memory,
+                    status: t.status,
+                    cpu_usage: t.cpu_usage,
+                    memory_usage: t.memory_usage,
+                    runtime: t.runtime,
+                    command: t.command,
+                })
+                .collect();
+
             export_processes.push(ExportProcess {
                 pid: process.pid,
                 name: process.name,
@@ -316,20 +1102,28 @@ pub fn export_all_data(conn: &mut SqliteConnection) -> Result<ExportData> {
                 threads: export_threads,
             });
         }
-        
+
         // 获取崩溃日志
-        let crash_logs = get_crash_logs_by_server(conn, &server.server_id)?;
+        let mut crash_logs = if filter.includes(ExportEntity::Crashes) {
+            get_crash_logs_by_server(conn, &server.server_id)?
+        } else {
+            Vec::new()
+        };
+        crash_logs.retain(|log| filter.in_range(log.timestamp));
         let mut export_crash_logs = Vec::new();
-        
+
         for log in crash_logs {
             // 获取 AI 建议
             let recommendations = get_recommendations_by_crash_log(conn, log.id)?;
-            let export_recommendations: Vec<ExportRecommendation> = recommendations.into_iter().map(|r| ExportRecommendation {
-                priority: r.priority,
-                action: r.action,
-                command: r.command,
-            }).collect();
-            
+            let export_recommendations: Vec<ExportRecommendation> = recommendations
+                .into_iter()
+                .map(|r| ExportRecommendation {
+                    priority: r.priority,
+                    action: r.action,
+                    command: r.command,
+                })
+                .collect();
+
             export_crash_logs.push(ExportCrashLog {
                 id: log.log_id,
                 timestamp: log.timestamp,
@@ -346,102 +1140,195 @@ pub fn export_all_data(conn: &mut SqliteConnection) -> Result<ExportData> {
                 },
             });
         }
-        
+
         export_servers.push(ExportServer {
             server_id: server.server_id,
             server_name: server.server_name,
             server_ip: server.server_ip,
             server_os: server.server_os,
             server_status: server.server_status,
+            labels,
             system_metrics: export_metrics,
             processes: export_processes,
             crash_logs: export_crash_logs,
         });
     }
-    
+
     Ok(ExportData {
         servers: export_servers,
     })
 }
 
 // 智能插入相关的数据库操作
-pub fn get_system_metric_by_timestamp(conn: &mut SqliteConnection, server_id_param: &str, timestamp_param: i64) -> Result<Option<SystemMetric>> {
+pub fn get_system_metric_by_timestamp(
+    conn: &mut SqliteConnection,
+    server_id_param: &str,
+    timestamp_param: i64,
+) -> Result<Option<SystemMetric>> {
     use crate::schema::system_metrics::dsl::*;
-    
+
     let metric = system_metrics
         .filter(server_id.eq(server_id_param))
         .filter(timestamp.eq(timestamp_param))
         .first::<SystemMetric>(conn)
         .optional()?;
-    
+
     Ok(metric)
 }
 
-pub fn update_system_metric(conn: &mut SqliteConnection, server_id_param: &str, timestamp_param: i64, new_metric: &NewSystemMetric) -> Result<()> {
+pub fn update_system_metric(
+    conn: &mut SqliteConnection,
+    server_id_param: &str,
+    timestamp_param: i64,
+    new_metric: &NewSystemMetric,
+) -> Result<()> {
     use crate::schema::system_metrics::dsl::*;
-    
-    diesel::update(system_metrics.filter(server_id.eq(server_id_param)).filter(timestamp.eq(timestamp_param)))
-        .set((
-            cpu_usage.eq(new_metric.cpu_usage),
-            memory_usage.eq(new_metric.memory_usage),
-            disk_usage.eq(new_metric.disk_usage),
-            io_read.eq(new_metric.io_read),
-            io_write.eq(new_metric.io_write),
-            network_in.eq(new_metric.network_in),
-            network_out.eq(new_metric.network_out),
-        ))
-        .execute(conn)?;
-    
+
+    diesel::update(
+        system_metrics
+            .filter(server_id.eq(server_id_param))
+            .filter(timestamp.eq(timestamp_param)),
+    )
+    .set((
+        cpu_usage.eq(new_metric.cpu_usage),
+        memory_usage.eq(new_metric.memory_usage),
+        disk_usage.eq(new_metric.disk_usage),
+        io_read.eq(new_metric.io_read),
+        io_write.eq(new_metric.io_write),
+        network_in.eq(new_metric.network_in),
+        network_out.eq(new_metric.network_out),
+        updated_at.eq(db_now()),
+    ))
+    .execute(conn)?;
+
     Ok(())
 }
 
-pub fn get_process_by_name_and_user(conn: &mut SqliteConnection, server_id_param: &str, name_param: &str, user_name_param: &str) -> Result<Option<Process>> {
+pub fn get_process_by_name_and_user(
+    conn: &mut SqliteConnection,
+    server_id_param: &str,
+    name_param: &str,
+    user_name_param: &str,
+) -> Result<Option<Process>> {
     use crate::schema::processes::dsl::*;
-    
+
     let process = processes
         .filter(server_id.eq(server_id_param))
         .filter(name.eq(name_param))
         .filter(user_name.eq(user_name_param))
         .first::<Process>(conn)
         .optional()?;
-    
+
     Ok(process)
 }
 
-pub fn update_process_status(conn: &mut SqliteConnection, process_id: i32, new_status: &str) -> Result<()> {
+pub fn update_process_status(
+    conn: &mut SqliteConnection,
+    process_id: i32,
+    new_status: &ProcessState,
+) -> Result<()> {
     use crate::schema::processes::dsl::*;
-    
+
     diesel::update(processes.filter(id.eq(process_id)))
-        .set((status.eq(new_status), updated_at.eq(chrono::Utc::now().naive_utc())))
+        .set((status.eq(new_status), updated_at.eq(db_now())))
         .execute(conn)?;
-    
+
     Ok(())
 }
 
-pub fn delete_threads_by_process(conn: &mut SqliteConnection, server_id_param: &str, pid_param: i32) -> Result<()> {
+pub fn delete_threads_by_process(
+    conn: &mut SqliteConnection,
+    server_id_param: &str,
+    pid_param: i32,
+) -> Result<()> {
     use crate::schema::threads::dsl::*;
-    
-    diesel::delete(threads.filter(server_id.eq(server_id_param)).filter(pid.eq(pid_param)))
-        .execute(conn)?;
-    
+
+    diesel::delete(
+        threads
+            .filter(server_id.eq(server_id_param))
+            .filter(pid.eq(pid_param)),
+    )
+    .execute(conn)?;
+
     Ok(())
 }
 
-pub fn get_crash_log_by_timestamp(conn: &mut SqliteConnection, server_id_param: &str, timestamp_param: i64) -> Result<Option<CrashLog>> {
+pub fn get_crash_log_by_id(
+    conn: &mut SqliteConnection,
+    crash_log_id: i32,
+) -> Result<Option<CrashLog>> {
+    use crate::schema::crash_logs::dsl::*;
+
+    Ok(crash_logs
+        .find(crash_log_id)
+        .first::<CrashLog>(conn)
+        .optional()?)
+}
+
+/// 服务器是否已有 stack_trace 包含 `marker` 的线程异常崩溃日志 (区分大小写，与 `str::contains` 一致)
+pub fn thread_exception_crash_log_exists(
+    conn: &mut SqliteConnection,
+    server_id_param: &str,
+    marker: &str,
+) -> Result<bool> {
+    use crate::schema::crash_logs::dsl::*;
+    use diesel::dsl::sql;
+    use diesel::sql_types::{Bool, Text};
+
+    let exists = diesel::select(diesel::dsl::exists(
+        crash_logs
+            .filter(server_id.eq(server_id_param))
+            .filter(crash_type.eq(CrashType::ThreadException))
+            .filter(
+                sql::<Bool>("instr(stack_trace, ")
+                    .bind::<Text, _>(marker)
+                    .sql(") > 0"),
+            ),
+    ))
+    .get_result(conn)?;
+
+    Ok(exists)
+}
+
+pub fn get_crash_log_by_timestamp(
+    conn: &mut SqliteConnection,
+    server_id_param: &str,
+    timestamp_param: i64,
+) -> Result<Option<CrashLog>> {
     use crate::schema::crash_logs::dsl::*;
-    
+
     let log = crash_logs
         .filter(server_id.eq(server_id_param))
         .filter(timestamp.eq(timestamp_param))
         .first::<CrashLog>(conn)
         .optional()?;
-    
+
     Ok(log)
 }
 
-pub fn update_crash_log(conn: &mut SqliteConnection, crash_log_id: i32, new_log: &NewCrashLog) -> Result<()> {
+pub fn get_crash_log_by_log_id(
+    conn: &mut SqliteConnection,
+    server_id_param: &str,
+    log_id_param: i64,
+) -> Result<Option<CrashLog>> {
     use crate::schema::crash_logs::dsl::*;
-    
+
+    let log = crash_logs
+        .filter(server_id.eq(server_id_param))
+        .filter(log_id.eq(log_id_param))
+        .first::<CrashLog>(conn)
+        .optional()?;
+
+    Ok(log)
+}
+
+pub fn update_crash_log(
+    conn: &mut SqliteConnection,
+    crash_log_id: i32,
+    new_log: &NewCrashLog,
+) -> Result<()> {
+    use crate::schema::crash_logs::dsl::*;
+
     diesel::update(crash_logs.filter(id.eq(crash_log_id)))
         .set((
             crash_type.eq(&new_log.crash_type),
@@ -452,8 +1339,9 @@ pub fn update_crash_log(conn: &mut SqliteConnection, crash_log_id: i32, new_log:
             resolved.eq(new_log.resolved),
             ai_summary.eq(&new_log.ai_summary),
             ai_analysis.eq(&new_log.ai_analysis),
+            updated_at.eq(db_now()),
         ))
         .execute(conn)?;
-    
+
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,287 @@
+//! 领域枚举：服务器状态、进程状态、严重级别、崩溃类型和崩溃处理动作
+//!
+//! 数据库中以 TEXT 保存规范写法 (如 `running`、`S`、`high`、`thread_exception`)。
+//! 写入时对输入做规范化：忽略大小写和首尾空白，接受常见的同义写法；
+//! 无法识别的值保存在 `Other` 中原样写入，不会被拒绝。
+
+use diesel::backend::Backend;
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use diesel::sql_types::Text;
+use diesel::sqlite::Sqlite;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+/// 小写并把连字符、空格统一为下划线，用于匹配同义写法
+fn canonical(value: &str) -> String {
+    value.trim().to_lowercase().replace(['-', ' '], "_")
+}
+
+/// 为以 TEXT 保存的枚举实现字符串转换、serde 和 diesel 的读写
+///
+/// 枚举需要提供 `as_str` (规范写法) 和 `normalize` (解析输入)。
+macro_rules! text_enum {
+    ($name:ident) => {
+        impl From<&str> for $name {
+            fn from(value: &str) -> Self {
+                Self::normalize(value)
+            }
+        }
+
+        impl From<String> for $name {
+            fn from(value: String) -> Self {
+                Self::normalize(&value)
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(self.as_str())
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                Ok(Self::normalize(&String::deserialize(deserializer)?))
+            }
+        }
+
+        impl ToSql<Text, Sqlite> for $name {
+            fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> serialize::Result {
+                out.set_value(self.as_str());
+                Ok(IsNull::No)
+            }
+        }
+
+        impl FromSql<Text, Sqlite> for $name {
+            fn from_sql(bytes: <Sqlite as Backend>::RawValue<'_>) -> deserialize::Result<Self> {
+                let value = <String as FromSql<Text, Sqlite>>::from_sql(bytes)?;
+                Ok(Self::normalize(&value))
+            }
+        }
+    };
+}
+
+/// 服务器状态
+#[derive(Debug, Clone, PartialEq, Eq, Hash, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Text)]
+pub enum ServerStatus {
+    /// 正常运行 (也接受 online、up)
+    Running,
+    /// 告警 (也接受 warn、degraded)
+    Warning,
+    /// 维护中
+    Maintenance,
+    /// 离线 (也接受 down、stopped)
+    Offline,
+    Other(String),
+}
+
+impl ServerStatus {
+    pub fn as_str(&self) -> &str {
+        match self {
+            ServerStatus::Running => "running",
+            ServerStatus::Warning => "warning",
+            ServerStatus::Maintenance => "maintenance",
+            ServerStatus::Offline => "offline",
+            ServerStatus::Other(value) => value,
+        }
+    }
+
+    fn normalize(value: &str) -> Self {
+        match canonical(value).as_str() {
+            "running" | "online" | "up" => ServerStatus::Running,
+            "warning" | "warn" | "degraded" => ServerStatus::Warning,
+            "maintenance" => ServerStatus::Maintenance,
+            "offline" | "down" | "stopped" => ServerStatus::Offline,
+            _ => ServerStatus::Other(value.trim().to_string()),
+        }
+    }
+}
+
+text_enum!(ServerStatus);
+
+/// 进程 (线程) 状态，按 ps 的 STAT 码保存
+#[derive(Debug, Clone, PartialEq, Eq, Hash, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Text)]
+pub enum ProcessState {
+    /// R：运行或可运行
+    Running,
+    /// S：可中断睡眠
+    Sleeping,
+    /// D：不可中断睡眠 (通常在等待 IO)
+    DiskSleep,
+    /// Z：僵尸进程
+    Zombie,
+    /// T：已停止 (包括被跟踪停止的 t)
+    Stopped,
+    /// I：空闲内核线程
+    Idle,
+    Other(String),
+}
+
+impl ProcessState {
+    pub fn as_str(&self) -> &str {
+        match self {
+            ProcessState::Running => "R",
+            ProcessState::Sleeping => "S",
+            ProcessState::DiskSleep => "D",
+            ProcessState::Zombie => "Z",
+            ProcessState::Stopped => "T",
+            ProcessState::Idle => "I",
+            ProcessState::Other(value) => value,
+        }
+    }
+
+    /// 接受 STAT 码 (可带 ps 的附加标志，如 `Ss`、`R+`、`Sl`) 或状态名
+    fn normalize(value: &str) -> Self {
+        let trimmed = value.trim();
+
+        let mut chars = trimmed.chars();
+        if let Some(code) = chars.next()
+            && chars.all(|flag| "<NLsl+".contains(flag))
+        {
+            match code {
+                'R' => return ProcessState::Running,
+                'S' => return ProcessState::Sleeping,
+                'D' => return ProcessState::DiskSleep,
+                'Z' => return ProcessState::Zombie,
+                'T' | 't' => return ProcessState::Stopped,
+                'I' => return ProcessState::Idle,
+                _ => {}
+            }
+        }
+
+        match canonical(trimmed).as_str() {
+            "running" | "runnable" => ProcessState::Running,
+            "sleeping" | "sleep" => ProcessState::Sleeping,
+            "disk_sleep" | "uninterruptible" => ProcessState::DiskSleep,
+            "zombie" | "defunct" => ProcessState::Zombie,
+            "stopped" | "traced" => ProcessState::Stopped,
+            "idle" => ProcessState::Idle,
+            _ => ProcessState::Other(trimmed.to_string()),
+        }
+    }
+}
+
+text_enum!(ProcessState);
+
+/// 崩溃日志的严重级别
+#[derive(Debug, Clone, PartialEq, Eq, Hash, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Text)]
+pub enum Severity {
+    /// 也接受 minor、info
+    Low,
+    /// 也接受 moderate、warning
+    Medium,
+    /// 也接受 major、error
+    High,
+    /// 也接受 fatal、severe
+    Critical,
+    Other(String),
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Severity::Low => "low",
+            Severity::Medium => "medium",
+            Severity::High => "high",
+            Severity::Critical => "critical",
+            Severity::Other(value) => value,
+        }
+    }
+
+    fn normalize(value: &str) -> Self {
+        match canonical(value).as_str() {
+            "low" | "minor" | "info" => Severity::Low,
+            "medium" | "moderate" | "warning" | "warn" => Severity::Medium,
+            "high" | "major" | "error" => Severity::High,
+            "critical" | "fatal" | "severe" => Severity::Critical,
+            _ => Severity::Other(value.trim().to_string()),
+        }
+    }
+}
+
+text_enum!(Severity);
+
+/// 崩溃类型
+#[derive(Debug, Clone, PartialEq, Eq, Hash, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Text)]
+pub enum CrashType {
+    /// 段错误 (也接受 segfault、sigsegv)
+    SegmentationFault,
+    /// 内核异常，由 dmesg 中的 panic / oops 生成 (也接受 kernel_panic、kernel_oops)
+    KernelException,
+    /// 线程异常，由线程数超过阈值生成
+    ThreadException,
+    /// 内存耗尽 (也接受 out_of_memory、oom_kill)
+    Oom,
+    Other(String),
+}
+
+impl CrashType {
+    pub fn as_str(&self) -> &str {
+        match self {
+            CrashType::SegmentationFault => "segmentation_fault",
+            CrashType::KernelException => "kernel_exception",
+            CrashType::ThreadException => "thread_exception",
+            CrashType::Oom => "oom",
+            CrashType::Other(value) => value,
+        }
+    }
+
+    fn normalize(value: &str) -> Self {
+        match canonical(value).as_str() {
+            "segmentation_fault" | "segfault" | "sigsegv" => CrashType::SegmentationFault,
+            "kernel_exception" | "kernel_panic" | "kernel_oops" => CrashType::KernelException,
+            "thread_exception" => CrashType::ThreadException,
+            "oom" | "out_of_memory" | "oom_kill" => CrashType::Oom,
+            _ => CrashType::Other(value.trim().to_string()),
+        }
+    }
+}
+
+text_enum!(CrashType);
+
+/// 崩溃日志处理历史中的动作
+#[derive(Debug, Clone, PartialEq, Eq, Hash, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Text)]
+pub enum CrashAction {
+    /// 标记为已解决 (也接受 resolve)
+    Resolved,
+    /// 重新打开 (也接受 reopen)
+    Reopened,
+    /// 备注 (也接受 comment、annotate)
+    Note,
+    Other(String),
+}
+
+impl CrashAction {
+    pub fn as_str(&self) -> &str {
+        match self {
+            CrashAction::Resolved => "resolved",
+            CrashAction::Reopened => "reopened",
+            CrashAction::Note => "note",
+            CrashAction::Other(value) => value,
+        }
+    }
+
+    fn normalize(value: &str) -> Self {
+        match canonical(value).as_str() {
+            "resolved" | "resolve" => CrashAction::Resolved,
+            "reopened" | "reopen" => CrashAction::Reopened,
+            "note" | "comment" | "annotate" => CrashAction::Note,
+            _ => CrashAction::Other(value.trim().to_string()),
+        }
+    }
+}
+
+text_enum!(CrashAction);
@@ -0,0 +1,171 @@
+//! 库的错误类型
+//!
+//! 公开 API 统一返回 [`BlackBoxError`]，调用方可以按类别区分
+//! 记录不存在、输入不合法、数据库繁忙等情况，而不必匹配错误消息。
+
+use thiserror::Error;
+
+pub type Result<T, E = BlackBoxError> = std::result::Result<T, E>;
+
+#[derive(Debug, Error)]
+pub enum BlackBoxError {
+    /// 引用的记录或文件不存在
+    #[error("{entity} {key} 不存在")]
+    NotFound { entity: &'static str, key: String },
+
+    /// 输入数据或参数不合法
+    #[error("{message}")]
+    Validation { message: String },
+
+    /// 与已有数据冲突，如目标数据库非空、文件已存在
+    #[error("{message}")]
+    Conflict { message: String },
+
+    /// 数据库被锁 (SQLITE_BUSY / SQLITE_LOCKED)，重试后仍未获得锁
+    #[error("数据库繁忙: {message}")]
+    Busy { message: String },
+
+    /// 数据库结构版本与程序不符
+    #[error("{message}")]
+    Schema {
+        found: i32,
+        expected: i32,
+        message: String,
+    },
+
+    /// 文件或网络读写失败
+    #[error("{target}: {source}")]
+    Io {
+        /// 读写的文件路径或地址
+        target: String,
+        #[source]
+        source: std::io::Error,
+    },
+
+    /// 输入内容无法解析
+    #[error("{format} 解析失败: {message}")]
+    Parse {
+        format: &'static str,
+        message: String,
+    },
+
+    /// 其他数据库错误
+    #[error("数据库错误: {source}")]
+    Database {
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+}
+
+impl BlackBoxError {
+    pub fn not_found(entity: &'static str, key: impl Into<String>) -> Self {
+        BlackBoxError::NotFound {
+            entity,
+            key: key.into(),
+        }
+    }
+
+    pub fn validation(message: impl Into<String>) -> Self {
+        BlackBoxError::Validation {
+            message: message.into(),
+        }
+    }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        BlackBoxError::Conflict {
+            message: message.into(),
+        }
+    }
+
+    pub fn io(target: impl Into<String>, source: std::io::Error) -> Self {
+        BlackBoxError::Io {
+            target: target.into(),
+            source,
+        }
+    }
+
+    pub fn parse(format: &'static str, message: impl Into<String>) -> Self {
+        BlackBoxError::Parse {
+            format,
+            message: message.into(),
+        }
+    }
+
+    /// 结构版本不符，`message` 说明需要的处理方式
+    pub fn schema(found: i32, expected: i32, message: impl Into<String>) -> Self {
+        BlackBoxError::Schema {
+            found,
+            expected,
+            message: message.into(),
+        }
+    }
+
+    pub fn is_busy(&self) -> bool {
+        matches!(self, BlackBoxError::Busy { .. })
+    }
+}
+
+impl From<diesel::result::Error> for BlackBoxError {
+    fn from(error: diesel::result::Error) -> Self {
+        use diesel::result::DatabaseErrorKind;
+
+        if let diesel::result::Error::DatabaseError(kind, info) = &error {
+            match kind {
+                DatabaseErrorKind::UniqueViolation => {
+                    return BlackBoxError::conflict(info.message());
+                }
+                DatabaseErrorKind::Unknown if is_lock_message(info.message()) => {
+                    return BlackBoxError::Busy {
+                        message: info.message().to_string(),
+                    };
+                }
+                _ => {}
+            }
+        }
+
+        BlackBoxError::Database {
+            source: Box::new(error),
+        }
+    }
+}
+
+/// 错误消息是否为 SQLITE_BUSY / SQLITE_LOCKED 的标准描述
+///
+/// diesel 只为约束冲突区分错误类别，其余错误码只保留 `sqlite3_errmsg`；
+/// 锁冲突没有附加说明，消息就是错误码的 `sqlite3_errstr` (扩展码与主错误码相同)。
+fn is_lock_message(message: &str) -> bool {
+    [libsqlite3_sys::SQLITE_BUSY, libsqlite3_sys::SQLITE_LOCKED]
+        .into_iter()
+        .any(|code| {
+            // SAFETY: sqlite3_errstr 对任意错误码都返回指向静态字符串的指针
+            let description =
+                unsafe { std::ffi::CStr::from_ptr(libsqlite3_sys::sqlite3_errstr(code)) };
+            description.to_str() == Ok(message)
+        })
+}
+
+impl From<diesel::ConnectionError> for BlackBoxError {
+    fn from(error: diesel::ConnectionError) -> Self {
+        BlackBoxError::Database {
+            source: Box::new(error),
+        }
+    }
+}
+
+impl From<serde_json::Error> for BlackBoxError {
+    fn from(error: serde_json::Error) -> Self {
+        BlackBoxError::parse("JSON", error.to_string())
+    }
+}
+
+impl From<csv::Error> for BlackBoxError {
+    fn from(error: csv::Error) -> Self {
+        BlackBoxError::parse("CSV", error.to_string())
+    }
+}
+
+impl From<serde_yaml::Error> for BlackBoxError {
+    fn from(error: serde_yaml::Error) -> Self {
+        BlackBoxError::parse("YAML", error.to_string())
+    }
+}
@@ -0,0 +1,292 @@
+//! 插入与检测事件
+//!
+//! 智能插入通过 [`EventRecorder`] 包装存储，在写入成功时记录事件；
+//! 事务提交后 [`BlackBox`](crate::BlackBox) 按顺序同步通知已注册的 [`Subscriber`]，
+//! 事务回滚或忙等待重试时本次记录的事件被丢弃，订阅者只会看到已落盘的数据。
+//!
+//! 以下写入不产生事件：幂等键重复提交 (直接返回首次的结果)、JSON / 无损导入、数据库合并。
+
+use crate::error::Result;
+
+use crate::config::RetentionConfig;
+use crate::domain::*;
+use crate::models::*;
+use crate::store::Store;
+
+/// 智能插入过程中产生的事件
+#[derive(Debug, Clone)]
+pub enum InsertEvent {
+    /// 新建了服务器
+    ServerCreated(Server),
+    /// 已有服务器的状态发生变化
+    StatusChanged {
+        server: Server,
+        previous: ServerStatus,
+    },
+    /// 新建了崩溃日志，包括由 dmesg 和线程数检测生成的日志
+    CrashDetected(CrashLog),
+    /// 写入了一条系统指标，`updated` 表示覆盖了同一时间戳的已有指标
+    MetricInserted {
+        metric: NewSystemMetric,
+        updated: bool,
+    },
+}
+
+impl InsertEvent {
+    /// 调用订阅者对应的回调
+    pub fn dispatch(&self, subscriber: &dyn Subscriber) {
+        match self {
+            InsertEvent::ServerCreated(server) => subscriber.on_server_created(server),
+            InsertEvent::StatusChanged { server, previous } => {
+                subscriber.on_status_changed(server, previous)
+            }
+            InsertEvent::CrashDetected(crash_log) => subscriber.on_crash_detected(crash_log),
+            InsertEvent::MetricInserted { metric, .. } => subscriber.on_metric_inserted(metric),
+        }
+    }
+}
+
+/// 插入事件的订阅者
+///
+/// 回调在写入事务提交后、插入方法返回前同步执行，耗时操作应自行转交给其他线程。
+/// 所有方法都有空的默认实现，只需实现关心的事件。
+pub trait Subscriber: Send + Sync {
+    fn on_server_created(&self, _server: &Server) {}
+
+    /// `server` 是更新后的服务器，`previous` 是更新前的状态
+    fn on_status_changed(&self, _server: &Server, _previous: &ServerStatus) {}
+
+    fn on_crash_detected(&self, _crash_log: &CrashLog) {}
+
+    fn on_metric_inserted(&self, _metric: &NewSystemMetric) {}
+}
+
+/// 记录插入事件的存储包装
+///
+/// 所有操作都委托给内部存储；未启用时不记录事件，也不做额外的查询。
+pub struct EventRecorder<'a, S: Store + ?Sized> {
+    store: &'a mut S,
+    enabled: bool,
+    events: Vec<InsertEvent>,
+}
+
+impl<'a, S: Store + ?Sized> EventRecorder<'a, S> {
+    pub fn new(store: &'a mut S, enabled: bool) -> Self {
+        Self {
+            store,
+            enabled,
+            events: Vec::new(),
+        }
+    }
+
+    /// 取出按发生顺序排列的事件
+    pub fn into_events(self) -> Vec<InsertEvent> {
+        self.events
+    }
+}
+
+impl<S: Store + ?Sized> Store for EventRecorder<'_, S> {
+    fn create_server(&mut self, new_server: &NewServer) -> Result<Server> {
+        let server = self.store.create_server(new_server)?;
+        if self.enabled {
+            self.events.push(InsertEvent::ServerCreated(server.clone()));
+        }
+        Ok(server)
+    }
+
+    fn get_server_by_id(&mut self, server_id: &str) -> Result<Option<Server>> {
+        self.store.get_server_by_id(server_id)
+    }
+
+    fn get_all_servers(&mut self) -> Result<Vec<Server>> {
+        self.store.get_all_servers()
+    }
+
+    fn update_server_status(&mut self, server_id: &str, status: &ServerStatus) -> Result<Server> {
+        if !self.enabled {
+            return self.store.update_server_status(server_id, status);
+        }
+
+        let previous = self
+            .store
+            .get_server_by_id(server_id)?
+            .map(|server| server.server_status);
+        let server = self.store.update_server_status(server_id, status)?;
+        if let Some(previous) = previous
+            && previous != server.server_status
+        {
+            self.events.push(InsertEvent::StatusChanged {
+                server: server.clone(),
+                previous,
+            });
+        }
+        Ok(server)
+    }
+
+    fn create_system_metric(&mut self, new_metric: &NewSystemMetric) -> Result<()> {
+        self.store.create_system_metric(new_metric)?;
+        if self.enabled {
+            self.events.push(InsertEvent::MetricInserted {
+                metric: new_metric.clone(),
+                updated: false,
+            });
+        }
+        Ok(())
+    }
+
+    fn get_system_metric_by_timestamp(
+        &mut self,
+        server_id: &str,
+        timestamp: i64,
+    ) -> Result<Option<SystemMetric>> {
+        self.store
+            .get_system_metric_by_timestamp(server_id, timestamp)
+    }
+
+    fn update_system_metric(
+        &mut self,
+        server_id: &str,
+        timestamp: i64,
+        new_metric: &NewSystemMetric,
+    ) -> Result<()> {
+        self.store
+            .update_system_metric(server_id, timestamp, new_metric)?;
+        if self.enabled {
+            self.events.push(InsertEvent::MetricInserted {
+                metric: new_metric.clone(),
+                updated: true,
+            });
+        }
+        Ok(())
+    }
+
+    fn get_metrics_by_server(
+        &mut self,
+        server_id: &str,
+        limit: Option<i64>,
+    ) -> Result<Vec<SystemMetric>> {
+        self.store.get_metrics_by_server(server_id, limit)
+    }
+
+    fn create_process(&mut self, new_process: &NewProcess) -> Result<()> {
+        self.store.create_process(new_process)
+    }
+
+    fn get_process_by_name_and_user(
+        &mut self,
+        server_id: &str,
+        name: &str,
+        user_name: &str,
+    ) -> Result<Option<Process>> {
+        self.store
+            .get_process_by_name_and_user(server_id, name, user_name)
+    }
+
+    fn update_process_status(&mut self, process_id: i32, status: &ProcessState) -> Result<()> {
+        self.store.update_process_status(process_id, status)
+    }
+
+    fn get_processes_by_server(&mut self, server_id: &str) -> Result<Vec<Process>> {
+        self.store.get_processes_by_server(server_id)
+    }
+
+    fn create_process_trend(&mut self, new_trend: &NewProcessTrend) -> Result<()> {
+        self.store.create_process_trend(new_trend)
+    }
+
+    fn get_process_trend_by_timestamp(
+        &mut self,
+        server_id: &str,
+        pid: i32,
+        timestamp: i64,
+    ) -> Result<Option<ProcessTrend>> {
+        self.store
+            .get_process_trend_by_timestamp(server_id, pid, timestamp)
+    }
+
+    fn update_process_trend(&mut self, trend_id: i32, new_trend: &NewProcessTrend) -> Result<()> {
+        self.store.update_process_trend(trend_id, new_trend)
+    }
+
+    fn get_process_trends(&mut self, server_id: &str, pid: i32) -> Result<Vec<ProcessTrend>> {
+        self.store.get_process_trends(server_id, pid)
+    }
+
+    fn create_thread(&mut self, new_thread: &NewThread) -> Result<()> {
+        self.store.create_thread(new_thread)
+    }
+
+    fn get_threads_by_process(&mut self, server_id: &str, pid: i32) -> Result<Vec<Thread>> {
+        self.store.get_threads_by_process(server_id, pid)
+    }
+
+    fn delete_threads_by_process(&mut self, server_id: &str, pid: i32) -> Result<()> {
+        self.store.delete_threads_by_process(server_id, pid)
+    }
+
+    fn create_crash_log(&mut self, new_log: &NewCrashLog) -> Result<i32> {
+        let id = self.store.create_crash_log(new_log)?;
+        if self.enabled
+            && let Some(crash_log) = self.store.get_crash_log_by_id(id)?
+        {
+            self.events.push(InsertEvent::CrashDetected(crash_log));
+        }
+        Ok(id)
+    }
+
+    fn get_crash_log_by_id(&mut self, crash_log_id: i32) -> Result<Option<CrashLog>> {
+        self.store.get_crash_log_by_id(crash_log_id)
+    }
+
+    fn get_crash_log_by_timestamp(
+        &mut self,
+        server_id: &str,
+        timestamp: i64,
+    ) -> Result<Option<CrashLog>> {
+        self.store.get_crash_log_by_timestamp(server_id, timestamp)
+    }
+
+    fn get_crash_log_by_log_id(
+        &mut self,
+        server_id: &str,
+        log_id: i64,
+    ) -> Result<Option<CrashLog>> {
+        self.store.get_crash_log_by_log_id(server_id, log_id)
+    }
+
+    fn thread_exception_crash_log_exists(&mut self, server_id: &str, marker: &str) -> Result<bool> {
+        self.store
+            .thread_exception_crash_log_exists(server_id, marker)
+    }
+
+    fn update_crash_log(&mut self, crash_log_id: i32, new_log: &NewCrashLog) -> Result<()> {
+        self.store.update_crash_log(crash_log_id, new_log)
+    }
+
+    fn get_crash_logs_by_server(&mut self, server_id: &str) -> Result<Vec<CrashLog>> {
+        self.store.get_crash_logs_by_server(server_id)
+    }
+
+    fn get_unresolved_crash_logs(&mut self) -> Result<Vec<CrashLog>> {
+        self.store.get_unresolved_crash_logs()
+    }
+
+    fn create_ai_recommendation(&mut self, new_recommendation: &NewAiRecommendation) -> Result<()> {
+        self.store.create_ai_recommendation(new_recommendation)
+    }
+
+    fn get_recommendations_by_crash_log(
+        &mut self,
+        crash_log_id: i32,
+    ) -> Result<Vec<AiRecommendation>> {
+        self.store.get_recommendations_by_crash_log(crash_log_id)
+    }
+
+    fn delete_recommendations_by_crash_log(&mut self, crash_log_id: i32) -> Result<()> {
+        self.store.delete_recommendations_by_crash_log(crash_log_id)
+    }
+
+    fn cleanup_old_data(&mut self, retention: &RetentionConfig) -> Result<()> {
+        self.store.cleanup_old_data(retention)
+    }
+}
@@ -0,0 +1,628 @@
+//! 边缘节点到中心节点的存储转发
+//!
+//! 边缘节点写入本地数据库时，把原始负载 (智能插入 JSON、CSV、行协议、JSON 导入、
+//! 标签变更、崩溃日志的处理、旧数据清理、合并的源数据库) 在同一事务中写入 outbox 表；转发时按入队顺序把负载 POST 到
+//! 中心节点 `blackbox serve` 的 `/ingest/<种类>`，成功后出队。
+//!
+//! 网络错误和中心节点暂时不可用 (5xx、408、429) 时按指数退避重试，中心节点不可达期间
+//! 负载一直保存在本地；中心节点明确拒绝 (其余 4xx) 的负载转入死信，不再投递，
+//! 也不阻塞后面的负载，可以用 `forward requeue` 重新入队或 `forward discard` 丢弃。
+//!
+//! 每条负载在入队时分配一个稳定的记录 id，通过 `Idempotency-Key` 请求头发送；
+//! 中心节点在 ingest_ledger 表中记录已接收的 id，重复投递直接返回首次的结果。
+
+use crate::error::{BlackBoxError, Result};
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::SmartDataType;
+use crate::crash;
+use crate::database::{
+    db_now, delete_recommendations_by_crash_log, get_crash_log_by_timestamp, get_server_by_id,
+    remove_server_label, set_server_label,
+};
+use crate::domain::CrashAction;
+use crate::influx::Precision;
+use crate::merge::MergeReport;
+use crate::models::*;
+use crate::services::{DatabaseManager, IdempotencyService, InsertResult};
+
+/// 携带记录 id 的请求头
+pub const RECORD_ID_HEADER: &str = "Idempotency-Key";
+
+/// outbox 中负载的种类，对应中心节点的 `/ingest/<种类>` 接口
+#[derive(Debug, Clone, PartialEq)]
+pub enum PayloadKind {
+    /// 智能插入 JSON，路径为数据类型名 (如 `servers`)
+    Json(SmartDataType),
+    /// 带表头的 CSV，路径如 `csv/servers`
+    Csv(SmartDataType),
+    /// InfluxDB 行协议，路径带时间戳精度，如 `line_protocol/ms`
+    LineProtocol(Precision),
+    /// 服务器标签的设置和删除 ([`LabelChange`])
+    Labels,
+    /// 按服务器嵌套的 JSON 导入文件 (`import`)
+    Import,
+    /// 崩溃日志的处理 ([`CrashChange`])
+    Crash,
+    /// 按保留天数清理的系统指标 ([`MetricsCleanup`])
+    Cleanup,
+    /// 合并的源数据库或无损导出文件，负载为无损导出格式
+    Merge,
+}
+
+impl PayloadKind {
+    /// 接口路径中 `/ingest/` 之后的部分，也记入 outbox 和 ingest_ledger 的 data_type 列
+    pub fn as_path(&self) -> String {
+        match self {
+            PayloadKind::Json(data_type) => data_type.as_str().to_string(),
+            PayloadKind::Csv(data_type) => format!("csv/{}", data_type.as_str()),
+            PayloadKind::LineProtocol(precision) => format!("line_protocol/{}", precision.as_str()),
+            PayloadKind::Labels => "labels".to_string(),
+            PayloadKind::Import => "import".to_string(),
+            PayloadKind::Crash => "crash".to_string(),
+            PayloadKind::Cleanup => "cleanup".to_string(),
+            PayloadKind::Merge => "merge".to_string(),
+        }
+    }
+
+    pub fn parse(path: &str) -> Result<Self> {
+        match path.split_once('/') {
+            Some(("csv", data_type)) => Ok(PayloadKind::Csv(SmartDataType::parse(data_type)?)),
+            Some(("line_protocol", precision)) => {
+                Ok(PayloadKind::LineProtocol(Precision::parse(precision)?))
+            }
+            Some(_) => Err(BlackBoxError::validation(format!(
+                "未知的负载种类: {}",
+                path
+            ))),
+            None => match path {
+                "labels" => Ok(PayloadKind::Labels),
+                "import" => Ok(PayloadKind::Import),
+                "crash" => Ok(PayloadKind::Crash),
+                "cleanup" => Ok(PayloadKind::Cleanup),
+                "merge" => Ok(PayloadKind::Merge),
+                _ => Ok(PayloadKind::Json(SmartDataType::parse(path)?)),
+            },
+        }
+    }
+}
+
+/// 一次标签变更 (`label set` 或 `label remove`)
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LabelChange {
+    pub server_id: String,
+    #[serde(default)]
+    pub set: Vec<(String, String)>,
+    #[serde(default)]
+    pub remove: Vec<String>,
+}
+
+/// 中心节点应用标签变更，调用方负责事务
+pub fn apply_labels(conn: &mut SqliteConnection, change: &LabelChange) -> Result<InsertResult> {
+    if get_server_by_id(conn, &change.server_id)?.is_none() {
+        return Err(BlackBoxError::not_found("服务器", &change.server_id));
+    }
+
+    let mut result = InsertResult::new();
+    for (key, value) in &change.set {
+        set_server_label(conn, &change.server_id, key, value)?;
+        result.add_updated();
+    }
+    for key in &change.remove {
+        if remove_server_label(conn, &change.server_id, key)? {
+            result.add_updated();
+        }
+    }
+    Ok(result)
+}
+
+/// 崩溃日志的一次处理 (解决、重新打开、备注、调整建议或分析)
+///
+/// 边缘节点与中心节点的自增 id 不同，崩溃日志按服务器 + 时间戳定位；
+/// 负载携带处理后的解决状态和分析内容，调整过建议时还带有完整的建议列表。
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CrashChange {
+    pub server_id: String,
+    pub timestamp: i64,
+    pub resolved: bool,
+    pub message: String,
+    pub ai_summary: Option<String>,
+    pub ai_analysis: Option<String>,
+    /// 本次处理记入历史的记录
+    #[serde(default)]
+    pub event: Option<CrashEventChange>,
+    /// 处理后的全部建议，按优先级排列；没有调整建议时为 None
+    #[serde(default)]
+    pub recommendations: Option<Vec<RecommendationState>>,
+}
+
+/// 记入处理历史的一条记录
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CrashEventChange {
+    pub action: CrashAction,
+    pub actor: String,
+    pub note: Option<String>,
+}
+
+/// 一条建议及其执行状态
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RecommendationState {
+    pub action: String,
+    pub command: String,
+    #[serde(default)]
+    pub applied: bool,
+    pub applied_at: Option<chrono::NaiveDateTime>,
+    pub outcome: Option<String>,
+}
+
+impl CrashChange {
+    /// 读取崩溃日志处理后的状态，调用方负责事务
+    ///
+    /// `event` 为本次记入历史的记录，`with_recommendations` 为 true 时带上全部建议。
+    pub fn load(
+        conn: &mut SqliteConnection,
+        crash_log_id: i32,
+        event: Option<&CrashEvent>,
+        with_recommendations: bool,
+    ) -> Result<Self> {
+        let crash_log = crash::get(conn, crash_log_id)?;
+        let recommendations = if with_recommendations {
+            Some(
+                crash::recommendations(conn, crash_log_id)?
+                    .into_iter()
+                    .map(|r| RecommendationState {
+                        action: r.action,
+                        command: r.command,
+                        applied: r.applied,
+                        applied_at: r.applied_at,
+                        outcome: r.outcome,
+                    })
+                    .collect(),
+            )
+        } else {
+            None
+        };
+
+        Ok(Self {
+            server_id: crash_log.server_id,
+            timestamp: crash_log.timestamp,
+            resolved: crash_log.resolved,
+            message: crash_log.message,
+            ai_summary: crash_log.ai_summary,
+            ai_analysis: crash_log.ai_analysis,
+            event: event.map(|e| CrashEventChange {
+                action: e.action.clone(),
+                actor: e.actor.clone(),
+                note: e.note.clone(),
+            }),
+            recommendations,
+        })
+    }
+}
+
+/// 中心节点应用崩溃日志的处理，调用方负责事务
+pub fn apply_crash_change(
+    conn: &mut SqliteConnection,
+    change: &CrashChange,
+) -> Result<InsertResult> {
+    use crate::schema::{ai_recommendations, crash_events, crash_logs};
+
+    let crash_log = get_crash_log_by_timestamp(conn, &change.server_id, change.timestamp)?
+        .ok_or_else(|| {
+            BlackBoxError::not_found(
+                "崩溃日志",
+                format!("{}@{}", change.server_id, change.timestamp),
+            )
+        })?;
+
+    diesel::update(crash_logs::table.find(crash_log.id))
+        .set((
+            crash_logs::resolved.eq(change.resolved),
+            crash_logs::message.eq(&change.message),
+            crash_logs::ai_summary.eq(&change.ai_summary),
+            crash_logs::ai_analysis.eq(&change.ai_analysis),
+            crash_logs::updated_at.eq(db_now()),
+        ))
+        .execute(conn)?;
+
+    if let Some(recommendations) = &change.recommendations {
+        delete_recommendations_by_crash_log(conn, crash_log.id)?;
+        for (index, recommendation) in recommendations.iter().enumerate() {
+            diesel::insert_into(ai_recommendations::table)
+                .values((
+                    ai_recommendations::crash_log_id.eq(crash_log.id),
+                    ai_recommendations::priority.eq(index as i32 + 1),
+                    ai_recommendations::action.eq(&recommendation.action),
+                    ai_recommendations::command.eq(&recommendation.command),
+                    ai_recommendations::applied.eq(recommendation.applied),
+                    ai_recommendations::applied_at.eq(recommendation.applied_at),
+                    ai_recommendations::outcome.eq(&recommendation.outcome),
+                ))
+                .execute(conn)?;
+        }
+    }
+
+    if let Some(event) = &change.event {
+        diesel::insert_into(crash_events::table)
+            .values(&NewCrashEvent {
+                crash_log_id: crash_log.id,
+                action: event.action.clone(),
+                actor: event.actor.clone(),
+                note: event.note.clone(),
+            })
+            .execute(conn)?;
+    }
+
+    let mut result = InsertResult::new();
+    result.add_updated();
+    Ok(result)
+}
+
+/// 按保留天数清理系统指标 (`clean`)
+///
+/// 中心节点汇集了多个边缘节点的数据，只清理发出负载的边缘节点上的服务器。
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MetricsCleanup {
+    /// 早于该时间戳 (毫秒) 的指标被删除
+    pub before: i64,
+    pub server_ids: Vec<String>,
+}
+
+/// 中心节点清理系统指标，调用方负责事务
+pub fn apply_cleanup(
+    conn: &mut SqliteConnection,
+    cleanup: &MetricsCleanup,
+) -> Result<InsertResult> {
+    use crate::schema::system_metrics::dsl::*;
+
+    let mut result = InsertResult::new();
+    result.updated_count = diesel::delete(
+        system_metrics
+            .filter(timestamp.lt(cleanup.before))
+            .filter(server_id.eq_any(&cleanup.server_ids)),
+    )
+    .execute(conn)?;
+    Ok(result)
+}
+
+/// 合并报告折算为插入结果：新增计为成功，按源数据库更新计为更新
+pub fn merge_result(report: &MergeReport) -> InsertResult {
+    let mut result = InsertResult::new();
+    result.success_count = report.tables.iter().map(|table| table.inserted).sum();
+    result.updated_count = report.tables.iter().map(|table| table.updated).sum();
+    result
+}
+
+/// 转发参数
+#[derive(Debug, Clone)]
+pub struct ForwardOptions {
+    /// 每次从 outbox 读取的条数
+    pub batch_size: i64,
+    /// 第一次失败后的等待时间，之后每次翻倍
+    pub initial_backoff: Duration,
+    /// 退避等待时间的上限
+    pub max_backoff: Duration,
+    /// 单次 HTTP 请求的超时时间
+    pub timeout: Duration,
+}
+
+impl Default for ForwardOptions {
+    fn default() -> Self {
+        Self {
+            batch_size: 100,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(300),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// 投递失败的负载
+#[derive(Debug, Clone)]
+pub struct ForwardFailure {
+    pub record_id: String,
+    /// 包括本次在内的累计失败次数
+    pub attempts: i32,
+    pub error: String,
+    /// 下一次重试的时间 (毫秒时间戳)
+    pub retry_at: i64,
+}
+
+/// 一轮转发的结果
+#[derive(Debug, Clone, Default)]
+pub struct ForwardReport {
+    pub delivered: usize,
+    /// 已投递的负载中，中心节点此前已接收过的条数
+    pub duplicates: usize,
+    /// 本轮遇到的可重试失败，失败后不再投递后续负载以保持顺序
+    pub failure: Option<ForwardFailure>,
+    /// 本轮被中心节点拒绝、转入死信的负载
+    pub dead_lettered: Vec<ForwardFailure>,
+    /// 队首负载仍在退避中时，允许重试的时间 (毫秒时间戳)
+    pub waiting_until: Option<i64>,
+    /// 本轮结束后 outbox 中剩余的条数
+    pub pending: i64,
+}
+
+/// outbox 的状态
+#[derive(Debug, Clone)]
+pub struct OutboxStatus {
+    /// 等待投递的条数 (不含死信)
+    pub pending: i64,
+    /// 被中心节点拒绝、不再投递的条数
+    pub dead: i64,
+    /// 最早入队、下一个待投递的负载
+    pub head: Option<OutboxEntry>,
+}
+
+/// 中心节点对一次投递的应答
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct IngestAck {
+    pub record_id: Option<String>,
+    /// 该记录 id 此前已被接收，本次没有重复写入
+    pub duplicate: bool,
+    pub success_count: usize,
+    pub updated_count: usize,
+    pub error_count: usize,
+}
+
+/// 将负载加入 outbox，返回分配的记录 id
+///
+/// 调用方应在写入本地数据的同一事务中调用，保证本地数据与待转发负载一致。
+pub fn enqueue(
+    conn: &mut SqliteConnection,
+    kind: &PayloadKind,
+    payload: &str,
+    continue_on_error: bool,
+) -> Result<String> {
+    use crate::schema::outbox;
+
+    let record_id = uuid::Uuid::new_v4().to_string();
+    diesel::insert_into(outbox::table)
+        .values(&NewOutboxEntry {
+            record_id: record_id.clone(),
+            data_type: kind.as_path(),
+            payload: payload.to_string(),
+            continue_on_error,
+            next_attempt_at: chrono::Utc::now().timestamp_millis(),
+        })
+        .execute(conn)?;
+
+    Ok(record_id)
+}
+
+pub fn outbox_status(conn: &mut SqliteConnection) -> Result<OutboxStatus> {
+    use crate::schema::outbox::dsl::*;
+
+    Ok(OutboxStatus {
+        pending: outbox.filter(dead_at.is_null()).count().get_result(conn)?,
+        dead: outbox
+            .filter(dead_at.is_not_null())
+            .count()
+            .get_result(conn)?,
+        head: outbox
+            .filter(dead_at.is_null())
+            .order(id.asc())
+            .first(conn)
+            .optional()?,
+    })
+}
+
+/// 按入队顺序列出死信
+pub fn dead_letters(conn: &mut SqliteConnection) -> Result<Vec<OutboxEntry>> {
+    use crate::schema::outbox::dsl::*;
+
+    Ok(outbox
+        .filter(dead_at.is_not_null())
+        .order(id.asc())
+        .load(conn)?)
+}
+
+/// 把死信重新放回队列 (例如中心节点升级后)，保持原来的入队顺序，返回更新的条数
+pub fn requeue(conn: &mut SqliteConnection, record: &str) -> Result<usize> {
+    use crate::schema::outbox::dsl::*;
+
+    Ok(diesel::update(
+        outbox
+            .filter(record_id.eq(record))
+            .filter(dead_at.is_not_null()),
+    )
+    .set((
+        dead_at.eq(None::<i64>),
+        attempts.eq(0),
+        next_attempt_at.eq(chrono::Utc::now().timestamp_millis()),
+    ))
+    .execute(conn)?)
+}
+
+/// 按记录 id 丢弃 outbox 中的负载 (包括死信)，返回删除的条数
+pub fn discard(conn: &mut SqliteConnection, record: &str) -> Result<usize> {
+    use crate::schema::outbox::dsl::*;
+
+    Ok(diesel::delete(outbox.filter(record_id.eq(record))).execute(conn)?)
+}
+
+/// 第 `attempts` 次失败后的等待时间
+pub fn backoff_delay(attempts: i32, options: &ForwardOptions) -> Duration {
+    let exponent = attempts.saturating_sub(1).clamp(0, 20) as u32;
+    options
+        .initial_backoff
+        .saturating_mul(1 << exponent)
+        .min(options.max_backoff)
+}
+
+/// 按入队顺序投递 outbox 中到期的负载
+///
+/// 某条负载投递失败 (可重试) 后本轮立即结束，后续负载不会越过它先发送，
+/// 避免例如进程数据先于其所属的服务器到达中心节点。被中心节点拒绝的负载转入死信，
+/// 继续投递后面的负载。
+pub fn forward_pending(
+    db_manager: &DatabaseManager,
+    endpoint: &str,
+    options: &ForwardOptions,
+) -> Result<ForwardReport> {
+    use crate::schema::outbox::dsl::*;
+
+    let agent = ureq::AgentBuilder::new().timeout(options.timeout).build();
+    let mut conn = db_manager.get_connection()?;
+    let mut report = ForwardReport::default();
+
+    'batches: loop {
+        let entries: Vec<OutboxEntry> = outbox
+            .filter(dead_at.is_null())
+            .order(id.asc())
+            .limit(options.batch_size)
+            .load(&mut *conn)?;
+        if entries.is_empty() {
+            break;
+        }
+
+        for entry in entries {
+            let now = chrono::Utc::now().timestamp_millis();
+            if entry.next_attempt_at > now {
+                report.waiting_until = Some(entry.next_attempt_at);
+                break 'batches;
+            }
+
+            match post_entry(&agent, endpoint, &entry) {
+                Ok(ack) => {
+                    db_manager.with_busy_retry(|| {
+                        Ok(diesel::delete(outbox.find(entry.id)).execute(&mut *conn)?)
+                    })?;
+                    report.delivered += 1;
+                    if ack.duplicate {
+                        report.duplicates += 1;
+                    }
+                }
+                Err(PostError::Rejected(e)) => {
+                    let failure = ForwardFailure {
+                        record_id: entry.record_id.clone(),
+                        attempts: entry.attempts + 1,
+                        error: e.to_string(),
+                        retry_at: now,
+                    };
+                    db_manager.with_busy_retry(|| {
+                        Ok(diesel::update(outbox.find(entry.id))
+                            .set((
+                                attempts.eq(failure.attempts),
+                                dead_at.eq(Some(now)),
+                                last_error.eq(Some(&failure.error)),
+                            ))
+                            .execute(&mut *conn)?)
+                    })?;
+                    report.dead_lettered.push(failure);
+                }
+                Err(PostError::Transient(e)) => {
+                    let failure = ForwardFailure {
+                        record_id: entry.record_id.clone(),
+                        attempts: entry.attempts + 1,
+                        error: e.to_string(),
+                        retry_at: now
+                            + backoff_delay(entry.attempts + 1, options).as_millis() as i64,
+                    };
+                    db_manager.with_busy_retry(|| {
+                        Ok(diesel::update(outbox.find(entry.id))
+                            .set((
+                                attempts.eq(failure.attempts),
+                                next_attempt_at.eq(failure.retry_at),
+                                last_error.eq(Some(&failure.error)),
+                            ))
+                            .execute(&mut *conn)?)
+                    })?;
+                    report.failure = Some(failure);
+                    break 'batches;
+                }
+            }
+        }
+    }
+
+    report.pending = outbox
+        .filter(dead_at.is_null())
+        .count()
+        .get_result(&mut *conn)?;
+    Ok(report)
+}
+
+/// 投递失败的原因
+enum PostError {
+    /// 中心节点拒绝了负载 (4xx)，原样重试也不会成功
+    Rejected(BlackBoxError),
+    /// 网络错误或中心节点暂时不可用，稍后重试
+    Transient(BlackBoxError),
+}
+
+/// 把一条负载 POST 到中心节点
+fn post_entry(
+    agent: &ureq::Agent,
+    endpoint: &str,
+    entry: &OutboxEntry,
+) -> std::result::Result<IngestAck, PostError> {
+    let url = format!(
+        "{}/ingest/{}",
+        endpoint.trim_end_matches('/'),
+        entry.data_type
+    );
+
+    let mut request = agent
+        .post(&url)
+        .set(RECORD_ID_HEADER, &entry.record_id)
+        .set("Content-Type", "application/json");
+    if entry.continue_on_error {
+        request = request.query("continue_on_error", "true");
+    }
+
+    match request.send_string(&entry.payload) {
+        Ok(response) => {
+            let body = response
+                .into_string()
+                .map_err(|e| PostError::Transient(BlackBoxError::io(&url, e)))?;
+            serde_json::from_str(&body).map_err(|e| PostError::Transient(e.into()))
+        }
+        Err(ureq::Error::Status(code, response)) => {
+            let body = response.into_string().unwrap_or_default();
+            let error = BlackBoxError::io(
+                url,
+                std::io::Error::other(format!("中心节点返回 {}: {}", code, body.trim())),
+            );
+            // 408 和 429 表示暂时无法处理，其余 4xx 说明负载本身被拒绝
+            if (400..500).contains(&code) && code != 408 && code != 429 {
+                Err(PostError::Rejected(error))
+            } else {
+                Err(PostError::Transient(error))
+            }
+        }
+        Err(ureq::Error::Transport(e)) => Err(PostError::Transient(BlackBoxError::io(
+            url,
+            std::io::Error::other(format!("无法连接中心节点: {}", e)),
+        ))),
+    }
+}
+
+/// 中心节点写入转发来的负载，调用方负责事务
+///
+/// 带记录 id 且已在台账中时直接返回首次接收的结果；否则写入数据并记入台账。
+pub fn ingest(
+    conn: &mut SqliteConnection,
+    kind: &PayloadKind,
+    record: Option<&str>,
+    insert: impl FnOnce(&mut SqliteConnection) -> Result<InsertResult>,
+) -> Result<IngestAck> {
+    let (result, duplicate) = IdempotencyService::run(conn, record, &kind.as_path(), insert)?;
+
+    Ok(IngestAck {
+        record_id: record.map(str::to_string),
+        duplicate,
+        success_count: result.success_count,
+        updated_count: result.updated_count,
+        error_count: result.error_count,
+    })
+}
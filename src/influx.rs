@@ -0,0 +1,405 @@
+//! InfluxDB 行协议的导出与解析
+//!
+//! 支持两个 measurement：
+//! - `system_metrics`：tag `server_id`，字段与 `SmartSystemMetric` 一致
+//! - `process_trends`：tag `server_id`、`pid`、`name`、`user`，字段 `cpu_usage`、`memory_usage`、`thread_count`
+//!
+//! 导出的时间戳为毫秒，写入 InfluxDB 时需指定 `precision=ms`。
+
+use crate::error::{BlackBoxError, Result};
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use std::io::Write;
+
+use crate::database::get_named_process_trends;
+use crate::domain::ProcessState;
+use crate::models::*;
+
+pub const SYSTEM_METRICS_MEASUREMENT: &str = "system_metrics";
+pub const PROCESS_TRENDS_MEASUREMENT: &str = "process_trends";
+
+/// 时间戳精度
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precision {
+    Nanoseconds,
+    Microseconds,
+    Milliseconds,
+    Seconds,
+}
+
+impl Precision {
+    /// 解析 InfluxDB HTTP API 的 precision 参数 (ns、us、ms、s)
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "ns" | "n" => Ok(Precision::Nanoseconds),
+            "us" | "u" => Ok(Precision::Microseconds),
+            "ms" => Ok(Precision::Milliseconds),
+            "s" => Ok(Precision::Seconds),
+            _ => Err(BlackBoxError::validation(format!(
+                "不支持的时间戳精度: {}",
+                value
+            ))),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Precision::Nanoseconds => "ns",
+            Precision::Microseconds => "us",
+            Precision::Milliseconds => "ms",
+            Precision::Seconds => "s",
+        }
+    }
+
+    fn to_millis(self, timestamp: i64) -> i64 {
+        match self {
+            Precision::Nanoseconds => timestamp / 1_000_000,
+            Precision::Microseconds => timestamp / 1_000,
+            Precision::Milliseconds => timestamp,
+            Precision::Seconds => timestamp * 1_000,
+        }
+    }
+}
+
+/// 写入行协议失败时错误中的读写目标 (输出可能是文件或标准输出)
+const OUTPUT: &str = "行协议输出";
+
+/// 导出结果：(系统指标行数, 进程趋势行数)
+pub fn export<W: Write>(conn: &mut SqliteConnection, mut writer: W) -> Result<(usize, usize)> {
+    use crate::schema::system_metrics::dsl::*;
+
+    let mut metric_count = 0;
+    let rows = system_metrics
+        .order((server_id.asc(), timestamp.asc()))
+        .load_iter::<SystemMetric, _>(conn)?;
+    for row in rows {
+        writeln!(writer, "{}", format_system_metric(&row?))
+            .map_err(|e| BlackBoxError::io(OUTPUT, e))?;
+        metric_count += 1;
+    }
+
+    let trends = get_named_process_trends(conn)?;
+    for trend in &trends {
+        writeln!(writer, "{}", format_process_trend(trend))
+            .map_err(|e| BlackBoxError::io(OUTPUT, e))?;
+    }
+
+    writer.flush().map_err(|e| BlackBoxError::io(OUTPUT, e))?;
+    Ok((metric_count, trends.len()))
+}
+
+pub fn format_system_metric(m: &SystemMetric) -> String {
+    format!(
+        "{},server_id={} cpu_usage={},memory_usage={},disk_usage={},io_read={},io_write={},network_in={},network_out={} {}",
+        SYSTEM_METRICS_MEASUREMENT,
+        escape_tag(&m.server_id),
+        m.cpu_usage,
+        m.memory_usage,
+        m.disk_usage,
+        m.io_read,
+        m.io_write,
+        m.network_in,
+        m.network_out,
+        m.timestamp
+    )
+}
+
+pub fn format_process_trend(t: &NamedProcessTrend) -> String {
+    format!(
+        "{},server_id={},pid={},name={},user={} cpu_usage={},memory_usage={},thread_count={}i {}",
+        PROCESS_TRENDS_MEASUREMENT,
+        escape_tag(&t.server_id),
+        t.pid,
+        escape_tag(&t.name),
+        escape_tag(&t.user_name),
+        t.cpu_usage,
+        t.memory_usage,
+        t.thread_count,
+        t.timestamp
+    )
+}
+
+/// tag 键值中的逗号、等号和空格需要转义
+fn escape_tag(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, ',' | '=' | ' ' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// 字段值
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    Float(f64),
+    Integer(i64),
+    Boolean(bool),
+    String(String),
+}
+
+impl FieldValue {
+    fn as_f64(&self) -> Option<f64> {
+        match self {
+            FieldValue::Float(v) => Some(*v),
+            FieldValue::Integer(v) => Some(*v as f64),
+            _ => None,
+        }
+    }
+}
+
+/// 一行行协议数据
+#[derive(Debug, Clone)]
+pub struct Point {
+    pub measurement: String,
+    pub tags: Vec<(String, String)>,
+    pub fields: Vec<(String, FieldValue)>,
+    pub timestamp: Option<i64>,
+}
+
+impl Point {
+    fn tag(&self, key: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn field(&self, key: &str) -> Option<&FieldValue> {
+        self.fields.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    fn required_tag(&self, key: &str) -> Result<String> {
+        self.tag(key)
+            .map(str::to_string)
+            .ok_or_else(|| BlackBoxError::validation(format!("缺少 tag {}", key)))
+    }
+
+    fn number_field(&self, key: &str) -> Result<Option<f64>> {
+        match self.field(key) {
+            None => Ok(None),
+            Some(value) => value
+                .as_f64()
+                .map(Some)
+                .ok_or_else(|| BlackBoxError::validation(format!("字段 {} 不是数值", key))),
+        }
+    }
+
+    fn required_number_field(&self, key: &str) -> Result<f64> {
+        self.number_field(key)?
+            .ok_or_else(|| BlackBoxError::validation(format!("缺少字段 {}", key)))
+    }
+}
+
+/// 解析单行行协议
+pub fn parse_line(line: &str) -> Result<Point> {
+    let sections = split_unescaped(line, ' ', true);
+    let sections: Vec<&str> = sections.into_iter().filter(|s| !s.is_empty()).collect();
+    if sections.len() < 2 || sections.len() > 3 {
+        return Err(BlackBoxError::validation(
+            "格式应为 measurement[,tag=value] field=value[,field=value] [timestamp]",
+        ));
+    }
+
+    let mut series = split_unescaped(sections[0], ',', false).into_iter();
+    let measurement = unescape(series.next().unwrap_or_default());
+    if measurement.is_empty() {
+        return Err(BlackBoxError::validation("缺少 measurement"));
+    }
+
+    let mut tags = Vec::new();
+    for tag in series {
+        let (key, value) = split_key_value(tag)?;
+        tags.push((unescape(key), unescape(value)));
+    }
+
+    let mut fields = Vec::new();
+    for field in split_unescaped(sections[1], ',', true) {
+        let (key, value) = split_key_value(field)?;
+        fields.push((unescape(key), parse_field_value(value)?));
+    }
+
+    let timestamp = match sections.get(2) {
+        Some(ts) => Some(
+            ts.parse::<i64>()
+                .map_err(|_| BlackBoxError::validation(format!("无效的时间戳: {}", ts)))?,
+        ),
+        None => None,
+    };
+
+    Ok(Point {
+        measurement,
+        tags,
+        fields,
+        timestamp,
+    })
+}
+
+/// 按未转义 (且可选地不在引号内) 的分隔符切分
+fn split_unescaped(input: &str, separator: char, respect_quotes: bool) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+    let mut in_quotes = false;
+
+    for (i, c) in input.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if respect_quotes && c == '"' {
+            in_quotes = !in_quotes;
+        } else if c == separator && !in_quotes {
+            parts.push(&input[start..i]);
+            start = i + c.len_utf8();
+        }
+    }
+    parts.push(&input[start..]);
+    parts
+}
+
+fn split_key_value(pair: &str) -> Result<(&str, &str)> {
+    let parts = split_unescaped(pair, '=', true);
+    match parts.as_slice() {
+        [key, _, ..] if !key.is_empty() => Ok((key, &pair[key.len() + 1..])),
+        _ => Err(BlackBoxError::validation(format!("无效的键值对: {}", pair))),
+    }
+}
+
+fn unescape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if let Some(next) = chars.next() {
+                result.push(next);
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+fn parse_field_value(value: &str) -> Result<FieldValue> {
+    if let Some(inner) = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        return Ok(FieldValue::String(unescape(inner)));
+    }
+
+    match value {
+        "t" | "T" | "true" | "True" | "TRUE" => return Ok(FieldValue::Boolean(true)),
+        "f" | "F" | "false" | "False" | "FALSE" => return Ok(FieldValue::Boolean(false)),
+        _ => {}
+    }
+
+    if let Some(int) = value.strip_suffix('i').or_else(|| value.strip_suffix('u')) {
+        return int
+            .parse::<i64>()
+            .map(FieldValue::Integer)
+            .map_err(|_| BlackBoxError::validation(format!("无效的整数字段值: {}", value)));
+    }
+
+    value
+        .parse::<f64>()
+        .map(FieldValue::Float)
+        .map_err(|_| BlackBoxError::validation(format!("无效的字段值: {}", value)))
+}
+
+/// 按 measurement 分流后的写入数据
+#[derive(Debug, Default)]
+pub struct ParsedPoints {
+    pub system_metrics: Vec<SmartSystemMetric>,
+    pub process_trends: Vec<SmartProcessTrendPoint>,
+    /// 逐行的解析错误
+    pub errors: Vec<String>,
+}
+
+/// 解析整段行协议文本，空行和 `#` 开头的注释行会被跳过
+pub fn parse(content: &str, precision: Precision) -> ParsedPoints {
+    let mut parsed = ParsedPoints::default();
+    let now = chrono::Utc::now().timestamp_millis();
+
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let result = parse_line(line).and_then(|point| {
+            let timestamp = point
+                .timestamp
+                .map(|ts| precision.to_millis(ts))
+                .unwrap_or(now);
+            match point.measurement.as_str() {
+                SYSTEM_METRICS_MEASUREMENT => {
+                    parsed
+                        .system_metrics
+                        .push(to_system_metric(&point, timestamp)?);
+                    Ok(())
+                }
+                PROCESS_TRENDS_MEASUREMENT => {
+                    parsed
+                        .process_trends
+                        .push(to_process_trend(&point, timestamp)?);
+                    Ok(())
+                }
+                other => Err(BlackBoxError::validation(format!(
+                    "不支持的 measurement: {}",
+                    other
+                ))),
+            }
+        });
+
+        if let Err(e) = result {
+            parsed.errors.push(format!("第 {} 行: {}", index + 1, e));
+        }
+    }
+
+    parsed
+}
+
+fn to_system_metric(point: &Point, timestamp: i64) -> Result<SmartSystemMetric> {
+    // IO 和网络字段缺省为 0，CPU、内存和磁盘必须提供
+    let optional =
+        |key: &str| -> Result<f32> { Ok(point.number_field(key)?.unwrap_or(0.0) as f32) };
+
+    Ok(SmartSystemMetric {
+        server_id: point.required_tag("server_id")?,
+        timestamp,
+        cpu_usage: point.required_number_field("cpu_usage")? as f32,
+        memory_usage: point.required_number_field("memory_usage")? as f32,
+        disk_usage: point.required_number_field("disk_usage")? as f32,
+        io_read: optional("io_read")?,
+        io_write: optional("io_write")?,
+        network_in: optional("network_in")?,
+        network_out: optional("network_out")?,
+    })
+}
+
+fn to_process_trend(point: &Point, timestamp: i64) -> Result<SmartProcessTrendPoint> {
+    let pid = point.required_tag("pid")?;
+    let pid = pid
+        .parse::<i32>()
+        .map_err(|_| BlackBoxError::validation(format!("无效的 pid: {}", pid)))?;
+
+    // 状态可以作为 tag 或字符串字段提供
+    let status = match (point.tag("status"), point.field("status")) {
+        (Some(status), _) => Some(ProcessState::from(status)),
+        (None, Some(FieldValue::String(status))) => Some(ProcessState::from(status.as_str())),
+        _ => None,
+    };
+
+    Ok(SmartProcessTrendPoint {
+        server_id: point.required_tag("server_id")?,
+        pid,
+        name: point.required_tag("name")?,
+        user_name: point.required_tag("user")?,
+        status,
+        timestamp,
+        cpu_usage: point.required_number_field("cpu_usage")? as f32,
+        memory_usage: point.required_number_field("memory_usage")? as f32,
+        thread_count: point.required_number_field("thread_count")? as i32,
+    })
+}
@@ -0,0 +1,197 @@
+//! 崩溃特征知识库
+//!
+//! 知识库把已知的崩溃特征 (标题、消息、堆栈和崩溃类型上的正则表达式) 映射到摘要、分析和
+//! 按优先级排列的处理建议。配置文件的 `[analyzer] knowledge_base` 指定知识库文件后，
+//! 智能插入新建崩溃日志时按文件中的顺序匹配，第一条匹配的特征写入 `ai_summary`、
+//! `ai_analysis` 和 AI 建议，不依赖任何外部模型。
+//!
+//! ```toml
+//! [[signatures]]
+//! name = "thread-limit"
+//! stack_trace = "pthread_create.*Resource temporarily unavailable"
+//! summary = "线程数达到上限，无法创建新线程"
+//! analysis = "进程或用户的线程数达到 nproc / threads-max 限制。"
+//!
+//! [[signatures.recommendations]]
+//! action = "查看线程上限"
+//! command = "ulimit -u; cat /proc/sys/kernel/threads-max"
+//! ```
+//!
+//! 扩展名为 `.json` 的文件按 JSON 读取，结构相同；其余按 TOML 读取。
+
+use crate::error::{BlackBoxError, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::analyze::Analysis;
+use crate::models::*;
+
+/// 知识库文件
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct KnowledgeBaseFile {
+    #[serde(default)]
+    pub signatures: Vec<Signature>,
+}
+
+/// 一条崩溃特征，设置的正则表达式必须全部匹配，至少设置一个
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Signature {
+    pub name: String,
+    pub title: Option<String>,
+    pub message: Option<String>,
+    pub stack_trace: Option<String>,
+    pub crash_type: Option<String>,
+    pub summary: String,
+    #[serde(default)]
+    pub analysis: String,
+    #[serde(default)]
+    pub recommendations: Vec<SignatureRecommendation>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SignatureRecommendation {
+    /// 未设置时按在列表中的顺序编号
+    pub priority: Option<i32>,
+    pub action: String,
+    #[serde(default)]
+    pub command: String,
+}
+
+/// 编译后的知识库
+#[derive(Debug, Clone)]
+pub struct KnowledgeBase {
+    entries: Vec<Entry>,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    signature: Signature,
+    title: Option<Regex>,
+    message: Option<Regex>,
+    stack_trace: Option<Regex>,
+    crash_type: Option<Regex>,
+}
+
+impl KnowledgeBase {
+    /// 读取并编译知识库文件
+    pub fn load(path: &str) -> Result<Self> {
+        let content = fs::read_to_string(path).map_err(|e| BlackBoxError::io(path, e))?;
+        let file: KnowledgeBaseFile = if Path::new(path)
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+        {
+            serde_json::from_str(&content)
+                .map_err(|e| BlackBoxError::parse("JSON", format!("{}: {}", path, e)))?
+        } else {
+            toml::from_str(&content)
+                .map_err(|e| BlackBoxError::parse("TOML", format!("{}: {}", path, e)))?
+        };
+        Self::new(file.signatures)
+    }
+
+    /// 编译特征，正则表达式无效、没有设置任何正则或名称重复时返回校验错误
+    pub fn new(signatures: Vec<Signature>) -> Result<Self> {
+        let mut entries: Vec<Entry> = Vec::with_capacity(signatures.len());
+
+        for signature in signatures {
+            if signature.name.trim().is_empty() {
+                return Err(BlackBoxError::validation("知识库特征的 name 不能为空"));
+            }
+            if entries
+                .iter()
+                .any(|entry| entry.signature.name == signature.name)
+            {
+                return Err(BlackBoxError::validation(format!(
+                    "知识库特征名称重复: {}",
+                    signature.name
+                )));
+            }
+
+            let compile = |field: &str, pattern: &Option<String>| {
+                pattern.as_deref().map(Regex::new).transpose().map_err(|e| {
+                    BlackBoxError::validation(format!(
+                        "知识库特征 {} 的 {} 正则无效: {}",
+                        signature.name, field, e
+                    ))
+                })
+            };
+            let entry = Entry {
+                title: compile("title", &signature.title)?,
+                message: compile("message", &signature.message)?,
+                stack_trace: compile("stack_trace", &signature.stack_trace)?,
+                crash_type: compile("crash_type", &signature.crash_type)?,
+                signature,
+            };
+            if entry.title.is_none()
+                && entry.message.is_none()
+                && entry.stack_trace.is_none()
+                && entry.crash_type.is_none()
+            {
+                return Err(BlackBoxError::validation(format!(
+                    "知识库特征 {} 至少需要设置 title、message、stack_trace、crash_type 中的一项",
+                    entry.signature.name
+                )));
+            }
+            entries.push(entry);
+        }
+
+        Ok(Self { entries })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 按顺序查找第一条匹配的特征
+    pub fn find(&self, crash_log: &CrashLog) -> Option<&Signature> {
+        let matches = |regex: &Option<Regex>, text: &str| {
+            regex.as_ref().is_none_or(|regex| regex.is_match(text))
+        };
+
+        self.entries
+            .iter()
+            .find(|entry| {
+                matches(&entry.title, &crash_log.title)
+                    && matches(&entry.message, &crash_log.message)
+                    && matches(
+                        &entry.stack_trace,
+                        crash_log.stack_trace.as_deref().unwrap_or_default(),
+                    )
+                    && matches(&entry.crash_type, crash_log.crash_type.as_str())
+            })
+            .map(|entry| &entry.signature)
+    }
+
+    /// 匹配的特征对应的分析结果，建议按优先级排列
+    pub fn analyze(&self, crash_log: &CrashLog) -> Option<Analysis> {
+        let signature = self.find(crash_log)?;
+
+        let mut recommendations: Vec<NewAiRecommendation> = signature
+            .recommendations
+            .iter()
+            .enumerate()
+            .map(|(index, recommendation)| NewAiRecommendation {
+                crash_log_id: crash_log.id,
+                priority: recommendation.priority.unwrap_or(index as i32 + 1),
+                action: recommendation.action.clone(),
+                command: recommendation.command.clone(),
+            })
+            .collect();
+        recommendations.sort_by_key(|recommendation| recommendation.priority);
+
+        Some(Analysis {
+            summary: signature.summary.clone(),
+            analysis: signature.analysis.clone(),
+            recommendations,
+        })
+    }
+}
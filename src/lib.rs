@@ -1,21 +1,52 @@
 //! BlackBox - 服务器监控数据管理系统库
-//! 
+//!
 //! 提供高性能的服务器监控数据管理功能，支持智能数据插入、复杂查询分析和数据库管理。
 
-pub mod schema;
-pub mod models;
+pub mod analyze;
+pub mod changes;
+pub mod config;
+pub mod crash;
+pub mod csv_io;
 pub mod database;
+pub mod domain;
+pub mod error;
+pub mod events;
+pub mod forward;
+pub mod influx;
+pub mod knowledge;
+pub mod lossless;
+pub mod merge;
+pub mod models;
+pub mod notify;
+pub mod output;
+pub mod prometheus;
+pub mod schema;
+pub mod server;
 pub mod services;
+pub mod store;
 
-use anyhow::Result;
+use diesel::sqlite::SqliteConnection;
+use serde::Serialize;
+use std::fmt;
 use std::fs;
+use std::sync::Arc;
 
-pub use models::*;
+use csv_io::CsvTable;
+use forward::PayloadKind;
+use knowledge::KnowledgeBase;
+use notify::Notifier;
+
+pub use config::*;
 pub use database::*;
+pub use domain::*;
+pub use error::{BlackBoxError, Result};
+pub use events::*;
+pub use models::*;
 pub use services::*;
+pub use store::*;
 
 /// 智能数据插入类型
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum SmartDataType {
     /// 服务器信息 (已存在则更新状态)
     Servers,
@@ -29,33 +60,229 @@ pub enum SmartDataType {
     Combined,
 }
 
+impl SmartDataType {
+    /// 转发接口路径中使用的类型名
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SmartDataType::Servers => "servers",
+            SmartDataType::SystemMetrics => "system_metrics",
+            SmartDataType::Processes => "processes",
+            SmartDataType::CrashLogs => "crash_logs",
+            SmartDataType::Combined => "combined",
+        }
+    }
+
+    /// 解析类型名，同时接受下划线和连字符写法
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.replace('-', "_").as_str() {
+            "servers" => Ok(SmartDataType::Servers),
+            "system_metrics" => Ok(SmartDataType::SystemMetrics),
+            "processes" => Ok(SmartDataType::Processes),
+            "crash_logs" => Ok(SmartDataType::CrashLogs),
+            "combined" => Ok(SmartDataType::Combined),
+            _ => Err(BlackBoxError::validation(format!(
+                "未知的数据类型: {}",
+                name
+            ))),
+        }
+    }
+}
+
 /// BlackBox 核心库结构
 pub struct BlackBox {
     db_manager: DatabaseManager,
+    config: BlackBoxConfig,
+    subscribers: Vec<Arc<dyn Subscriber>>,
+    /// 配置了通知目标时发送崩溃和告警通知
+    notifier: Option<Notifier>,
+    /// 应用配置时读取的崩溃特征知识库，未配置或读取失败时为 None
+    knowledge_base: Option<Arc<KnowledgeBase>>,
+}
+
+/// [`BlackBox`] 的构建器，未设置的项使用 [`BlackBoxConfig`] 的默认值
+///
+/// # 示例
+/// ```rust
+/// use blackbox::BlackBox;
+///
+/// let blackbox = BlackBox::builder()
+///     .db_path("monitoring.db")
+///     .retention_hours(48)
+///     .thread_exception_threshold(4000)
+///     .build();
+/// ```
+#[derive(Clone, Default)]
+pub struct BlackBoxBuilder {
+    config: BlackBoxConfig,
+    subscribers: Vec<Arc<dyn Subscriber>>,
+    knowledge_base: Option<Arc<KnowledgeBase>>,
+}
+
+impl fmt::Debug for BlackBoxBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BlackBoxBuilder")
+            .field("config", &self.config)
+            .field("subscribers", &self.subscribers.len())
+            .field(
+                "knowledge_base",
+                &self
+                    .knowledge_base
+                    .as_ref()
+                    .map(|knowledge_base| knowledge_base.len()),
+            )
+            .finish()
+    }
+}
+
+impl BlackBoxBuilder {
+    /// 以已有配置 (如从 TOML 文件读取的配置) 为基础
+    pub fn config(mut self, config: BlackBoxConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn db_path(mut self, path: impl Into<String>) -> Self {
+        self.config.database.path = Some(path.into());
+        self
+    }
+
+    pub fn connection_options(mut self, options: ConnectionOptions) -> Self {
+        self.config.connection = options;
+        self
+    }
+
+    /// 智能插入前清理旧数据的保留小时数
+    pub fn retention_hours(mut self, hours: i64) -> Self {
+        self.config.retention.hours = hours;
+        self
+    }
+
+    pub fn thread_exception_threshold(mut self, threshold: i32) -> Self {
+        self.config.detection.thread_exception_threshold = threshold;
+        self
+    }
+
+    /// dmesg 中判定为系统崩溃的字符串
+    pub fn crash_indicators(mut self, indicators: Vec<String>) -> Self {
+        self.config.detection.crash_indicators = indicators;
+        self
+    }
+
+    /// 注册插入事件的订阅者，见 [`BlackBox::subscribe`]
+    pub fn subscriber(mut self, subscriber: Arc<dyn Subscriber>) -> Self {
+        self.subscribers.push(subscriber);
+        self
+    }
+
+    /// 使用已编译的崩溃特征知识库，代替配置中的 `analyzer.knowledge_base` 文件
+    pub fn knowledge_base(mut self, knowledge_base: KnowledgeBase) -> Self {
+        self.knowledge_base = Some(Arc::new(knowledge_base));
+        self
+    }
+
+    pub fn build(self) -> BlackBox {
+        // 直接提供了知识库时不再读取配置中的文件
+        let knowledge_base = match self.knowledge_base {
+            Some(knowledge_base) => Some(knowledge_base),
+            None => BlackBox::load_knowledge_base(&self.config),
+        };
+        let mut blackbox = BlackBox::with_knowledge_base(self.config, knowledge_base);
+        blackbox.subscribers = self.subscribers;
+        blackbox
+    }
 }
 
 impl BlackBox {
     /// 创建新的 BlackBox 实例
-    /// 
+    ///
     /// # 参数
     /// * `db_path` - 数据库文件路径，None 则使用默认路径
-    /// 
+    ///
     /// # 示例
     /// ```rust
     /// use blackbox::BlackBox;
-    /// 
+    ///
     /// // 使用默认数据库路径
     /// let blackbox = BlackBox::new(None);
-    /// 
+    ///
     /// // 使用指定数据库路径
     /// let blackbox = BlackBox::new(Some("monitoring.db".to_string()));
     /// ```
     pub fn new(db_path: Option<String>) -> Self {
-        Self { 
-            db_manager: DatabaseManager::new(db_path)
+        Self::from_config(BlackBoxConfig {
+            database: DatabaseConfig { path: db_path },
+            ..Default::default()
+        })
+    }
+
+    /// 创建构建器，用于设置连接参数、保留期和崩溃检测规则
+    pub fn builder() -> BlackBoxBuilder {
+        BlackBoxBuilder::default()
+    }
+
+    /// 按配置创建 BlackBox 实例
+    ///
+    /// 配置了 `analyzer.knowledge_base` 时在这里读取并校验知识库文件；
+    /// 文件缺失或内容无效时输出警告，新建的崩溃日志改用内置规则分析。
+    pub fn from_config(config: BlackBoxConfig) -> Self {
+        let knowledge_base = Self::load_knowledge_base(&config);
+        Self::with_knowledge_base(config, knowledge_base)
+    }
+
+    fn with_knowledge_base(
+        config: BlackBoxConfig,
+        knowledge_base: Option<Arc<KnowledgeBase>>,
+    ) -> Self {
+        Self {
+            db_manager: DatabaseManager::with_options(
+                config.database.path.clone(),
+                config.connection.clone(),
+            ),
+            notifier: Self::build_notifier(&config),
+            config,
+            subscribers: Vec::new(),
+            knowledge_base,
+        }
+    }
+
+    /// 读取配置的知识库文件，失败时警告并返回 None
+    fn load_knowledge_base(config: &BlackBoxConfig) -> Option<Arc<KnowledgeBase>> {
+        let path = config.analyzer.knowledge_base.as_deref()?;
+        match KnowledgeBase::load(path) {
+            Ok(knowledge_base) => Some(Arc::new(knowledge_base)),
+            Err(e) => {
+                eprintln!("⚠️  知识库加载失败，使用内置规则分析 ({}): {}", path, e);
+                None
+            }
         }
     }
 
+    /// 使用自定义连接参数创建 BlackBox 实例
+    ///
+    /// # 示例
+    /// ```rust
+    /// use blackbox::{BlackBox, ConnectionOptions};
+    ///
+    /// let options = ConnectionOptions {
+    ///     busy_timeout_ms: 10_000,
+    ///     synchronous: "FULL".to_string(),
+    ///     ..Default::default()
+    /// };
+    /// let blackbox = BlackBox::with_connection_options(Some("monitoring.db".to_string()), options);
+    /// ```
+    pub fn with_connection_options(db_path: Option<String>, options: ConnectionOptions) -> Self {
+        Self::from_config(BlackBoxConfig {
+            database: DatabaseConfig { path: db_path },
+            connection: options,
+            ..Default::default()
+        })
+    }
+
+    /// 当前生效的配置
+    pub fn config(&self) -> &BlackBoxConfig {
+        &self.config
+    }
+
     /// 获取当前数据库路径
     pub fn get_db_path(&self) -> &Option<String> {
         self.db_manager.get_db_path()
@@ -63,221 +290,1238 @@ impl BlackBox {
 
     /// 设置数据库路径
     pub fn set_db_path(&mut self, db_path: Option<String>) {
-        self.db_manager = DatabaseManager::new(db_path);
+        self.config.database.path = db_path;
+        self.db_manager = DatabaseManager::with_options(
+            self.config.database.path.clone(),
+            self.config.connection.clone(),
+        );
+        self.notifier = Self::build_notifier(&self.config);
+    }
+
+    /// 通知使用独立的连接管理器写入投递记录
+    fn build_notifier(config: &BlackBoxConfig) -> Option<Notifier> {
+        config.notify.is_enabled().then(|| {
+            Notifier::new(
+                DatabaseManager::with_options(
+                    config.database.path.clone(),
+                    config.connection.clone(),
+                ),
+                config.notify.clone(),
+            )
+        })
+    }
+
+    /// 按配置生成智能插入参数
+    fn insert_options(&self, continue_on_error: bool) -> Result<InsertOptions> {
+        Ok(InsertOptions {
+            continue_on_error,
+            retention: self.config.retention.clone(),
+            detection: self.config.detection.clone(),
+            knowledge_base: self.knowledge_base(),
+        })
+    }
+
+    /// 崩溃特征知识库，未配置或加载失败时为 None
+    pub fn knowledge_base(&self) -> Option<Arc<KnowledgeBase>> {
+        self.knowledge_base.clone()
+    }
+
+    /// 注册插入事件的订阅者
+    ///
+    /// 智能插入 (JSON、CSV、行协议和转发写入) 的事务提交后，按注册顺序同步调用订阅者。
+    /// 导入、合并以及幂等键重复提交不产生事件。配置的通知目标 ([`notify::Notifier`])
+    /// 只在这里入队，由后台线程投递。
+    ///
+    /// # 示例
+    /// ```rust
+    /// use std::sync::Arc;
+    /// use blackbox::{BlackBox, CrashLog, Subscriber};
+    ///
+    /// struct CrashPrinter;
+    ///
+    /// impl Subscriber for CrashPrinter {
+    ///     fn on_crash_detected(&self, crash_log: &CrashLog) {
+    ///         println!("{} 发生崩溃: {}", crash_log.server_id, crash_log.title);
+    ///     }
+    /// }
+    ///
+    /// let mut blackbox = BlackBox::new(Some("monitoring.db".to_string()));
+    /// blackbox.subscribe(Arc::new(CrashPrinter));
+    /// ```
+    pub fn subscribe(&mut self, subscriber: Arc<dyn Subscriber>) {
+        self.subscribers.push(subscriber);
+    }
+
+    /// 没有订阅者和通知目标时不记录事件
+    fn records_events(&self) -> bool {
+        !self.subscribers.is_empty() || self.notifier.is_some()
+    }
+
+    /// 包装连接以记录插入事件
+    fn recorder<'a>(&self, conn: &'a mut SqliteConnection) -> EventRecorder<'a, SqliteConnection> {
+        EventRecorder::new(conn, self.records_events())
+    }
+
+    /// 把已提交的事件通知给所有订阅者
+    fn notify(&self, events: &[InsertEvent]) {
+        for event in events {
+            for subscriber in &self.subscribers {
+                event.dispatch(subscriber.as_ref());
+            }
+            if let Some(notifier) = &self.notifier {
+                event.dispatch(notifier);
+            }
+        }
     }
     /// 初始化数据库
-    /// 
+    ///
     /// # 参数
     /// * `force` - 是否强制重新创建数据库
-    /// 
+    ///
     /// # 示例
-    /// ```rust
+    /// ```rust,no_run
     /// use blackbox::BlackBox;
-    /// 
+    ///
+    /// # fn main() -> anyhow::Result<()> {
     /// // 初始化默认数据库
     /// let blackbox = BlackBox::new(None);
     /// blackbox.init_database(false)?;
-    /// 
+    ///
     /// // 初始化指定数据库
     /// let blackbox = BlackBox::new(Some("monitoring.db".to_string()));
     /// blackbox.init_database(false)?;
-    /// 
+    ///
     /// // 强制重新创建数据库
     /// let blackbox = BlackBox::new(Some("test.db".to_string()));
     /// blackbox.init_database(true)?;
+    /// # Ok(())
+    /// # }
     /// ```
     pub fn init_database(&self, force: bool) -> Result<()> {
         DatabaseInitService::init_database(&self.db_manager, force)
     }
 
+    /// 将数据库结构升级到当前版本
+    ///
+    /// # 返回
+    /// 返回升级前的结构版本号
+    pub fn migrate_database(&self) -> Result<i32> {
+        DatabaseInitService::migrate_database(&self.db_manager)
+    }
+
+    /// 获取数据库结构版本
+    pub fn get_schema_version(&self) -> Result<i32> {
+        let mut conn = self.db_manager.get_connection()?;
+        get_schema_version(&mut conn)
+    }
+
+    /// 检查孤儿数据（引用了不存在的服务器或崩溃日志的记录）
+    pub fn check_orphans(&self) -> Result<OrphanReport> {
+        let mut conn = self.db_manager.get_connection()?;
+        IntegrityService::find_orphans(&mut conn)
+    }
+
+    /// 诊断数据库：物理完整性、结构版本和逻辑一致性
+    ///
+    /// # 参数
+    /// * `quick` - 使用 `quick_check` 代替耗时更长的 `integrity_check`
+    pub fn diagnose(&self, quick: bool) -> Result<DoctorReport> {
+        let mut conn = self.db_manager.get_connection()?;
+        IntegrityService::diagnose(&mut conn, quick)
+    }
+
+    /// 修复逻辑不一致（重复的系统指标、孤儿数据）
+    pub fn repair(&self) -> Result<RepairReport> {
+        let mut conn = self.db_manager.get_connection()?;
+        IntegrityService::repair(&mut conn)
+    }
+
+    /// 将可读的数据抢救到新的数据库文件
+    ///
+    /// # 参数
+    /// * `target_path` - 新数据库文件路径（必须不存在）
+    pub fn salvage_to(&self, target_path: &str) -> Result<SalvageReport> {
+        let mut conn = self.db_manager.get_connection()?;
+        let target = DatabaseManager::new(Some(target_path.to_string()));
+        IntegrityService::salvage(&mut conn, &target)
+    }
+
+    /// 在线备份数据库
+    ///
+    /// 备份期间其他进程可以继续写入，适合定时任务使用。
+    ///
+    /// # 参数
+    /// * `target_path` - 备份文件路径
+    /// * `overwrite` - 备份文件已存在时是否覆盖
+    ///
+    /// # 返回
+    /// 返回备份文件大小（字节）
+    pub fn backup_to(&self, target_path: &str, overwrite: bool) -> Result<u64> {
+        let mut conn = self.db_manager.get_connection()?;
+        BackupService::backup(&mut conn, target_path, overwrite)
+    }
+
+    /// 从备份文件恢复数据库（会覆盖当前数据库）
+    ///
+    /// # 参数
+    /// * `source_path` - 备份文件路径，结构版本必须与当前程序一致
+    pub fn restore_from(&self, source_path: &str) -> Result<()> {
+        BackupService::restore(source_path, &self.db_manager)
+    }
+
     /// 智能插入数据
-    /// 
+    ///
     /// # 参数
     /// * `data_type` - 数据类型
     /// * `json_data` - JSON 格式的数据字符串
     /// * `continue_on_error` - 遇到错误时是否继续处理
-    /// 
+    ///
     /// # 示例
-    /// ```rust
+    /// ```rust,no_run
     /// use blackbox::{BlackBox, SmartDataType};
-    /// 
+    ///
+    /// # fn main() -> anyhow::Result<()> {
     /// let blackbox = BlackBox::new(Some("test.db".to_string()));
     /// let json_data = r#"[{"serverId":"srv-01","serverName":"测试服务器","serverIp":"192.168.1.100","serverOs":"Ubuntu 22.04","serverStatus":"running"}]"#;
     /// let result = blackbox.smart_insert(SmartDataType::Servers, json_data, false)?;
+    /// # Ok(())
+    /// # }
     /// ```
     pub fn smart_insert(
         &self,
-        data_type: SmartDataType, 
-        json_data: &str, 
-        continue_on_error: bool
+        data_type: SmartDataType,
+        json_data: &str,
+        continue_on_error: bool,
     ) -> Result<InsertResult> {
+        let (result, _) =
+            self.insert_json(data_type, json_data, continue_on_error, self.forwards())?;
+        Ok(result)
+    }
+
+    /// 智能插入数据，并把原始负载加入 outbox 等待转发到中心节点
+    ///
+    /// 本地写入与入队在同一事务中完成；本地写入失败时不会入队。
+    ///
+    /// # 返回
+    /// 插入结果和分配给负载的记录 id
+    pub fn smart_insert_and_forward(
+        &self,
+        data_type: SmartDataType,
+        json_data: &str,
+        continue_on_error: bool,
+    ) -> Result<(InsertResult, String)> {
+        let (result, record_id) =
+            self.insert_json(data_type, json_data, continue_on_error, true)?;
+        Ok((result, record_id.unwrap_or_default()))
+    }
+
+    /// 是否为边缘节点模式 (配置 `forward.enabled`)，所有写入都加入转发队列
+    fn forwards(&self) -> bool {
+        self.config.forward.enabled
+    }
+
+    /// 写入 JSON 负载，`forward` 为 true 时在同一事务中加入转发队列并返回记录 id
+    fn insert_json(
+        &self,
+        data_type: SmartDataType,
+        json_data: &str,
+        continue_on_error: bool,
+        forward: bool,
+    ) -> Result<(InsertResult, Option<String>)> {
         let mut conn = self.db_manager.get_connection()?;
-        
-        match data_type {
-            SmartDataType::Servers => {
-                let servers: Vec<NewServer> = serde_json::from_str(json_data)?;
-                SmartInsertService::insert_servers(&mut conn, servers, continue_on_error)
-            }
-            SmartDataType::SystemMetrics => {
-                let metrics: Vec<SmartSystemMetric> = serde_json::from_str(json_data)?;
-                SmartInsertService::insert_system_metrics(&mut conn, metrics, continue_on_error)
-            }
-            SmartDataType::Processes => {
-                let processes: Vec<SmartProcessInsert> = serde_json::from_str(json_data)?;
-                SmartInsertService::insert_processes(&mut conn, processes, continue_on_error)
-            }
-            SmartDataType::CrashLogs => {
-                let crash_logs: Vec<SmartCrashLog> = serde_json::from_str(json_data)?;
-                SmartInsertService::insert_crash_logs(&mut conn, crash_logs, continue_on_error)
-            }
-            SmartDataType::Combined => {
-                let combined_data: CombinedInsertData = serde_json::from_str(json_data)?;
-                SmartInsertService::insert_combined_data(&mut conn, combined_data, continue_on_error)
-            }
+        let options = self.insert_options(continue_on_error)?;
+
+        // 整批数据在一个 IMMEDIATE 事务中写入：开始时即获取写锁，
+        // 被其他写入者阻塞时可以安全地整体重试
+        let (result, record_id, events) = self.db_manager.with_busy_retry(|| {
+            conn.immediate_transaction(|conn| {
+                let (result, events) =
+                    insert_payload(conn, &data_type, json_data, &options, self.records_events())?;
+                let record_id = forward
+                    .then(|| {
+                        forward::enqueue(
+                            conn,
+                            &PayloadKind::Json(data_type.clone()),
+                            json_data,
+                            continue_on_error,
+                        )
+                    })
+                    .transpose()?;
+                Ok((result, record_id, events))
+            })
+        })?;
+        self.notify(&events);
+        Ok((result, record_id))
+    }
+
+    /// 写入边缘节点转发来的 JSON 负载 (中心节点)
+    ///
+    /// 带记录 id 时按 id 去重：已接收过的负载不会重复写入，直接返回首次的结果。
+    pub fn ingest_forwarded(
+        &self,
+        data_type: SmartDataType,
+        json_data: &str,
+        record_id: Option<&str>,
+        continue_on_error: bool,
+    ) -> Result<forward::IngestAck> {
+        self.ingest_forwarded_payload(
+            &PayloadKind::Json(data_type),
+            json_data,
+            record_id,
+            continue_on_error,
+        )
+    }
+
+    /// 写入边缘节点转发来的任意种类负载 (中心节点)
+    ///
+    /// 按种类以与边缘节点相同的规则写入；带记录 id 时按 id 去重。
+    pub fn ingest_forwarded_payload(
+        &self,
+        kind: &PayloadKind,
+        payload: &str,
+        record_id: Option<&str>,
+        continue_on_error: bool,
+    ) -> Result<forward::IngestAck> {
+        if *kind == PayloadKind::Merge {
+            return self.ingest_forwarded_merge(payload, record_id);
         }
+
+        let mut conn = self.db_manager.get_connection()?;
+        let options = self.insert_options(continue_on_error)?;
+        let record_events = self.records_events();
+
+        let (ack, events) = self.db_manager.with_busy_retry(|| {
+            conn.immediate_transaction(|conn| {
+                let mut events = Vec::new();
+                let ack = forward::ingest(conn, kind, record_id, |conn| match kind {
+                    PayloadKind::Json(data_type) => {
+                        let (result, recorded) =
+                            insert_payload(conn, data_type, payload, &options, record_events)?;
+                        events = recorded;
+                        Ok(result)
+                    }
+                    PayloadKind::Csv(data_type) => {
+                        recorded(conn, record_events, &mut events, |store| {
+                            insert_csv_rows(store, data_type, payload, &options)
+                        })
+                    }
+                    PayloadKind::LineProtocol(precision) => {
+                        recorded(conn, record_events, &mut events, |store| {
+                            insert_line_protocol(store, payload, *precision, &options)
+                        })
+                    }
+                    PayloadKind::Labels => {
+                        forward::apply_labels(conn, &serde_json::from_str(payload)?)
+                    }
+                    PayloadKind::Import => {
+                        let json_data: JsonData = serde_json::from_str(payload)?;
+                        let mut result = InsertResult::new();
+                        result.success_count = json_data.servers.len();
+                        JsonImportService::import_json_data(
+                            conn,
+                            json_data,
+                            &self.config.retention,
+                        )?;
+                        Ok(result)
+                    }
+                    PayloadKind::Crash => {
+                        forward::apply_crash_change(conn, &serde_json::from_str(payload)?)
+                    }
+                    PayloadKind::Cleanup => {
+                        forward::apply_cleanup(conn, &serde_json::from_str(payload)?)
+                    }
+                    PayloadKind::Merge => unreachable!("合并负载在事务外单独处理"),
+                })?;
+                Ok((ack, events))
+            })
+        })?;
+        self.notify(&events);
+        Ok(ack)
+    }
+
+    /// 写入转发来的合并负载：先把无损导出写入临时数据库，再按合并规则并入当前数据库
+    fn ingest_forwarded_merge(
+        &self,
+        payload: &str,
+        record_id: Option<&str>,
+    ) -> Result<forward::IngestAck> {
+        let data: lossless::LosslessExport = serde_json::from_str(payload)?;
+        let staging_path = format!(
+            "{}.merge-{}.tmp",
+            self.db_manager.get_file_path(),
+            uuid::Uuid::new_v4()
+        );
+        let staging = DatabaseManager::new(Some(staging_path.clone()));
+
+        let ingested = (|| {
+            DatabaseInitService::init_database(&staging, false)?;
+            staging
+                .get_connection()?
+                .immediate_transaction(|conn| lossless::import(conn, &data))?;
+            staging.close_idle_connections();
+
+            let mut conn = self.db_manager.get_connection()?;
+            self.db_manager.with_busy_retry(|| {
+                merge::with_source(&mut conn, &staging_path, |conn| {
+                    forward::ingest(conn, &PayloadKind::Merge, record_id, |conn| {
+                        Ok(forward::merge_result(&merge::merge_attached(
+                            conn,
+                            "forwarded",
+                        )?))
+                    })
+                })
+            })
+        })();
+
+        staging.close_idle_connections();
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = fs::remove_file(format!("{}{}", staging_path, suffix));
+        }
+        ingested
+    }
+
+    /// 把 outbox 中到期的负载按顺序投递到中心节点
+    ///
+    /// # 参数
+    /// * `endpoint` - 中心节点 `blackbox serve` 的地址，如 `http://10.0.0.1:9464`
+    /// * `options` - 批量大小、退避和超时参数
+    pub fn forward_pending(
+        &self,
+        endpoint: &str,
+        options: &forward::ForwardOptions,
+    ) -> Result<forward::ForwardReport> {
+        forward::forward_pending(&self.db_manager, endpoint, options)
+    }
+
+    /// 查看 outbox 中待转发的负载
+    pub fn outbox_status(&self) -> Result<forward::OutboxStatus> {
+        let mut conn = self.db_manager.get_connection()?;
+        forward::outbox_status(&mut conn)
+    }
+
+    /// 丢弃 outbox 中指定记录 id 的负载，返回删除的条数
+    pub fn discard_outbox(&self, record_id: &str) -> Result<usize> {
+        let mut conn = self.db_manager.get_connection()?;
+        self.db_manager
+            .with_busy_retry(|| forward::discard(&mut conn, record_id))
+    }
+
+    /// 列出被中心节点拒绝、不再投递的负载
+    pub fn dead_letters(&self) -> Result<Vec<OutboxEntry>> {
+        let mut conn = self.db_manager.get_connection()?;
+        forward::dead_letters(&mut conn)
+    }
+
+    /// 把死信重新放回转发队列，返回更新的条数
+    pub fn requeue_outbox(&self, record_id: &str) -> Result<usize> {
+        let mut conn = self.db_manager.get_connection()?;
+        self.db_manager
+            .with_busy_retry(|| forward::requeue(&mut conn, record_id))
+    }
+
+    /// 向所有通知目标发送一条测试通知 (不受订阅事件和限流影响)，返回各目标的投递记录
+    pub fn send_test_notification(&self) -> Result<Vec<NotificationDelivery>> {
+        let Some(notifier) = &self.notifier else {
+            return Err(BlackBoxError::validation(
+                "没有配置通知目标，请在配置文件的 [notify] 中添加 webhooks 或 commands",
+            ));
+        };
+
+        let notification = notify::Notification {
+            event: notify::NotifyEvent::Crash,
+            server_id: "blackbox-test".to_string(),
+            title: "BlackBox 测试通知".to_string(),
+            message: "收到这条消息说明通知配置正确".to_string(),
+            timestamp: chrono::Utc::now().timestamp_millis(),
+            crash_log_id: None,
+            crash_type: None,
+            severity: Some(Severity::Low),
+            status: None,
+            previous_status: None,
+        };
+        notifier.send_test(&notification)
+    }
+
+    /// 等待后台线程投递完已入队的通知 (包括失败后等待重试的)，没有配置通知目标时直接返回
+    ///
+    /// `BlackBox` 被释放时也会等待，通常只在需要立即查看投递结果时调用。
+    pub fn flush_notifications(&self) {
+        if let Some(notifier) = &self.notifier {
+            notifier.flush();
+        }
+    }
+
+    /// 按投递时间倒序查看最近的通知投递记录
+    pub fn notification_log(&self, limit: i64) -> Result<Vec<NotificationDelivery>> {
+        let mut conn = self.db_manager.get_connection()?;
+        notify::recent_deliveries(&mut conn, limit)
     }
 
     /// 从文件智能插入数据
-    /// 
+    ///
     /// # 参数
     /// * `data_type` - 数据类型
     /// * `file_path` - JSON 文件路径
     /// * `continue_on_error` - 遇到错误时是否继续处理
     pub fn smart_insert_from_file(
         &self,
-        data_type: SmartDataType, 
-        file_path: &str, 
-        continue_on_error: bool
+        data_type: SmartDataType,
+        file_path: &str,
+        continue_on_error: bool,
     ) -> Result<InsertResult> {
-        let json_content = fs::read_to_string(file_path)
-            .map_err(|e| anyhow::anyhow!("无法读取文件 {}: {}", file_path, e))?;
-        
+        let json_content =
+            fs::read_to_string(file_path).map_err(|e| BlackBoxError::io(file_path, e))?;
+
         self.smart_insert(data_type, &json_content, continue_on_error)
     }
 
+    /// 从 CSV 内容智能插入数据
+    ///
+    /// 按表头映射到对应的智能插入结构，支持服务器、系统指标、进程和崩溃日志。
+    /// 无法解析的行会逐行记录到 `InsertResult::errors`。
+    ///
+    /// # 参数
+    /// * `data_type` - 数据类型 (不支持组合数据)
+    /// * `csv_data` - 带表头的 CSV 文本
+    /// * `continue_on_error` - 遇到错误时是否继续处理
+    pub fn smart_insert_csv(
+        &self,
+        data_type: SmartDataType,
+        csv_data: &str,
+        continue_on_error: bool,
+    ) -> Result<InsertResult> {
+        let (result, _) =
+            self.insert_csv(data_type, csv_data, continue_on_error, self.forwards())?;
+        Ok(result)
+    }
+
+    /// 从 CSV 内容智能插入数据，并把原始负载加入 outbox 等待转发到中心节点
+    ///
+    /// # 返回
+    /// 插入结果和分配给负载的记录 id
+    pub fn smart_insert_csv_and_forward(
+        &self,
+        data_type: SmartDataType,
+        csv_data: &str,
+        continue_on_error: bool,
+    ) -> Result<(InsertResult, String)> {
+        let (result, record_id) = self.insert_csv(data_type, csv_data, continue_on_error, true)?;
+        Ok((result, record_id.unwrap_or_default()))
+    }
+
+    fn insert_csv(
+        &self,
+        data_type: SmartDataType,
+        csv_data: &str,
+        continue_on_error: bool,
+        forward: bool,
+    ) -> Result<(InsertResult, Option<String>)> {
+        let mut conn = self.db_manager.get_connection()?;
+        let options = self.insert_options(continue_on_error)?;
+
+        let (result, record_id, events) = self.db_manager.with_busy_retry(|| {
+            conn.immediate_transaction(|conn| {
+                let mut store = self.recorder(conn);
+                let result = insert_csv_rows(&mut store, &data_type, csv_data, &options)?;
+                let events = store.into_events();
+                let record_id = forward
+                    .then(|| {
+                        forward::enqueue(
+                            conn,
+                            &PayloadKind::Csv(data_type.clone()),
+                            csv_data,
+                            continue_on_error,
+                        )
+                    })
+                    .transpose()?;
+                Ok((result, record_id, events))
+            })
+        })?;
+        self.notify(&events);
+        Ok((result, record_id))
+    }
+
+    /// 从 CSV 文件智能插入数据
+    ///
+    /// # 参数
+    /// * `data_type` - 数据类型
+    /// * `file_path` - CSV 文件路径
+    /// * `continue_on_error` - 遇到错误时是否继续处理
+    pub fn smart_insert_csv_from_file(
+        &self,
+        data_type: SmartDataType,
+        file_path: &str,
+        continue_on_error: bool,
+    ) -> Result<InsertResult> {
+        let csv_content =
+            fs::read_to_string(file_path).map_err(|e| BlackBoxError::io(file_path, e))?;
+
+        self.smart_insert_csv(data_type, &csv_content, continue_on_error)
+    }
+
+    /// 写入 InfluxDB 行协议数据
+    ///
+    /// 按 measurement 分流：`system_metrics` 写入系统指标，`process_trends` 写入进程趋势。
+    /// 无法解析的行会逐行记录到 `InsertResult::errors`。
+    ///
+    /// # 参数
+    /// * `content` - 行协议文本
+    /// * `precision` - 时间戳精度，入库时统一换算为毫秒
+    /// * `continue_on_error` - 遇到错误时是否继续处理
+    pub fn ingest_line_protocol(
+        &self,
+        content: &str,
+        precision: influx::Precision,
+        continue_on_error: bool,
+    ) -> Result<InsertResult> {
+        let mut conn = self.db_manager.get_connection()?;
+        let options = self.insert_options(continue_on_error)?;
+
+        let (result, events) = self.db_manager.with_busy_retry(|| {
+            conn.immediate_transaction(|conn| {
+                let mut store = self.recorder(conn);
+                let result = insert_line_protocol(&mut store, content, precision, &options)?;
+                let events = store.into_events();
+                if self.forwards() {
+                    forward::enqueue(
+                        conn,
+                        &PayloadKind::LineProtocol(precision),
+                        content,
+                        continue_on_error,
+                    )?;
+                }
+                Ok((result, events))
+            })
+        })?;
+        self.notify(&events);
+        Ok(result)
+    }
+
     /// 导入 JSON 数据到数据库
-    /// 
+    ///
+    /// 根据 `formatVersion` 字段自动识别格式：没有该字段的是按服务器嵌套的 JSON，
+    /// 为 2 的是无损导出 (见 [`lossless`])，会按原 id 原样写回。
+    ///
+    /// 边缘节点把导入的文件加入转发队列，无损导出在中心节点按合并规则写入；
+    /// `clean` 只清空本地数据，不会转发到中心节点。
+    ///
     /// # 参数
     /// * `file_path` - JSON 文件路径
     /// * `clean` - 是否清空现有数据
     pub fn import_json_data(&self, file_path: &str, clean: bool) -> Result<()> {
+        let json_content =
+            fs::read_to_string(file_path).map_err(|e| BlackBoxError::io(file_path, e))?;
+        let probe: lossless::FormatProbe = serde_json::from_str(&json_content)?;
+
         let mut conn = self.db_manager.get_connection()?;
-        
-        if clean {
-            DataCleanService::clean_database(&mut conn)?;
+
+        if probe.format_version.is_some() {
+            let data: lossless::LosslessExport = serde_json::from_str(&json_content)?;
+            return conn.immediate_transaction(|conn| {
+                if clean {
+                    DataCleanService::clean_database(conn)?;
+                }
+
+                lossless::import(conn, &data)?;
+                self.enqueue_change(conn, &PayloadKind::Merge, &json_content)
+            });
         }
-        
-        let json_content = fs::read_to_string(file_path)?;
+
         let json_data: JsonData = serde_json::from_str(&json_content)?;
-        
-        JsonImportService::import_json_data(&mut conn, json_data)?;
+
+        conn.immediate_transaction(|conn| {
+            if clean {
+                DataCleanService::clean_database(conn)?;
+            }
+
+            JsonImportService::import_json_data(conn, json_data, &self.config.retention)?;
+            self.enqueue_change(conn, &PayloadKind::Import, &json_content)
+        })
+    }
+
+    /// 边缘节点模式下把本地变更加入转发队列，调用方负责事务
+    fn enqueue_change(
+        &self,
+        conn: &mut SqliteConnection,
+        kind: &PayloadKind,
+        payload: &str,
+    ) -> Result<()> {
+        if self.forwards() {
+            forward::enqueue(conn, kind, payload, false)?;
+        }
         Ok(())
     }
 
+    /// 边缘节点模式下把崩溃日志的处理加入转发队列，调用方负责事务
+    ///
+    /// `event` 为本次记入历史的记录，调整过建议时 `with_recommendations` 为 true。
+    fn enqueue_crash_change(
+        &self,
+        conn: &mut SqliteConnection,
+        crash_log_id: i32,
+        event: Option<&CrashEvent>,
+        with_recommendations: bool,
+    ) -> Result<()> {
+        if !self.forwards() {
+            return Ok(());
+        }
+        let change = forward::CrashChange::load(conn, crash_log_id, event, with_recommendations)?;
+        forward::enqueue(
+            conn,
+            &PayloadKind::Crash,
+            &serde_json::to_string(&change)?,
+            false,
+        )?;
+        Ok(())
+    }
+
+    /// 无损导出全部数据到 JSON 文件 (formatVersion 2)
+    ///
+    /// # 参数
+    /// * `output_path` - 输出文件路径
+    /// * `pretty` - 是否格式化输出
+    ///
+    /// # 返回
+    /// 各表导出的行数
+    pub fn export_lossless(
+        &self,
+        output_path: &str,
+        pretty: bool,
+    ) -> Result<Vec<(&'static str, usize)>> {
+        let mut conn = self.db_manager.get_connection()?;
+
+        let data = lossless::export(&mut conn)?;
+
+        let json_content = if pretty {
+            serde_json::to_string_pretty(&data)?
+        } else {
+            serde_json::to_string(&data)?
+        };
+
+        fs::write(output_path, json_content).map_err(|e| BlackBoxError::io(output_path, e))?;
+        Ok(data.table_counts())
+    }
+
+    /// 将另一个 blackbox 数据库合并到当前数据库
+    ///
+    /// 按智能插入的规则逐表写入，自增 id 会被重映射；整个源数据库在一个事务中合并。
+    ///
+    /// # 参数
+    /// * `source_path` - 源数据库文件路径
+    ///
+    /// # 返回
+    /// 各表的合并数量和冲突列表
+    pub fn merge_database(&self, source_path: &str) -> Result<merge::MergeReport> {
+        let same_file = match (
+            fs::canonicalize(source_path),
+            fs::canonicalize(self.db_manager.get_file_path()),
+        ) {
+            (Ok(source), Ok(target)) => source == target,
+            _ => false,
+        };
+        if same_file {
+            return Err(BlackBoxError::validation(format!(
+                "源数据库 {} 与目标数据库相同",
+                source_path
+            )));
+        }
+
+        let mut conn = self.db_manager.get_connection()?;
+
+        let version = get_schema_version(&mut conn)?;
+        if version != SCHEMA_VERSION {
+            return Err(BlackBoxError::schema(
+                version,
+                SCHEMA_VERSION,
+                format!(
+                    "目标数据库结构版本为 v{} (需要 v{})，请先执行 migrate",
+                    version, SCHEMA_VERSION
+                ),
+            ));
+        }
+
+        if !self.forwards() {
+            return merge::merge_database(&mut conn, source_path);
+        }
+
+        // 边缘节点把源数据库的无损导出随合并一起加入转发队列
+        let payload = serde_json::to_string(&Self::export_merge_source(source_path)?)?;
+        merge::with_source(&mut conn, source_path, |conn| {
+            let report = merge::merge_attached(conn, source_path)?;
+            forward::enqueue(conn, &PayloadKind::Merge, &payload, false)?;
+            Ok(report)
+        })
+    }
+
+    /// 读取合并源数据库的无损导出，结构版本与当前程序不一致时报错
+    fn export_merge_source(source_path: &str) -> Result<lossless::LosslessExport> {
+        if !std::path::Path::new(source_path).exists() {
+            return Err(BlackBoxError::not_found("数据库文件", source_path));
+        }
+
+        let source = DatabaseManager::new(Some(source_path.to_string()));
+        let mut conn = source.get_connection()?;
+        let version = get_schema_version(&mut conn)?;
+        if version != SCHEMA_VERSION {
+            return Err(BlackBoxError::schema(
+                version,
+                SCHEMA_VERSION,
+                format!(
+                    "源数据库 {} 的结构版本为 v{} (需要 v{})，请先对其执行 migrate",
+                    source_path, version, SCHEMA_VERSION
+                ),
+            ));
+        }
+        lossless::export(&mut conn)
+    }
+
     /// 导出数据到 JSON 文件
-    /// 
+    ///
     /// # 参数
     /// * `output_path` - 输出文件路径
     /// * `pretty` - 是否格式化输出
-    pub fn export_to_json(&self, output_path: &str, pretty: bool) -> Result<()> {
+    /// * `filter` - 筛选条件，`ExportFilter::default()` 导出全部数据
+    ///
+    /// # 返回
+    /// 导出的服务器数量
+    pub fn export_to_json(
+        &self,
+        output_path: &str,
+        pretty: bool,
+        filter: &ExportFilter,
+    ) -> Result<usize> {
         let mut conn = self.db_manager.get_connection()?;
-        
-        let export_data = export_all_data(&mut conn)?;
-        
+
+        let export_data = export_filtered_data(&mut conn, filter)?;
+
         let json_content = if pretty {
             serde_json::to_string_pretty(&export_data)?
         } else {
             serde_json::to_string(&export_data)?
         };
-        
-        fs::write(output_path, json_content)?;
-        Ok(())
+
+        fs::write(output_path, json_content).map_err(|e| BlackBoxError::io(output_path, e))?;
+        Ok(export_data.servers.len())
+    }
+
+    /// 为服务器设置标签，已有的同名标签会被覆盖
+    pub fn set_server_labels(&self, server_id: &str, labels: &[(String, String)]) -> Result<()> {
+        let mut conn = self.db_manager.get_connection()?;
+
+        self.db_manager.with_busy_retry(|| {
+            conn.immediate_transaction(|conn| {
+                let change = forward::LabelChange {
+                    server_id: server_id.to_string(),
+                    set: labels.to_vec(),
+                    remove: Vec::new(),
+                };
+                forward::apply_labels(conn, &change)?;
+                if self.forwards() {
+                    forward::enqueue(
+                        conn,
+                        &PayloadKind::Labels,
+                        &serde_json::to_string(&change)?,
+                        false,
+                    )?;
+                }
+                Ok(())
+            })
+        })
+    }
+
+    /// 删除服务器标签，返回实际删除的数量
+    pub fn remove_server_labels(&self, server_id: &str, keys: &[String]) -> Result<usize> {
+        let mut conn = self.db_manager.get_connection()?;
+
+        self.db_manager.with_busy_retry(|| {
+            conn.immediate_transaction(|conn| {
+                let mut removed = 0;
+                for key in keys {
+                    if remove_server_label(conn, server_id, key)? {
+                        removed += 1;
+                    }
+                }
+                if removed > 0 && self.forwards() {
+                    let change = forward::LabelChange {
+                        server_id: server_id.to_string(),
+                        set: Vec::new(),
+                        remove: keys.to_vec(),
+                    };
+                    forward::enqueue(
+                        conn,
+                        &PayloadKind::Labels,
+                        &serde_json::to_string(&change)?,
+                        false,
+                    )?;
+                }
+                Ok(removed)
+            })
+        })
+    }
+
+    /// 查询标签；`server_id` 为 None 时返回全部服务器的标签
+    pub fn get_server_labels(&self, server_id: Option<&str>) -> Result<Vec<ServerLabel>> {
+        let mut conn = self.db_manager.get_connection()?;
+
+        get_server_labels(&mut conn, server_id)
+    }
+
+    /// 按时间戳倒序列出崩溃日志
+    pub fn list_crash_logs(&self, filter: &crash::CrashLogFilter) -> Result<Vec<CrashLog>> {
+        let mut conn = self.db_manager.get_connection()?;
+        crash::list(&mut conn, filter)
+    }
+
+    /// 查看崩溃日志及其 AI 建议和处理历史
+    pub fn crash_log_history(&self, crash_log_id: i32) -> Result<crash::CrashLogHistory> {
+        let mut conn = self.db_manager.get_connection()?;
+        crash::history(&mut conn, crash_log_id)
+    }
+
+    /// 将崩溃日志标记为已解决
+    ///
+    /// # 参数
+    /// * `crash_log_id` - 崩溃日志 id
+    /// * `actor` - 操作人，记入处理历史
+    /// * `note` - 解决说明
+    pub fn resolve_crash_log(
+        &self,
+        crash_log_id: i32,
+        actor: &str,
+        note: Option<&str>,
+    ) -> Result<CrashLog> {
+        self.set_crash_log_resolved(crash_log_id, true, actor, note)
+    }
+
+    /// 重新打开已解决的崩溃日志
+    pub fn reopen_crash_log(
+        &self,
+        crash_log_id: i32,
+        actor: &str,
+        note: Option<&str>,
+    ) -> Result<CrashLog> {
+        self.set_crash_log_resolved(crash_log_id, false, actor, note)
+    }
+
+    fn set_crash_log_resolved(
+        &self,
+        crash_log_id: i32,
+        resolved: bool,
+        actor: &str,
+        note: Option<&str>,
+    ) -> Result<CrashLog> {
+        let mut conn = self.db_manager.get_connection()?;
+
+        self.db_manager.with_busy_retry(|| {
+            conn.immediate_transaction(|conn| {
+                let crash_log = crash::set_resolved(conn, crash_log_id, resolved, actor, note)?;
+                if self.forwards() {
+                    let event = crash::history(conn, crash_log_id)?.events.pop();
+                    self.enqueue_crash_change(conn, crash_log_id, event.as_ref(), false)?;
+                }
+                Ok(crash_log)
+            })
+        })
+    }
+
+    /// 为崩溃日志添加备注
+    pub fn annotate_crash_log(
+        &self,
+        crash_log_id: i32,
+        actor: &str,
+        note: &str,
+    ) -> Result<CrashEvent> {
+        let mut conn = self.db_manager.get_connection()?;
+
+        self.db_manager.with_busy_retry(|| {
+            conn.immediate_transaction(|conn| {
+                let event = crash::annotate(conn, crash_log_id, actor, note)?;
+                self.enqueue_crash_change(conn, crash_log_id, Some(&event), false)?;
+                Ok(event)
+            })
+        })
+    }
+
+    /// 崩溃日志的 AI 建议，按优先级排列
+    pub fn crash_recommendations(&self, crash_log_id: i32) -> Result<Vec<AiRecommendation>> {
+        let mut conn = self.db_manager.get_connection()?;
+        crash::recommendations(&mut conn, crash_log_id)
+    }
+
+    /// 为崩溃日志添加一条建议，指定优先级时插入到该位置
+    pub fn add_crash_recommendation(
+        &self,
+        crash_log_id: i32,
+        recommendation: &SmartRecommendation,
+    ) -> Result<AiRecommendation> {
+        let mut conn = self.db_manager.get_connection()?;
+
+        self.db_manager.with_busy_retry(|| {
+            conn.immediate_transaction(|conn| {
+                let added = crash::add_recommendation(conn, crash_log_id, recommendation)?;
+                self.enqueue_crash_change(conn, crash_log_id, None, true)?;
+                Ok(added)
+            })
+        })
+    }
+
+    /// 用新的列表替换崩溃日志的全部建议
+    pub fn replace_crash_recommendations(
+        &self,
+        crash_log_id: i32,
+        recommendations: &[SmartRecommendation],
+    ) -> Result<Vec<AiRecommendation>> {
+        let mut conn = self.db_manager.get_connection()?;
+
+        self.db_manager.with_busy_retry(|| {
+            conn.immediate_transaction(|conn| {
+                let replaced = crash::replace_recommendations(conn, crash_log_id, recommendations)?;
+                self.enqueue_crash_change(conn, crash_log_id, None, true)?;
+                Ok(replaced)
+            })
+        })
+    }
+
+    /// 按给出的建议 id 顺序重新排列崩溃日志的建议
+    pub fn reorder_crash_recommendations(
+        &self,
+        crash_log_id: i32,
+        ids: &[i32],
+    ) -> Result<Vec<AiRecommendation>> {
+        let mut conn = self.db_manager.get_connection()?;
+
+        self.db_manager.with_busy_retry(|| {
+            conn.immediate_transaction(|conn| {
+                let reordered = crash::reorder_recommendations(conn, crash_log_id, ids)?;
+                self.enqueue_crash_change(conn, crash_log_id, None, true)?;
+                Ok(reordered)
+            })
+        })
+    }
+
+    /// 标记建议为已执行并记录结果，`applied` 为 false 时取消标记
+    pub fn mark_recommendation_applied(
+        &self,
+        recommendation_id: i32,
+        applied: bool,
+        outcome: Option<&str>,
+    ) -> Result<AiRecommendation> {
+        let mut conn = self.db_manager.get_connection()?;
+
+        self.db_manager.with_busy_retry(|| {
+            conn.immediate_transaction(|conn| {
+                let marked = crash::set_applied(conn, recommendation_id, applied, outcome)?;
+                self.enqueue_crash_change(conn, marked.crash_log_id, None, true)?;
+                Ok(marked)
+            })
+        })
+    }
+
+    /// 删除一条建议，其余建议重新编号
+    pub fn remove_crash_recommendation(&self, recommendation_id: i32) -> Result<AiRecommendation> {
+        let mut conn = self.db_manager.get_connection()?;
+
+        self.db_manager.with_busy_retry(|| {
+            conn.immediate_transaction(|conn| {
+                let removed = crash::remove_recommendation(conn, recommendation_id)?;
+                self.enqueue_crash_change(conn, removed.crash_log_id, None, true)?;
+                Ok(removed)
+            })
+        })
+    }
+
+    /// 按配置文件的 `[analyzer]` 创建分析器，未配置外部命令时使用内置规则
+    pub fn analyzer(&self) -> Box<dyn analyze::Analyzer> {
+        analyze::from_config(&self.config.analyzer)
+    }
+
+    /// 摘要或分析仍是占位文本、等待分析的崩溃日志
+    pub fn pending_crash_logs(&self) -> Result<Vec<CrashLog>> {
+        let mut conn = self.db_manager.get_connection()?;
+        analyze::pending(&mut conn)
+    }
+
+    /// 分析一条崩溃日志并写入摘要、分析和建议
+    ///
+    /// 分析在写入事务之外执行，外部命令耗时较长时不会阻塞其他写入。
+    pub fn analyze_crash_log(
+        &self,
+        analyzer: &dyn analyze::Analyzer,
+        crash_log_id: i32,
+    ) -> Result<analyze::Analysis> {
+        let mut conn = self.db_manager.get_connection()?;
+
+        let crash_log = crash::get(&mut conn, crash_log_id)?;
+        let context = analyze::AnalysisContext::load(&mut *conn, &crash_log.server_id)?;
+        let analysis = analyzer.analyze(&crash_log, &context)?;
+
+        self.db_manager.with_busy_retry(|| {
+            conn.immediate_transaction(|conn| {
+                let crash_log = crash::get(conn, crash_log_id)?;
+                analyze::apply(conn, &crash_log, &analysis)?;
+                self.enqueue_crash_change(conn, crash_log_id, None, true)
+            })
+        })?;
+        Ok(analysis)
+    }
+
+    /// 将一张表导出为 CSV 文件
+    ///
+    /// # 参数
+    /// * `table` - 要导出的数据表
+    /// * `output_path` - 输出文件路径
+    ///
+    /// # 返回
+    /// 写入的行数
+    pub fn export_table_to_csv(&self, table: CsvTable, output_path: &str) -> Result<usize> {
+        let mut conn = self.db_manager.get_connection()?;
+
+        let file = fs::File::create(output_path).map_err(|e| BlackBoxError::io(output_path, e))?;
+        csv_io::export_table(&mut conn, table, std::io::BufWriter::new(file))
+    }
+
+    /// 增量导出自游标以来新增或修改的行，输出 NDJSON 变更记录
+    ///
+    /// # 参数
+    /// * `since` - 上次导出保存的游标，空游标表示全量导出
+    /// * `writer` - 变更记录的输出目标
+    ///
+    /// # 返回
+    /// 各表的变更数量和新的游标
+    pub fn export_changes<W: std::io::Write>(
+        &self,
+        since: &changes::ExportCursor,
+        writer: W,
+    ) -> Result<changes::ChangeExportSummary> {
+        let mut conn = self.db_manager.get_connection()?;
+
+        changes::export_changes(&mut conn, since, writer)
+    }
+
+    /// 将系统指标和进程趋势导出为 InfluxDB 行协议 (毫秒时间戳)
+    ///
+    /// # 返回
+    /// (系统指标行数, 进程趋势行数)
+    pub fn export_to_influx(&self, output_path: &str) -> Result<(usize, usize)> {
+        let mut conn = self.db_manager.get_connection()?;
+
+        let file = fs::File::create(output_path).map_err(|e| BlackBoxError::io(output_path, e))?;
+        influx::export(&mut conn, std::io::BufWriter::new(file))
+    }
+
+    /// 生成 Prometheus 指标文本
+    ///
+    /// # 参数
+    /// * `openmetrics` - 是否使用 OpenMetrics 格式 (否则为 Prometheus 文本格式 0.0.4)
+    pub fn render_metrics(&self, openmetrics: bool) -> Result<String> {
+        let mut conn = self.db_manager.get_connection()?;
+
+        let snapshot = prometheus::MetricsSnapshot::collect(&mut conn)?;
+        Ok(prometheus::render(&snapshot, openmetrics))
     }
 
     /// 查询数据库统计信息
-    /// 
+    ///
     /// # 返回
     /// 返回数据库统计信息
     pub fn get_statistics(&self) -> Result<DatabaseStats> {
         let mut conn = self.db_manager.get_connection()?;
-        
+
+        // 三条聚合查询代替逐个服务器加载全部记录
         let servers = get_all_servers(&mut conn)?;
+        let metric_summary = get_metric_summary_by_server(&mut conn)?;
+        let process_counts = count_processes_by_server(&mut conn)?;
+        let crash_counts = count_crash_logs_by_server(&mut conn)?;
+
         let mut stats = DatabaseStats {
             server_count: servers.len(),
             servers: Vec::new(),
         };
-        
+
         for server in servers {
-            let metrics = get_metrics_by_server(&mut conn, &server.server_id, None)?;
-            let processes = get_processes_by_server(&mut conn, &server.server_id)?;
-            let crashes = get_crash_logs_by_server(&mut conn, &server.server_id)?;
-            
+            let (metrics_count, latest_metric_time) = metric_summary
+                .get(&server.server_id)
+                .copied()
+                .unwrap_or((0, None));
+
             let server_stat = ServerStats {
-                server: server,
-                metrics_count: metrics.len(),
-                processes_count: processes.len(),
-                crashes_count: crashes.len(),
-                latest_metric_time: metrics.first().map(|m| m.timestamp),
+                metrics_count: metrics_count as usize,
+                processes_count: process_counts.get(&server.server_id).copied().unwrap_or(0)
+                    as usize,
+                crashes_count: crash_counts.get(&server.server_id).copied().unwrap_or(0) as usize,
+                latest_metric_time,
+                server,
             };
-            
+
             stats.servers.push(server_stat);
         }
-        
+
         Ok(stats)
     }
 
     /// 查询服务器详细信息
-    /// 
+    ///
     /// # 参数
     /// * `server_filter` - 服务器过滤条件（ID 或名称）
     /// * `limit` - 限制返回的记录数
     pub fn query_servers(
         &self,
-        server_filter: Option<&str>, 
-        limit: Option<i64>
+        server_filter: Option<&str>,
+        limit: Option<i64>,
     ) -> Result<Vec<ServerDetail>> {
         let mut conn = self.db_manager.get_connection()?;
-        
+
         let servers = get_all_servers(&mut conn)?;
-        
+
         // 根据过滤条件选择服务器
         let target_servers: Vec<_> = if let Some(filter) = server_filter {
-            servers.into_iter()
+            servers
+                .into_iter()
                 .filter(|s| s.server_id == filter || s.server_name.contains(filter))
                 .collect()
         } else {
             servers
         };
-        
+
         let mut results = Vec::new();
-        
+
         for server in target_servers {
             let display_limit = limit.unwrap_or(5);
             let metrics = get_metrics_by_server(&mut conn, &server.server_id, Some(display_limit))?;
             let processes = get_processes_by_server(&mut conn, &server.server_id)?;
             let crashes = get_crash_logs_by_server(&mut conn, &server.server_id)?;
-            
-            let mut process_details = Vec::new();
-            for process in &processes {
-                let trends = get_process_trends(&mut conn, &server.server_id, process.pid)?;
-                let threads = get_threads_by_process(&mut conn, &server.server_id, process.pid)?;
-                
-                process_details.push(ProcessDetail {
-                    process: process.clone(),
-                    trends,
-                    threads,
-                });
-            }
-            
-            let mut crash_details = Vec::new();
-            for crash in &crashes {
-                let recommendations = get_recommendations_by_crash_log(&mut conn, crash.id)?;
-                crash_details.push(CrashDetail {
-                    crash_log: crash.clone(),
-                    recommendations,
-                });
-            }
-            
+
+            // 趋势、线程和 AI 建议按服务器批量加载，再在内存中分组
+            let pids: Vec<i32> = processes.iter().map(|p| p.pid).collect();
+            let trends_by_pid = get_process_trends_by_pids(&mut conn, &server.server_id, &pids)?;
+            let threads_by_pid = get_threads_by_pids(&mut conn, &server.server_id, &pids)?;
+
+            let process_details = processes
+                .into_iter()
+                .map(|process| ProcessDetail {
+                    trends: trends_by_pid.get(&process.pid).cloned().unwrap_or_default(),
+                    threads: threads_by_pid
+                        .get(&process.pid)
+                        .cloned()
+                        .unwrap_or_default(),
+                    process,
+                })
+                .collect::<Vec<_>>();
+
+            let crash_ids: Vec<i32> = crashes.iter().map(|c| c.id).collect();
+            let mut recommendations_by_crash =
+                get_recommendations_by_crash_logs(&mut conn, &crash_ids)?;
+
+            let crash_details = crashes
+                .into_iter()
+                .map(|crash| CrashDetail {
+                    recommendations: recommendations_by_crash
+                        .remove(&crash.id)
+                        .unwrap_or_default(),
+                    crash_log: crash,
+                })
+                .collect::<Vec<_>>();
+
             results.push(ServerDetail {
                 server,
                 metrics,
@@ -285,34 +1529,56 @@ impl BlackBox {
                 crashes: crash_details,
             });
         }
-        
+
         Ok(results)
     }
 
     /// 清理旧数据
-    /// 
+    ///
+    /// 边缘节点把清理的截止时间和本地的服务器列表加入转发队列，
+    /// 中心节点只清理这些服务器的指标。
+    ///
     /// # 参数
     /// * `days` - 保留最近 N 天的数据
     pub fn clean_old_data(&self, days: i64) -> Result<usize> {
         let mut conn = self.db_manager.get_connection()?;
-        
+
         let cutoff_time = chrono::Utc::now().timestamp_millis() - (days * 24 * 60 * 60 * 1000);
-        let deleted = delete_old_metrics(&mut conn, cutoff_time)?;
-        
+        let deleted = self.db_manager.with_busy_retry(|| {
+            conn.immediate_transaction(|conn| {
+                let deleted = delete_old_metrics(conn, cutoff_time)?;
+                if deleted > 0 && self.forwards() {
+                    let cleanup = forward::MetricsCleanup {
+                        before: cutoff_time,
+                        server_ids: get_all_servers(conn)?
+                            .into_iter()
+                            .map(|server| server.server_id)
+                            .collect(),
+                    };
+                    forward::enqueue(
+                        conn,
+                        &PayloadKind::Cleanup,
+                        &serde_json::to_string(&cleanup)?,
+                        false,
+                    )?;
+                }
+                Ok(deleted)
+            })
+        })?;
+
         Ok(deleted)
     }
-
 }
 
 /// 数据库统计信息
-#[derive(Debug, Clone)]
+#[derive(Serialize, Debug, Clone)]
 pub struct DatabaseStats {
     pub server_count: usize,
     pub servers: Vec<ServerStats>,
 }
 
 /// 服务器统计信息
-#[derive(Debug, Clone)]
+#[derive(Serialize, Debug, Clone)]
 pub struct ServerStats {
     pub server: Server,
     pub metrics_count: usize,
@@ -322,7 +1588,7 @@ pub struct ServerStats {
 }
 
 /// 服务器详细信息
-#[derive(Debug, Clone)]
+#[derive(Serialize, Debug, Clone)]
 pub struct ServerDetail {
     pub server: Server,
     pub metrics: Vec<SystemMetric>,
@@ -331,7 +1597,7 @@ pub struct ServerDetail {
 }
 
 /// 进程详细信息
-#[derive(Debug, Clone)]
+#[derive(Serialize, Debug, Clone)]
 pub struct ProcessDetail {
     pub process: Process,
     pub trends: Vec<ProcessTrend>,
@@ -339,8 +1605,172 @@ pub struct ProcessDetail {
 }
 
 /// 崩溃详细信息
-#[derive(Debug, Clone)]
+#[derive(Serialize, Debug, Clone)]
 pub struct CrashDetail {
     pub crash_log: CrashLog,
     pub recommendations: Vec<AiRecommendation>,
-}
\ No newline at end of file
+}
+
+/// 按数据类型解析 JSON 负载并写入，调用方负责事务
+///
+/// 负载带有幂等键 (`batchId` / `idempotencyKey`) 时，同一个键只写入一次，
+/// 重复提交返回首次的插入结果，不产生事件。
+fn insert_payload(
+    conn: &mut SqliteConnection,
+    data_type: &SmartDataType,
+    json_data: &str,
+    options: &InsertOptions,
+    record_events: bool,
+) -> Result<(InsertResult, Vec<InsertEvent>)> {
+    let kind = data_type.as_str();
+    let mut events = Vec::new();
+
+    let (result, _) = match data_type {
+        SmartDataType::Servers => {
+            let (batch_id, servers) = parse_batch::<NewServer>(json_data)?;
+            IdempotencyService::run(conn, batch_id.as_deref(), kind, |conn| {
+                recorded(conn, record_events, &mut events, |store| {
+                    SmartInsertService::insert_servers(store, servers, options)
+                })
+            })?
+        }
+        SmartDataType::SystemMetrics => {
+            let (batch_id, metrics) = parse_batch::<SmartSystemMetric>(json_data)?;
+            IdempotencyService::run(conn, batch_id.as_deref(), kind, |conn| {
+                recorded(conn, record_events, &mut events, |store| {
+                    SmartInsertService::insert_system_metrics(store, metrics, options)
+                })
+            })?
+        }
+        SmartDataType::Processes => {
+            let (batch_id, processes) = parse_batch::<SmartProcessInsert>(json_data)?;
+            IdempotencyService::run(conn, batch_id.as_deref(), kind, |conn| {
+                recorded(conn, record_events, &mut events, |store| {
+                    SmartInsertService::insert_processes(store, processes, options)
+                })
+            })?
+        }
+        SmartDataType::CrashLogs => {
+            let (batch_id, crash_logs) = parse_batch::<SmartCrashLog>(json_data)?;
+            IdempotencyService::run(conn, batch_id.as_deref(), kind, |conn| {
+                recorded(conn, record_events, &mut events, |store| {
+                    SmartInsertService::insert_crash_logs(store, crash_logs, options)
+                })
+            })?
+        }
+        SmartDataType::Combined => {
+            let mut combined_data: CombinedInsertData = serde_json::from_str(json_data)?;
+            let batch_id = combined_data.batch_id.take();
+            IdempotencyService::run(conn, batch_id.as_deref(), kind, |conn| {
+                recorded(conn, record_events, &mut events, |store| {
+                    SmartInsertService::insert_combined_data(store, combined_data, options)
+                })
+            })?
+        }
+    };
+
+    Ok((result, events))
+}
+
+/// 按数据类型解析带表头的 CSV 并写入，调用方负责事务
+fn insert_csv_rows<S: Store>(
+    store: &mut S,
+    data_type: &SmartDataType,
+    csv_data: &str,
+    options: &InsertOptions,
+) -> Result<InsertResult> {
+    match data_type {
+        SmartDataType::Servers => csv_io::insert_parsed(
+            store,
+            csv_io::parse_rows::<NewServer>(csv_data)?,
+            options,
+            SmartInsertService::insert_servers,
+        ),
+        SmartDataType::SystemMetrics => csv_io::insert_parsed(
+            store,
+            csv_io::parse_rows::<SmartSystemMetric>(csv_data)?,
+            options,
+            SmartInsertService::insert_system_metrics,
+        ),
+        SmartDataType::Processes => csv_io::insert_parsed(
+            store,
+            csv_io::parse_rows::<csv_io::CsvProcessRow>(csv_data)?,
+            options,
+            SmartInsertService::insert_processes,
+        ),
+        SmartDataType::CrashLogs => csv_io::insert_parsed(
+            store,
+            csv_io::parse_rows::<SmartCrashLog>(csv_data)?,
+            options,
+            SmartInsertService::insert_crash_logs,
+        ),
+        SmartDataType::Combined => Err(BlackBoxError::validation(
+            "组合数据包含嵌套结构，不支持 CSV 格式，请使用 JSON",
+        )),
+    }
+}
+
+/// 解析行协议并按 measurement 分流写入，调用方负责事务
+fn insert_line_protocol<S: Store>(
+    store: &mut S,
+    content: &str,
+    precision: influx::Precision,
+    options: &InsertOptions,
+) -> Result<InsertResult> {
+    let parsed = influx::parse(content, precision);
+    if !parsed.errors.is_empty() && !options.continue_on_error {
+        return Err(BlackBoxError::parse(
+            "行协议",
+            format!(
+                "有 {} 行无法解析:\n{}",
+                parsed.errors.len(),
+                parsed.errors.join("\n")
+            ),
+        ));
+    }
+
+    let mut result = InsertResult::new();
+    if !parsed.system_metrics.is_empty() {
+        result.merge(SmartInsertService::insert_system_metrics(
+            store,
+            parsed.system_metrics,
+            options,
+        )?);
+    }
+    if !parsed.process_trends.is_empty() {
+        result.merge(SmartInsertService::insert_process_trend_points(
+            store,
+            parsed.process_trends,
+            options,
+        )?);
+    }
+    for message in parsed.errors {
+        result.add_error_message(message);
+    }
+    Ok(result)
+}
+
+/// 在事件记录器上执行写入，并把记录到的事件追加到 `events`
+fn recorded(
+    conn: &mut SqliteConnection,
+    record_events: bool,
+    events: &mut Vec<InsertEvent>,
+    insert: impl FnOnce(&mut EventRecorder<'_, SqliteConnection>) -> Result<InsertResult>,
+) -> Result<InsertResult> {
+    let mut store = EventRecorder::new(conn, record_events);
+    let result = insert(&mut store)?;
+    events.extend(store.into_events());
+    Ok(result)
+}
+
+/// 解析数组负载：直接的 JSON 数组，或带幂等键的 [`InsertBatch`] 对象
+fn parse_batch<T: serde::de::DeserializeOwned>(
+    json_data: &str,
+) -> Result<(Option<String>, Vec<T>)> {
+    if json_data.trim_start().starts_with('{') {
+        let batch: InsertBatch<T> = serde_json::from_str(json_data)?;
+        Ok((batch.batch_id, batch.data))
+    } else {
+        Ok((None, serde_json::from_str(json_data)?))
+    }
+}
@@ -0,0 +1,167 @@
+//! 无损 JSON 导出与导入 (formatVersion 2)
+//!
+//! 与按服务器嵌套的 JSON 格式 (formatVersion 1，无版本字段) 不同，
+//! 这里按表原样保存每一行的全部列，包括 id、外键、created_at 和 updated_at。
+//! 导入时按原 id 写回，因此只能导入到没有数据的数据库。
+
+use crate::error::{BlackBoxError, Result};
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use serde::{Deserialize, Serialize};
+
+use crate::database::get_schema_version;
+use crate::models::*;
+use crate::services::SCHEMA_VERSION;
+
+/// 无损导出格式的版本号
+pub const FORMAT_VERSION: u32 = 2;
+
+/// 批量插入时每条语句的行数，避免超出 SQLite 的参数数量上限
+const INSERT_CHUNK_SIZE: usize = 500;
+
+/// 无损导出文档，各表按外键依赖顺序排列
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LosslessExport {
+    pub format_version: u32,
+    /// 导出时数据库的结构版本
+    pub schema_version: i32,
+    pub servers: Vec<Server>,
+    #[serde(default)]
+    pub server_labels: Vec<ServerLabel>,
+    pub system_metrics: Vec<SystemMetric>,
+    pub processes: Vec<Process>,
+    pub process_trends: Vec<ProcessTrend>,
+    pub threads: Vec<Thread>,
+    pub crash_logs: Vec<CrashLog>,
+    pub ai_recommendations: Vec<AiRecommendation>,
+    #[serde(default)]
+    pub crash_events: Vec<CrashEvent>,
+}
+
+impl LosslessExport {
+    /// 各表的行数，按导出顺序排列
+    pub fn table_counts(&self) -> Vec<(&'static str, usize)> {
+        vec![
+            ("servers", self.servers.len()),
+            ("server_labels", self.server_labels.len()),
+            ("system_metrics", self.system_metrics.len()),
+            ("processes", self.processes.len()),
+            ("process_trends", self.process_trends.len()),
+            ("threads", self.threads.len()),
+            ("crash_logs", self.crash_logs.len()),
+            ("ai_recommendations", self.ai_recommendations.len()),
+            ("crash_events", self.crash_events.len()),
+        ]
+    }
+}
+
+/// 只读取版本字段，用于区分导入文件的格式
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FormatProbe {
+    pub format_version: Option<u32>,
+}
+
+/// 在一个读事务中按主键顺序读取所有表
+pub fn export(conn: &mut SqliteConnection) -> Result<LosslessExport> {
+    use crate::schema::*;
+
+    conn.transaction::<_, BlackBoxError, _>(|conn| {
+        Ok(LosslessExport {
+            format_version: FORMAT_VERSION,
+            schema_version: get_schema_version(conn)?,
+            servers: servers::table.order(servers::id).load(conn)?,
+            server_labels: server_labels::table.order(server_labels::id).load(conn)?,
+            system_metrics: system_metrics::table.order(system_metrics::id).load(conn)?,
+            processes: processes::table.order(processes::id).load(conn)?,
+            process_trends: process_trends::table.order(process_trends::id).load(conn)?,
+            threads: threads::table.order(threads::id).load(conn)?,
+            crash_logs: crash_logs::table.order(crash_logs::id).load(conn)?,
+            ai_recommendations: ai_recommendations::table
+                .order(ai_recommendations::id)
+                .load(conn)?,
+            crash_events: crash_events::table.order(crash_events::id).load(conn)?,
+        })
+    })
+}
+
+/// 按原 id 写回全部行，调用方负责事务
+///
+/// 目标数据库必须没有任何数据，否则原 id 会与已有记录冲突。
+pub fn import(conn: &mut SqliteConnection, data: &LosslessExport) -> Result<()> {
+    use crate::schema::*;
+
+    if data.format_version != FORMAT_VERSION {
+        return Err(BlackBoxError::validation(format!(
+            "不支持的导出格式版本 {} (当前支持 {})",
+            data.format_version, FORMAT_VERSION
+        )));
+    }
+    if data.schema_version > SCHEMA_VERSION {
+        return Err(BlackBoxError::schema(
+            data.schema_version,
+            SCHEMA_VERSION,
+            format!(
+                "导出文件来自更新的数据库结构 v{} (当前程序支持 v{})",
+                data.schema_version, SCHEMA_VERSION
+            ),
+        ));
+    }
+
+    let existing: i64 = servers::table.count().get_result(conn)?;
+    if existing > 0 {
+        return Err(BlackBoxError::conflict(format!(
+            "无损导入要求数据库中没有数据 (当前有 {} 台服务器)，请使用 --clean 或导入到新数据库",
+            existing
+        )));
+    }
+
+    for chunk in data.servers.chunks(INSERT_CHUNK_SIZE) {
+        diesel::insert_into(servers::table)
+            .values(chunk)
+            .execute(conn)?;
+    }
+    for chunk in data.server_labels.chunks(INSERT_CHUNK_SIZE) {
+        diesel::insert_into(server_labels::table)
+            .values(chunk)
+            .execute(conn)?;
+    }
+    for chunk in data.system_metrics.chunks(INSERT_CHUNK_SIZE) {
+        diesel::insert_into(system_metrics::table)
+            .values(chunk)
+            .execute(conn)?;
+    }
+    for chunk in data.processes.chunks(INSERT_CHUNK_SIZE) {
+        diesel::insert_into(processes::table)
+            .values(chunk)
+            .execute(conn)?;
+    }
+    for chunk in data.process_trends.chunks(INSERT_CHUNK_SIZE) {
+        diesel::insert_into(process_trends::table)
+            .values(chunk)
+            .execute(conn)?;
+    }
+    for chunk in data.threads.chunks(INSERT_CHUNK_SIZE) {
+        diesel::insert_into(threads::table)
+            .values(chunk)
+            .execute(conn)?;
+    }
+    for chunk in data.crash_logs.chunks(INSERT_CHUNK_SIZE) {
+        diesel::insert_into(crash_logs::table)
+            .values(chunk)
+            .execute(conn)?;
+    }
+    for chunk in data.ai_recommendations.chunks(INSERT_CHUNK_SIZE) {
+        diesel::insert_into(ai_recommendations::table)
+            .values(chunk)
+            .execute(conn)?;
+    }
+    for chunk in data.crash_events.chunks(INSERT_CHUNK_SIZE) {
+        diesel::insert_into(crash_events::table)
+            .values(chunk)
+            .execute(conn)?;
+    }
+
+    Ok(())
+}
@@ -1,6 +1,23 @@
 use anyhow::Result;
+use blackbox::changes::ExportCursor;
+use blackbox::crash::CrashLogFilter;
+use blackbox::csv_io::CsvTable;
+use blackbox::forward::{ForwardOptions, ForwardReport};
+use blackbox::influx::Precision;
+use blackbox::notify::{STATUS_DELIVERED, STATUS_PENDING, STATUS_RATE_LIMITED};
+use blackbox::output::{self, CsvRows, OutputFormat as LibOutputFormat};
+use blackbox::{
+    AiRecommendation, BlackBox, BlackBoxConfig, BlackBoxError, DEFAULT_DB_PATH, ExportEntity,
+    ExportFilter, NotificationDelivery, SCHEMA_VERSION, SmartDataType as LibSmartDataType,
+    SmartRecommendation,
+};
 use clap::{Parser, Subcommand};
-use blackbox::{BlackBox, SmartDataType as LibSmartDataType};
+use serde::Serialize;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(name = "blackbox")]
@@ -10,7 +27,11 @@ struct Cli {
     /// 数据库文件路径
     #[arg(long, short, global = true, help = "指定数据库文件路径")]
     db: Option<String>,
-    
+
+    /// TOML 配置文件路径，未指定时读取环境变量 BLACKBOX_CONFIG
+    #[arg(long, global = true, value_name = "FILE")]
+    config: Option<String>,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -26,14 +47,41 @@ enum Commands {
         #[arg(long)]
         clean: bool,
     },
-    /// 从数据库导出数据到 JSON 文件
+    /// 从数据库导出数据到 JSON、CSV、行协议或 NDJSON 变更记录
     Export {
-        /// 输出文件路径
-        #[arg(short, long, default_value = "export.json")]
-        file: String,
+        /// 输出路径 (默认 export.json / export.lp / changes.ndjson，NDJSON 可用 - 表示标准输出；CSV 单表默认 <表名>.csv，多表时为目录，默认 export/)
+        #[arg(short, long)]
+        file: Option<String>,
         /// 是否格式化输出
         #[arg(long, default_value = "true")]
         pretty: bool,
+        /// 导出格式
+        #[arg(long, value_enum, default_value = "json")]
+        format: ExportFormat,
+        /// 要导出的表 (仅 CSV，可重复指定，默认全部)
+        #[arg(long, value_enum)]
+        table: Vec<ExportTable>,
+        /// 只导出指定服务器 (仅 JSON，可重复指定)
+        #[arg(short, long)]
+        server: Vec<String>,
+        /// 只导出同时具有这些标签的服务器，格式为 key=value (仅 JSON，可重复指定)
+        #[arg(long, value_parser = parse_label)]
+        label: Vec<(String, String)>,
+        /// 时间范围起点，毫秒时间戳或 UTC 时间 (如 2026-10-18T08:00:00) (仅 JSON)
+        #[arg(long, value_parser = parse_time)]
+        from: Option<i64>,
+        /// 时间范围终点 (包含)，格式同 --from (仅 JSON)
+        #[arg(long, value_parser = parse_time)]
+        to: Option<i64>,
+        /// 要导出的数据类别，逗号分隔 (仅 JSON，默认全部)
+        #[arg(long, value_enum, value_delimiter = ',')]
+        include: Vec<ExportInclude>,
+        /// 无损导出 (formatVersion 2)：按表保存全部列和 id，可用 import 原样恢复 (仅 JSON，不支持筛选)
+        #[arg(long)]
+        lossless: bool,
+        /// 增量导出的游标文件 (仅 NDJSON)：只导出该游标之后新增或修改的行，完成后写回新游标；文件不存在时全量导出
+        #[arg(long, value_name = "CURSOR_FILE")]
+        since: Option<String>,
     },
     /// 查询并显示数据库内容
     Query {
@@ -43,6 +91,9 @@ enum Commands {
         /// 限制显示的记录数
         #[arg(short, long)]
         limit: Option<i64>,
+        /// 输出格式 (默认取配置 output.format)
+        #[arg(short, long, value_enum)]
+        output: Option<OutputFormat>,
     },
     /// 初始化数据库文件
     Init {
@@ -55,26 +106,348 @@ enum Commands {
         /// 数据类型 (servers, system_metrics, processes, crash_logs)
         #[arg(value_enum)]
         data_type: SmartDataType,
-        /// JSON 文件路径
+        /// 数据文件路径
         #[arg(short, long)]
         file: String,
+        /// 数据文件格式 (CSV 按表头映射列，不支持组合数据)
+        #[arg(long, value_enum, default_value = "json")]
+        format: DataFormat,
+        /// 遇到错误时是否继续处理
+        #[arg(long, default_value = "false")]
+        continue_on_error: bool,
+        /// 写入后加入转发队列，由 forward 命令投递到中心节点 (配置 forward.enabled 时总是加入)
+        #[arg(long)]
+        forward: bool,
+    },
+    /// 写入 InfluxDB 行协议数据 (system_metrics、process_trends)
+    Ingest {
+        /// 行协议文件路径，- 表示从标准输入读取
+        #[arg(short, long, default_value = "-")]
+        file: String,
+        /// 时间戳精度 (ns、us、ms、s)
+        #[arg(long, default_value = "ms")]
+        precision: String,
         /// 遇到错误时是否继续处理
         #[arg(long, default_value = "false")]
         continue_on_error: bool,
     },
     /// 数据库统计信息
-    Stats,
+    Stats {
+        /// 输出格式 (默认取配置 output.format)
+        #[arg(short, long, value_enum)]
+        output: Option<OutputFormat>,
+    },
+    /// 检查数据完整性（孤儿数据、结构版本）
+    Check,
+    /// 升级数据库结构到当前版本 (启用级联删除并清理孤儿数据)
+    Migrate,
+    /// 诊断数据库 (完整性检查、结构版本、逻辑一致性)，可选修复或抢救数据
+    Doctor {
+        /// 使用 quick_check 代替完整的 integrity_check
+        #[arg(long)]
+        quick: bool,
+        /// 修复逻辑不一致 (删除重复指标和孤儿数据)
+        #[arg(long)]
+        repair: bool,
+        /// 将可读的数据抢救到新的数据库文件
+        #[arg(long, value_name = "FILE")]
+        salvage: Option<String>,
+    },
+    /// 在线备份数据库 (备份期间可继续写入)
+    Backup {
+        /// 备份文件路径
+        #[arg(long, value_name = "FILE")]
+        to: String,
+        /// 备份文件已存在时覆盖
+        #[arg(long)]
+        force: bool,
+    },
+    /// 从备份文件恢复数据库 (会覆盖当前数据库)
+    Restore {
+        /// 备份文件路径
+        #[arg(long, value_name = "FILE")]
+        from: String,
+        /// 确认执行恢复
+        #[arg(long)]
+        confirm: bool,
+    },
+    /// 启动 HTTP 服务，在 /metrics 暴露 Prometheus 指标
+    Serve {
+        /// 监听地址
+        #[arg(long, default_value = "127.0.0.1:9464")]
+        listen: String,
+    },
+    /// 将多个 blackbox 数据库合并到一个数据库 (按智能插入规则去重)
+    Merge {
+        /// 源数据库文件，可重复指定，按顺序合并
+        #[arg(long = "from", value_name = "FILE", required = true)]
+        from: Vec<String>,
+        /// 目标数据库文件，不存在时自动创建
+        #[arg(long, value_name = "FILE")]
+        into: String,
+        /// 将完整的合并报告 (含全部冲突) 写入 JSON 文件
+        #[arg(long, value_name = "FILE")]
+        report: Option<String>,
+    },
+    /// 将转发队列中的数据投递到中心节点 (blackbox serve)
+    Forward {
+        #[command(subcommand)]
+        action: ForwardAction,
+    },
+    /// 管理服务器标签 (用于按标签筛选导出)
+    Label {
+        #[command(subcommand)]
+        action: LabelAction,
+    },
+    /// 处理崩溃日志 (标记解决、重新打开、添加备注、查看历史)
+    Crash {
+        #[command(subcommand)]
+        action: CrashCommand,
+    },
+    /// 崩溃和告警通知 (目标在配置文件的 [notify] 中设置)
+    Notify {
+        #[command(subcommand)]
+        action: NotifyAction,
+    },
+    /// 查看配置
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
     /// 清理旧数据
     Clean {
-        /// 保留最近 N 天的数据
-        #[arg(short, long, default_value = "30")]
-        days: i64,
+        /// 保留最近 N 天的数据 (默认取配置 retention.clean_days)
+        #[arg(short, long)]
+        days: Option<i64>,
         /// 确认执行清理
         #[arg(long)]
         confirm: bool,
     },
 }
 
+#[derive(Subcommand)]
+enum LabelAction {
+    /// 设置标签，已有的同名标签会被覆盖
+    Set {
+        /// 服务器 ID
+        server: String,
+        /// 标签，格式为 key=value
+        #[arg(required = true, value_parser = parse_label)]
+        labels: Vec<(String, String)>,
+    },
+    /// 删除标签
+    Remove {
+        /// 服务器 ID
+        server: String,
+        /// 标签键
+        #[arg(required = true)]
+        keys: Vec<String>,
+    },
+    /// 列出标签
+    List {
+        /// 只列出指定服务器的标签
+        server: Option<String>,
+        /// 输出格式 (默认取配置 output.format)
+        #[arg(short, long, value_enum)]
+        output: Option<OutputFormat>,
+    },
+}
+
+#[derive(Subcommand)]
+enum CrashCommand {
+    /// 按时间倒序列出崩溃日志
+    List {
+        /// 只列出指定服务器的日志
+        #[arg(short, long)]
+        server: Option<String>,
+        /// 只列出未解决的日志
+        #[arg(long, conflicts_with = "resolved")]
+        unresolved: bool,
+        /// 只列出已解决的日志
+        #[arg(long)]
+        resolved: bool,
+        /// 限制显示的记录数
+        #[arg(short, long)]
+        limit: Option<i64>,
+        /// 输出格式 (默认取配置 output.format)
+        #[arg(short, long, value_enum)]
+        output: Option<OutputFormat>,
+    },
+    /// 查看崩溃日志详情、AI 建议和处理历史
+    Show {
+        /// 崩溃日志 ID
+        id: i32,
+        /// 输出格式 (默认取配置 output.format)
+        #[arg(short, long, value_enum)]
+        output: Option<OutputFormat>,
+    },
+    /// 标记为已解决
+    Resolve {
+        /// 崩溃日志 ID
+        id: i32,
+        /// 处理说明
+        #[arg(long)]
+        note: Option<String>,
+        /// 操作人 (默认取环境变量 USER)
+        #[arg(long)]
+        by: Option<String>,
+    },
+    /// 重新打开已解决的崩溃日志
+    Reopen {
+        /// 崩溃日志 ID
+        id: i32,
+        /// 重新打开的原因
+        #[arg(long)]
+        note: Option<String>,
+        /// 操作人 (默认取环境变量 USER)
+        #[arg(long)]
+        by: Option<String>,
+    },
+    /// 生成摘要、分析和处理建议 (使用配置的 [analyzer]，默认内置规则)
+    Analyze {
+        /// 崩溃日志 ID
+        #[arg(required_unless_present = "pending", conflicts_with = "pending")]
+        id: Option<i32>,
+        /// 分析所有仍是占位文本 (正在等待 AI 生成) 的日志
+        #[arg(long)]
+        pending: bool,
+    },
+    /// 添加备注
+    Note {
+        /// 崩溃日志 ID
+        id: i32,
+        /// 备注内容
+        text: String,
+        /// 操作人 (默认取环境变量 USER)
+        #[arg(long)]
+        by: Option<String>,
+    },
+    /// 管理 AI 建议 (添加、替换、排序、标记已执行)
+    Recommend {
+        #[command(subcommand)]
+        action: RecommendCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum RecommendCommand {
+    /// 按优先级列出崩溃日志的建议
+    List {
+        /// 崩溃日志 ID
+        crash_id: i32,
+        /// 输出格式 (默认取配置 output.format)
+        #[arg(short, long, value_enum)]
+        output: Option<OutputFormat>,
+    },
+    /// 添加一条建议
+    Add {
+        /// 崩溃日志 ID
+        crash_id: i32,
+        /// 建议的操作
+        action: String,
+        /// 执行的命令
+        #[arg(long, default_value = "")]
+        command: String,
+        /// 插入到的位置 (从 1 开始)，默认排在最后
+        #[arg(long)]
+        priority: Option<i32>,
+    },
+    /// 用 JSON 数组替换全部建议，格式同 crash_logs 的 recommendations
+    Replace {
+        /// 崩溃日志 ID
+        crash_id: i32,
+        /// JSON 文件路径，"-" 表示从标准输入读取
+        #[arg(short, long)]
+        file: String,
+    },
+    /// 按给出的建议 ID 顺序重新排列
+    Reorder {
+        /// 崩溃日志 ID
+        crash_id: i32,
+        /// 全部建议 ID，按新的顺序
+        #[arg(required = true)]
+        ids: Vec<i32>,
+    },
+    /// 标记建议为已执行
+    Apply {
+        /// 建议 ID
+        id: i32,
+        /// 执行结果
+        #[arg(long)]
+        outcome: Option<String>,
+    },
+    /// 取消已执行标记
+    Unapply {
+        /// 建议 ID
+        id: i32,
+    },
+    /// 删除一条建议
+    Remove {
+        /// 建议 ID
+        id: i32,
+    },
+}
+
+#[derive(Subcommand)]
+enum NotifyAction {
+    /// 查看最近的投递记录
+    Log {
+        /// 显示的条数
+        #[arg(short, long, default_value = "20")]
+        limit: i64,
+        /// 输出格式 (默认取配置 output.format)
+        #[arg(short, long, value_enum)]
+        output: Option<OutputFormat>,
+    },
+    /// 向所有通知目标发送一条测试通知
+    Test,
+}
+
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// 以 TOML 格式打印合并配置文件、环境变量和命令行参数后的生效配置
+    Show,
+}
+
+#[derive(Subcommand)]
+enum ForwardAction {
+    /// 按入队顺序投递，失败时按指数退避重试
+    Run {
+        /// 中心节点地址，如 http://10.0.0.1:9464
+        #[arg(long)]
+        to: String,
+        /// 持续运行，每隔 N 秒检查一次队列；为 0 时只投递一轮
+        #[arg(long, default_value = "0")]
+        interval: u64,
+        /// 每次从队列读取的条数
+        #[arg(long, default_value = "100")]
+        batch: i64,
+        /// 单次请求超时 (秒)
+        #[arg(long, default_value = "10")]
+        timeout: u64,
+        /// 最长退避时间 (秒)
+        #[arg(long, default_value = "300")]
+        max_backoff: u64,
+    },
+    /// 查看队列中待投递的数据
+    Status,
+    /// 列出被中心节点拒绝、不再投递的数据 (死信)
+    Dead,
+    /// 把死信重新放回队列 (如中心节点升级后)
+    Requeue {
+        /// 记录 ID
+        record_id: String,
+    },
+    /// 丢弃队列中的数据或死信
+    Discard {
+        /// 记录 ID
+        record_id: String,
+        /// 确认丢弃
+        #[arg(long)]
+        confirm: bool,
+    },
+}
+
 #[derive(clap::ValueEnum, Clone, Debug)]
 enum SmartDataType {
     /// 服务器信息 (已存在则更新状态)
@@ -89,6 +462,101 @@ enum SmartDataType {
     Combined,
 }
 
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum ExportFormat {
+    /// JSON
+    Json,
+    /// CSV (每张表一个文件)
+    Csv,
+    /// InfluxDB 行协议 (系统指标和进程趋势，毫秒时间戳)
+    Influx,
+    /// NDJSON 变更记录，每行一条，最后一行为新的游标
+    Ndjson,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum DataFormat {
+    /// JSON
+    Json,
+    /// CSV (带表头)
+    Csv,
+}
+
+/// JSON 导出可选的数据类别
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum ExportInclude {
+    /// 系统指标
+    Metrics,
+    /// 进程 (含趋势和线程)
+    Processes,
+    /// 崩溃日志 (含 AI 建议)
+    Crashes,
+}
+
+impl From<ExportInclude> for ExportEntity {
+    fn from(include: ExportInclude) -> Self {
+        match include {
+            ExportInclude::Metrics => ExportEntity::Metrics,
+            ExportInclude::Processes => ExportEntity::Processes,
+            ExportInclude::Crashes => ExportEntity::Crashes,
+        }
+    }
+}
+
+/// 可导出的数据表，同时接受数据库中的表名 (如 system_metrics)
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum ExportTable {
+    Servers,
+    #[value(alias = "system_metrics")]
+    SystemMetrics,
+    Processes,
+    #[value(alias = "process_trends")]
+    ProcessTrends,
+    Threads,
+    #[value(alias = "crash_logs")]
+    CrashLogs,
+    #[value(alias = "ai_recommendations")]
+    AiRecommendations,
+}
+
+impl From<ExportTable> for CsvTable {
+    fn from(table: ExportTable) -> Self {
+        match table {
+            ExportTable::Servers => CsvTable::Servers,
+            ExportTable::SystemMetrics => CsvTable::SystemMetrics,
+            ExportTable::Processes => CsvTable::Processes,
+            ExportTable::ProcessTrends => CsvTable::ProcessTrends,
+            ExportTable::Threads => CsvTable::Threads,
+            ExportTable::CrashLogs => CsvTable::CrashLogs,
+            ExportTable::AiRecommendations => CsvTable::AiRecommendations,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum OutputFormat {
+    /// 人类可读的表格文本
+    Table,
+    /// JSON
+    Json,
+    /// CSV (每个服务器一行)
+    Csv,
+    /// YAML
+    Yaml,
+}
+
+impl OutputFormat {
+    /// 对应的机器可读格式，表格由命令行自行渲染
+    fn machine_format(self) -> Option<LibOutputFormat> {
+        match self {
+            OutputFormat::Table => None,
+            OutputFormat::Json => Some(LibOutputFormat::Json),
+            OutputFormat::Csv => Some(LibOutputFormat::Csv),
+            OutputFormat::Yaml => Some(LibOutputFormat::Yaml),
+        }
+    }
+}
+
 impl From<SmartDataType> for LibSmartDataType {
     fn from(cli_type: SmartDataType) -> Self {
         match cli_type {
@@ -101,78 +569,281 @@ impl From<SmartDataType> for LibSmartDataType {
     }
 }
 
-fn main() -> Result<()> {
+fn main() -> ExitCode {
     let cli = Cli::parse();
-    
-    // 创建 BlackBox 实例
-    let blackbox = BlackBox::new(cli.db.clone());
-    
+
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            ExitCode::from(exit_code(&e))
+        }
+    }
+}
+
+/// 按错误类别返回退出码，参数错误由 clap 以 2 退出
+fn exit_code(error: &anyhow::Error) -> u8 {
+    match error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<BlackBoxError>())
+    {
+        Some(BlackBoxError::NotFound { .. }) => 3,
+        Some(BlackBoxError::Validation { .. }) => 4,
+        Some(BlackBoxError::Conflict { .. }) => 5,
+        Some(BlackBoxError::Busy { .. }) => 6,
+        Some(BlackBoxError::Schema { .. }) => 7,
+        Some(BlackBoxError::Io { .. }) => 8,
+        Some(BlackBoxError::Parse { .. }) => 9,
+        Some(BlackBoxError::Database { .. }) => 10,
+        None => 1,
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
+    // 合并配置文件和命令行参数，创建 BlackBox 实例
+    let mut config = BlackBoxConfig::from_env(cli.config.as_deref())?;
+    if let Some(db) = cli.db.clone() {
+        config.database.path = Some(db);
+    }
+    // 取值已在读取配置时校验
+    let default_output = <OutputFormat as clap::ValueEnum>::from_str(&config.output.format, true)
+        .map_err(BlackBoxError::validation)?;
+    let blackbox = BlackBox::from_config(config);
+
     match cli.command {
         Some(Commands::Import { file, clean }) => {
             println!("📥 正在导入数据...");
             blackbox.import_json_data(&file, clean)?;
             println!("✅ 数据导入完成！");
         }
-        Some(Commands::Export { file, pretty }) => {
-            println!("📤 正在导出数据...");
-            blackbox.export_to_json(&file, pretty)?;
-            println!("✅ 数据导出完成！");
+        Some(Commands::Export {
+            file,
+            pretty,
+            format,
+            table,
+            server,
+            label,
+            from,
+            to,
+            include,
+            lossless,
+            since,
+        }) => {
+            let filter = ExportFilter {
+                server_ids: server,
+                labels: label,
+                from,
+                to,
+                include: if include.is_empty() {
+                    ExportEntity::ALL.to_vec()
+                } else {
+                    include.into_iter().map(ExportEntity::from).collect()
+                },
+            };
+            let filtered = !filter.server_ids.is_empty()
+                || !filter.labels.is_empty()
+                || filter.from.is_some()
+                || filter.to.is_some()
+                || filter.include.len() < ExportEntity::ALL.len();
+
+            if filtered && format != ExportFormat::Json {
+                return Err(BlackBoxError::validation(
+                    "--server、--label、--from、--to、--include 仅适用于 JSON 导出",
+                )
+                .into());
+            }
+
+            if lossless && (format != ExportFormat::Json || filtered) {
+                return Err(BlackBoxError::validation(
+                    "--lossless 仅适用于不带筛选条件的 JSON 导出",
+                )
+                .into());
+            }
+
+            if lossless {
+                let path = file.as_deref().unwrap_or("export.json");
+                println!("📤 正在无损导出数据...");
+                for (table, count) in blackbox.export_lossless(path, pretty)? {
+                    println!("   📄 {}: {} 行", table, count);
+                }
+                println!("✅ 数据导出完成！-> {}", path);
+            } else if format == ExportFormat::Ndjson {
+                export_changes(&blackbox, file, since)?;
+            } else if since.is_some() {
+                return Err(BlackBoxError::validation(
+                    "--since 仅适用于 NDJSON 导出 (--format ndjson)",
+                )
+                .into());
+            } else {
+                export_data(&blackbox, file, pretty, format, table, &filter)?;
+            }
         }
-        Some(Commands::Query { server, limit }) => {
-            query_data(&blackbox, server.as_deref(), limit)?;
+        Some(Commands::Query {
+            server,
+            limit,
+            output,
+        }) => {
+            query_data(
+                &blackbox,
+                server.as_deref(),
+                limit,
+                output.unwrap_or(default_output),
+            )?;
         }
         Some(Commands::Init { force }) => {
             println!("🔧 正在初始化数据库...");
             blackbox.init_database(force)?;
             println!("✅ 数据库初始化完成！");
         }
-        Some(Commands::Insert { data_type, file, continue_on_error }) => {
-            smart_insert_from_file(&blackbox, data_type, &file, continue_on_error)?;
+        Some(Commands::Insert {
+            data_type,
+            file,
+            format,
+            continue_on_error,
+            forward,
+        }) => {
+            smart_insert_from_file(
+                &blackbox,
+                data_type,
+                &file,
+                format,
+                continue_on_error,
+                forward,
+            )?;
+        }
+        Some(Commands::Ingest {
+            file,
+            precision,
+            continue_on_error,
+        }) => {
+            ingest_line_protocol(&blackbox, &file, &precision, continue_on_error)?;
+        }
+        Some(Commands::Stats { output }) => {
+            show_statistics(&blackbox, output.unwrap_or(default_output))?;
+        }
+        Some(Commands::Check) => {
+            check_integrity(&blackbox)?;
         }
-        Some(Commands::Stats) => {
-            show_statistics(&blackbox)?;
+        Some(Commands::Migrate) => {
+            println!("🔧 正在升级数据库结构...");
+            let from_version = blackbox.migrate_database()?;
+            if from_version == SCHEMA_VERSION {
+                println!("✅ 数据库结构已是最新版本 (v{})", SCHEMA_VERSION);
+            } else {
+                println!(
+                    "✅ 数据库结构已从 v{} 升级到 v{}",
+                    from_version, SCHEMA_VERSION
+                );
+            }
+        }
+        Some(Commands::Doctor {
+            quick,
+            repair,
+            salvage,
+        }) => {
+            run_doctor(&blackbox, quick, repair, salvage.as_deref())?;
+        }
+        Some(Commands::Backup { to, force }) => {
+            println!("💾 正在备份数据库...");
+            let size = blackbox.backup_to(&to, force)?;
+            println!("✅ 备份完成: {} ({} 字节)", to, size);
+        }
+        Some(Commands::Restore { from, confirm }) => {
+            restore_database(&blackbox, &from, confirm)?;
+        }
+        Some(Commands::Serve { listen }) => {
+            println!("📡 指标服务已启动: http://{}/metrics", listen);
+            blackbox::server::serve(&blackbox, &listen)?;
+        }
+        Some(Commands::Merge { from, into, report }) => {
+            merge_databases(&blackbox, &from, &into, report.as_deref())?;
+        }
+        Some(Commands::Forward { action }) => {
+            forward_outbox(&blackbox, action)?;
+        }
+        Some(Commands::Label { action }) => {
+            manage_labels(&blackbox, action, default_output)?;
+        }
+        Some(Commands::Crash { action }) => {
+            manage_crash_logs(&blackbox, action, default_output)?;
+        }
+        Some(Commands::Notify { action }) => {
+            manage_notifications(&blackbox, action, default_output)?;
+        }
+        Some(Commands::Config {
+            action: ConfigAction::Show,
+        }) => {
+            let mut effective = blackbox.config().clone();
+            effective
+                .database
+                .path
+                .get_or_insert_with(|| DEFAULT_DB_PATH.to_string());
+            print!("{}", effective.to_toml()?);
         }
         Some(Commands::Clean { days, confirm }) => {
+            let days = days.unwrap_or(blackbox.config().retention.clean_days);
             clean_old_data(&blackbox, days, confirm)?;
         }
         None => {
             // 默认行为：显示统计信息
             println!("🖥️  服务器监控数据管理系统");
-            show_statistics(&blackbox)?;
+            show_statistics(&blackbox, OutputFormat::Table)?;
             println!("\n💡 使用 --help 查看所有可用命令");
         }
     }
-    
+
     Ok(())
 }
 
-fn show_statistics(blackbox: &BlackBox) -> Result<()> {
+/// 以机器可读格式输出结果，表格格式返回 false 交由调用方渲染
+fn print_machine_output<T: Serialize + CsvRows>(value: &T, format: OutputFormat) -> Result<bool> {
+    match format.machine_format() {
+        Some(machine_format) => {
+            let rendered = output::render(value, machine_format)?;
+            // 输出通常接到 jq / head 等管道，下游提前关闭时不视为错误
+            match writeln!(io::stdout().lock(), "{}", rendered) {
+                Err(e) if e.kind() != io::ErrorKind::BrokenPipe => return Err(e.into()),
+                _ => {}
+            }
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+fn show_statistics(blackbox: &BlackBox, format: OutputFormat) -> Result<()> {
     let stats = blackbox.get_statistics()?;
-    
+    if print_machine_output(&stats, format)? {
+        return Ok(());
+    }
+
     println!("\n📊 数据库统计信息");
     println!("═══════════════════");
-    
+
     if stats.server_count == 0 {
         println!("📭 数据库为空，请先导入数据");
         return Ok(());
     }
-    
+
     println!("🖥️  服务器总数: {}", stats.server_count);
-    
+
     let mut total_metrics = 0;
     let mut total_processes = 0;
     let mut total_crashes = 0;
-    
+
     for server_stat in &stats.servers {
         total_metrics += server_stat.metrics_count;
         total_processes += server_stat.processes_count;
         total_crashes += server_stat.crashes_count;
-        
-        println!("\n🔸 {} ({})", server_stat.server.server_name, server_stat.server.server_status);
+
+        println!(
+            "\n🔸 {} ({})",
+            server_stat.server.server_name, server_stat.server.server_status
+        );
         println!("   📈 系统指标: {} 条", server_stat.metrics_count);
         println!("   ⚙️  进程数量: {} 个", server_stat.processes_count);
         println!("   🚨 崩溃日志: {} 条", server_stat.crashes_count);
-        
+
         if let Some(latest_time) = server_stat.latest_metric_time {
             let datetime = chrono::DateTime::from_timestamp_millis(latest_time)
                 .unwrap_or_default()
@@ -180,38 +851,46 @@ fn show_statistics(blackbox: &BlackBox) -> Result<()> {
             println!("   🕒 最新数据: {}", datetime);
         }
     }
-    
+
     println!("\n📋 总计统计");
     println!("   📊 系统指标: {} 条", total_metrics);
     println!("   🔄 进程记录: {} 个", total_processes);
     println!("   ⚠️  崩溃日志: {} 条", total_crashes);
-    
+
     Ok(())
 }
 
-fn query_data(blackbox: &BlackBox, server_filter: Option<&str>, limit: Option<i64>) -> Result<()> {
+fn query_data(
+    blackbox: &BlackBox,
+    server_filter: Option<&str>,
+    limit: Option<i64>,
+    format: OutputFormat,
+) -> Result<()> {
+    let server_details = blackbox.query_servers(server_filter, limit)?;
+    if print_machine_output(&server_details, format)? {
+        return Ok(());
+    }
+
     println!("\n🔍 数据查询结果");
     println!("═══════════════");
-    
-    let server_details = blackbox.query_servers(server_filter, limit)?;
-    
+
     if server_details.is_empty() {
         println!("❌ 未找到匹配的服务器");
         return Ok(());
     }
-    
+
     println!("\n🖥️  匹配的服务器 ({} 个):", server_details.len());
     for detail in &server_details {
-        println!("  🔸 {} ({}) - 状态: {}", 
-                detail.server.server_name, 
-                detail.server.server_ip, 
-                detail.server.server_status);
+        println!(
+            "  🔸 {} ({}) - 状态: {}",
+            detail.server.server_name, detail.server.server_ip, detail.server.server_status
+        );
     }
-    
+
     // 显示详细信息
     for detail in &server_details {
         println!("\n═══ {} 详细信息 ═══", detail.server.server_name);
-        
+
         // 系统指标
         let display_limit = limit.unwrap_or(5);
         println!("\n📊 最新 {} 条系统指标:", display_limit);
@@ -219,91 +898,125 @@ fn query_data(blackbox: &BlackBox, server_filter: Option<&str>, limit: Option<i6
             let datetime = chrono::DateTime::from_timestamp_millis(metric.timestamp)
                 .unwrap_or_default()
                 .format("%Y-%m-%d %H:%M:%S");
-            
-            println!("  时间: {} | CPU: {:.1}% | 内存: {:.1}% | 磁盘: {:.1}%", 
-                    datetime,
-                    metric.cpu_usage, 
-                    metric.memory_usage, 
-                    metric.disk_usage);
+
+            println!(
+                "  时间: {} | CPU: {:.1}% | 内存: {:.1}% | 磁盘: {:.1}%",
+                datetime, metric.cpu_usage, metric.memory_usage, metric.disk_usage
+            );
         }
-        
+
         // 进程信息
         if !detail.processes.is_empty() {
             println!("\n🔄 运行中的进程 ({} 个):", detail.processes.len());
             for process_detail in &detail.processes {
-                println!("  PID: {} | 名称: {} | 用户: {} | 状态: {}", 
-                        process_detail.process.pid, 
-                        process_detail.process.name, 
-                        process_detail.process.user_name, 
-                        process_detail.process.status);
-                
+                println!(
+                    "  PID: {} | 名称: {} | 用户: {} | 状态: {}",
+                    process_detail.process.pid,
+                    process_detail.process.name,
+                    process_detail.process.user_name,
+                    process_detail.process.status
+                );
+
                 // 显示进程的线程信息
                 if !process_detail.threads.is_empty() {
                     println!("    └─ 线程数: {}", process_detail.threads.len());
-                    for thread in process_detail.threads.iter().take(2) { // 只显示前2个线程
-                        println!("      └─ TID: {} | CPU: {}% | 内存: {}% | 命令: {}", 
-                                thread.thread_id,
-                                thread.cpu_usage,
-                                thread.memory_usage,
-                                thread.command.chars().take(50).collect::<String>());
+                    for thread in process_detail.threads.iter().take(2) {
+                        // 只显示前2个线程
+                        println!(
+                            "      └─ TID: {} | CPU: {}% | 内存: {}% | 命令: {}",
+                            thread.thread_id,
+                            thread.cpu_usage,
+                            thread.memory_usage,
+                            thread.command.chars().take(50).collect::<String>()
+                        );
                     }
                     if process_detail.threads.len() > 2 {
-                        println!("      └─ ... 还有 {} 个线程", process_detail.threads.len() - 2);
+                        println!(
+                            "      └─ ... 还有 {} 个线程",
+                            process_detail.threads.len() - 2
+                        );
                     }
                 }
-                
+
                 // 显示进程趋势
                 if !process_detail.trends.is_empty() {
                     let latest_trend = &process_detail.trends[0];
                     let datetime = chrono::DateTime::from_timestamp_millis(latest_trend.timestamp)
                         .unwrap_or_default()
                         .format("%H:%M:%S");
-                    println!("    └─ 最新趋势 ({}): CPU: {:.1}% | 内存: {:.1}% | 线程数: {}", 
-                            datetime,
-                            latest_trend.cpu_usage,
-                            latest_trend.memory_usage,
-                            latest_trend.thread_count);
+                    println!(
+                        "    └─ 最新趋势 ({}): CPU: {:.1}% | 内存: {:.1}% | 线程数: {}",
+                        datetime,
+                        latest_trend.cpu_usage,
+                        latest_trend.memory_usage,
+                        latest_trend.thread_count
+                    );
                 }
             }
         }
-        
+
         // 崩溃日志
         if !detail.crashes.is_empty() {
             println!("\n🚨 崩溃日志 ({} 条):", detail.crashes.len());
-            for crash_detail in detail.crashes.iter().take(3) { // 只显示前3条
-                let datetime = chrono::DateTime::from_timestamp_millis(crash_detail.crash_log.timestamp)
-                    .unwrap_or_default()
-                    .format("%Y-%m-%d %H:%M:%S");
-                
-                println!("  时间: {} | 类型: {} | 严重性: {} | 已解决: {}", 
-                        datetime,
-                        crash_detail.crash_log.crash_type,
-                        crash_detail.crash_log.severity,
-                        if crash_detail.crash_log.resolved { "是" } else { "否" });
+            for crash_detail in detail.crashes.iter().take(3) {
+                // 只显示前3条
+                let datetime =
+                    chrono::DateTime::from_timestamp_millis(crash_detail.crash_log.timestamp)
+                        .unwrap_or_default()
+                        .format("%Y-%m-%d %H:%M:%S");
+
+                println!(
+                    "  时间: {} | 类型: {} | 严重性: {} | 已解决: {}",
+                    datetime,
+                    crash_detail.crash_log.crash_type,
+                    crash_detail.crash_log.severity,
+                    if crash_detail.crash_log.resolved {
+                        "是"
+                    } else {
+                        "否"
+                    }
+                );
                 println!("    标题: {}", crash_detail.crash_log.title);
-                println!("    消息: {}", crash_detail.crash_log.message.chars().take(100).collect::<String>());
-                
+                println!(
+                    "    消息: {}",
+                    crash_detail
+                        .crash_log
+                        .message
+                        .chars()
+                        .take(100)
+                        .collect::<String>()
+                );
+
                 // 显示 AI 建议
                 if !crash_detail.recommendations.is_empty() {
-                    println!("    🤖 AI 建议 ({} 条):", crash_detail.recommendations.len());
+                    println!(
+                        "    🤖 AI 建议 ({} 条):",
+                        crash_detail.recommendations.len()
+                    );
                     for rec in crash_detail.recommendations.iter().take(2) {
-                        println!("      {}. {} (优先级: {})", 
-                                rec.priority, 
-                                rec.action,
-                                rec.priority);
-                        println!("         命令: {}", rec.command.chars().take(80).collect::<String>());
+                        println!(
+                            "      {}. {} (优先级: {})",
+                            rec.priority, rec.action, rec.priority
+                        );
+                        println!(
+                            "         命令: {}",
+                            rec.command.chars().take(80).collect::<String>()
+                        );
                     }
                 }
                 println!();
             }
         }
-        
+
         // 统计信息
         if !detail.metrics.is_empty() {
-            let avg_cpu: f32 = detail.metrics.iter().map(|m| m.cpu_usage).sum::<f32>() / detail.metrics.len() as f32;
-            let avg_memory: f32 = detail.metrics.iter().map(|m| m.memory_usage).sum::<f32>() / detail.metrics.len() as f32;
-            let avg_disk: f32 = detail.metrics.iter().map(|m| m.disk_usage).sum::<f32>() / detail.metrics.len() as f32;
-            
+            let avg_cpu: f32 = detail.metrics.iter().map(|m| m.cpu_usage).sum::<f32>()
+                / detail.metrics.len() as f32;
+            let avg_memory: f32 = detail.metrics.iter().map(|m| m.memory_usage).sum::<f32>()
+                / detail.metrics.len() as f32;
+            let avg_disk: f32 = detail.metrics.iter().map(|m| m.disk_usage).sum::<f32>()
+                / detail.metrics.len() as f32;
+
             println!("\n📈 统计摘要:");
             println!("  平均 CPU 使用率: {:.1}%", avg_cpu);
             println!("  平均内存使用率: {:.1}%", avg_memory);
@@ -313,20 +1026,774 @@ fn query_data(blackbox: &BlackBox, server_filter: Option<&str>, limit: Option<i6
             println!("  崩溃日志数量: {}", detail.crashes.len());
         }
     }
-    
+
+    Ok(())
+}
+
+fn export_data(
+    blackbox: &BlackBox,
+    file: Option<String>,
+    pretty: bool,
+    format: ExportFormat,
+    tables: Vec<ExportTable>,
+    filter: &ExportFilter,
+) -> Result<()> {
+    if !tables.is_empty() && format != ExportFormat::Csv {
+        return Err(BlackBoxError::validation("--table 仅适用于 CSV 导出").into());
+    }
+
+    println!("📤 正在导出数据...");
+
+    match format {
+        ExportFormat::Json => {
+            let path = file.as_deref().unwrap_or("export.json");
+            let count = blackbox.export_to_json(path, pretty, filter)?;
+            println!("   📄 {} 台服务器 -> {}", count, path);
+            if count == 0 {
+                println!("   ⚠️  没有服务器符合筛选条件");
+            }
+        }
+        ExportFormat::Influx => {
+            let path = file.unwrap_or_else(|| "export.lp".to_string());
+            let (metrics, trends) = blackbox.export_to_influx(&path)?;
+            println!(
+                "   📄 system_metrics: {} 行, process_trends: {} 行 -> {}",
+                metrics, trends, path
+            );
+        }
+        ExportFormat::Ndjson => {
+            return Err(BlackBoxError::validation("NDJSON 导出请使用 export_changes").into());
+        }
+        ExportFormat::Csv => {
+            let tables: Vec<CsvTable> = if tables.is_empty() {
+                CsvTable::ALL.to_vec()
+            } else {
+                tables.into_iter().map(CsvTable::from).collect()
+            };
+
+            // 单表且未指定已有目录时写单个文件，否则每张表一个文件写入目录
+            let single_file =
+                tables.len() == 1 && !file.as_deref().is_some_and(|f| Path::new(f).is_dir());
+            if single_file {
+                let table = tables[0];
+                let path = file.unwrap_or_else(|| format!("{}.csv", table.table_name()));
+                let count = blackbox.export_table_to_csv(table, &path)?;
+                println!("   📄 {}: {} 行 -> {}", table.table_name(), count, path);
+            } else {
+                let dir = file.unwrap_or_else(|| "export".to_string());
+                fs::create_dir_all(&dir)?;
+                for table in tables {
+                    let path = Path::new(&dir).join(format!("{}.csv", table.table_name()));
+                    let path = path.to_string_lossy();
+                    let count = blackbox.export_table_to_csv(table, &path)?;
+                    println!("   📄 {}: {} 行 -> {}", table.table_name(), count, path);
+                }
+            }
+        }
+    }
+
+    println!("✅ 数据导出完成！");
+    Ok(())
+}
+
+/// 终端中每个源数据库最多列出的冲突数，完整列表见 --report
+const MAX_PRINTED_CONFLICTS: usize = 20;
+
+fn merge_databases(
+    blackbox: &BlackBox,
+    sources: &[String],
+    into: &str,
+    report_path: Option<&str>,
+) -> Result<()> {
+    let target = BlackBox::builder()
+        .config(blackbox.config().clone())
+        .db_path(into)
+        .build();
+    if !Path::new(into).exists() {
+        println!("🔧 目标数据库不存在，正在初始化 {}...", into);
+        target.init_database(false)?;
+    }
+
+    let mut reports = Vec::new();
+    for source in sources {
+        println!("🔀 正在合并 {} -> {}...", source, into);
+        let report = target.merge_database(source)?;
+
+        for stats in &report.tables {
+            if stats.inserted + stats.updated + stats.unchanged > 0 {
+                println!(
+                    "   📄 {}: 新增 {}，更新 {}，相同 {}",
+                    stats.table, stats.inserted, stats.updated, stats.unchanged
+                );
+            }
+        }
+        if report.conflicts.is_empty() {
+            println!("   ✅ 无冲突");
+        } else {
+            println!("   ⚠️  冲突 {} 处:", report.conflicts.len());
+            for conflict in report.conflicts.iter().take(MAX_PRINTED_CONFLICTS) {
+                println!(
+                    "      - [{}] {}: {}",
+                    conflict.table, conflict.key, conflict.detail
+                );
+            }
+            if report.conflicts.len() > MAX_PRINTED_CONFLICTS {
+                println!(
+                    "      ... 另有 {} 处，使用 --report 查看完整列表",
+                    report.conflicts.len() - MAX_PRINTED_CONFLICTS
+                );
+            }
+        }
+
+        reports.push(report);
+    }
+
+    if let Some(path) = report_path {
+        fs::write(path, serde_json::to_string_pretty(&reports)?)?;
+        println!("📝 合并报告已写入 {}", path);
+    }
+
+    println!("✅ 合并完成！");
     Ok(())
 }
 
-fn smart_insert_from_file(blackbox: &BlackBox, data_type: SmartDataType, filename: &str, continue_on_error: bool) -> Result<()> {
-    println!("🧠 正在智能插入 {:?} 类型的数据 (文件: {})...", data_type, filename);
-    
-    let result = blackbox.smart_insert_from_file(data_type.into(), filename, continue_on_error)?;
-    
+fn manage_crash_logs(
+    blackbox: &BlackBox,
+    action: CrashCommand,
+    default_output: OutputFormat,
+) -> Result<()> {
+    match action {
+        CrashCommand::List {
+            server,
+            unresolved,
+            resolved,
+            limit,
+            output,
+        } => {
+            let filter = CrashLogFilter {
+                server_id: server,
+                resolved: if resolved {
+                    Some(true)
+                } else if unresolved {
+                    Some(false)
+                } else {
+                    None
+                },
+                limit,
+            };
+            let crash_logs = blackbox.list_crash_logs(&filter)?;
+            if print_machine_output(&crash_logs, output.unwrap_or(default_output))? {
+                return Ok(());
+            }
+
+            if crash_logs.is_empty() {
+                println!("📭 没有匹配的崩溃日志");
+            }
+            for crash_log in &crash_logs {
+                println!(
+                    "{} #{} {} | {} | {} | 严重性: {} | {}",
+                    if crash_log.resolved { "✅" } else { "🚨" },
+                    crash_log.id,
+                    format_millis(crash_log.timestamp),
+                    crash_log.server_id,
+                    crash_log.crash_type,
+                    crash_log.severity,
+                    crash_log.title
+                );
+            }
+        }
+        CrashCommand::Show { id, output } => {
+            let history = blackbox.crash_log_history(id)?;
+            if print_machine_output(&history, output.unwrap_or(default_output))? {
+                return Ok(());
+            }
+
+            let crash_log = &history.crash_log;
+            println!("\n🚨 崩溃日志 #{}", crash_log.id);
+            println!("═══════════════");
+            println!("  服务器: {}", crash_log.server_id);
+            println!("  时间: {}", format_millis(crash_log.timestamp));
+            println!(
+                "  类型: {} | 严重性: {} | 已解决: {}",
+                crash_log.crash_type,
+                crash_log.severity,
+                if crash_log.resolved { "是" } else { "否" }
+            );
+            println!("  标题: {}", crash_log.title);
+            println!("  消息: {}", crash_log.message);
+            if let Some(summary) = &crash_log.ai_summary {
+                println!("  AI 摘要: {}", summary);
+            }
+
+            if !history.recommendations.is_empty() {
+                println!("\n🤖 AI 建议 ({} 条):", history.recommendations.len());
+                for rec in &history.recommendations {
+                    print_recommendation(rec);
+                }
+            }
+
+            println!("\n📝 处理历史 ({} 条):", history.events.len());
+            for event in &history.events {
+                let time = event.created_at.format("%Y-%m-%d %H:%M:%S");
+                match &event.note {
+                    Some(note) => println!("  {} {} {}: {}", time, event.actor, event.action, note),
+                    None => println!("  {} {} {}", time, event.actor, event.action),
+                }
+            }
+        }
+        CrashCommand::Resolve { id, note, by } => {
+            blackbox.resolve_crash_log(id, &actor(by), note.as_deref())?;
+            println!("✅ 崩溃日志 {} 已标记为已解决", id);
+        }
+        CrashCommand::Reopen { id, note, by } => {
+            blackbox.reopen_crash_log(id, &actor(by), note.as_deref())?;
+            println!("🔄 崩溃日志 {} 已重新打开", id);
+        }
+        CrashCommand::Analyze { id, pending } => {
+            let analyzer = blackbox.analyzer();
+            let ids = match id {
+                Some(id) => vec![id],
+                None => blackbox
+                    .pending_crash_logs()?
+                    .into_iter()
+                    .map(|crash_log| crash_log.id)
+                    .collect(),
+            };
+            if ids.is_empty() {
+                println!("📭 没有等待分析的崩溃日志");
+                return Ok(());
+            }
+
+            println!(
+                "🤖 使用分析器 {} 分析 {} 条崩溃日志...",
+                analyzer.name(),
+                ids.len()
+            );
+            let mut failed = 0;
+            for id in &ids {
+                match blackbox.analyze_crash_log(analyzer.as_ref(), *id) {
+                    Ok(analysis) => {
+                        println!(
+                            "✅ #{} {} ({} 条建议)",
+                            id,
+                            analysis.summary,
+                            analysis.recommendations.len()
+                        );
+                    }
+                    // 指定单条日志时直接返回错误，保留对应的退出码
+                    Err(e) if !pending => return Err(e.into()),
+                    Err(e) => {
+                        failed += 1;
+                        println!("❌ #{} 分析失败: {}", id, e);
+                    }
+                }
+            }
+            if failed > 0 {
+                anyhow::bail!("{} 条崩溃日志分析失败", failed);
+            }
+        }
+        CrashCommand::Note { id, text, by } => {
+            blackbox.annotate_crash_log(id, &actor(by), &text)?;
+            println!("📝 已为崩溃日志 {} 添加备注", id);
+        }
+        CrashCommand::Recommend { action } => {
+            manage_recommendations(blackbox, action, default_output)?
+        }
+    }
+
+    Ok(())
+}
+
+fn manage_recommendations(
+    blackbox: &BlackBox,
+    action: RecommendCommand,
+    default_output: OutputFormat,
+) -> Result<()> {
+    match action {
+        RecommendCommand::List { crash_id, output } => {
+            let recommendations = blackbox.crash_recommendations(crash_id)?;
+            if print_machine_output(&recommendations, output.unwrap_or(default_output))? {
+                return Ok(());
+            }
+
+            if recommendations.is_empty() {
+                println!("📭 崩溃日志 {} 没有 AI 建议", crash_id);
+            }
+            for rec in &recommendations {
+                print_recommendation(rec);
+            }
+        }
+        RecommendCommand::Add {
+            crash_id,
+            action,
+            command,
+            priority,
+        } => {
+            let rec = blackbox.add_crash_recommendation(
+                crash_id,
+                &SmartRecommendation {
+                    priority,
+                    action,
+                    command,
+                },
+            )?;
+            println!("✅ 已添加建议 #{} (优先级 {})", rec.id, rec.priority);
+        }
+        RecommendCommand::Replace { crash_id, file } => {
+            let content = if file == "-" {
+                io::read_to_string(io::stdin()).map_err(|e| BlackBoxError::io("stdin", e))?
+            } else {
+                fs::read_to_string(&file).map_err(|e| BlackBoxError::io(&file, e))?
+            };
+            let new: Vec<SmartRecommendation> = serde_json::from_str(&content)
+                .map_err(|e| BlackBoxError::parse("JSON", e.to_string()))?;
+            let recommendations = blackbox.replace_crash_recommendations(crash_id, &new)?;
+            println!(
+                "🔄 崩溃日志 {} 的建议已替换为 {} 条",
+                crash_id,
+                recommendations.len()
+            );
+        }
+        RecommendCommand::Reorder { crash_id, ids } => {
+            let recommendations = blackbox.reorder_crash_recommendations(crash_id, &ids)?;
+            println!("🔢 已重新排列 {} 条建议:", recommendations.len());
+            for rec in &recommendations {
+                print_recommendation(rec);
+            }
+        }
+        RecommendCommand::Apply { id, outcome } => {
+            blackbox.mark_recommendation_applied(id, true, outcome.as_deref())?;
+            println!("✅ 建议 {} 已标记为已执行", id);
+        }
+        RecommendCommand::Unapply { id } => {
+            blackbox.mark_recommendation_applied(id, false, None)?;
+            println!("↩️ 建议 {} 已取消执行标记", id);
+        }
+        RecommendCommand::Remove { id } => {
+            let rec = blackbox.remove_crash_recommendation(id)?;
+            println!(
+                "🗑️ 已删除崩溃日志 {} 的建议 #{}: {}",
+                rec.crash_log_id, rec.id, rec.action
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn print_recommendation(rec: &AiRecommendation) {
+    println!("  {}. [#{}] {}", rec.priority, rec.id, rec.action);
+    if !rec.command.is_empty() {
+        println!("     命令: {}", rec.command);
+    }
+    if rec.applied {
+        let time = rec
+            .applied_at
+            .map(|at| at.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_default();
+        match &rec.outcome {
+            Some(outcome) => println!("     ✅ 已执行 {} | 结果: {}", time, outcome),
+            None => println!("     ✅ 已执行 {}", time),
+        }
+    }
+}
+
+/// 未指定操作人时取当前登录用户
+fn actor(by: Option<String>) -> String {
+    by.or_else(|| std::env::var("USER").ok())
+        .filter(|name| !name.trim().is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn manage_notifications(
+    blackbox: &BlackBox,
+    action: NotifyAction,
+    default_output: OutputFormat,
+) -> Result<()> {
+    match action {
+        NotifyAction::Log { limit, output } => {
+            let deliveries = blackbox.notification_log(limit.max(1))?;
+            if print_machine_output(&deliveries, output.unwrap_or(default_output))? {
+                return Ok(());
+            }
+
+            if deliveries.is_empty() {
+                println!("📭 没有通知投递记录");
+            }
+            for delivery in deliveries {
+                print_delivery(&delivery);
+            }
+        }
+        NotifyAction::Test => {
+            for delivery in blackbox.send_test_notification()? {
+                print_delivery(&delivery);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn print_delivery(delivery: &NotificationDelivery) {
+    let icon = match delivery.status.as_str() {
+        STATUS_DELIVERED => "✅",
+        STATUS_PENDING => "⏳",
+        STATUS_RATE_LIMITED => "⏸️ ",
+        _ => "❌",
+    };
+    println!(
+        "{} {} {} -> {} ({}，{}，尝试 {} 次)",
+        icon,
+        format_millis(delivery.sent_at),
+        delivery.event,
+        delivery.notifier,
+        delivery.server_id,
+        delivery.status,
+        delivery.attempts
+    );
+    if let Some(error) = &delivery.last_error {
+        println!("   错误: {}", error);
+    }
+}
+
+fn manage_labels(
+    blackbox: &BlackBox,
+    action: LabelAction,
+    default_output: OutputFormat,
+) -> Result<()> {
+    match action {
+        LabelAction::Set { server, labels } => {
+            blackbox.set_server_labels(&server, &labels)?;
+            println!("✅ 已为服务器 {} 设置 {} 个标签", server, labels.len());
+        }
+        LabelAction::Remove { server, keys } => {
+            let removed = blackbox.remove_server_labels(&server, &keys)?;
+            println!("✅ 已删除服务器 {} 的 {} 个标签", server, removed);
+        }
+        LabelAction::List { server, output } => {
+            let labels = blackbox.get_server_labels(server.as_deref())?;
+            if print_machine_output(&labels, output.unwrap_or(default_output))? {
+                return Ok(());
+            }
+
+            if labels.is_empty() {
+                println!("📭 没有标签");
+            }
+            for label in labels {
+                println!(
+                    "🏷️  {} {}={}",
+                    label.server_id, label.label_key, label.label_value
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn forward_outbox(blackbox: &BlackBox, action: ForwardAction) -> Result<()> {
+    match action {
+        ForwardAction::Run {
+            to,
+            interval,
+            batch,
+            timeout,
+            max_backoff,
+        } => {
+            let options = ForwardOptions {
+                batch_size: batch.max(1),
+                timeout: Duration::from_secs(timeout),
+                max_backoff: Duration::from_secs(max_backoff),
+                ..Default::default()
+            };
+            if interval > 0 {
+                println!("📡 正在持续转发到 {} (每 {} 秒检查一次队列)", to, interval);
+            }
+
+            loop {
+                let report = blackbox.forward_pending(&to, &options)?;
+                print_forward_report(&report);
+
+                if interval == 0 {
+                    break;
+                }
+                std::thread::sleep(Duration::from_secs(interval));
+            }
+        }
+        ForwardAction::Status => {
+            let status = blackbox.outbox_status()?;
+            match status.head {
+                None => println!("📭 转发队列中没有待投递的数据"),
+                Some(head) => {
+                    println!("📮 转发队列中有 {} 条数据", status.pending);
+                    println!(
+                        "   队首: {} ({}，入队于 {})",
+                        head.record_id,
+                        head.data_type,
+                        head.created_at.format("%Y-%m-%d %H:%M:%S")
+                    );
+                    if head.attempts > 0 {
+                        println!(
+                            "   已失败 {} 次，下次重试: {}",
+                            head.attempts,
+                            format_millis(head.next_attempt_at)
+                        );
+                    }
+                    if let Some(error) = head.last_error {
+                        println!("   最近错误: {}", error);
+                    }
+                }
+            }
+            if status.dead > 0 {
+                println!(
+                    "☠️  另有 {} 条被中心节点拒绝的数据，使用 forward dead 查看",
+                    status.dead
+                );
+            }
+        }
+        ForwardAction::Dead => {
+            let entries = blackbox.dead_letters()?;
+            if entries.is_empty() {
+                println!("📭 没有被拒绝的数据");
+            }
+            for entry in entries {
+                println!(
+                    "☠️  {} ({}，拒绝于 {})",
+                    entry.record_id,
+                    entry.data_type,
+                    format_millis(entry.dead_at.unwrap_or_default())
+                );
+                if let Some(error) = entry.last_error {
+                    println!("   {}", error);
+                }
+            }
+        }
+        ForwardAction::Requeue { record_id } => match blackbox.requeue_outbox(&record_id)? {
+            0 => println!("📭 没有被拒绝的记录 {}", record_id),
+            _ => println!("📮 已把记录 {} 放回转发队列", record_id),
+        },
+        ForwardAction::Discard { record_id, confirm } => {
+            if !confirm {
+                println!(
+                    "⚠️  将从转发队列中丢弃记录 {}，这部分数据不会再发送到中心节点",
+                    record_id
+                );
+                println!("   使用 --confirm 参数确认执行");
+                return Ok(());
+            }
+            match blackbox.discard_outbox(&record_id)? {
+                0 => println!("📭 转发队列中没有记录 {}", record_id),
+                _ => println!("🗑️  已丢弃记录 {}", record_id),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn print_forward_report(report: &ForwardReport) {
+    if report.delivered > 0 {
+        print!("✅ 已投递 {} 条", report.delivered);
+        if report.duplicates > 0 {
+            print!(" (其中 {} 条中心节点此前已接收)", report.duplicates);
+        }
+        println!();
+    }
+    for dead in &report.dead_lettered {
+        println!(
+            "☠️  {} 被中心节点拒绝，已转入死信: {}",
+            dead.record_id, dead.error
+        );
+    }
+    if let Some(failure) = &report.failure {
+        println!(
+            "❌ 投递 {} 失败 (第 {} 次): {}",
+            failure.record_id, failure.attempts, failure.error
+        );
+        println!("   将于 {} 重试", format_millis(failure.retry_at));
+    } else if let Some(waiting_until) = report.waiting_until {
+        println!(
+            "⏳ 队首数据正在退避，将于 {} 重试",
+            format_millis(waiting_until)
+        );
+    }
+    if report.pending > 0 {
+        println!("📮 队列中剩余 {} 条", report.pending);
+    } else if report.delivered > 0 {
+        println!("📭 转发队列已清空");
+    }
+}
+
+fn format_millis(millis: i64) -> String {
+    chrono::DateTime::from_timestamp_millis(millis)
+        .unwrap_or_default()
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+/// 解析 key=value 形式的标签
+fn parse_label(value: &str) -> std::result::Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((key, label_value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_string(), label_value.trim().to_string()))
+        }
+        _ => Err(format!("标签格式应为 key=value: {}", value)),
+    }
+}
+
+/// 解析毫秒时间戳，或按 UTC 解析的日期时间 (RFC 3339、YYYY-MM-DD HH:MM:SS、YYYY-MM-DD)
+fn parse_time(value: &str) -> std::result::Result<i64, String> {
+    if let Ok(millis) = value.parse::<i64>() {
+        return Ok(millis);
+    }
+    if let Ok(datetime) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Ok(datetime.timestamp_millis());
+    }
+    for format in [
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M",
+    ] {
+        if let Ok(datetime) = chrono::NaiveDateTime::parse_from_str(value, format) {
+            return Ok(datetime.and_utc().timestamp_millis());
+        }
+    }
+    if let Ok(date) = chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(date
+            .and_hms_opt(0, 0, 0)
+            .unwrap_or_default()
+            .and_utc()
+            .timestamp_millis());
+    }
+
+    Err(format!(
+        "无法解析时间: {} (应为毫秒时间戳或 2026-10-18T08:00:00 形式的 UTC 时间)",
+        value
+    ))
+}
+
+/// 导出 NDJSON 变更记录，指定游标文件时只导出增量并在成功后写回新游标
+fn export_changes(blackbox: &BlackBox, file: Option<String>, since: Option<String>) -> Result<()> {
+    let cursor = match &since {
+        Some(path) => ExportCursor::load(path)?,
+        None => ExportCursor::default(),
+    };
+    let path = file.unwrap_or_else(|| "changes.ndjson".to_string());
+
+    // 写到标准输出时，进度信息改走标准错误，避免混入变更流
+    let to_stdout = path == "-";
+    let report = |line: String| {
+        if to_stdout {
+            eprintln!("{}", line)
+        } else {
+            println!("{}", line)
+        }
+    };
+
+    report("📤 正在导出变更记录...".to_string());
+
+    let summary = if to_stdout {
+        blackbox.export_changes(&cursor, io::stdout().lock())?
+    } else {
+        let file = fs::File::create(&path).map_err(|e| BlackBoxError::io(&path, e))?;
+        blackbox.export_changes(&cursor, io::BufWriter::new(file))?
+    };
+
+    for (table, count) in &summary.tables {
+        if *count > 0 {
+            report(format!("   📄 {}: {} 条", table, count));
+        }
+    }
+    report(format!("   共 {} 条变更 -> {}", summary.total(), path));
+
+    if let Some(cursor_path) = &since {
+        summary.cursor.save(cursor_path)?;
+        report(format!("   🔖 新游标已写入 {}", cursor_path));
+    }
+
+    report("✅ 数据导出完成！".to_string());
+    Ok(())
+}
+
+fn ingest_line_protocol(
+    blackbox: &BlackBox,
+    filename: &str,
+    precision: &str,
+    continue_on_error: bool,
+) -> Result<()> {
+    let precision = Precision::parse(precision)?;
+    let content = if filename == "-" {
+        io::read_to_string(io::stdin()).map_err(|e| BlackBoxError::io("stdin", e))?
+    } else {
+        fs::read_to_string(filename).map_err(|e| BlackBoxError::io(filename, e))?
+    };
+
+    let result = blackbox.ingest_line_protocol(&content, precision, continue_on_error)?;
+
+    println!("📊 行协议写入完成:");
+    println!("   ✅ 新建: {} 条记录", result.success_count);
+    println!("   🔄 更新: {} 条记录", result.updated_count);
+    println!("   ❌ 失败: {} 条记录", result.error_count);
+    for message in &result.errors {
+        println!("      - {}", message);
+    }
+
+    Ok(())
+}
+
+fn smart_insert_from_file(
+    blackbox: &BlackBox,
+    data_type: SmartDataType,
+    filename: &str,
+    format: DataFormat,
+    continue_on_error: bool,
+    forward: bool,
+) -> Result<()> {
+    println!(
+        "🧠 正在智能插入 {:?} 类型的数据 (文件: {})...",
+        data_type, filename
+    );
+
+    let result = match (format, forward) {
+        (DataFormat::Json, false) => {
+            blackbox.smart_insert_from_file(data_type.into(), filename, continue_on_error)?
+        }
+        (DataFormat::Csv, false) => {
+            blackbox.smart_insert_csv_from_file(data_type.into(), filename, continue_on_error)?
+        }
+        (DataFormat::Json, true) => {
+            let json_content =
+                fs::read_to_string(filename).map_err(|e| BlackBoxError::io(filename, e))?;
+            let (result, record_id) = blackbox.smart_insert_and_forward(
+                data_type.into(),
+                &json_content,
+                continue_on_error,
+            )?;
+            println!("📮 已加入转发队列 (记录 ID: {})", record_id);
+            result
+        }
+        (DataFormat::Csv, true) => {
+            let csv_content =
+                fs::read_to_string(filename).map_err(|e| BlackBoxError::io(filename, e))?;
+            let (result, record_id) = blackbox.smart_insert_csv_and_forward(
+                data_type.into(),
+                &csv_content,
+                continue_on_error,
+            )?;
+            println!("📮 已加入转发队列 (记录 ID: {})", record_id);
+            result
+        }
+    };
+
     println!("\n📊 智能插入处理完成:");
     println!("   ✅ 新建: {} 条记录", result.success_count);
     println!("   🔄 更新: {} 条记录", result.updated_count);
     println!("   ❌ 失败: {} 条记录", result.error_count);
-    
+    for message in &result.errors {
+        println!("      - {}", message);
+    }
+
     if result.error_count == 0 {
         println!("   🎉 所有数据处理成功！");
     } else if result.success_count + result.updated_count > 0 {
@@ -334,7 +1801,146 @@ fn smart_insert_from_file(blackbox: &BlackBox, data_type: SmartDataType, filenam
     } else {
         println!("   💥 数据处理失败，请检查输入格式和错误信息");
     }
-    
+
+    Ok(())
+}
+
+fn check_integrity(blackbox: &BlackBox) -> Result<()> {
+    println!("\n🩺 数据完整性检查");
+    println!("═══════════════════");
+
+    let schema_version = blackbox.get_schema_version()?;
+    if schema_version < SCHEMA_VERSION {
+        println!(
+            "⚠️  结构版本: v{} (最新为 v{}，请执行 migrate 升级)",
+            schema_version, SCHEMA_VERSION
+        );
+    } else {
+        println!("✅ 结构版本: v{}", schema_version);
+    }
+
+    let orphans = blackbox.check_orphans()?;
+    println!("\n🔗 孤儿数据");
+    for (table, count) in &orphans.tables {
+        println!("   {}: {} 条", table, count);
+    }
+
+    if orphans.total() == 0 {
+        println!("\n🎉 未发现孤儿数据");
+    } else {
+        println!(
+            "\n⚠️  共发现 {} 条孤儿数据，执行 migrate 可清理",
+            orphans.total()
+        );
+    }
+
+    Ok(())
+}
+
+fn run_doctor(blackbox: &BlackBox, quick: bool, repair: bool, salvage: Option<&str>) -> Result<()> {
+    println!("\n🩺 数据库诊断");
+    println!("═══════════════════");
+
+    let report = blackbox.diagnose(quick)?;
+
+    let check_name = if quick {
+        "quick_check"
+    } else {
+        "integrity_check"
+    };
+    if report.integrity_errors.is_empty() {
+        println!("✅ {}: ok", check_name);
+    } else {
+        println!(
+            "❌ {}: 发现 {} 个问题",
+            check_name,
+            report.integrity_errors.len()
+        );
+        for message in report.integrity_errors.iter().take(10) {
+            println!("   - {}", message);
+        }
+    }
+
+    if report.schema_ok() {
+        println!("✅ 结构版本: v{}", report.schema_version);
+    } else {
+        println!(
+            "❌ 结构版本: v{} (期望 v{})",
+            report.schema_version, SCHEMA_VERSION
+        );
+        for table in &report.missing_tables {
+            println!("   - 缺少表: {}", table);
+        }
+        for column in &report.missing_columns {
+            println!("   - 缺少列: {}", column);
+        }
+    }
+
+    println!("\n🔎 逻辑一致性");
+    println!(
+        "   📈 重复系统指标 (同服务器同时间戳): {} 条",
+        report.duplicate_metrics
+    );
+    let processes = report.orphans.count("processes");
+    let recommendations = report.orphans.count("ai_recommendations");
+    println!("   ⚙️  无服务器的进程: {} 条", processes);
+    println!("   🤖 悬空的 AI 建议: {} 条", recommendations);
+    println!(
+        "   🔗 其他孤儿数据: {} 条",
+        report.orphans.total() - processes - recommendations
+    );
+
+    if report.is_healthy() {
+        println!("\n🎉 数据库状态良好");
+    }
+
+    if repair {
+        let repaired = blackbox.repair()?;
+        println!("\n🔧 修复完成:");
+        println!(
+            "   🗑️  删除重复系统指标: {} 条",
+            repaired.duplicate_metrics_removed
+        );
+        println!(
+            "   🗑️  删除孤儿数据: {} 条",
+            repaired.orphans_removed.total()
+        );
+        if !report.integrity_errors.is_empty() {
+            println!("   ⚠️  物理损坏无法原地修复，请使用 --salvage 抢救数据");
+        }
+        if report.schema_version < SCHEMA_VERSION {
+            println!("   ⚠️  结构版本过旧，请执行 migrate 升级");
+        }
+    }
+
+    if let Some(target) = salvage {
+        let salvaged = blackbox.salvage_to(target)?;
+        println!("\n🛟 数据已抢救到 {}:", target);
+        for table in &salvaged.tables {
+            if table.failed > 0 {
+                println!(
+                    "   {}: {} 条 (无法读取 {} 条)",
+                    table.table, table.copied, table.failed
+                );
+            } else {
+                println!("   {}: {} 条", table.table, table.copied);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn restore_database(blackbox: &BlackBox, from: &str, confirm: bool) -> Result<()> {
+    if !confirm {
+        println!("⚠️  此操作将使用 {} 覆盖当前数据库", from);
+        println!("   请确保没有其他进程正在写入，并使用 --confirm 参数确认执行");
+        return Ok(());
+    }
+
+    println!("♻️  正在从备份恢复数据库...");
+    blackbox.restore_from(from)?;
+    println!("✅ 数据库恢复完成！");
     Ok(())
 }
 
@@ -344,9 +1950,9 @@ fn clean_old_data(blackbox: &BlackBox, days: i64, confirm: bool) -> Result<()> {
         println!("   请使用 --confirm 参数确认执行");
         return Ok(());
     }
-    
+
     let deleted = blackbox.clean_old_data(days)?;
-    
+
     println!("🗑️  已删除 {} 条旧的系统指标数据", deleted);
     Ok(())
-}
\ No newline at end of file
+}
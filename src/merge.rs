@@ -0,0 +1,697 @@
+//! 合并多个 blackbox 数据库 (blackbox merge)
+//!
+//! 源数据库以 ATTACH 方式挂到目标连接上，逐表按智能插入的规则写入目标库：
+//! - 服务器按 server_id 匹配，已存在时只更新状态
+//! - 系统指标按服务器 + 时间戳匹配，进程按服务器 + 进程名 + 用户匹配
+//! - 进程趋势按服务器 + 进程 + 时间戳匹配，线程随进程整体替换
+//! - 崩溃日志按服务器 + 时间戳去重，AI 建议和处理历史按新的崩溃日志 id 重映射
+//!
+//! 同一键在两边取值不同时记为冲突：服务器的名称、IP、系统保留目标库的值，
+//! 其余数据以源数据库为准。崩溃日志与重复上报的规则相同，保留目标库的解决状态和已生成的分析，
+//! 解决状态只随合并进来的解决、重新打开记录变化。
+
+use crate::error::{BlackBoxError, Result};
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::analyze;
+use crate::database::*;
+use crate::domain::CrashAction;
+use crate::models::*;
+use crate::services::SCHEMA_VERSION;
+
+/// 源数据库挂载时使用的 schema 名
+const SOURCE_SCHEMA: &str = "merge_source";
+
+/// 单张表的合并结果
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MergeTableStats {
+    pub table: &'static str,
+    /// 目标库中新增的行数
+    pub inserted: usize,
+    /// 按源数据库更新的行数
+    pub updated: usize,
+    /// 两边完全相同、无需写入的行数
+    pub unchanged: usize,
+}
+
+/// 同一键在源和目标中取值不同
+#[derive(Debug, Clone, Serialize)]
+pub struct MergeConflict {
+    pub table: &'static str,
+    /// 匹配键，如 `web-01@1702345678000`
+    pub key: String,
+    pub detail: String,
+}
+
+/// 一个源数据库的合并报告
+#[derive(Debug, Clone, Serialize)]
+pub struct MergeReport {
+    pub source: String,
+    pub tables: Vec<MergeTableStats>,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+impl MergeReport {
+    fn new(source: &str) -> Self {
+        let tables = [
+            "servers",
+            "server_labels",
+            "system_metrics",
+            "processes",
+            "process_trends",
+            "threads",
+            "crash_logs",
+            "ai_recommendations",
+            "crash_events",
+        ]
+        .into_iter()
+        .map(|table| MergeTableStats {
+            table,
+            ..Default::default()
+        })
+        .collect();
+
+        Self {
+            source: source.to_string(),
+            tables,
+            conflicts: Vec::new(),
+        }
+    }
+
+    fn stats(&mut self, table: &str) -> &mut MergeTableStats {
+        self.tables
+            .iter_mut()
+            .find(|s| s.table == table)
+            .expect("合并报告包含所有数据表")
+    }
+
+    fn conflict(&mut self, table: &'static str, key: String, detail: String) {
+        self.conflicts.push(MergeConflict { table, key, detail });
+    }
+}
+
+/// 将一个源数据库合并到 `conn` 指向的目标库
+///
+/// 源数据库必须与当前程序的结构版本一致；整个源数据库在一个事务中合并，
+/// 失败时目标库保持不变。
+pub fn merge_database(conn: &mut SqliteConnection, source_path: &str) -> Result<MergeReport> {
+    with_source(conn, source_path, |conn| merge_attached(conn, source_path))
+}
+
+/// 挂载源数据库并检查结构版本，在一个 IMMEDIATE 事务中执行 `merge`，结束后卸载
+///
+/// SQLite 不允许在事务中 ATTACH，需要与合并一起提交的写入 (如加入转发队列)
+/// 放在 `merge` 中完成。
+pub fn with_source<T>(
+    conn: &mut SqliteConnection,
+    source_path: &str,
+    merge: impl FnOnce(&mut SqliteConnection) -> Result<T>,
+) -> Result<T> {
+    use diesel::sql_query;
+    use diesel::sql_types::Text;
+
+    if !std::path::Path::new(source_path).exists() {
+        return Err(BlackBoxError::not_found("源数据库", source_path));
+    }
+
+    sql_query(format!("ATTACH DATABASE ? AS {}", SOURCE_SCHEMA))
+        .bind::<Text, _>(source_path)
+        .execute(conn)?;
+
+    let merged = (|| {
+        let version = get_attached_schema_version(conn, SOURCE_SCHEMA)?;
+        if version != SCHEMA_VERSION {
+            return Err(BlackBoxError::schema(
+                version,
+                SCHEMA_VERSION,
+                format!(
+                    "源数据库 {} 的结构版本为 v{} (需要 v{})，请先对其执行 migrate",
+                    source_path, version, SCHEMA_VERSION
+                ),
+            ));
+        }
+
+        conn.immediate_transaction(merge)
+    })();
+
+    sql_query(format!("DETACH DATABASE {}", SOURCE_SCHEMA)).execute(conn)?;
+    merged
+}
+
+/// 把 [`with_source`] 挂载的源数据库逐表合并到目标库，调用方负责事务
+///
+/// `source` 只用于合并报告中标识源数据库。
+pub fn merge_attached(conn: &mut SqliteConnection, source: &str) -> Result<MergeReport> {
+    let mut report = MergeReport::new(source);
+
+    merge_servers(conn, &mut report)?;
+    merge_server_labels(conn, &mut report)?;
+    merge_system_metrics(conn, &mut report)?;
+    let pid_map = merge_processes(conn, &mut report)?;
+    merge_process_trends(conn, &mut report, &pid_map)?;
+    merge_threads(conn, &mut report, &pid_map)?;
+    let crash_log_map = merge_crash_logs(conn, &mut report)?;
+    merge_ai_recommendations(conn, &mut report, &crash_log_map)?;
+    merge_crash_events(conn, &mut report, &crash_log_map)?;
+
+    Ok(report)
+}
+
+fn merge_servers(conn: &mut SqliteConnection, report: &mut MergeReport) -> Result<()> {
+    for source in load_attached_rows::<Server>(conn, SOURCE_SCHEMA, "servers")? {
+        match get_server_by_id(conn, &source.server_id)? {
+            Some(target) => {
+                let mut differences = Vec::new();
+                if target.server_name != source.server_name {
+                    differences.push(format!(
+                        "名称 {} / {}",
+                        target.server_name, source.server_name
+                    ));
+                }
+                if target.server_ip != source.server_ip {
+                    differences.push(format!("IP {} / {}", target.server_ip, source.server_ip));
+                }
+                if target.server_os != source.server_os {
+                    differences.push(format!("系统 {} / {}", target.server_os, source.server_os));
+                }
+                if !differences.is_empty() {
+                    report.conflict(
+                        "servers",
+                        source.server_id.clone(),
+                        format!("{} (保留目标库的值)", differences.join("，")),
+                    );
+                }
+
+                if target.server_status == source.server_status {
+                    report.stats("servers").unchanged += 1;
+                } else {
+                    update_server_status(conn, &source.server_id, &source.server_status)?;
+                    report.stats("servers").updated += 1;
+                }
+            }
+            None => {
+                create_server(
+                    conn,
+                    &NewServer {
+                        server_id: source.server_id,
+                        server_name: source.server_name,
+                        server_ip: source.server_ip,
+                        server_os: source.server_os,
+                        server_status: source.server_status,
+                    },
+                )?;
+                report.stats("servers").inserted += 1;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn merge_server_labels(conn: &mut SqliteConnection, report: &mut MergeReport) -> Result<()> {
+    for source in load_attached_rows::<ServerLabel>(conn, SOURCE_SCHEMA, "server_labels")? {
+        let existing = get_server_labels(conn, Some(&source.server_id))?
+            .into_iter()
+            .find(|l| l.label_key == source.label_key);
+
+        match existing {
+            Some(target) if target.label_value == source.label_value => {
+                report.stats("server_labels").unchanged += 1;
+                continue;
+            }
+            Some(target) => {
+                report.conflict(
+                    "server_labels",
+                    format!("{}:{}", source.server_id, source.label_key),
+                    format!("{} -> {}", target.label_value, source.label_value),
+                );
+                report.stats("server_labels").updated += 1;
+            }
+            None => report.stats("server_labels").inserted += 1,
+        }
+
+        set_server_label(
+            conn,
+            &source.server_id,
+            &source.label_key,
+            &source.label_value,
+        )?;
+    }
+
+    Ok(())
+}
+
+fn merge_system_metrics(conn: &mut SqliteConnection, report: &mut MergeReport) -> Result<()> {
+    for source in load_attached_rows::<SystemMetric>(conn, SOURCE_SCHEMA, "system_metrics")? {
+        let new_metric = NewSystemMetric {
+            server_id: source.server_id.clone(),
+            timestamp: source.timestamp,
+            cpu_usage: source.cpu_usage,
+            memory_usage: source.memory_usage,
+            disk_usage: source.disk_usage,
+            io_read: source.io_read,
+            io_write: source.io_write,
+            network_in: source.network_in,
+            network_out: source.network_out,
+        };
+
+        match get_system_metric_by_timestamp(conn, &source.server_id, source.timestamp)? {
+            Some(target) => {
+                let same = (
+                    target.cpu_usage,
+                    target.memory_usage,
+                    target.disk_usage,
+                    target.io_read,
+                    target.io_write,
+                    target.network_in,
+                    target.network_out,
+                ) == (
+                    source.cpu_usage,
+                    source.memory_usage,
+                    source.disk_usage,
+                    source.io_read,
+                    source.io_write,
+                    source.network_in,
+                    source.network_out,
+                );
+                if same {
+                    report.stats("system_metrics").unchanged += 1;
+                } else {
+                    report.conflict(
+                        "system_metrics",
+                        format!("{}@{}", source.server_id, source.timestamp),
+                        "同一时间戳的指标取值不同 (采用源数据库的值)".to_string(),
+                    );
+                    update_system_metric(conn, &source.server_id, source.timestamp, &new_metric)?;
+                    report.stats("system_metrics").updated += 1;
+                }
+            }
+            None => {
+                create_system_metric(conn, &new_metric)?;
+                report.stats("system_metrics").inserted += 1;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// 返回 (server_id, 源 pid) -> 目标 pid 的映射
+fn merge_processes(
+    conn: &mut SqliteConnection,
+    report: &mut MergeReport,
+) -> Result<HashMap<(String, i32), i32>> {
+    let mut pid_map = HashMap::new();
+
+    for source in load_attached_rows::<Process>(conn, SOURCE_SCHEMA, "processes")? {
+        let target_pid = match get_process_by_name_and_user(
+            conn,
+            &source.server_id,
+            &source.name,
+            &source.user_name,
+        )? {
+            Some(target) => {
+                if target.pid != source.pid {
+                    report.conflict(
+                        "processes",
+                        format!("{}:{}:{}", source.server_id, source.name, source.user_name),
+                        format!(
+                            "pid {} / {} (趋势和线程已映射到目标库的 pid)",
+                            target.pid, source.pid
+                        ),
+                    );
+                }
+
+                if target.status == source.status {
+                    report.stats("processes").unchanged += 1;
+                } else {
+                    update_process_status(conn, target.id, &source.status)?;
+                    report.stats("processes").updated += 1;
+                }
+                target.pid
+            }
+            None => {
+                create_process(
+                    conn,
+                    &NewProcess {
+                        server_id: source.server_id.clone(),
+                        pid: source.pid,
+                        name: source.name.clone(),
+                        user_name: source.user_name.clone(),
+                        status: source.status.clone(),
+                    },
+                )?;
+                report.stats("processes").inserted += 1;
+                source.pid
+            }
+        };
+
+        pid_map.insert((source.server_id, source.pid), target_pid);
+    }
+
+    Ok(pid_map)
+}
+
+fn merge_process_trends(
+    conn: &mut SqliteConnection,
+    report: &mut MergeReport,
+    pid_map: &HashMap<(String, i32), i32>,
+) -> Result<()> {
+    for source in load_attached_rows::<ProcessTrend>(conn, SOURCE_SCHEMA, "process_trends")? {
+        let target_pid = pid_map
+            .get(&(source.server_id.clone(), source.pid))
+            .copied()
+            .unwrap_or(source.pid);
+        let new_trend = NewProcessTrend {
+            server_id: source.server_id.clone(),
+            pid: target_pid,
+            timestamp: source.timestamp,
+            cpu_usage: source.cpu_usage,
+            memory_usage: source.memory_usage,
+            thread_count: source.thread_count,
+        };
+
+        match get_process_trend_by_timestamp(conn, &source.server_id, target_pid, source.timestamp)?
+        {
+            Some(target) => {
+                let same = (target.cpu_usage, target.memory_usage, target.thread_count)
+                    == (source.cpu_usage, source.memory_usage, source.thread_count);
+                if same {
+                    report.stats("process_trends").unchanged += 1;
+                } else {
+                    report.conflict(
+                        "process_trends",
+                        format!("{}:{}@{}", source.server_id, target_pid, source.timestamp),
+                        "同一时间戳的趋势取值不同 (采用源数据库的值)".to_string(),
+                    );
+                    update_process_trend(conn, target.id, &new_trend)?;
+                    report.stats("process_trends").updated += 1;
+                }
+            }
+            None => {
+                create_process_trend(conn, &new_trend)?;
+                report.stats("process_trends").inserted += 1;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// 与智能插入一致，源数据库中有线程数据的进程整体替换目标库中的线程
+fn merge_threads(
+    conn: &mut SqliteConnection,
+    report: &mut MergeReport,
+    pid_map: &HashMap<(String, i32), i32>,
+) -> Result<()> {
+    let mut by_process: BTreeMap<(String, i32), Vec<Thread>> = BTreeMap::new();
+    for thread in load_attached_rows::<Thread>(conn, SOURCE_SCHEMA, "threads")? {
+        let target_pid = pid_map
+            .get(&(thread.server_id.clone(), thread.pid))
+            .copied()
+            .unwrap_or(thread.pid);
+        by_process
+            .entry((thread.server_id.clone(), target_pid))
+            .or_default()
+            .push(thread);
+    }
+
+    for ((server_id, target_pid), threads) in by_process {
+        let existing = get_threads_by_process(conn, &server_id, target_pid)?;
+        if existing
+            .iter()
+            .map(thread_values)
+            .eq(threads.iter().map(thread_values))
+        {
+            report.stats("threads").unchanged += threads.len();
+            continue;
+        } else if !existing.is_empty() {
+            report.stats("threads").updated += threads.len();
+        } else {
+            report.stats("threads").inserted += threads.len();
+        }
+
+        delete_threads_by_process(conn, &server_id, target_pid)?;
+        for thread in threads {
+            create_thread(
+                conn,
+                &NewThread {
+                    server_id: server_id.clone(),
+                    pid: target_pid,
+                    thread_id: thread.thread_id,
+                    user_name: thread.user_name,
+                    priority: thread.priority,
+                    nice_value: thread.nice_value,
+                    virtual_memory: thread.virtual_memory,
+                    resident_memory: thread.resident_memory,
+                    shared_memory: thread.shared_memory,
+                    status: thread.status,
+                    cpu_usage: thread.cpu_usage,
+                    memory_usage: thread.memory_usage,
+                    runtime: thread.runtime,
+                    command: thread.command,
+                },
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// 线程除 id、pid 和创建时间外的全部列
+type ThreadValues<'a> = (
+    i32,
+    &'a str,
+    i32,
+    i32,
+    &'a str,
+    &'a str,
+    &'a str,
+    &'a str,
+    &'a str,
+    &'a str,
+    &'a str,
+    &'a str,
+);
+
+/// 用于判断线程数据是否相同
+fn thread_values(t: &Thread) -> ThreadValues<'_> {
+    (
+        t.thread_id,
+        &t.user_name,
+        t.priority,
+        t.nice_value,
+        &t.virtual_memory,
+        &t.resident_memory,
+        &t.shared_memory,
+        t.status.as_str(),
+        &t.cpu_usage,
+        &t.memory_usage,
+        &t.runtime,
+        &t.command,
+    )
+}
+
+/// 返回源崩溃日志 id -> 目标崩溃日志 id 的映射
+fn merge_crash_logs(
+    conn: &mut SqliteConnection,
+    report: &mut MergeReport,
+) -> Result<HashMap<i32, i32>> {
+    let mut crash_log_map = HashMap::new();
+
+    for source in load_attached_rows::<CrashLog>(conn, SOURCE_SCHEMA, "crash_logs")? {
+        let new_log = NewCrashLog {
+            server_id: source.server_id.clone(),
+            log_id: source.log_id,
+            timestamp: source.timestamp,
+            crash_type: source.crash_type.clone(),
+            severity: source.severity.clone(),
+            title: source.title.clone(),
+            message: source.message.clone(),
+            stack_trace: source.stack_trace.clone(),
+            resolved: source.resolved,
+            ai_summary: source.ai_summary.clone(),
+            ai_analysis: source.ai_analysis.clone(),
+        };
+
+        let target_id = match get_crash_log_by_timestamp(conn, &source.server_id, source.timestamp)?
+        {
+            Some(target) => {
+                // 与重复上报相同：保留解决状态和已生成的分析
+                let merged = analyze::keep_existing(&target, new_log);
+                let same = (
+                    target.log_id,
+                    &target.crash_type,
+                    &target.severity,
+                    &target.title,
+                    &target.message,
+                    &target.stack_trace,
+                    &target.ai_summary,
+                    &target.ai_analysis,
+                ) == (
+                    merged.log_id,
+                    &merged.crash_type,
+                    &merged.severity,
+                    &merged.title,
+                    &merged.message,
+                    &merged.stack_trace,
+                    &merged.ai_summary,
+                    &merged.ai_analysis,
+                );
+                if same {
+                    report.stats("crash_logs").unchanged += 1;
+                } else {
+                    report.conflict(
+                        "crash_logs",
+                        format!("{}@{}", source.server_id, source.timestamp),
+                        format!(
+                            "崩溃日志 {} / {} 内容不同 (采用源数据库的值，保留解决状态和已有分析)",
+                            target.log_id, source.log_id
+                        ),
+                    );
+                    update_crash_log(conn, target.id, &merged)?;
+                    report.stats("crash_logs").updated += 1;
+                }
+                target.id
+            }
+            None => {
+                report.stats("crash_logs").inserted += 1;
+                create_crash_log(conn, &new_log)?
+            }
+        };
+
+        crash_log_map.insert(source.id, target_id);
+    }
+
+    Ok(crash_log_map)
+}
+
+/// 按重映射后的崩溃日志 id 写入，目标库中已有的相同建议不会重复添加
+///
+/// 源库中已执行而目标库中未执行的相同建议，沿用源库的执行时间和结果。
+fn merge_ai_recommendations(
+    conn: &mut SqliteConnection,
+    report: &mut MergeReport,
+    crash_log_map: &HashMap<i32, i32>,
+) -> Result<()> {
+    use crate::schema::ai_recommendations;
+
+    for source in load_attached_rows::<AiRecommendation>(conn, SOURCE_SCHEMA, "ai_recommendations")?
+    {
+        let Some(&crash_log_id) = crash_log_map.get(&source.crash_log_id) else {
+            continue;
+        };
+
+        let existing = get_recommendations_by_crash_log(conn, crash_log_id)?
+            .into_iter()
+            .find(|r| {
+                r.priority == source.priority
+                    && r.action == source.action
+                    && r.command == source.command
+            });
+        match existing {
+            Some(target) if source.applied && !target.applied => {
+                diesel::update(ai_recommendations::table.find(target.id))
+                    .set((
+                        ai_recommendations::applied.eq(true),
+                        ai_recommendations::applied_at.eq(source.applied_at),
+                        ai_recommendations::outcome.eq(&source.outcome),
+                        ai_recommendations::updated_at.eq(db_now()),
+                    ))
+                    .execute(conn)?;
+                report.stats("ai_recommendations").updated += 1;
+            }
+            Some(_) => report.stats("ai_recommendations").unchanged += 1,
+            None => {
+                diesel::insert_into(ai_recommendations::table)
+                    .values((
+                        ai_recommendations::crash_log_id.eq(crash_log_id),
+                        ai_recommendations::priority.eq(source.priority),
+                        ai_recommendations::action.eq(&source.action),
+                        ai_recommendations::command.eq(&source.command),
+                        ai_recommendations::applied.eq(source.applied),
+                        ai_recommendations::applied_at.eq(source.applied_at),
+                        ai_recommendations::outcome.eq(&source.outcome),
+                    ))
+                    .execute(conn)?;
+                report.stats("ai_recommendations").inserted += 1;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// 按重映射后的崩溃日志 id 写入，保留原操作时间；目标库中已有的相同记录不会重复添加
+///
+/// 写入了解决或重新打开记录的崩溃日志，按合并后最新的一条更新解决状态。
+fn merge_crash_events(
+    conn: &mut SqliteConnection,
+    report: &mut MergeReport,
+    crash_log_map: &HashMap<i32, i32>,
+) -> Result<()> {
+    use crate::schema::{crash_events, crash_logs};
+
+    let mut resolved_changes = BTreeSet::new();
+    for source in load_attached_rows::<CrashEvent>(conn, SOURCE_SCHEMA, "crash_events")? {
+        let Some(&crash_log_id) = crash_log_map.get(&source.crash_log_id) else {
+            continue;
+        };
+
+        let existing: Vec<CrashEvent> = crash_events::table
+            .filter(crash_events::crash_log_id.eq(crash_log_id))
+            .load(conn)?;
+        let exists = existing.iter().any(|e| {
+            (&e.action, &e.actor, &e.note, e.created_at)
+                == (
+                    &source.action,
+                    &source.actor,
+                    &source.note,
+                    source.created_at,
+                )
+        });
+        if exists {
+            report.stats("crash_events").unchanged += 1;
+            continue;
+        }
+
+        diesel::insert_into(crash_events::table)
+            .values((
+                crash_events::crash_log_id.eq(crash_log_id),
+                crash_events::action.eq(&source.action),
+                crash_events::actor.eq(&source.actor),
+                crash_events::note.eq(&source.note),
+                crash_events::created_at.eq(source.created_at),
+            ))
+            .execute(conn)?;
+        report.stats("crash_events").inserted += 1;
+
+        if matches!(source.action, CrashAction::Resolved | CrashAction::Reopened) {
+            resolved_changes.insert(crash_log_id);
+        }
+    }
+
+    for crash_log_id in resolved_changes {
+        let latest: CrashEvent = crash_events::table
+            .filter(crash_events::crash_log_id.eq(crash_log_id))
+            .filter(crash_events::action.eq_any([CrashAction::Resolved, CrashAction::Reopened]))
+            .order((crash_events::created_at.desc(), crash_events::id.desc()))
+            .first(conn)?;
+        let resolved = latest.action == CrashAction::Resolved;
+        diesel::update(
+            crash_logs::table
+                .find(crash_log_id)
+                .filter(crash_logs::resolved.ne(resolved)),
+        )
+        .set((
+            crash_logs::resolved.eq(resolved),
+            crash_logs::updated_at.eq(db_now()),
+        ))
+        .execute(conn)?;
+    }
+
+    Ok(())
+}
@@ -1,8 +1,13 @@
+use chrono::NaiveDateTime;
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
-use chrono::NaiveDateTime;
+use std::collections::BTreeMap;
 
-#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+use crate::domain::*;
+
+#[derive(
+    Queryable, QueryableByName, Selectable, Insertable, Serialize, Deserialize, Debug, Clone,
+)]
 #[diesel(table_name = crate::schema::servers)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
 pub struct Server {
@@ -11,7 +16,7 @@ pub struct Server {
     pub server_name: String,
     pub server_ip: String,
     pub server_os: String,
-    pub server_status: String,
+    pub server_status: ServerStatus,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
 }
@@ -24,10 +29,12 @@ pub struct NewServer {
     pub server_name: String,
     pub server_ip: String,
     pub server_os: String,
-    pub server_status: String,
+    pub server_status: ServerStatus,
 }
 
-#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[derive(
+    Queryable, QueryableByName, Selectable, Insertable, Serialize, Deserialize, Debug, Clone,
+)]
 #[diesel(table_name = crate::schema::system_metrics)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
 pub struct SystemMetric {
@@ -42,9 +49,10 @@ pub struct SystemMetric {
     pub network_in: f32,
     pub network_out: f32,
     pub created_at: NaiveDateTime,
+    pub updated_at: Option<NaiveDateTime>,
 }
 
-#[derive(Insertable, Serialize, Deserialize, Debug)]
+#[derive(Insertable, Serialize, Deserialize, Debug, Clone)]
 #[diesel(table_name = crate::schema::system_metrics)]
 #[serde(rename_all = "camelCase")]
 pub struct NewSystemMetric {
@@ -60,7 +68,9 @@ pub struct NewSystemMetric {
 }
 
 // 进程模型
-#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[derive(
+    Queryable, QueryableByName, Selectable, Insertable, Serialize, Deserialize, Debug, Clone,
+)]
 #[diesel(table_name = crate::schema::processes)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
 pub struct Process {
@@ -69,7 +79,7 @@ pub struct Process {
     pub pid: i32,
     pub name: String,
     pub user_name: String,
-    pub status: String,
+    pub status: ProcessState,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
 }
@@ -82,11 +92,13 @@ pub struct NewProcess {
     pub pid: i32,
     pub name: String,
     pub user_name: String,
-    pub status: String,
+    pub status: ProcessState,
 }
 
 // 进程趋势模型
-#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[derive(
+    Queryable, QueryableByName, Selectable, Insertable, Serialize, Deserialize, Debug, Clone,
+)]
 #[diesel(table_name = crate::schema::process_trends)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
 pub struct ProcessTrend {
@@ -98,6 +110,7 @@ pub struct ProcessTrend {
     pub memory_usage: f32,
     pub thread_count: i32,
     pub created_at: NaiveDateTime,
+    pub updated_at: Option<NaiveDateTime>,
 }
 
 #[derive(Insertable, Serialize, Deserialize, Debug)]
@@ -113,7 +126,9 @@ pub struct NewProcessTrend {
 }
 
 // 线程模型
-#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[derive(
+    Queryable, QueryableByName, Selectable, Insertable, Serialize, Deserialize, Debug, Clone,
+)]
 #[diesel(table_name = crate::schema::threads)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
 pub struct Thread {
@@ -127,7 +142,7 @@ pub struct Thread {
     pub virtual_memory: String,
     pub resident_memory: String,
     pub shared_memory: String,
-    pub status: String,
+    pub status: ProcessState,
     pub cpu_usage: String,
     pub memory_usage: String,
     pub runtime: String,
@@ -148,7 +163,7 @@ pub struct NewThread {
     pub virtual_memory: String,
     pub resident_memory: String,
     pub shared_memory: String,
-    pub status: String,
+    pub status: ProcessState,
     pub cpu_usage: String,
     pub memory_usage: String,
     pub runtime: String,
@@ -156,7 +171,9 @@ pub struct NewThread {
 }
 
 // 崩溃日志模型
-#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[derive(
+    Queryable, QueryableByName, Selectable, Insertable, Serialize, Deserialize, Debug, Clone,
+)]
 #[diesel(table_name = crate::schema::crash_logs)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
 pub struct CrashLog {
@@ -164,8 +181,8 @@ pub struct CrashLog {
     pub server_id: String,
     pub log_id: i64,
     pub timestamp: i64,
-    pub crash_type: String,
-    pub severity: String,
+    pub crash_type: CrashType,
+    pub severity: Severity,
     pub title: String,
     pub message: String,
     pub stack_trace: Option<String>,
@@ -173,6 +190,7 @@ pub struct CrashLog {
     pub ai_summary: Option<String>,
     pub ai_analysis: Option<String>,
     pub created_at: NaiveDateTime,
+    pub updated_at: Option<NaiveDateTime>,
 }
 
 #[derive(Insertable, Serialize, Deserialize, Debug)]
@@ -182,8 +200,8 @@ pub struct NewCrashLog {
     pub server_id: String,
     pub log_id: i64,
     pub timestamp: i64,
-    pub crash_type: String,
-    pub severity: String,
+    pub crash_type: CrashType,
+    pub severity: Severity,
     pub title: String,
     pub message: String,
     pub stack_trace: Option<String>,
@@ -193,7 +211,9 @@ pub struct NewCrashLog {
 }
 
 // AI 建议模型
-#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[derive(
+    Queryable, QueryableByName, Selectable, Insertable, Serialize, Deserialize, Debug, Clone,
+)]
 #[diesel(table_name = crate::schema::ai_recommendations)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
 pub struct AiRecommendation {
@@ -203,6 +223,13 @@ pub struct AiRecommendation {
     pub action: String,
     pub command: String,
     pub created_at: NaiveDateTime,
+    /// 是否已执行
+    #[serde(default)]
+    pub applied: bool,
+    pub applied_at: Option<NaiveDateTime>,
+    /// 执行结果
+    pub outcome: Option<String>,
+    pub updated_at: Option<NaiveDateTime>,
 }
 
 #[derive(Insertable, Serialize, Deserialize, Debug)]
@@ -215,6 +242,150 @@ pub struct NewAiRecommendation {
     pub command: String,
 }
 
+// 崩溃日志处理历史模型
+#[derive(
+    Queryable, QueryableByName, Selectable, Insertable, Serialize, Deserialize, Debug, Clone,
+)]
+#[diesel(table_name = crate::schema::crash_events)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct CrashEvent {
+    pub id: i32,
+    pub crash_log_id: i32,
+    pub action: CrashAction,
+    /// 操作人
+    pub actor: String,
+    pub note: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schema::crash_events)]
+pub struct NewCrashEvent {
+    pub crash_log_id: i32,
+    pub action: CrashAction,
+    pub actor: String,
+    pub note: Option<String>,
+}
+
+#[derive(
+    Queryable, QueryableByName, Selectable, Insertable, Serialize, Deserialize, Debug, Clone,
+)]
+#[diesel(table_name = crate::schema::server_labels)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct ServerLabel {
+    pub id: i32,
+    pub server_id: String,
+    pub label_key: String,
+    pub label_value: String,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Serialize, Deserialize, Debug)]
+#[diesel(table_name = crate::schema::server_labels)]
+#[serde(rename_all = "camelCase")]
+pub struct NewServerLabel {
+    pub server_id: String,
+    pub label_key: String,
+    pub label_value: String,
+}
+
+/// 待转发到中心节点的负载
+#[derive(Queryable, Selectable, Serialize, Debug, Clone)]
+#[diesel(table_name = crate::schema::outbox)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct OutboxEntry {
+    pub id: i32,
+    /// 稳定的记录 id，重试时保持不变，中心节点据此去重
+    pub record_id: String,
+    pub data_type: String,
+    pub payload: String,
+    pub continue_on_error: bool,
+    pub attempts: i32,
+    /// 下一次允许投递的时间 (毫秒时间戳)
+    pub next_attempt_at: i64,
+    pub last_error: Option<String>,
+    pub created_at: NaiveDateTime,
+    /// 被中心节点永久拒绝的时间 (毫秒时间戳)，不为空时不再投递
+    pub dead_at: Option<i64>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schema::outbox)]
+pub struct NewOutboxEntry {
+    pub record_id: String,
+    pub data_type: String,
+    pub payload: String,
+    pub continue_on_error: bool,
+    pub next_attempt_at: i64,
+}
+
+/// 已写入的批次，记录转发负载的记录 id 或插入负载中的幂等键
+#[derive(Queryable, Selectable, Serialize, Debug, Clone)]
+#[diesel(table_name = crate::schema::ingest_ledger)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct LedgerEntry {
+    pub id: i32,
+    pub record_id: String,
+    pub data_type: String,
+    pub success_count: i32,
+    pub updated_count: i32,
+    pub error_count: i32,
+    pub received_at: NaiveDateTime,
+    /// 错误详情 (JSON 数组)
+    pub errors: Option<String>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schema::ingest_ledger)]
+pub struct NewLedgerEntry {
+    pub record_id: String,
+    pub data_type: String,
+    pub success_count: i32,
+    pub updated_count: i32,
+    pub error_count: i32,
+    pub errors: Option<String>,
+}
+
+/// 一次通知投递的记录
+#[derive(Queryable, Selectable, Serialize, Debug, Clone)]
+#[diesel(table_name = crate::schema::notification_log)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct NotificationDelivery {
+    pub id: i32,
+    /// 配置中通知目标的名称
+    pub notifier: String,
+    /// crash 或 alert
+    pub event: String,
+    pub server_id: String,
+    pub crash_log_id: Option<i32>,
+    /// pending、delivered、failed 或 rate_limited
+    pub status: String,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    /// 发送的通知内容 (JSON)
+    pub payload: String,
+    /// 入队时间，投递成功后为投递时间 (毫秒时间戳)
+    pub sent_at: i64,
+    /// pending 状态下一次尝试投递的时间 (毫秒时间戳)
+    pub next_attempt_at: Option<i64>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schema::notification_log)]
+pub struct NewNotificationDelivery {
+    pub notifier: String,
+    pub event: String,
+    pub server_id: String,
+    pub crash_log_id: Option<i32>,
+    pub status: String,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub payload: String,
+    pub sent_at: i64,
+    pub next_attempt_at: Option<i64>,
+}
+
 // JSON 数据结构，用于解析 data.json
 #[derive(Deserialize, Debug)]
 pub struct JsonData {
@@ -228,10 +399,11 @@ pub struct JsonServer {
     pub server_name: String,
     pub server_ip: String,
     pub server_os: String,
-    pub server_status: String,
+    pub server_status: ServerStatus,
     pub system_metrics: Vec<JsonSystemMetric>,
     pub processes: Option<Vec<JsonProcess>>,
     pub crash_logs: Option<Vec<JsonCrashLog>>,
+    pub labels: Option<BTreeMap<String, String>>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -253,7 +425,7 @@ pub struct JsonProcess {
     pub pid: i32,
     pub name: String,
     pub user_name: String,
-    pub status: String,
+    pub status: ProcessState,
     pub trend: Option<Vec<JsonProcessTrend>>,
     pub threads: Option<Vec<JsonThread>>,
 }
@@ -277,7 +449,7 @@ pub struct JsonThread {
     pub virtual_memory: String,
     pub resident_memory: String,
     pub shared_memory: String,
-    pub status: String,
+    pub status: ProcessState,
     pub cpu_usage: String,
     pub memory_usage: String,
     pub runtime: String,
@@ -289,8 +461,8 @@ pub struct JsonThread {
 pub struct JsonCrashLog {
     pub id: i64,
     pub timestamp: i64,
-    pub crash_type: String,
-    pub severity: String,
+    pub crash_type: CrashType,
+    pub severity: Severity,
     pub title: String,
     pub message: String,
     pub stack_trace: String,
@@ -321,7 +493,7 @@ pub struct SmartProcessInsert {
     pub pid: i32,
     pub name: String,
     pub user_name: String,
-    pub status: String,
+    pub status: ProcessState,
     pub timestamp: i64,
     pub trend: Vec<SmartProcessTrend>,
     pub threads: Vec<SmartThread>,
@@ -329,13 +501,27 @@ pub struct SmartProcessInsert {
     pub server_name: Option<String>,
     pub server_ip: Option<String>,
     pub server_os: Option<String>,
-    pub server_status: Option<String>,
+    pub server_status: Option<ServerStatus>,
+}
+
+/// 带幂等键的数组负载：`{"batchId": "...", "data": [...]}`
+///
+/// 各类数组负载 (服务器、系统指标、进程、崩溃日志) 都可以用这种形式提交。
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct InsertBatch<T> {
+    #[serde(default, alias = "idempotencyKey")]
+    pub batch_id: Option<String>,
+    pub data: Vec<T>,
 }
 
 // 组合插入数据结构 - 同时包含进程和系统指标数据
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct CombinedInsertData {
+    /// 幂等键，同一个键重复提交时不再写入
+    #[serde(default, alias = "idempotencyKey")]
+    pub batch_id: Option<String>,
     pub process: Vec<CombinedProcessData>,
     pub metrics: Vec<SmartSystemMetric>,
     pub dmesg: Option<String>,
@@ -348,11 +534,11 @@ pub struct CombinedProcessData {
     pub server_name: String,
     pub server_ip: String,
     pub server_os: String,
-    pub server_status: String,
+    pub server_status: ServerStatus,
     pub pid: i32,
     pub name: String,
     pub user_name: String,
-    pub status: String,
+    pub status: ProcessState,
     pub timestamp: i64,
     pub trend: Vec<SmartProcessTrend>,
     pub threads: Vec<SmartThread>,
@@ -376,7 +562,7 @@ pub struct SmartThread {
     pub virtual_memory: String,
     pub resident_memory: String,
     pub shared_memory: String,
-    pub status: String,
+    pub status: ProcessState,
     pub cpu_usage: String,
     pub memory_usage: String,
     pub runtime: String,
@@ -403,14 +589,41 @@ pub struct SmartCrashLog {
     pub server_id: String,
     pub log_id: i64,
     pub timestamp: i64,
-    pub crash_type: String,
-    pub severity: String,
+    pub crash_type: CrashType,
+    pub severity: Severity,
     pub title: String,
     pub message: String,
     pub stack_trace: Option<String>,
     pub resolved: bool,
     pub ai_summary: Option<String>,
     pub ai_analysis: Option<String>,
+    /// 提供时替换该崩溃日志的全部 AI 建议，未提供时保留已有建议
+    #[serde(default)]
+    pub recommendations: Option<Vec<SmartRecommendation>>,
+}
+
+/// 随崩溃日志提交或手动添加的 AI 建议，未提供优先级时按列表顺序编号
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SmartRecommendation {
+    pub priority: Option<i32>,
+    pub action: String,
+    #[serde(default)]
+    pub command: String,
+}
+/// 行协议写入的进程趋势点 (measurement 为 process_trends)
+#[derive(Debug, Clone)]
+pub struct SmartProcessTrendPoint {
+    pub server_id: String,
+    pub pid: i32,
+    pub name: String,
+    pub user_name: String,
+    /// 未提供时沿用已有进程的状态
+    pub status: Option<ProcessState>,
+    pub timestamp: i64,
+    pub cpu_usage: f32,
+    pub memory_usage: f32,
+    pub thread_count: i32,
 }
 // 导出用的数据结构
 #[derive(Serialize, Debug)]
@@ -418,6 +631,63 @@ pub struct ExportData {
     pub servers: Vec<ExportServer>,
 }
 
+/// 可按需导出的数据类别
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportEntity {
+    /// 系统指标
+    Metrics,
+    /// 进程 (含趋势和线程)
+    Processes,
+    /// 崩溃日志 (含 AI 建议)
+    Crashes,
+}
+
+impl ExportEntity {
+    pub const ALL: [ExportEntity; 3] = [
+        ExportEntity::Metrics,
+        ExportEntity::Processes,
+        ExportEntity::Crashes,
+    ];
+}
+
+/// 导出筛选条件，默认值表示导出全部数据
+#[derive(Debug, Clone)]
+pub struct ExportFilter {
+    /// 只导出这些服务器，为空时不限制
+    pub server_ids: Vec<String>,
+    /// 服务器必须同时具有的标签 (键, 值)
+    pub labels: Vec<(String, String)>,
+    /// 时间范围下限 (毫秒时间戳，包含)
+    pub from: Option<i64>,
+    /// 时间范围上限 (毫秒时间戳，包含)
+    pub to: Option<i64>,
+    /// 要导出的数据类别
+    pub include: Vec<ExportEntity>,
+}
+
+impl Default for ExportFilter {
+    fn default() -> Self {
+        Self {
+            server_ids: Vec::new(),
+            labels: Vec::new(),
+            from: None,
+            to: None,
+            include: ExportEntity::ALL.to_vec(),
+        }
+    }
+}
+
+impl ExportFilter {
+    pub fn includes(&self, entity: ExportEntity) -> bool {
+        self.include.contains(&entity)
+    }
+
+    /// 时间戳是否落在 [from, to] 范围内
+    pub fn in_range(&self, timestamp: i64) -> bool {
+        self.from.is_none_or(|from| timestamp >= from) && self.to.is_none_or(|to| timestamp <= to)
+    }
+}
+
 #[derive(Serialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct ExportServer {
@@ -425,7 +695,9 @@ pub struct ExportServer {
     pub server_name: String,
     pub server_ip: String,
     pub server_os: String,
-    pub server_status: String,
+    pub server_status: ServerStatus,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
     pub system_metrics: Vec<ExportSystemMetric>,
     pub processes: Vec<ExportProcess>,
     pub crash_logs: Vec<ExportCrashLog>,
@@ -450,7 +722,7 @@ pub struct ExportProcess {
     pub pid: i32,
     pub name: String,
     pub user_name: String,
-    pub status: String,
+    pub status: ProcessState,
     pub trend: Vec<ExportProcessTrend>,
     pub threads: Vec<ExportThread>,
 }
@@ -474,7 +746,7 @@ pub struct ExportThread {
     pub virtual_memory: String,
     pub resident_memory: String,
     pub shared_memory: String,
-    pub status: String,
+    pub status: ProcessState,
     pub cpu_usage: String,
     pub memory_usage: String,
     pub runtime: String,
@@ -486,8 +758,8 @@ pub struct ExportThread {
 pub struct ExportCrashLog {
     pub id: i64,
     pub timestamp: i64,
-    pub crash_type: String,
-    pub severity: String,
+    pub crash_type: CrashType,
+    pub severity: Severity,
     pub title: String,
     pub message: String,
     pub stack_trace: String,
@@ -508,4 +780,87 @@ pub struct ExportRecommendation {
     pub priority: i32,
     pub action: String,
     pub command: String,
-}
\ No newline at end of file
+}
+// 指标暴露 (Prometheus) 用的查询结果
+#[derive(QueryableByName, Debug, Clone)]
+pub struct LatestServerMetric {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub server_id: String,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub server_name: String,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub timestamp: i64,
+    #[diesel(sql_type = diesel::sql_types::Float)]
+    pub cpu_usage: f32,
+    #[diesel(sql_type = diesel::sql_types::Float)]
+    pub memory_usage: f32,
+    #[diesel(sql_type = diesel::sql_types::Float)]
+    pub disk_usage: f32,
+    #[diesel(sql_type = diesel::sql_types::Float)]
+    pub io_read: f32,
+    #[diesel(sql_type = diesel::sql_types::Float)]
+    pub io_write: f32,
+    #[diesel(sql_type = diesel::sql_types::Float)]
+    pub network_in: f32,
+    #[diesel(sql_type = diesel::sql_types::Float)]
+    pub network_out: f32,
+}
+
+#[derive(QueryableByName, Debug, Clone)]
+pub struct LatestProcessTrend {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub server_id: String,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub server_name: String,
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    pub pid: i32,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub name: String,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub user_name: String,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub timestamp: i64,
+    #[diesel(sql_type = diesel::sql_types::Float)]
+    pub cpu_usage: f32,
+    #[diesel(sql_type = diesel::sql_types::Float)]
+    pub memory_usage: f32,
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    pub thread_count: i32,
+}
+
+#[derive(QueryableByName, Debug, Clone)]
+pub struct CrashLogCount {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub server_id: String,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub server_name: String,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub crash_type: CrashType,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub severity: Severity,
+    #[diesel(sql_type = diesel::sql_types::Bool)]
+    pub resolved: bool,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub count: i64,
+}
+
+/// 带进程名和用户的趋势数据，用于行协议导出
+#[derive(QueryableByName, Debug, Clone)]
+pub struct NamedProcessTrend {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub server_id: String,
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    pub pid: i32,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub name: String,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub user_name: String,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub timestamp: i64,
+    #[diesel(sql_type = diesel::sql_types::Float)]
+    pub cpu_usage: f32,
+    #[diesel(sql_type = diesel::sql_types::Float)]
+    pub memory_usage: f32,
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    pub thread_count: i32,
+}
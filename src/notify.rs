@@ -0,0 +1,639 @@
+//! 崩溃和告警通知
+//!
+//! 配置了通知目标 (`[notify]`) 时，[`BlackBox`](crate::BlackBox) 自动注册 [`Notifier`] 订阅者：
+//! - 新建崩溃日志 (包括 dmesg 和线程数检测生成的日志) 时发送 `crash` 通知
+//! - 服务器状态变为 warning 或 offline 时发送 `alert` 通知
+//!
+//! webhook 以 JSON 请求体 POST [`Notification`]；本地命令通过 `BLACKBOX_*` 环境变量接收通知。
+//! 写入事务提交后通知只作为 pending 记录写入 notification_log 表，由后台线程投递，
+//! 失败时按指数退避重试，写入路径不会等待通知目标。同一目标对同一服务器的同类事件
+//! 在限流窗口内只投递一次，被限流跳过的也记入 notification_log 表。
+
+use crate::error::{BlackBoxError, Result};
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use serde::{Deserialize, Serialize};
+use std::process::{Command, Stdio};
+use std::sync::{Mutex, mpsc};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::config::{CommandConfig, NotifyConfig, WebhookConfig};
+use crate::domain::*;
+use crate::events::Subscriber;
+use crate::models::*;
+use crate::services::DatabaseManager;
+
+/// 已入队，等待后台线程投递或重试
+pub const STATUS_PENDING: &str = "pending";
+/// 投递成功
+pub const STATUS_DELIVERED: &str = "delivered";
+/// 重试次数用尽仍然失败
+pub const STATUS_FAILED: &str = "failed";
+/// 处于限流窗口内，没有投递
+pub const STATUS_RATE_LIMITED: &str = "rate_limited";
+
+/// 通知事件
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NotifyEvent {
+    /// 新的崩溃日志
+    Crash,
+    /// 服务器状态变为 warning 或 offline
+    Alert,
+}
+
+impl NotifyEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotifyEvent::Crash => "crash",
+            NotifyEvent::Alert => "alert",
+        }
+    }
+
+    pub fn all() -> Vec<NotifyEvent> {
+        vec![NotifyEvent::Crash, NotifyEvent::Alert]
+    }
+}
+
+/// 发送给通知目标的内容
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Notification {
+    pub event: NotifyEvent,
+    pub server_id: String,
+    pub title: String,
+    pub message: String,
+    /// 事件发生时间 (毫秒时间戳)
+    pub timestamp: i64,
+    /// 以下为崩溃通知的字段
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crash_log_id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crash_type: Option<CrashType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub severity: Option<Severity>,
+    /// 以下为告警通知的字段
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<ServerStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_status: Option<ServerStatus>,
+}
+
+impl Notification {
+    pub fn crash(crash_log: &CrashLog) -> Self {
+        Self {
+            event: NotifyEvent::Crash,
+            server_id: crash_log.server_id.clone(),
+            title: crash_log.title.clone(),
+            message: crash_log.message.clone(),
+            timestamp: crash_log.timestamp,
+            crash_log_id: Some(crash_log.id),
+            crash_type: Some(crash_log.crash_type.clone()),
+            severity: Some(crash_log.severity.clone()),
+            status: None,
+            previous_status: None,
+        }
+    }
+
+    pub fn alert(server: &Server, previous: &ServerStatus) -> Self {
+        Self {
+            event: NotifyEvent::Alert,
+            server_id: server.server_id.clone(),
+            title: format!(
+                "服务器 {} 状态变为 {}",
+                server.server_name, server.server_status
+            ),
+            message: format!(
+                "服务器 {} ({}) 状态由 {} 变为 {}",
+                server.server_id, server.server_ip, previous, server.server_status
+            ),
+            timestamp: chrono::Utc::now().timestamp_millis(),
+            crash_log_id: None,
+            crash_type: None,
+            severity: None,
+            status: Some(server.server_status.clone()),
+            previous_status: Some(previous.clone()),
+        }
+    }
+
+    /// 传给本地命令的环境变量
+    fn env_vars(&self) -> Result<Vec<(&'static str, String)>> {
+        let mut vars = vec![
+            ("BLACKBOX_EVENT", self.event.as_str().to_string()),
+            ("BLACKBOX_SERVER_ID", self.server_id.clone()),
+            ("BLACKBOX_TITLE", self.title.clone()),
+            ("BLACKBOX_MESSAGE", self.message.clone()),
+            ("BLACKBOX_TIMESTAMP", self.timestamp.to_string()),
+            ("BLACKBOX_NOTIFICATION", serde_json::to_string(self)?),
+        ];
+        if let Some(id) = self.crash_log_id {
+            vars.push(("BLACKBOX_CRASH_LOG_ID", id.to_string()));
+        }
+        if let Some(crash_type) = &self.crash_type {
+            vars.push(("BLACKBOX_CRASH_TYPE", crash_type.to_string()));
+        }
+        if let Some(severity) = &self.severity {
+            vars.push(("BLACKBOX_SEVERITY", severity.to_string()));
+        }
+        if let Some(status) = &self.status {
+            vars.push(("BLACKBOX_STATUS", status.to_string()));
+        }
+        if let Some(previous) = &self.previous_status {
+            vars.push(("BLACKBOX_PREVIOUS_STATUS", previous.to_string()));
+        }
+        Ok(vars)
+    }
+}
+
+/// 通知目标
+enum Target<'a> {
+    Webhook(&'a WebhookConfig),
+    Command(&'a CommandConfig),
+}
+
+impl Target<'_> {
+    fn name(&self) -> &str {
+        match self {
+            Target::Webhook(webhook) => &webhook.name,
+            Target::Command(command) => &command.name,
+        }
+    }
+
+    fn accepts(&self, event: NotifyEvent) -> bool {
+        match self {
+            Target::Webhook(webhook) => webhook.events.contains(&event),
+            Target::Command(command) => command.events.contains(&event),
+        }
+    }
+
+    fn deliver(&self, notification: &Notification, timeout: Duration) -> Result<()> {
+        match self {
+            Target::Webhook(webhook) => post_webhook(webhook, notification, timeout),
+            Target::Command(command) => run_command(command, notification, timeout),
+        }
+    }
+}
+
+/// 把插入事件转为通知的订阅者
+///
+/// 写入路径只把通知作为 pending 记录写入 notification_log，由后台线程投递；
+/// 失败时按 next_attempt_at 退避重试，不阻塞写入。`Notifier` 被释放时等待未完成的投递。
+pub struct Notifier {
+    db_manager: DatabaseManager,
+    config: NotifyConfig,
+    worker: Mutex<Option<Worker>>,
+}
+
+/// 后台投递线程
+struct Worker {
+    sender: mpsc::Sender<WorkerMessage>,
+    handle: JoinHandle<()>,
+}
+
+enum WorkerMessage {
+    /// 有新的通知入队
+    Wake,
+    /// 投递完所有 pending 通知 (包括等待重试的) 后回复
+    Flush(mpsc::Sender<()>),
+}
+
+impl Notifier {
+    /// `db_manager` 用于写入投递记录，应指向产生事件的数据库
+    pub fn new(db_manager: DatabaseManager, config: NotifyConfig) -> Self {
+        Self {
+            db_manager,
+            config,
+            worker: Mutex::new(None),
+        }
+    }
+
+    /// 为所有订阅了该事件的目标加入一条待投递的通知，返回各目标的记录
+    ///
+    /// 限流窗口内的重复事件直接记为 rate_limited；其余记为 pending，由后台线程投递。
+    pub fn send(&self, notification: &Notification) -> Result<Vec<NotificationDelivery>> {
+        let payload = serde_json::to_string(notification)?;
+        let mut deliveries = Vec::new();
+        for target in self
+            .targets()
+            .filter(|target| target.accepts(notification.event))
+        {
+            let now = chrono::Utc::now().timestamp_millis();
+            let mut delivery = NewNotificationDelivery {
+                notifier: target.name().to_string(),
+                event: notification.event.as_str().to_string(),
+                server_id: notification.server_id.clone(),
+                crash_log_id: notification.crash_log_id,
+                status: STATUS_PENDING.to_string(),
+                attempts: 0,
+                last_error: None,
+                payload: payload.clone(),
+                sent_at: now,
+                next_attempt_at: Some(now),
+            };
+            if self.is_rate_limited(&delivery)? {
+                delivery.status = STATUS_RATE_LIMITED.to_string();
+                delivery.next_attempt_at = None;
+            }
+            deliveries.push(self.record(&delivery)?);
+        }
+
+        if deliveries
+            .iter()
+            .any(|delivery| delivery.status == STATUS_PENDING)
+        {
+            self.wake();
+        }
+        Ok(deliveries)
+    }
+
+    /// 不论订阅的事件和限流窗口，立即把通知投递给所有目标 (用于测试通知配置)
+    pub fn send_test(&self, notification: &Notification) -> Result<Vec<NotificationDelivery>> {
+        let payload = serde_json::to_string(notification)?;
+        let mut deliveries = Vec::new();
+        for target in self.targets() {
+            let (attempts, error) = self.deliver_with_retry(&target, notification);
+            deliveries.push(
+                self.record(&NewNotificationDelivery {
+                    notifier: target.name().to_string(),
+                    event: notification.event.as_str().to_string(),
+                    server_id: notification.server_id.clone(),
+                    crash_log_id: notification.crash_log_id,
+                    status: if error.is_none() {
+                        STATUS_DELIVERED
+                    } else {
+                        STATUS_FAILED
+                    }
+                    .to_string(),
+                    attempts,
+                    last_error: error,
+                    payload: payload.clone(),
+                    sent_at: chrono::Utc::now().timestamp_millis(),
+                    next_attempt_at: None,
+                })?,
+            );
+        }
+
+        Ok(deliveries)
+    }
+
+    /// 等待后台线程投递完所有 pending 通知，包括失败后等待重试的
+    pub fn flush(&self) {
+        let Ok(worker) = self.worker.lock() else {
+            return;
+        };
+        if let Some(worker) = worker.as_ref() {
+            let (reply, done) = mpsc::channel();
+            if worker.sender.send(WorkerMessage::Flush(reply)).is_ok() {
+                let _ = done.recv();
+            }
+        }
+    }
+
+    /// 通知后台线程有新的通知入队，第一次调用时启动线程
+    fn wake(&self) {
+        let Ok(mut worker) = self.worker.lock() else {
+            return;
+        };
+        if let Some(running) = worker.as_ref()
+            && running.sender.send(WorkerMessage::Wake).is_ok()
+        {
+            return;
+        }
+
+        let db_manager = DatabaseManager::with_options(
+            self.db_manager.get_db_path().clone(),
+            self.db_manager.get_options().clone(),
+        );
+        let config = self.config.clone();
+        let (sender, receiver) = mpsc::channel();
+        let handle = std::thread::spawn(move || run_worker(&db_manager, &config, receiver));
+        *worker = Some(Worker { sender, handle });
+    }
+
+    fn targets(&self) -> impl Iterator<Item = Target<'_>> {
+        targets(&self.config)
+    }
+
+    /// 按退避间隔重试，返回尝试次数和最后一次的错误
+    fn deliver_with_retry(
+        &self,
+        target: &Target<'_>,
+        notification: &Notification,
+    ) -> (i32, Option<String>) {
+        let timeout = Duration::from_secs(self.config.timeout_secs);
+        let mut last_error = None;
+
+        for attempt in 1..=self.config.max_attempts {
+            match target.deliver(notification, timeout) {
+                Ok(()) => return (attempt as i32, None),
+                Err(e) => last_error = Some(e.to_string()),
+            }
+            if attempt < self.config.max_attempts {
+                std::thread::sleep(retry_delay(attempt as i32, &self.config));
+            }
+        }
+
+        (self.config.max_attempts as i32, last_error)
+    }
+
+    /// 限流窗口内是否已向该目标投递 (或待投递) 过同一服务器的同类事件
+    fn is_rate_limited(&self, delivery: &NewNotificationDelivery) -> Result<bool> {
+        use crate::schema::notification_log::dsl::*;
+
+        if self.config.rate_limit_secs == 0 {
+            return Ok(false);
+        }
+
+        let window_start = delivery.sent_at - (self.config.rate_limit_secs as i64) * 1000;
+        let mut conn = self.db_manager.get_connection()?;
+        let recent: i64 = notification_log
+            .filter(notifier.eq(&delivery.notifier))
+            .filter(event.eq(&delivery.event))
+            .filter(server_id.eq(&delivery.server_id))
+            .filter(status.ne(STATUS_RATE_LIMITED))
+            .filter(sent_at.gt(window_start))
+            .count()
+            .get_result(&mut *conn)?;
+
+        Ok(recent > 0)
+    }
+
+    fn record(&self, delivery: &NewNotificationDelivery) -> Result<NotificationDelivery> {
+        use crate::schema::notification_log::dsl::*;
+
+        let mut conn = self.db_manager.get_connection()?;
+        self.db_manager.with_busy_retry(|| {
+            conn.immediate_transaction(|conn| {
+                diesel::insert_into(notification_log)
+                    .values(delivery)
+                    .execute(conn)?;
+                Ok(notification_log
+                    .order(id.desc())
+                    .select(NotificationDelivery::as_select())
+                    .first(conn)?)
+            })
+        })
+    }
+
+    fn send_logged(&self, notification: Notification) {
+        if let Err(e) = self.send(&notification) {
+            eprintln!(
+                "⚠️  通知入队失败 ({} {}): {}",
+                notification.event.as_str(),
+                notification.server_id,
+                e
+            );
+        }
+    }
+}
+
+impl Drop for Notifier {
+    /// 等待未完成的投递，避免命令行进程退出时丢失刚入队的通知
+    fn drop(&mut self) {
+        self.flush();
+        if let Some(worker) = self.worker.get_mut().ok().and_then(Option::take) {
+            drop(worker.sender);
+            let _ = worker.handle.join();
+        }
+    }
+}
+
+impl Subscriber for Notifier {
+    fn on_status_changed(&self, server: &Server, previous: &ServerStatus) {
+        if matches!(
+            server.server_status,
+            ServerStatus::Warning | ServerStatus::Offline
+        ) {
+            self.send_logged(Notification::alert(server, previous));
+        }
+    }
+
+    fn on_crash_detected(&self, crash_log: &CrashLog) {
+        self.send_logged(Notification::crash(crash_log));
+    }
+}
+
+fn targets(config: &NotifyConfig) -> impl Iterator<Item = Target<'_>> {
+    config
+        .webhooks
+        .iter()
+        .map(Target::Webhook)
+        .chain(config.commands.iter().map(Target::Command))
+}
+
+/// 第 `attempts` 次失败后的等待时间
+fn retry_delay(attempts: i32, config: &NotifyConfig) -> Duration {
+    let exponent = attempts.saturating_sub(1).clamp(0, 20) as u32;
+    Duration::from_millis(config.retry_backoff_ms).saturating_mul(1 << exponent)
+}
+
+/// 后台线程：投递到期的通知，然后等到下一条重试到期或有新通知入队
+fn run_worker(
+    db_manager: &DatabaseManager,
+    config: &NotifyConfig,
+    receiver: mpsc::Receiver<WorkerMessage>,
+) {
+    let mut flushes: Vec<mpsc::Sender<()>> = Vec::new();
+    let mut closed = false;
+
+    loop {
+        let next = deliver_due(db_manager, config).unwrap_or_else(|e| {
+            eprintln!("⚠️  通知投递失败: {}", e);
+            None
+        });
+        if next.is_none() {
+            for reply in flushes.drain(..) {
+                let _ = reply.send(());
+            }
+            if closed {
+                return;
+            }
+        }
+
+        let message = match next {
+            Some(at) => {
+                let wait = Duration::from_millis(
+                    (at - chrono::Utc::now().timestamp_millis()).max(0) as u64,
+                );
+                if closed {
+                    std::thread::sleep(wait);
+                    continue;
+                }
+                match receiver.recv_timeout(wait) {
+                    Ok(message) => message,
+                    Err(mpsc::RecvTimeoutError::Timeout) => continue,
+                    Err(mpsc::RecvTimeoutError::Disconnected) => {
+                        closed = true;
+                        continue;
+                    }
+                }
+            }
+            None => match receiver.recv() {
+                Ok(message) => message,
+                Err(_) => return,
+            },
+        };
+        if let WorkerMessage::Flush(reply) = message {
+            flushes.push(reply);
+        }
+    }
+}
+
+/// 按入队顺序投递到期的 pending 通知，返回剩余 pending 通知中最早的重试时间
+fn deliver_due(db_manager: &DatabaseManager, config: &NotifyConfig) -> Result<Option<i64>> {
+    use crate::schema::notification_log::dsl::*;
+
+    let timeout = Duration::from_secs(config.timeout_secs);
+    let mut conn = db_manager.get_connection()?;
+    let due: Vec<NotificationDelivery> = notification_log
+        .filter(status.eq(STATUS_PENDING))
+        .filter(next_attempt_at.le(chrono::Utc::now().timestamp_millis()))
+        .order(id.asc())
+        .select(NotificationDelivery::as_select())
+        .load(&mut *conn)?;
+
+    for delivery in due {
+        // 先占用这条记录：投递期间推迟它的重试时间，其他进程的投递线程不会重复发送
+        let attempt = delivery.attempts + 1;
+        let lease_until =
+            chrono::Utc::now().timestamp_millis() + (config.timeout_secs as i64 + 60) * 1000;
+        let claimed = db_manager.with_busy_retry(|| {
+            Ok(diesel::update(
+                notification_log
+                    .find(delivery.id)
+                    .filter(status.eq(STATUS_PENDING))
+                    .filter(attempts.eq(delivery.attempts)),
+            )
+            .set((attempts.eq(attempt), next_attempt_at.eq(Some(lease_until))))
+            .execute(&mut *conn)?)
+        })?;
+        if claimed == 0 {
+            continue;
+        }
+
+        let result = match targets(config).find(|target| target.name() == delivery.notifier) {
+            Some(target) => serde_json::from_str::<Notification>(&delivery.payload)
+                .map_err(BlackBoxError::from)
+                .and_then(|notification| target.deliver(&notification, timeout)),
+            None => Err(BlackBoxError::validation(format!(
+                "通知目标 {} 已不在配置中",
+                delivery.notifier
+            ))),
+        };
+
+        let now = chrono::Utc::now().timestamp_millis();
+        let (new_status, retry_at, error) = match result {
+            Ok(()) => (STATUS_DELIVERED, None, None),
+            Err(e) if attempt < config.max_attempts as i32 => (
+                STATUS_PENDING,
+                Some(now + retry_delay(attempt, config).as_millis() as i64),
+                Some(e.to_string()),
+            ),
+            Err(e) => (STATUS_FAILED, None, Some(e.to_string())),
+        };
+        db_manager.with_busy_retry(|| {
+            let sent = if new_status == STATUS_DELIVERED {
+                now
+            } else {
+                delivery.sent_at
+            };
+            Ok(diesel::update(notification_log.find(delivery.id))
+                .set((
+                    status.eq(new_status),
+                    next_attempt_at.eq(retry_at),
+                    last_error.eq(&error),
+                    sent_at.eq(sent),
+                ))
+                .execute(&mut *conn)?)
+        })?;
+    }
+
+    Ok(notification_log
+        .filter(status.eq(STATUS_PENDING))
+        .select(diesel::dsl::min(next_attempt_at))
+        .first::<Option<i64>>(&mut *conn)?)
+}
+
+/// 按投递时间倒序返回最近的投递记录
+pub fn recent_deliveries(
+    conn: &mut SqliteConnection,
+    limit: i64,
+) -> Result<Vec<NotificationDelivery>> {
+    use crate::schema::notification_log::dsl::*;
+
+    Ok(notification_log
+        .order((sent_at.desc(), id.desc()))
+        .limit(limit)
+        .select(NotificationDelivery::as_select())
+        .load(conn)?)
+}
+
+fn post_webhook(
+    webhook: &WebhookConfig,
+    notification: &Notification,
+    timeout: Duration,
+) -> Result<()> {
+    let agent = ureq::AgentBuilder::new().timeout(timeout).build();
+    let body = serde_json::to_string(notification)?;
+
+    match agent
+        .post(&webhook.url)
+        .set("Content-Type", "application/json")
+        .send_string(&body)
+    {
+        Ok(_) => Ok(()),
+        Err(ureq::Error::Status(code, response)) => {
+            let body = response.into_string().unwrap_or_default();
+            Err(BlackBoxError::io(
+                &webhook.url,
+                std::io::Error::other(format!("webhook 返回 {}: {}", code, body.trim())),
+            ))
+        }
+        Err(ureq::Error::Transport(e)) => Err(BlackBoxError::io(
+            &webhook.url,
+            std::io::Error::other(format!("无法连接 webhook: {}", e)),
+        )),
+    }
+}
+
+fn run_command(
+    command: &CommandConfig,
+    notification: &Notification,
+    timeout: Duration,
+) -> Result<()> {
+    let mut child = Command::new(&command.program)
+        .args(&command.args)
+        .envs(notification.env_vars()?)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| BlackBoxError::io(&command.program, e))?;
+
+    let started = Instant::now();
+    loop {
+        if let Some(status) = child
+            .try_wait()
+            .map_err(|e| BlackBoxError::io(&command.program, e))?
+        {
+            return if status.success() {
+                Ok(())
+            } else {
+                Err(BlackBoxError::io(
+                    &command.program,
+                    std::io::Error::other(format!("命令退出状态: {}", status)),
+                ))
+            };
+        }
+        if started.elapsed() >= timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(BlackBoxError::io(
+                &command.program,
+                std::io::Error::other(format!("命令执行超过 {} 秒，已终止", timeout.as_secs())),
+            ));
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+}
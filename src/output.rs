@@ -0,0 +1,285 @@
+//! 查询结果的机器可读输出 (JSON / CSV / YAML)
+//!
+//! 表格形式由命令行直接渲染，这里只负责可供脚本和仪表盘消费的格式。
+
+use crate::error::{BlackBoxError, Result};
+use serde::Serialize;
+
+use crate::crash::CrashLogHistory;
+use crate::{
+    AiRecommendation, CrashAction, CrashLog, CrashType, DatabaseStats, NotificationDelivery,
+    ServerDetail, ServerLabel, ServerStatus, Severity,
+};
+
+/// 机器可读的输出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Json,
+    Csv,
+    Yaml,
+}
+
+/// 可以展开为 CSV 行的结果类型
+///
+/// CSV 不支持嵌套结构，每种结果需要给出一个扁平的行类型。
+pub trait CsvRows {
+    type Row: Serialize;
+
+    fn csv_rows(&self) -> Vec<Self::Row>;
+}
+
+/// 按指定格式渲染结果
+pub fn render<T: Serialize + CsvRows>(value: &T, format: OutputFormat) -> Result<String> {
+    let rendered = match format {
+        OutputFormat::Json => serde_json::to_string_pretty(value)?,
+        OutputFormat::Yaml => serde_yaml::to_string(value)?,
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(Vec::new());
+            for row in value.csv_rows() {
+                writer.serialize(row)?;
+            }
+            let bytes = writer
+                .into_inner()
+                .map_err(|e| BlackBoxError::io("CSV", e.into_error()))?;
+            String::from_utf8(bytes).map_err(|e| BlackBoxError::parse("CSV", e.to_string()))?
+        }
+    };
+
+    Ok(rendered.trim_end().to_string())
+}
+
+/// stats 的 CSV 行：每个服务器一行
+#[derive(Serialize, Debug)]
+pub struct ServerStatsRow {
+    pub server_id: String,
+    pub server_name: String,
+    pub server_status: ServerStatus,
+    pub metrics_count: usize,
+    pub processes_count: usize,
+    pub crashes_count: usize,
+    pub latest_metric_time: Option<i64>,
+}
+
+impl CsvRows for DatabaseStats {
+    type Row = ServerStatsRow;
+
+    fn csv_rows(&self) -> Vec<ServerStatsRow> {
+        self.servers
+            .iter()
+            .map(|s| ServerStatsRow {
+                server_id: s.server.server_id.clone(),
+                server_name: s.server.server_name.clone(),
+                server_status: s.server.server_status.clone(),
+                metrics_count: s.metrics_count,
+                processes_count: s.processes_count,
+                crashes_count: s.crashes_count,
+                latest_metric_time: s.latest_metric_time,
+            })
+            .collect()
+    }
+}
+
+/// query 的 CSV 行：每个服务器一行摘要，平均值基于本次返回的系统指标
+#[derive(Serialize, Debug)]
+pub struct ServerDetailRow {
+    pub server_id: String,
+    pub server_name: String,
+    pub server_ip: String,
+    pub server_status: ServerStatus,
+    pub metrics_count: usize,
+    pub latest_metric_time: Option<i64>,
+    pub avg_cpu_usage: Option<f32>,
+    pub avg_memory_usage: Option<f32>,
+    pub avg_disk_usage: Option<f32>,
+    pub processes_count: usize,
+    pub crashes_count: usize,
+    pub unresolved_crashes_count: usize,
+}
+
+impl CsvRows for Vec<ServerDetail> {
+    type Row = ServerDetailRow;
+
+    fn csv_rows(&self) -> Vec<ServerDetailRow> {
+        self.iter()
+            .map(|d| {
+                let average = |f: fn(&crate::SystemMetric) -> f32| {
+                    if d.metrics.is_empty() {
+                        None
+                    } else {
+                        Some(d.metrics.iter().map(f).sum::<f32>() / d.metrics.len() as f32)
+                    }
+                };
+
+                ServerDetailRow {
+                    server_id: d.server.server_id.clone(),
+                    server_name: d.server.server_name.clone(),
+                    server_ip: d.server.server_ip.clone(),
+                    server_status: d.server.server_status.clone(),
+                    metrics_count: d.metrics.len(),
+                    latest_metric_time: d.metrics.iter().map(|m| m.timestamp).max(),
+                    avg_cpu_usage: average(|m| m.cpu_usage),
+                    avg_memory_usage: average(|m| m.memory_usage),
+                    avg_disk_usage: average(|m| m.disk_usage),
+                    processes_count: d.processes.len(),
+                    crashes_count: d.crashes.len(),
+                    unresolved_crashes_count: d
+                        .crashes
+                        .iter()
+                        .filter(|c| !c.crash_log.resolved)
+                        .count(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// crash list 的 CSV 行：每条崩溃日志一行，不含堆栈和 AI 分析
+#[derive(Serialize, Debug)]
+pub struct CrashLogRow {
+    pub id: i32,
+    pub server_id: String,
+    pub timestamp: i64,
+    pub crash_type: CrashType,
+    pub severity: Severity,
+    pub title: String,
+    pub resolved: bool,
+}
+
+impl CsvRows for Vec<CrashLog> {
+    type Row = CrashLogRow;
+
+    fn csv_rows(&self) -> Vec<CrashLogRow> {
+        self.iter()
+            .map(|log| CrashLogRow {
+                id: log.id,
+                server_id: log.server_id.clone(),
+                timestamp: log.timestamp,
+                crash_type: log.crash_type.clone(),
+                severity: log.severity.clone(),
+                title: log.title.clone(),
+                resolved: log.resolved,
+            })
+            .collect()
+    }
+}
+
+/// crash show 的 CSV 行：每条处理历史一行
+#[derive(Serialize, Debug)]
+pub struct CrashEventRow {
+    pub crash_log_id: i32,
+    pub action: CrashAction,
+    pub actor: String,
+    pub note: Option<String>,
+    pub created_at: String,
+}
+
+impl CsvRows for CrashLogHistory {
+    type Row = CrashEventRow;
+
+    fn csv_rows(&self) -> Vec<CrashEventRow> {
+        self.events
+            .iter()
+            .map(|event| CrashEventRow {
+                crash_log_id: event.crash_log_id,
+                action: event.action.clone(),
+                actor: event.actor.clone(),
+                note: event.note.clone(),
+                created_at: event.created_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+            })
+            .collect()
+    }
+}
+
+/// crash recommend list 的 CSV 行：每条建议一行
+#[derive(Serialize, Debug)]
+pub struct RecommendationRow {
+    pub id: i32,
+    pub crash_log_id: i32,
+    pub priority: i32,
+    pub action: String,
+    pub command: String,
+    pub applied: bool,
+    pub applied_at: Option<String>,
+    pub outcome: Option<String>,
+}
+
+impl CsvRows for Vec<AiRecommendation> {
+    type Row = RecommendationRow;
+
+    fn csv_rows(&self) -> Vec<RecommendationRow> {
+        self.iter()
+            .map(|rec| RecommendationRow {
+                id: rec.id,
+                crash_log_id: rec.crash_log_id,
+                priority: rec.priority,
+                action: rec.action.clone(),
+                command: rec.command.clone(),
+                applied: rec.applied,
+                applied_at: rec
+                    .applied_at
+                    .map(|at| at.format("%Y-%m-%d %H:%M:%S").to_string()),
+                outcome: rec.outcome.clone(),
+            })
+            .collect()
+    }
+}
+
+/// label list 的 CSV 行：每个标签一行
+#[derive(Serialize, Debug)]
+pub struct ServerLabelRow {
+    pub server_id: String,
+    pub label_key: String,
+    pub label_value: String,
+    pub updated_at: String,
+}
+
+impl CsvRows for Vec<ServerLabel> {
+    type Row = ServerLabelRow;
+
+    fn csv_rows(&self) -> Vec<ServerLabelRow> {
+        self.iter()
+            .map(|label| ServerLabelRow {
+                server_id: label.server_id.clone(),
+                label_key: label.label_key.clone(),
+                label_value: label.label_value.clone(),
+                updated_at: label.updated_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+            })
+            .collect()
+    }
+}
+
+/// notify log 的 CSV 行：每条投递记录一行，不含通知内容
+#[derive(Serialize, Debug)]
+pub struct NotificationDeliveryRow {
+    pub id: i32,
+    pub notifier: String,
+    pub event: String,
+    pub server_id: String,
+    pub crash_log_id: Option<i32>,
+    pub status: String,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub sent_at: i64,
+    pub next_attempt_at: Option<i64>,
+}
+
+impl CsvRows for Vec<NotificationDelivery> {
+    type Row = NotificationDeliveryRow;
+
+    fn csv_rows(&self) -> Vec<NotificationDeliveryRow> {
+        self.iter()
+            .map(|delivery| NotificationDeliveryRow {
+                id: delivery.id,
+                notifier: delivery.notifier.clone(),
+                event: delivery.event.clone(),
+                server_id: delivery.server_id.clone(),
+                crash_log_id: delivery.crash_log_id,
+                status: delivery.status.clone(),
+                attempts: delivery.attempts,
+                last_error: delivery.last_error.clone(),
+                sent_at: delivery.sent_at,
+                next_attempt_at: delivery.next_attempt_at,
+            })
+            .collect()
+    }
+}
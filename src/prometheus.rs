@@ -0,0 +1,222 @@
+//! Prometheus / OpenMetrics 文本格式的指标暴露
+//!
+//! 每次抓取时从数据库读取最新快照：服务器最新系统指标、进程最新趋势，
+//! 以及按类型、严重性和解决状态分组的崩溃日志数量。
+
+use crate::error::Result;
+use diesel::sqlite::SqliteConnection;
+use std::fmt::{Display, Write};
+
+use crate::database::*;
+use crate::models::*;
+
+/// Prometheus 文本格式 0.0.4 的 Content-Type
+pub const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+/// OpenMetrics 1.0 的 Content-Type
+pub const OPENMETRICS_CONTENT_TYPE: &str =
+    "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// 仪表盘指标定义：(指标名, 说明, 取值函数)
+type GaugeSpec<T> = (&'static str, &'static str, fn(&T) -> f32);
+
+/// 一次抓取所需的全部数据
+#[derive(Debug, Clone)]
+pub struct MetricsSnapshot {
+    pub servers: Vec<Server>,
+    pub system_metrics: Vec<LatestServerMetric>,
+    pub process_trends: Vec<LatestProcessTrend>,
+    pub crash_counts: Vec<CrashLogCount>,
+}
+
+impl MetricsSnapshot {
+    pub fn collect(conn: &mut SqliteConnection) -> Result<Self> {
+        Ok(Self {
+            servers: get_all_servers(conn)?,
+            system_metrics: get_latest_metrics_per_server(conn)?,
+            process_trends: get_latest_trends_per_process(conn)?,
+            crash_counts: count_crash_logs_by_kind(conn)?,
+        })
+    }
+}
+
+/// 渲染为文本格式，`openmetrics` 为 true 时输出 OpenMetrics 格式
+pub fn render(snapshot: &MetricsSnapshot, openmetrics: bool) -> String {
+    let mut out = String::new();
+
+    // OpenMetrics 有专门的 info 类型，族名不带 _info 后缀
+    if openmetrics {
+        family(&mut out, "blackbox_server", "info", "服务器信息");
+    } else {
+        family(
+            &mut out,
+            "blackbox_server_info",
+            "gauge",
+            "服务器信息，值恒为 1",
+        );
+    }
+    for s in &snapshot.servers {
+        sample(
+            &mut out,
+            "blackbox_server_info",
+            &[
+                ("server_id", &s.server_id),
+                ("server_name", &s.server_name),
+                ("server_ip", &s.server_ip),
+                ("server_os", &s.server_os),
+                ("status", s.server_status.as_str()),
+            ],
+            1,
+        );
+    }
+
+    // 服务器最新系统指标
+    let system_gauges: [GaugeSpec<LatestServerMetric>; 7] = [
+        (
+            "blackbox_system_cpu_usage_percent",
+            "最新 CPU 使用率",
+            |m| m.cpu_usage,
+        ),
+        (
+            "blackbox_system_memory_usage_percent",
+            "最新内存使用率",
+            |m| m.memory_usage,
+        ),
+        (
+            "blackbox_system_disk_usage_percent",
+            "最新磁盘使用率",
+            |m| m.disk_usage,
+        ),
+        ("blackbox_system_io_read", "最新磁盘读取速率", |m| {
+            m.io_read
+        }),
+        (
+            "blackbox_system_io_write",
+            "最新磁盘写入速率",
+            |m| m.io_write,
+        ),
+        ("blackbox_system_network_in", "最新网络入流量", |m| {
+            m.network_in
+        }),
+        (
+            "blackbox_system_network_out",
+            "最新网络出流量",
+            |m| m.network_out,
+        ),
+    ];
+    for (name, help, value) in system_gauges {
+        family(&mut out, name, "gauge", help);
+        for m in &snapshot.system_metrics {
+            sample(&mut out, name, &server_labels(m), value(m));
+        }
+    }
+
+    family(
+        &mut out,
+        "blackbox_system_metric_timestamp_seconds",
+        "gauge",
+        "最新系统指标的采集时间 (Unix 秒)",
+    );
+    for m in &snapshot.system_metrics {
+        sample(
+            &mut out,
+            "blackbox_system_metric_timestamp_seconds",
+            &server_labels(m),
+            m.timestamp as f64 / 1000.0,
+        );
+    }
+
+    // 进程最新趋势
+    let process_gauges: [GaugeSpec<LatestProcessTrend>; 3] = [
+        (
+            "blackbox_process_cpu_usage_percent",
+            "进程最新 CPU 使用率",
+            |t| t.cpu_usage,
+        ),
+        (
+            "blackbox_process_memory_usage_percent",
+            "进程最新内存使用率",
+            |t| t.memory_usage,
+        ),
+        ("blackbox_process_threads", "进程最新线程数", |t| {
+            t.thread_count as f32
+        }),
+    ];
+    for (name, help, value) in process_gauges {
+        family(&mut out, name, "gauge", help);
+        for t in &snapshot.process_trends {
+            let pid = t.pid.to_string();
+            sample(
+                &mut out,
+                name,
+                &[
+                    ("server_id", &t.server_id),
+                    ("server_name", &t.server_name),
+                    ("pid", &pid),
+                    ("process", &t.name),
+                    ("user", &t.user_name),
+                ],
+                value(t),
+            );
+        }
+    }
+
+    // OpenMetrics 中计数器的 TYPE 行不带 _total 后缀
+    let crash_family = if openmetrics {
+        "blackbox_crash_logs"
+    } else {
+        "blackbox_crash_logs_total"
+    };
+    family(&mut out, crash_family, "counter", "崩溃日志数量");
+    for c in &snapshot.crash_counts {
+        sample(
+            &mut out,
+            "blackbox_crash_logs_total",
+            &[
+                ("server_id", &c.server_id),
+                ("server_name", &c.server_name),
+                ("crash_type", c.crash_type.as_str()),
+                ("severity", c.severity.as_str()),
+                ("resolved", if c.resolved { "true" } else { "false" }),
+            ],
+            c.count,
+        );
+    }
+
+    if openmetrics {
+        out.push_str("# EOF\n");
+    }
+
+    out
+}
+
+fn server_labels(m: &LatestServerMetric) -> [(&str, &str); 2] {
+    [("server_id", &m.server_id), ("server_name", &m.server_name)]
+}
+
+fn family(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn sample(out: &mut String, name: &str, labels: &[(&str, &str)], value: impl Display) {
+    out.push_str(name);
+    if !labels.is_empty() {
+        out.push('{');
+        for (i, (key, val)) in labels.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let _ = write!(out, "{}=\"{}\"", key, escape_label_value(val));
+        }
+        out.push('}');
+    }
+    let _ = writeln!(out, " {}", value);
+}
+
+/// 标签值中的反斜杠、双引号和换行需要转义
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -26,6 +26,7 @@ diesel::table! {
         network_in -> Float,
         network_out -> Float,
         created_at -> Timestamp,
+        updated_at -> Nullable<Timestamp>,
     }
 }
 
@@ -52,6 +53,7 @@ diesel::table! {
         memory_usage -> Float,
         thread_count -> Integer,
         created_at -> Timestamp,
+        updated_at -> Nullable<Timestamp>,
     }
 }
 
@@ -91,6 +93,7 @@ diesel::table! {
         ai_summary -> Nullable<Text>,
         ai_analysis -> Nullable<Text>,
         created_at -> Timestamp,
+        updated_at -> Nullable<Timestamp>,
     }
 }
 
@@ -102,6 +105,76 @@ diesel::table! {
         action -> Text,
         command -> Text,
         created_at -> Timestamp,
+        applied -> Bool,
+        applied_at -> Nullable<Timestamp>,
+        outcome -> Nullable<Text>,
+        updated_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    server_labels (id) {
+        id -> Integer,
+        server_id -> Text,
+        label_key -> Text,
+        label_value -> Text,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    outbox (id) {
+        id -> Integer,
+        record_id -> Text,
+        data_type -> Text,
+        payload -> Text,
+        continue_on_error -> Bool,
+        attempts -> Integer,
+        next_attempt_at -> BigInt,
+        last_error -> Nullable<Text>,
+        created_at -> Timestamp,
+        dead_at -> Nullable<BigInt>,
+    }
+}
+
+diesel::table! {
+    ingest_ledger (id) {
+        id -> Integer,
+        record_id -> Text,
+        data_type -> Text,
+        success_count -> Integer,
+        updated_count -> Integer,
+        error_count -> Integer,
+        received_at -> Timestamp,
+        errors -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    notification_log (id) {
+        id -> Integer,
+        notifier -> Text,
+        event -> Text,
+        server_id -> Text,
+        crash_log_id -> Nullable<Integer>,
+        status -> Text,
+        attempts -> Integer,
+        last_error -> Nullable<Text>,
+        payload -> Text,
+        sent_at -> BigInt,
+        next_attempt_at -> Nullable<BigInt>,
+    }
+}
+
+diesel::table! {
+    crash_events (id) {
+        id -> Integer,
+        crash_log_id -> Integer,
+        action -> Text,
+        actor -> Text,
+        note -> Nullable<Text>,
+        created_at -> Timestamp,
     }
 }
 
@@ -115,4 +188,9 @@ diesel::allow_tables_to_appear_in_same_query!(
     threads,
     crash_logs,
     ai_recommendations,
-);
\ No newline at end of file
+    server_labels,
+    outbox,
+    ingest_ledger,
+    notification_log,
+    crash_events,
+);
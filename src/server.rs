@@ -0,0 +1,188 @@
+//! 内置 HTTP 服务 (blackbox serve)
+//!
+//! 单线程依次处理请求，数据库访问复用 [`BlackBox`] 的连接池。
+
+use crate::error::{BlackBoxError, Result};
+use std::io::Read;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::BlackBox;
+use crate::forward::{PayloadKind, RECORD_ID_HEADER};
+use crate::influx::Precision;
+use crate::prometheus::{OPENMETRICS_CONTENT_TYPE, PROMETHEUS_CONTENT_TYPE};
+
+/// 写入接口请求体的大小上限 (64 MiB)，超过时返回 413
+pub const MAX_BODY: u64 = 64 * 1024 * 1024;
+
+/// 在指定地址上提供 HTTP 服务，直到进程退出
+pub fn serve(blackbox: &BlackBox, addr: &str) -> Result<()> {
+    let server = Server::http(addr)
+        .map_err(|e| BlackBoxError::io(addr, std::io::Error::other(format!("无法监听: {}", e))))?;
+
+    for mut request in server.incoming_requests() {
+        let response = handle_request(blackbox, &mut request);
+        // 客户端提前断开时无需处理
+        let _ = request.respond(response);
+    }
+
+    Ok(())
+}
+
+fn handle_request(
+    blackbox: &BlackBox,
+    request: &mut Request,
+) -> Response<std::io::Cursor<Vec<u8>>> {
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+
+    match (request.method(), path) {
+        (Method::Get, "/metrics") => {
+            let openmetrics = accepts_openmetrics(request);
+            match blackbox.render_metrics(openmetrics) {
+                Ok(body) => {
+                    let content_type = if openmetrics { OPENMETRICS_CONTENT_TYPE } else { PROMETHEUS_CONTENT_TYPE };
+                    text_response(200, body, content_type)
+                }
+                Err(e) => text_response(500, format!("读取指标失败: {}\n", e), "text/plain; charset=utf-8"),
+            }
+        }
+        // 兼容 InfluxDB v1 / v2 写入接口，Telegraf 可以直接指向这里
+        (Method::Post, "/write") | (Method::Post, "/api/v2/write") => {
+            let body = match read_body(request) {
+                Ok(body) => body,
+                Err(response) => return response,
+            };
+            match write_line_protocol(blackbox, &body, query) {
+                Ok(()) => Response::from_string(String::new()).with_status_code(204),
+                Err(e) => text_response(error_status(&e), format!("{}\n", e), "text/plain; charset=utf-8"),
+            }
+        }
+        // 边缘节点转发的负载 (JSON、CSV、行协议、导入、标签、崩溃日志处理、清理和合并)
+        (Method::Post, path) if path.starts_with("/ingest/") => {
+            let kind = match PayloadKind::parse(&path["/ingest/".len()..]) {
+                Ok(kind) => kind,
+                Err(e) => return text_response(404, format!("{}\n", e), "text/plain; charset=utf-8"),
+            };
+            let body = match read_body(request) {
+                Ok(body) => body,
+                Err(response) => return response,
+            };
+            match ingest_forwarded(blackbox, request, &body, &kind, query) {
+                Ok(body) => text_response(200, body, "application/json"),
+                Err(e) => text_response(error_status(&e), format!("{}\n", e), "text/plain; charset=utf-8"),
+            }
+        }
+        (Method::Get, "/") => text_response(
+            200,
+            "BlackBox 指标服务\n\nGET  /metrics         Prometheus / OpenMetrics 指标\nPOST /write           InfluxDB 行协议写入\nPOST /ingest/<种类>   边缘节点转发写入\n".to_string(),
+            "text/plain; charset=utf-8",
+        ),
+        _ => text_response(404, "Not Found\n".to_string(), "text/plain; charset=utf-8"),
+    }
+}
+
+/// 写入请求体中的行协议，任一行无法解析时整体拒绝
+///
+/// 与 InfluxDB 一致，未指定 precision 参数时时间戳按纳秒处理。
+fn write_line_protocol(blackbox: &BlackBox, body: &str, query: &str) -> Result<()> {
+    let precision = query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == "precision")
+        .map(|(_, value)| Precision::parse(value))
+        .transpose()?
+        .unwrap_or(Precision::Nanoseconds);
+
+    blackbox.ingest_line_protocol(body, precision, false)?;
+    Ok(())
+}
+
+/// 写入转发来的负载，按 Idempotency-Key 请求头去重，返回 JSON 格式的应答
+fn ingest_forwarded(
+    blackbox: &BlackBox,
+    request: &Request,
+    body: &str,
+    kind: &PayloadKind,
+    query: &str,
+) -> Result<String> {
+    let continue_on_error = query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .any(|(key, value)| key == "continue_on_error" && value == "true");
+    let record_id = request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv(RECORD_ID_HEADER))
+        .map(|h| h.value.as_str().to_string());
+
+    let ack =
+        blackbox.ingest_forwarded_payload(kind, body, record_id.as_deref(), continue_on_error)?;
+    Ok(serde_json::to_string(&ack)?)
+}
+
+/// 写入失败时的状态码
+///
+/// 边缘节点把 4xx 视为负载被拒绝并转入死信，因此只有负载本身有问题时返回 4xx；
+/// 数据库繁忙、结构版本不符等中心节点自身的问题返回 5xx，边缘节点会稍后重试。
+fn error_status(error: &BlackBoxError) -> u16 {
+    match error {
+        BlackBoxError::Validation { .. } | BlackBoxError::Parse { .. } => 400,
+        BlackBoxError::NotFound { .. } => 404,
+        BlackBoxError::Conflict { .. } => 409,
+        BlackBoxError::Busy { .. } | BlackBoxError::Schema { .. } => 503,
+        _ => 500,
+    }
+}
+
+/// 读取请求体，最多读取 [`MAX_BODY`] 字节，超过时返回 413 应答
+fn read_body(
+    request: &mut Request,
+) -> std::result::Result<String, Response<std::io::Cursor<Vec<u8>>>> {
+    let too_large = || {
+        text_response(
+            413,
+            format!("请求体超过 {} 字节上限\n", MAX_BODY),
+            "text/plain; charset=utf-8",
+        )
+    };
+
+    // 声明的长度已经超限时不必读取
+    if request
+        .body_length()
+        .is_some_and(|length| length as u64 > MAX_BODY)
+    {
+        return Err(too_large());
+    }
+
+    let mut body = String::new();
+    // 多读一个字节以区分恰好等于上限和超过上限
+    if let Err(e) = Read::take(request.as_reader(), MAX_BODY + 1).read_to_string(&mut body) {
+        return Err(text_response(
+            400,
+            format!("无法读取请求体: {}\n", e),
+            "text/plain; charset=utf-8",
+        ));
+    }
+    if body.len() as u64 > MAX_BODY {
+        return Err(too_large());
+    }
+    Ok(body)
+}
+
+/// 抓取端在 Accept 中声明支持 OpenMetrics 时使用 OpenMetrics 格式
+fn accepts_openmetrics(request: &Request) -> bool {
+    request.headers().iter().any(|h| {
+        h.field.equiv("Accept") && h.value.as_str().contains("application/openmetrics-text")
+    })
+}
+
+fn text_response(
+    status: u16,
+    body: String,
+    content_type: &str,
+) -> Response<std::io::Cursor<Vec<u8>>> {
+    let header = Header::from_bytes("Content-Type", content_type).expect("Content-Type 头合法");
+    Response::from_string(body)
+        .with_status_code(status)
+        .with_header(header)
+}
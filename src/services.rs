@@ -1,20 +1,50 @@
 //! 服务层 - 封装业务逻辑和公共操作
 
-use anyhow::Result;
+use crate::error::{BlackBoxError, Result};
 use diesel::prelude::*;
 use diesel::sqlite::SqliteConnection;
+use serde::{Deserialize, Serialize};
 use std::fs;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
 
+use crate::analyze::{self, AnalysisContext, Analyzer, PENDING_TEXT, RuleAnalyzer};
+use crate::config::{DetectionConfig, RetentionConfig};
 use crate::database::*;
+use crate::domain::*;
+use crate::knowledge::KnowledgeBase;
 use crate::models::*;
-const THREAD_EXCEPTION_THRESHOLD: i32 = 2000;
+use crate::store::Store;
+
+/// 当前数据库结构版本（记录在 `PRAGMA user_version` 中）
+pub const SCHEMA_VERSION: i32 = 11;
+
+/// 所有业务表，按外键依赖顺序排列（父表在前）
+const DATA_TABLES: [&str; 7] = [
+    "servers",
+    "system_metrics",
+    "processes",
+    "process_trends",
+    "threads",
+    "crash_logs",
+    "ai_recommendations",
+];
 
 /// 插入操作结果
-#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
 pub struct InsertResult {
     pub success_count: usize,
     pub updated_count: usize,
     pub error_count: usize,
+    /// 错误详情 (如 CSV 的逐行类型错误)
+    pub errors: Vec<String>,
+}
+
+impl Default for InsertResult {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl InsertResult {
@@ -23,6 +53,7 @@ impl InsertResult {
             success_count: 0,
             updated_count: 0,
             error_count: 0,
+            errors: Vec::new(),
         }
     }
 
@@ -38,21 +69,91 @@ impl InsertResult {
         self.error_count += 1;
     }
 
+    pub fn add_error_message(&mut self, message: String) {
+        self.error_count += 1;
+        self.errors.push(message);
+    }
+
     pub fn merge(&mut self, other: InsertResult) {
         self.success_count += other.success_count;
         self.updated_count += other.updated_count;
         self.error_count += other.error_count;
+        self.errors.extend(other.errors);
+    }
+}
+
+/// 智能插入的参数
+#[derive(Debug, Clone, Default)]
+pub struct InsertOptions {
+    /// 遇到错误时是否继续处理
+    pub continue_on_error: bool,
+    /// 插入前清理旧数据的保留期
+    pub retention: RetentionConfig,
+    /// 组合数据中的崩溃检测规则
+    pub detection: DetectionConfig,
+    /// 新建崩溃日志时匹配的崩溃特征知识库
+    pub knowledge_base: Option<Arc<KnowledgeBase>>,
+}
+
+impl InsertOptions {
+    /// 使用默认保留期和检测规则
+    pub fn new(continue_on_error: bool) -> Self {
+        Self {
+            continue_on_error,
+            ..Default::default()
+        }
     }
 }
 
 /// 数据库连接管理器
+///
+/// 内部维护一个空闲连接池，连接在归还后会被复用，避免每次操作都重新打开文件
+/// 并重复执行 PRAGMA。
 pub struct DatabaseManager {
     db_path: Option<String>,
+    options: ConnectionOptions,
+    idle: Mutex<Vec<SqliteConnection>>,
+}
+
+/// 从 [`DatabaseManager`] 借出的连接，离开作用域时自动归还
+pub struct PooledConnection<'a> {
+    conn: Option<SqliteConnection>,
+    manager: &'a DatabaseManager,
+}
+
+impl Deref for PooledConnection<'_> {
+    type Target = SqliteConnection;
+
+    fn deref(&self) -> &SqliteConnection {
+        self.conn.as_ref().expect("连接已归还")
+    }
+}
+
+impl DerefMut for PooledConnection<'_> {
+    fn deref_mut(&mut self) -> &mut SqliteConnection {
+        self.conn.as_mut().expect("连接已归还")
+    }
+}
+
+impl Drop for PooledConnection<'_> {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            self.manager.release(conn);
+        }
+    }
 }
 
 impl DatabaseManager {
     pub fn new(db_path: Option<String>) -> Self {
-        Self { db_path }
+        Self::with_options(db_path, ConnectionOptions::default())
+    }
+
+    pub fn with_options(db_path: Option<String>, options: ConnectionOptions) -> Self {
+        Self {
+            db_path,
+            options,
+            idle: Mutex::new(Vec::new()),
+        }
     }
 
     /// 获取数据库路径
@@ -60,10 +161,49 @@ impl DatabaseManager {
         &self.db_path
     }
 
-    /// 获取数据库连接
-    pub fn get_connection(&self) -> Result<SqliteConnection> {
-        let db_url = self.build_database_url();
-        establish_connection_with_url(Some(&db_url))
+    /// 获取连接参数
+    pub fn get_options(&self) -> &ConnectionOptions {
+        &self.options
+    }
+
+    /// 获取数据库连接（优先复用空闲连接）
+    pub fn get_connection(&self) -> Result<PooledConnection<'_>> {
+        let reused = self.idle.lock().map(|mut idle| idle.pop()).unwrap_or(None);
+
+        let conn = match reused {
+            Some(conn) => conn,
+            None => {
+                let db_url = self.build_database_url();
+                establish_connection_with_options(Some(&db_url), &self.options)?
+            }
+        };
+
+        Ok(PooledConnection {
+            conn: Some(conn),
+            manager: self,
+        })
+    }
+
+    /// 在数据库被锁时自动重试
+    pub fn with_busy_retry<T>(&self, op: impl FnMut() -> Result<T>) -> Result<T> {
+        with_busy_retry(&self.options, op)
+    }
+
+    /// 关闭所有空闲连接
+    ///
+    /// 数据库文件被删除或替换前必须调用，否则复用的连接仍指向旧文件。
+    pub fn close_idle_connections(&self) {
+        if let Ok(mut idle) = self.idle.lock() {
+            idle.clear();
+        }
+    }
+
+    fn release(&self, conn: SqliteConnection) {
+        if let Ok(mut idle) = self.idle.lock()
+            && idle.len() < self.options.max_idle_connections
+        {
+            idle.push(conn);
+        }
     }
 
     /// 构建数据库 URL
@@ -75,10 +215,19 @@ impl DatabaseManager {
                 format!("sqlite://{}", path)
             }
         } else {
-            "sqlite://./database.db".to_string()
+            format!("sqlite://{}", crate::config::DEFAULT_DB_PATH)
         }
     }
 
+    /// 获取数据库文件在磁盘上的路径（去掉 `sqlite://` 前缀）
+    pub fn get_file_path(&self) -> String {
+        let database_url = self.build_database_url();
+        database_url
+            .strip_prefix("sqlite://")
+            .unwrap_or(&database_url)
+            .to_string()
+    }
+
     /// 获取数据库路径（用于连接）
     pub fn get_db_path_for_connection(&self) -> Option<String> {
         self.db_path.as_ref().map(|path| {
@@ -91,41 +240,415 @@ impl DatabaseManager {
     }
 }
 
+/// 删除数据库文件及其 WAL/SHM 附属文件
+fn remove_database_files(file_path: &str) -> Result<()> {
+    for suffix in ["", "-wal", "-shm"] {
+        let path = format!("{}{}", file_path, suffix);
+        if std::path::Path::new(&path).exists() {
+            fs::remove_file(&path).map_err(|e| BlackBoxError::io(&path, e))?;
+        }
+    }
+    Ok(())
+}
+
 /// 数据库初始化服务
 pub struct DatabaseInitService;
 
 impl DatabaseInitService {
     /// 初始化数据库
     pub fn init_database(db_manager: &DatabaseManager, force: bool) -> Result<()> {
-        let database_url = db_manager.build_database_url();
-
-        // 提取文件路径
-        let file_path = database_url
-            .strip_prefix("sqlite://")
-            .unwrap_or(&database_url);
+        let file_path = db_manager.get_file_path();
 
         // 检查文件是否已存在
-        if std::path::Path::new(file_path).exists() {
+        if std::path::Path::new(&file_path).exists() {
             if !force {
-                return Err(anyhow::anyhow!(
+                return Err(BlackBoxError::conflict(format!(
                     "数据库文件已存在: {}，使用 force=true 强制重新创建",
                     file_path
-                ));
+                )));
             } else {
-                fs::remove_file(file_path)?;
+                db_manager.close_idle_connections();
+                remove_database_files(&file_path)?;
             }
         }
 
         // 创建数据库连接（这会自动创建文件）
         let mut conn = db_manager.get_connection()?;
 
-        // 执行建表 SQL
+        // 执行建表 SQL (v1 结构)，再依次应用后续版本的升级步骤
+        Self::create_tables(&mut conn)?;
+        Self::create_indexes(&mut conn)?;
+        Self::upgrade_from(&mut conn, 1)?;
+
+        Ok(())
+    }
+
+    /// 在内存数据库中构建当前版本的完整结构，用于结构比对
+    pub fn build_reference_schema() -> Result<SqliteConnection> {
+        let mut conn = SqliteConnection::establish(":memory:")?;
         Self::create_tables(&mut conn)?;
         Self::create_indexes(&mut conn)?;
+        Self::upgrade_from(&mut conn, 1)?;
+        Ok(conn)
+    }
+
+    /// 将旧版本数据库升级到当前结构版本
+    ///
+    /// 返回升级前的结构版本号。
+    pub fn migrate_database(db_manager: &DatabaseManager) -> Result<i32> {
+        let mut conn = db_manager.get_connection()?;
+
+        if !table_exists(&mut conn, "servers")? {
+            return Err(BlackBoxError::schema(
+                0,
+                SCHEMA_VERSION,
+                "数据库尚未初始化，请先执行 init",
+            ));
+        }
+
+        let from_version = get_schema_version(&mut conn)?;
+        if from_version > SCHEMA_VERSION {
+            return Err(BlackBoxError::schema(
+                from_version,
+                SCHEMA_VERSION,
+                format!(
+                    "数据库结构版本 {} 高于程序支持的版本 {}",
+                    from_version, SCHEMA_VERSION
+                ),
+            ));
+        }
+
+        if from_version < 1 {
+            Self::migrate_to_v1(&mut conn)?;
+        }
+        Self::upgrade_from(&mut conn, from_version.max(1))?;
+
+        Ok(from_version)
+    }
+
+    /// 从 v1 及以后的版本依次升级到 SCHEMA_VERSION
+    fn upgrade_from(conn: &mut SqliteConnection, version: i32) -> Result<()> {
+        if version < 2 {
+            Self::migrate_to_v2(conn)?;
+        }
+        if version < 3 {
+            Self::migrate_to_v3(conn)?;
+        }
+        if version < 4 {
+            Self::migrate_to_v4(conn)?;
+        }
+        if version < 5 {
+            Self::migrate_to_v5(conn)?;
+        }
+        if version < 6 {
+            Self::migrate_to_v6(conn)?;
+        }
+        if version < 7 {
+            Self::migrate_to_v7(conn)?;
+        }
+        if version < 8 {
+            Self::migrate_to_v8(conn)?;
+        }
+        if version < 9 {
+            Self::migrate_to_v9(conn)?;
+        }
+        if version < 10 {
+            Self::migrate_to_v10(conn)?;
+        }
+        if version < 11 {
+            Self::migrate_to_v11(conn)?;
+        }
 
         Ok(())
     }
 
+    /// v1 -> v2: 为会被原地更新的表增加 updated_at，用于增量导出
+    ///
+    /// SQLite 新增列不能使用 CURRENT_TIMESTAMP 默认值，新列允许为空，
+    /// 只在记录被修改时写入。
+    fn migrate_to_v2(conn: &mut SqliteConnection) -> Result<()> {
+        use diesel::sql_query;
+
+        conn.transaction::<_, BlackBoxError, _>(|conn| {
+            for table in ["system_metrics", "process_trends", "crash_logs"] {
+                sql_query(format!(
+                    "ALTER TABLE {table} ADD COLUMN updated_at TIMESTAMP"
+                ))
+                .execute(conn)?;
+            }
+
+            set_schema_version(conn, 2)?;
+            Ok(())
+        })
+    }
+
+    /// v2 -> v3: 新增服务器标签表，用于按标签筛选服务器
+    fn migrate_to_v3(conn: &mut SqliteConnection) -> Result<()> {
+        use diesel::sql_query;
+
+        conn.transaction::<_, BlackBoxError, _>(|conn| {
+            sql_query(
+                r#"
+                CREATE TABLE server_labels (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    server_id TEXT NOT NULL,
+                    label_key TEXT NOT NULL,
+                    label_value TEXT NOT NULL,
+                    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                    UNIQUE (server_id, label_key),
+                    FOREIGN KEY (server_id) REFERENCES servers(server_id) ON DELETE CASCADE
+                )
+            "#,
+            )
+            .execute(conn)?;
+            sql_query(
+                "CREATE INDEX idx_server_labels_key_value ON server_labels(label_key, label_value)",
+            )
+            .execute(conn)?;
+
+            set_schema_version(conn, 3)?;
+            Ok(())
+        })
+    }
+
+    /// v3 -> v4: 新增转发队列 (边缘节点) 和接收台账 (中心节点)
+    fn migrate_to_v4(conn: &mut SqliteConnection) -> Result<()> {
+        use diesel::sql_query;
+
+        conn.transaction::<_, BlackBoxError, _>(|conn| {
+            sql_query(
+                r#"
+                CREATE TABLE outbox (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    record_id TEXT NOT NULL UNIQUE,
+                    data_type TEXT NOT NULL,
+                    payload TEXT NOT NULL,
+                    continue_on_error BOOLEAN NOT NULL DEFAULT 0,
+                    attempts INTEGER NOT NULL DEFAULT 0,
+                    next_attempt_at BIGINT NOT NULL,
+                    last_error TEXT,
+                    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+                )
+            "#,
+            )
+            .execute(conn)?;
+            sql_query(
+                r#"
+                CREATE TABLE ingest_ledger (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    record_id TEXT NOT NULL UNIQUE,
+                    data_type TEXT NOT NULL,
+                    success_count INTEGER NOT NULL,
+                    updated_count INTEGER NOT NULL,
+                    error_count INTEGER NOT NULL,
+                    received_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+                )
+            "#,
+            )
+            .execute(conn)?;
+
+            set_schema_version(conn, 4)?;
+            Ok(())
+        })
+    }
+
+    /// v4 -> v5: 台账增加错误详情列，重复提交时返回完整的首次结果
+    fn migrate_to_v5(conn: &mut SqliteConnection) -> Result<()> {
+        use diesel::sql_query;
+
+        conn.transaction::<_, BlackBoxError, _>(|conn| {
+            sql_query("ALTER TABLE ingest_ledger ADD COLUMN errors TEXT").execute(conn)?;
+
+            set_schema_version(conn, 5)?;
+            Ok(())
+        })
+    }
+
+    /// v5 -> v6: 新增通知投递记录表
+    fn migrate_to_v6(conn: &mut SqliteConnection) -> Result<()> {
+        use diesel::sql_query;
+
+        conn.transaction::<_, BlackBoxError, _>(|conn| {
+            sql_query(
+                r#"
+                CREATE TABLE notification_log (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    notifier TEXT NOT NULL,
+                    event TEXT NOT NULL,
+                    server_id TEXT NOT NULL,
+                    crash_log_id INTEGER,
+                    status TEXT NOT NULL,
+                    attempts INTEGER NOT NULL DEFAULT 0,
+                    last_error TEXT,
+                    payload TEXT NOT NULL,
+                    sent_at BIGINT NOT NULL
+                )
+            "#,
+            )
+            .execute(conn)?;
+            sql_query("CREATE INDEX idx_notification_log_key ON notification_log(notifier, event, server_id, sent_at)")
+                .execute(conn)?;
+
+            set_schema_version(conn, 6)?;
+            Ok(())
+        })
+    }
+
+    /// v6 -> v7: 新增崩溃日志处理历史表
+    fn migrate_to_v7(conn: &mut SqliteConnection) -> Result<()> {
+        use diesel::sql_query;
+
+        conn.transaction::<_, BlackBoxError, _>(|conn| {
+            sql_query(
+                r#"
+                CREATE TABLE crash_events (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    crash_log_id INTEGER NOT NULL,
+                    action TEXT NOT NULL,
+                    actor TEXT NOT NULL,
+                    note TEXT,
+                    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                    FOREIGN KEY (crash_log_id) REFERENCES crash_logs(id) ON DELETE CASCADE
+                )
+            "#,
+            )
+            .execute(conn)?;
+            sql_query("CREATE INDEX idx_crash_events_crash_log ON crash_events(crash_log_id)")
+                .execute(conn)?;
+
+            set_schema_version(conn, 7)?;
+            Ok(())
+        })
+    }
+
+    /// v7 -> v8: AI 建议增加执行标记、执行时间、结果和 updated_at
+    fn migrate_to_v8(conn: &mut SqliteConnection) -> Result<()> {
+        use diesel::sql_query;
+
+        conn.transaction::<_, BlackBoxError, _>(|conn| {
+            sql_query(
+                "ALTER TABLE ai_recommendations ADD COLUMN applied BOOLEAN NOT NULL DEFAULT 0",
+            )
+            .execute(conn)?;
+            sql_query("ALTER TABLE ai_recommendations ADD COLUMN applied_at TIMESTAMP")
+                .execute(conn)?;
+            sql_query("ALTER TABLE ai_recommendations ADD COLUMN outcome TEXT").execute(conn)?;
+            sql_query("ALTER TABLE ai_recommendations ADD COLUMN updated_at TIMESTAMP")
+                .execute(conn)?;
+
+            set_schema_version(conn, 8)?;
+            Ok(())
+        })
+    }
+
+    /// v8 -> v9: outbox 增加 dead_at，被中心节点永久拒绝的负载不再阻塞队列
+    fn migrate_to_v9(conn: &mut SqliteConnection) -> Result<()> {
+        use diesel::sql_query;
+
+        conn.transaction::<_, BlackBoxError, _>(|conn| {
+            sql_query("ALTER TABLE outbox ADD COLUMN dead_at BIGINT").execute(conn)?;
+
+            set_schema_version(conn, 9)?;
+            Ok(())
+        })
+    }
+
+    /// v9 -> v10: 通知改为入队后由后台线程投递，notification_log 增加 next_attempt_at
+    fn migrate_to_v10(conn: &mut SqliteConnection) -> Result<()> {
+        use diesel::sql_query;
+
+        conn.transaction::<_, BlackBoxError, _>(|conn| {
+            sql_query("ALTER TABLE notification_log ADD COLUMN next_attempt_at BIGINT").execute(conn)?;
+            sql_query("CREATE INDEX idx_notification_log_pending ON notification_log(status, next_attempt_at)")
+                .execute(conn)?;
+
+            set_schema_version(conn, 10)?;
+            Ok(())
+        })
+    }
+
+    /// v10 -> v11: 把旧版本写入的大小写和别名写法改写为规范值
+    ///
+    /// 读取时虽然会规范化，但 SQL 的分组统计和等值过滤直接比较存储的文本。
+    fn migrate_to_v11(conn: &mut SqliteConnection) -> Result<()> {
+        conn.transaction::<_, BlackBoxError, _>(|conn| {
+            normalize_column(conn, "servers", "server_status", |value| {
+                ServerStatus::from(value).to_string()
+            })?;
+            for table in ["processes", "threads"] {
+                normalize_column(conn, table, "status", |value| {
+                    ProcessState::from(value).to_string()
+                })?;
+            }
+            normalize_column(conn, "crash_logs", "crash_type", |value| {
+                CrashType::from(value).to_string()
+            })?;
+            normalize_column(conn, "crash_logs", "severity", |value| {
+                Severity::from(value).to_string()
+            })?;
+
+            set_schema_version(conn, 11)?;
+            Ok(())
+        })
+    }
+
+    /// v0 -> v1: 重建所有表以启用 ON DELETE CASCADE，并丢弃孤儿数据
+    ///
+    /// SQLite 不支持修改已有表的外键定义，只能按官方推荐的流程
+    /// 在关闭外键检查的情况下重建表并拷贝数据。
+    fn migrate_to_v1(conn: &mut SqliteConnection) -> Result<()> {
+        use diesel::sql_query;
+
+        sql_query("PRAGMA foreign_keys = OFF").execute(conn)?;
+
+        let migrated = conn.transaction::<_, BlackBoxError, _>(|conn| {
+            // 旧索引会随表一起改名，先删除以免与新索引重名
+            for index_name in get_user_index_names(conn)? {
+                sql_query(format!("DROP INDEX IF EXISTS \"{}\"", index_name)).execute(conn)?;
+            }
+
+            for table in DATA_TABLES {
+                sql_query(format!("ALTER TABLE {table} RENAME TO {table}_legacy")).execute(conn)?;
+            }
+
+            Self::create_tables(conn)?;
+            Self::create_indexes(conn)?;
+
+            // 按依赖顺序拷贝数据，过滤掉引用不存在记录的孤儿行
+            sql_query("INSERT INTO servers SELECT * FROM servers_legacy").execute(conn)?;
+            for table in [
+                "system_metrics",
+                "processes",
+                "process_trends",
+                "threads",
+                "crash_logs",
+            ] {
+                sql_query(format!(
+                    "INSERT INTO {table} SELECT * FROM {table}_legacy \
+                     WHERE server_id IN (SELECT server_id FROM servers)"
+                ))
+                .execute(conn)?;
+            }
+            sql_query(
+                "INSERT INTO ai_recommendations SELECT * FROM ai_recommendations_legacy \
+                 WHERE crash_log_id IN (SELECT id FROM crash_logs)",
+            )
+            .execute(conn)?;
+
+            for table in DATA_TABLES.iter().rev() {
+                sql_query(format!("DROP TABLE {table}_legacy")).execute(conn)?;
+            }
+
+            set_schema_version(conn, 1)?;
+            Ok(())
+        });
+
+        // 无论迁移是否成功都恢复外键检查
+        sql_query("PRAGMA foreign_keys = ON").execute(conn)?;
+        migrated
+    }
+
     fn create_tables(conn: &mut SqliteConnection) -> Result<()> {
         use diesel::sql_query;
 
@@ -161,7 +684,7 @@ impl DatabaseInitService {
                 network_in REAL NOT NULL,
                 network_out REAL NOT NULL,
                 created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-                FOREIGN KEY (server_id) REFERENCES servers(server_id)
+                FOREIGN KEY (server_id) REFERENCES servers(server_id) ON DELETE CASCADE
             )
         "#,
         )
@@ -179,7 +702,7 @@ impl DatabaseInitService {
                 status TEXT NOT NULL,
                 created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
                 updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-                FOREIGN KEY (server_id) REFERENCES servers(server_id)
+                FOREIGN KEY (server_id) REFERENCES servers(server_id) ON DELETE CASCADE
             )
         "#,
         )
@@ -197,7 +720,7 @@ impl DatabaseInitService {
                 memory_usage REAL NOT NULL,
                 thread_count INTEGER NOT NULL,
                 created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-                FOREIGN KEY (server_id) REFERENCES servers(server_id)
+                FOREIGN KEY (server_id) REFERENCES servers(server_id) ON DELETE CASCADE
             )
         "#,
         )
@@ -223,7 +746,7 @@ impl DatabaseInitService {
                 runtime TEXT NOT NULL,
                 command TEXT NOT NULL,
                 created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-                FOREIGN KEY (server_id) REFERENCES servers(server_id)
+                FOREIGN KEY (server_id) REFERENCES servers(server_id) ON DELETE CASCADE
             )
         "#,
         )
@@ -246,7 +769,7 @@ impl DatabaseInitService {
                 ai_summary TEXT,
                 ai_analysis TEXT,
                 created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-                FOREIGN KEY (server_id) REFERENCES servers(server_id)
+                FOREIGN KEY (server_id) REFERENCES servers(server_id) ON DELETE CASCADE
             )
         "#,
         )
@@ -262,7 +785,7 @@ impl DatabaseInitService {
                 action TEXT NOT NULL,
                 command TEXT NOT NULL,
                 created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-                FOREIGN KEY (crash_log_id) REFERENCES crash_logs(id)
+                FOREIGN KEY (crash_log_id) REFERENCES crash_logs(id) ON DELETE CASCADE
             )
         "#,
         )
@@ -297,23 +820,434 @@ impl DatabaseInitService {
     }
 }
 
+/// 孤儿数据统计（引用了不存在的父记录的行数）
+///
+/// 每张带外键的表一项，按外键依赖顺序排列。
+#[derive(Debug, Clone, Default)]
+pub struct OrphanReport {
+    pub tables: Vec<(String, usize)>,
+}
+
+impl OrphanReport {
+    /// 某张表的孤儿行数
+    pub fn count(&self, table: &str) -> usize {
+        self.tables
+            .iter()
+            .find(|(name, _)| name == table)
+            .map_or(0, |(_, count)| *count)
+    }
+
+    pub fn total(&self) -> usize {
+        self.tables.iter().map(|(_, count)| count).sum()
+    }
+}
+
+/// 数据库诊断报告
+#[derive(Debug, Clone, Default)]
+pub struct DoctorReport {
+    /// integrity_check / quick_check 返回的错误信息（为空表示通过）
+    pub integrity_errors: Vec<String>,
+    pub schema_version: i32,
+    /// 缺失的表
+    pub missing_tables: Vec<String>,
+    /// 缺失的列，格式为 `表名.列名`
+    pub missing_columns: Vec<String>,
+    /// 同一服务器同一时间戳下多余的系统指标行数
+    pub duplicate_metrics: usize,
+    pub orphans: OrphanReport,
+}
+
+impl DoctorReport {
+    pub fn schema_ok(&self) -> bool {
+        self.schema_version == SCHEMA_VERSION
+            && self.missing_tables.is_empty()
+            && self.missing_columns.is_empty()
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.integrity_errors.is_empty()
+            && self.schema_ok()
+            && self.duplicate_metrics == 0
+            && self.orphans.total() == 0
+    }
+}
+
+/// 逻辑修复结果
+#[derive(Debug, Clone, Default)]
+pub struct RepairReport {
+    pub duplicate_metrics_removed: usize,
+    pub orphans_removed: OrphanReport,
+}
+
+/// 数据抢救结果（每张表成功/失败拷贝的行数）
+#[derive(Debug, Clone, Default)]
+pub struct SalvageReport {
+    pub tables: Vec<SalvageTableReport>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SalvageTableReport {
+    pub table: String,
+    pub copied: usize,
+    pub failed: usize,
+}
+
+/// 同一服务器同一时间戳只保留 id 最大的系统指标
+const DUPLICATE_METRIC_RULE: &str =
+    "id NOT IN (SELECT MAX(id) FROM system_metrics GROUP BY server_id, timestamp)";
+
+/// 数据完整性检查服务
+pub struct IntegrityService;
+
+impl IntegrityService {
+    /// 孤儿数据判定规则：(表名, WHERE 条件)，按外键依赖顺序排列
+    ///
+    /// 由当前版本结构中的外键生成：引用的父记录不存在，或父记录本身是孤儿数据。
+    fn orphan_rules() -> Result<Vec<(String, String)>> {
+        let mut reference = DatabaseInitService::build_reference_schema()?;
+
+        let mut rules: Vec<(String, String)> = Vec::new();
+        for (table, foreign_keys) in get_tables_by_dependency(&mut reference)? {
+            if foreign_keys.is_empty() {
+                continue;
+            }
+            let predicate = foreign_keys
+                .iter()
+                .map(|fk| {
+                    let parent_rule = rules.iter().find(|(name, _)| *name == fk.parent);
+                    match parent_rule {
+                        Some((_, parent_predicate)) => format!(
+                            "{} NOT IN (SELECT {} FROM {} WHERE NOT ({}))",
+                            fk.column, fk.parent_column, fk.parent, parent_predicate
+                        ),
+                        None => format!(
+                            "{} NOT IN (SELECT {} FROM {})",
+                            fk.column, fk.parent_column, fk.parent
+                        ),
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(" OR ");
+            rules.push((table, predicate));
+        }
+        Ok(rules)
+    }
+
+    /// 统计各表中的孤儿数据
+    pub fn find_orphans(conn: &mut SqliteConnection) -> Result<OrphanReport> {
+        let mut report = OrphanReport::default();
+        for (table, predicate) in Self::orphan_rules()? {
+            // 未升级的旧数据库没有后续版本新增的表
+            let count = if table_exists(conn, &table)? {
+                count_rows_where(conn, &table, &predicate)?
+            } else {
+                0
+            };
+            report.tables.push((table, count));
+        }
+        Ok(report)
+    }
+
+    /// 删除各表中的孤儿数据
+    pub fn delete_orphans(conn: &mut SqliteConnection) -> Result<OrphanReport> {
+        // 子表先删：未迁移到 v1 的数据库没有级联删除，先删崩溃日志会违反外键约束
+        let mut report = OrphanReport::default();
+        for (table, predicate) in Self::orphan_rules()?.into_iter().rev() {
+            let count = if table_exists(conn, &table)? {
+                delete_rows_where(conn, &table, &predicate)?
+            } else {
+                0
+            };
+            report.tables.insert(0, (table, count));
+        }
+        Ok(report)
+    }
+
+    /// 全面诊断数据库：物理完整性、结构版本以及逻辑一致性
+    pub fn diagnose(conn: &mut SqliteConnection, quick: bool) -> Result<DoctorReport> {
+        // 损坏严重时 PRAGMA 本身也会报错，此时把错误当作检查结果返回
+        let integrity_errors = match run_integrity_check(conn, quick) {
+            Ok(messages) => messages.into_iter().filter(|m| m != "ok").collect(),
+            Err(e) => vec![e.to_string()],
+        };
+
+        let mut report = DoctorReport {
+            integrity_errors,
+            schema_version: get_schema_version(conn)?,
+            ..Default::default()
+        };
+
+        // 以全新初始化的内存数据库作为期望结构进行比对
+        let mut reference = DatabaseInitService::build_reference_schema()?;
+        for table in get_table_names(&mut reference)? {
+            if !table_exists(conn, &table)? {
+                report.missing_tables.push(table);
+                continue;
+            }
+            let actual_columns = get_table_columns(conn, "main", &table)?;
+            for column in get_table_columns(&mut reference, "main", &table)? {
+                if !actual_columns.contains(&column) {
+                    report.missing_columns.push(format!("{}.{}", table, column));
+                }
+            }
+        }
+
+        // 缺表时逻辑检查无法进行；读取损坏的页失败时同样记为完整性错误
+        if report.missing_tables.is_empty() {
+            match count_rows_where(conn, "system_metrics", DUPLICATE_METRIC_RULE) {
+                Ok(count) => report.duplicate_metrics = count,
+                Err(e) => report.integrity_errors.push(e.to_string()),
+            }
+            match Self::find_orphans(conn) {
+                Ok(orphans) => report.orphans = orphans,
+                Err(e) => report.integrity_errors.push(e.to_string()),
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// 修复逻辑不一致：删除重复的系统指标和孤儿数据
+    pub fn repair(conn: &mut SqliteConnection) -> Result<RepairReport> {
+        conn.transaction(|conn| {
+            let duplicate_metrics_removed =
+                delete_rows_where(conn, "system_metrics", DUPLICATE_METRIC_RULE)?;
+            let orphans_removed = Self::delete_orphans(conn)?;
+
+            Ok(RepairReport {
+                duplicate_metrics_removed,
+                orphans_removed,
+            })
+        })
+    }
+
+    /// 将源数据库中可读的行抢救到一个全新的数据库
+    ///
+    /// 整表拷贝失败时（通常是页损坏）会退回到逐行拷贝，跳过无法读取的行。
+    /// 孤儿数据和重复指标不会被拷贝。
+    pub fn salvage(conn: &mut SqliteConnection, target: &DatabaseManager) -> Result<SalvageReport> {
+        use diesel::sql_query;
+        use diesel::sql_types::Text;
+
+        DatabaseInitService::init_database(target, false)?;
+
+        let target_path = target.get_file_path();
+        sql_query("ATTACH DATABASE ? AS salvage")
+            .bind::<Text, _>(&target_path)
+            .execute(conn)?;
+
+        let salvaged = Self::copy_readable_rows(conn);
+
+        sql_query("DETACH DATABASE salvage").execute(conn)?;
+        salvaged
+    }
+
+    fn copy_readable_rows(conn: &mut SqliteConnection) -> Result<SalvageReport> {
+        use diesel::sql_query;
+        use diesel::sql_types::BigInt;
+
+        let mut report = SalvageReport::default();
+
+        // 按目标库 (当前版本结构) 的外键依赖顺序拷贝，父表在前
+        let mut reference = DatabaseInitService::build_reference_schema()?;
+        for (table, foreign_keys) in get_tables_by_dependency(&mut reference)? {
+            let table = table.as_str();
+            // 旧版本的源数据库可能没有后续新增的表；读不出表结构时仍尝试拷贝
+            if !table_exists(conn, table).unwrap_or(true) {
+                continue;
+            }
+
+            // 只拷贝两边都存在的列，兼容旧版本结构
+            let source_columns = get_table_columns(conn, "main", table).unwrap_or_default();
+            let columns = get_table_columns(conn, "salvage", table)?
+                .into_iter()
+                .filter(|c| source_columns.contains(c))
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            // 父记录已拷贝到目标库的行才拷贝，跳过孤儿数据
+            let filter = if foreign_keys.is_empty() {
+                "1 = 1".to_string()
+            } else {
+                foreign_keys
+                    .iter()
+                    .map(|fk| {
+                        format!(
+                            "{} IN (SELECT {} FROM salvage.{})",
+                            fk.column, fk.parent_column, fk.parent
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" AND ")
+            };
+            let insert = format!(
+                "INSERT OR IGNORE INTO salvage.{table} ({columns}) SELECT {columns} FROM main.{table} WHERE {filter}"
+            );
+
+            let mut table_report = SalvageTableReport {
+                table: table.to_string(),
+                copied: 0,
+                failed: 0,
+            };
+
+            match sql_query(&insert).execute(conn) {
+                Ok(copied) => table_report.copied = copied,
+                Err(_) => {
+                    // 整表读取失败，逐行抢救；连 rowid 都无法扫描时按 1..=MAX(rowid) 逐个探测
+                    let rowids = match load_rowids(conn, table) {
+                        Ok(rowids) => rowids,
+                        Err(_) => (1..=get_max_rowid(conn, table).unwrap_or(0)).collect(),
+                    };
+                    for rowid in rowids {
+                        match sql_query(format!("{insert} AND main.{table}.rowid = ?"))
+                            .bind::<BigInt, _>(rowid)
+                            .execute(conn)
+                        {
+                            Ok(copied) => table_report.copied += copied,
+                            Err(_) => table_report.failed += 1,
+                        }
+                    }
+                }
+            }
+
+            report.tables.push(table_report);
+        }
+
+        // 重复指标按 id 保留最新一条
+        let duplicates = delete_rows_where(
+            conn,
+            "salvage.system_metrics",
+            "id NOT IN (SELECT MAX(id) FROM salvage.system_metrics GROUP BY server_id, timestamp)",
+        )?;
+        if let Some(metrics) = report
+            .tables
+            .iter_mut()
+            .find(|t| t.table == "system_metrics")
+        {
+            metrics.copied -= duplicates;
+        }
+
+        Ok(report)
+    }
+}
+
+/// 在线备份与恢复服务
+pub struct BackupService;
+
+impl BackupService {
+    /// 将数据库在线备份到指定文件
+    ///
+    /// 使用 `VACUUM INTO` 生成一致性快照，备份期间其他连接仍可继续写入。
+    /// 返回备份文件大小（字节）。
+    pub fn backup(conn: &mut SqliteConnection, target_path: &str, overwrite: bool) -> Result<u64> {
+        use diesel::sql_types::Text;
+
+        let target = std::path::Path::new(target_path);
+        if target.exists() && !overwrite {
+            return Err(BlackBoxError::conflict(format!(
+                "备份文件已存在: {}，使用 overwrite=true 覆盖",
+                target_path
+            )));
+        }
+
+        // 先写入临时文件，成功后再替换，失败时不影响已有的备份
+        let temp_path = format!("{}.backup-tmp", target_path);
+        if std::path::Path::new(&temp_path).exists() {
+            fs::remove_file(&temp_path).map_err(|e| BlackBoxError::io(&temp_path, e))?;
+        }
+        diesel::sql_query("VACUUM INTO ?")
+            .bind::<Text, _>(&temp_path)
+            .execute(conn)?;
+        fs::rename(&temp_path, target).map_err(|e| BlackBoxError::io(target_path, e))?;
+
+        Ok(fs::metadata(target)
+            .map_err(|e| BlackBoxError::io(target_path, e))?
+            .len())
+    }
+
+    /// 从备份文件恢复数据库
+    ///
+    /// 先校验备份文件的完整性和结构版本，再生成临时快照并原子替换目标文件。
+    /// 恢复期间不应有其他进程写入目标数据库。
+    pub fn restore(source_path: &str, target: &DatabaseManager) -> Result<()> {
+        if !std::path::Path::new(source_path).exists() {
+            return Err(BlackBoxError::not_found("备份文件", source_path));
+        }
+
+        // 直接打开备份文件，不应用连接参数，避免把备份文件切换成 WAL 模式
+        let mut source_conn = establish_connection_with_url(Some(source_path))?;
+
+        let errors: Vec<String> = run_integrity_check(&mut source_conn, true)?
+            .into_iter()
+            .filter(|m| m != "ok")
+            .collect();
+        if !errors.is_empty() {
+            return Err(BlackBoxError::validation(format!(
+                "备份文件 {} 已损坏: {}",
+                source_path,
+                errors.join("; ")
+            )));
+        }
+
+        let version = get_schema_version(&mut source_conn)?;
+        if version != SCHEMA_VERSION {
+            return Err(BlackBoxError::schema(
+                version,
+                SCHEMA_VERSION,
+                format!(
+                    "备份文件结构版本为 v{}，当前程序需要 v{}，请先对备份文件执行 migrate",
+                    version, SCHEMA_VERSION
+                ),
+            ));
+        }
+
+        let target_path = target.get_file_path();
+        let temp_path = format!("{}.restore-tmp", target_path);
+        Self::backup(&mut source_conn, &temp_path, true)?;
+        drop(source_conn);
+
+        // 先把旧数据库的 WAL 合并并清空，替换后残留的 WAL 不会有可回放的内容
+        if std::path::Path::new(&target_path).exists() {
+            let mut conn = target.get_connection()?;
+            diesel::connection::SimpleConnection::batch_execute(
+                &mut *conn,
+                "PRAGMA wal_checkpoint(TRUNCATE)",
+            )?;
+        }
+        target.close_idle_connections();
+
+        // rename 是原子的：任何时刻目标路径上都有一个完整的数据库，
+        // 之后再删除属于旧数据库的 WAL/SHM 文件
+        fs::rename(&temp_path, &target_path).map_err(|e| BlackBoxError::io(&target_path, e))?;
+        for suffix in ["-wal", "-shm"] {
+            let path = format!("{}{}", target_path, suffix);
+            if std::path::Path::new(&path).exists() {
+                fs::remove_file(&path).map_err(|e| BlackBoxError::io(&path, e))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
 /// 智能插入服务
 pub struct SmartInsertService;
 
 impl SmartInsertService {
     /// 智能插入服务器数据
-    pub fn insert_servers(
-        conn: &mut SqliteConnection,
+    pub fn insert_servers<S: Store + ?Sized>(
+        store: &mut S,
         servers: Vec<NewServer>,
-        continue_on_error: bool,
+        options: &InsertOptions,
     ) -> Result<InsertResult> {
+        let continue_on_error = options.continue_on_error;
         // 插入前清理旧数据
-        let _ = DataCleanService::cleanup_old_data(conn);
+        let _ = store.cleanup_old_data(&options.retention);
 
         let mut result = InsertResult::new();
 
         for server in servers {
-            match Self::handle_server_insert(conn, server) {
+            match Self::handle_server_insert(store, server) {
                 Ok(is_update) => {
                     if is_update {
                         result.add_updated();
@@ -334,27 +1268,28 @@ impl SmartInsertService {
     }
 
     /// 智能插入系统指标数据
-    pub fn insert_system_metrics(
-        conn: &mut SqliteConnection,
+    pub fn insert_system_metrics<S: Store + ?Sized>(
+        store: &mut S,
         metrics: Vec<SmartSystemMetric>,
-        continue_on_error: bool,
+        options: &InsertOptions,
     ) -> Result<InsertResult> {
+        let continue_on_error = options.continue_on_error;
         // 插入前清理旧数据
-        let _ = DataCleanService::cleanup_old_data(conn);
+        let _ = store.cleanup_old_data(&options.retention);
 
         let mut result = InsertResult::new();
 
         for metric in metrics {
             // 验证服务器是否存在
-            if get_server_by_id(conn, &metric.server_id)?.is_none() {
+            if store.get_server_by_id(&metric.server_id)?.is_none() {
                 result.add_error();
                 if !continue_on_error {
-                    return Err(anyhow::anyhow!("服务器 {} 不存在", metric.server_id));
+                    return Err(BlackBoxError::not_found("服务器", metric.server_id.clone()));
                 }
                 continue;
             }
 
-            match Self::handle_metric_insert(conn, metric) {
+            match Self::handle_metric_insert(store, metric) {
                 Ok(is_update) => {
                     if is_update {
                         result.add_updated();
@@ -375,18 +1310,19 @@ impl SmartInsertService {
     }
 
     /// 智能插入进程数据
-    pub fn insert_processes(
-        conn: &mut SqliteConnection,
+    pub fn insert_processes<S: Store + ?Sized>(
+        store: &mut S,
         processes: Vec<SmartProcessInsert>,
-        continue_on_error: bool,
+        options: &InsertOptions,
     ) -> Result<InsertResult> {
+        let continue_on_error = options.continue_on_error;
         // 插入前清理旧数据
-        let _ = DataCleanService::cleanup_old_data(conn);
+        let _ = store.cleanup_old_data(&options.retention);
 
         let mut result = InsertResult::new();
 
         for process_data in processes {
-            match Self::handle_process_insert(conn, process_data, continue_on_error) {
+            match Self::handle_process_insert(store, process_data, continue_on_error) {
                 Ok(is_update) => {
                     if is_update {
                         result.add_updated();
@@ -407,27 +1343,72 @@ impl SmartInsertService {
     }
 
     /// 智能插入崩溃日志数据
-    pub fn insert_crash_logs(
-        conn: &mut SqliteConnection,
+    pub fn insert_crash_logs<S: Store + ?Sized>(
+        store: &mut S,
         crash_logs: Vec<SmartCrashLog>,
-        continue_on_error: bool,
+        options: &InsertOptions,
     ) -> Result<InsertResult> {
+        let continue_on_error = options.continue_on_error;
         // 插入前清理旧数据
-        let _ = DataCleanService::cleanup_old_data(conn);
+        let _ = store.cleanup_old_data(&options.retention);
 
         let mut result = InsertResult::new();
 
         for log_data in crash_logs {
             // 验证服务器是否存在
-            if get_server_by_id(conn, &log_data.server_id)?.is_none() {
+            if store.get_server_by_id(&log_data.server_id)?.is_none() {
+                result.add_error();
+                if !continue_on_error {
+                    return Err(BlackBoxError::not_found(
+                        "服务器",
+                        log_data.server_id.clone(),
+                    ));
+                }
+                continue;
+            }
+
+            match Self::handle_crash_log_insert(store, log_data, options) {
+                Ok(is_update) => {
+                    if is_update {
+                        result.add_updated();
+                    } else {
+                        result.add_success();
+                    }
+                }
+                Err(e) => {
+                    result.add_error();
+                    if !continue_on_error {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// 智能插入进程趋势点 (来自行协议)
+    ///
+    /// 进程按服务器、进程名和用户匹配，不存在时自动创建；同一进程同一时间戳的趋势会被更新。
+    /// 与 `insert_processes` 不同，这里不会改动线程数据。
+    pub fn insert_process_trend_points<S: Store + ?Sized>(
+        store: &mut S,
+        points: Vec<SmartProcessTrendPoint>,
+        options: &InsertOptions,
+    ) -> Result<InsertResult> {
+        let continue_on_error = options.continue_on_error;
+        let mut result = InsertResult::new();
+
+        for point in points {
+            if store.get_server_by_id(&point.server_id)?.is_none() {
                 result.add_error();
                 if !continue_on_error {
-                    return Err(anyhow::anyhow!("服务器 {} 不存在", log_data.server_id));
+                    return Err(BlackBoxError::not_found("服务器", point.server_id.clone()));
                 }
                 continue;
             }
 
-            match Self::handle_crash_log_insert(conn, log_data) {
+            match Self::handle_process_trend_point(store, point) {
                 Ok(is_update) => {
                     if is_update {
                         result.add_updated();
@@ -448,13 +1429,14 @@ impl SmartInsertService {
     }
 
     /// 智能插入组合数据
-    pub fn insert_combined_data(
-        conn: &mut SqliteConnection,
+    pub fn insert_combined_data<S: Store + ?Sized>(
+        store: &mut S,
         combined_data: CombinedInsertData,
-        continue_on_error: bool,
+        options: &InsertOptions,
     ) -> Result<InsertResult> {
+        let continue_on_error = options.continue_on_error;
         // 插入前清理旧数据
-        let _ = DataCleanService::cleanup_old_data(conn);
+        let _ = store.cleanup_old_data(&options.retention);
 
         let mut result = InsertResult::new();
 
@@ -464,14 +1446,11 @@ impl SmartInsertService {
         // 先处理进程数据以确保服务器存在，然后检测线程数异常
         for process_data in &combined_data.process {
             // 先确保服务器存在
-            match get_server_by_id(conn, &process_data.server_id)? {
+            match store.get_server_by_id(&process_data.server_id)? {
                 Some(_) => {
                     // 服务器存在，更新状态
-                    let _ = update_server_status(
-                        conn,
-                        &process_data.server_id,
-                        &process_data.server_status,
-                    );
+                    let _ = store
+                        .update_server_status(&process_data.server_id, &process_data.server_status);
                 }
                 None => {
                     // 服务器不存在，创建新服务器
@@ -482,19 +1461,22 @@ impl SmartInsertService {
                         server_os: process_data.server_os.clone(),
                         server_status: process_data.server_status.clone(),
                     };
-                    let _ = create_server(conn, &new_server);
+                    let _ = store.create_server(&new_server);
                 }
             }
 
             // 检测线程数异常
-            if Self::has_thread_exception(&process_data) {
+            if Self::has_thread_exception(
+                process_data,
+                options.detection.thread_exception_threshold,
+            ) {
                 println!(
                     "检测到线程数异常，进程 PID={} NAME={} 线程数={}",
                     process_data.pid,
                     process_data.name,
                     process_data.trend.last().map_or(0, |t| t.thread_count)
                 );
-                match Self::handle_thread_exception_crash_log(conn, &process_data) {
+                match Self::handle_thread_exception_crash_log(store, process_data, options) {
                     Ok(_) => {
                         result.add_success();
                     }
@@ -510,7 +1492,7 @@ impl SmartInsertService {
 
         // 处理进程数据（包含服务器信息）
         for process_data in combined_data.process {
-            match Self::handle_combined_process_insert(conn, process_data, continue_on_error) {
+            match Self::handle_combined_process_insert(store, process_data, continue_on_error) {
                 Ok(is_update) => {
                     if is_update {
                         result.add_updated();
@@ -530,15 +1512,15 @@ impl SmartInsertService {
         // 处理系统指标数据
         for metric in combined_data.metrics {
             // 验证服务器是否存在
-            if get_server_by_id(conn, &metric.server_id)?.is_none() {
+            if store.get_server_by_id(&metric.server_id)?.is_none() {
                 result.add_error();
                 if !continue_on_error {
-                    return Err(anyhow::anyhow!("服务器 {} 不存在", metric.server_id));
+                    return Err(BlackBoxError::not_found("服务器", metric.server_id.clone()));
                 }
                 continue;
             }
 
-            match Self::handle_metric_insert(conn, metric) {
+            match Self::handle_metric_insert(store, metric) {
                 Ok(is_update) => {
                     if is_update {
                         result.add_updated();
@@ -556,19 +1538,24 @@ impl SmartInsertService {
         }
 
         // 处理 dmesg 数据，检测系统崩溃信息
-        if let Some(dmesg_content) = combined_data.dmesg {
-            if Self::is_system_crash(&dmesg_content) {
-                // 使用之前保存的服务器ID
-                if let Some(server_id) = first_server_id {
-                    match Self::handle_crash_log_from_dmesg(conn, &server_id, &dmesg_content) {
-                        Ok(_) => {
+        if let Some(dmesg_content) = combined_data.dmesg
+            && Self::is_system_crash(&dmesg_content, &options.detection.crash_indicators)
+        {
+            // 使用之前保存的服务器ID
+            if let Some(server_id) = first_server_id {
+                match Self::handle_crash_log_from_dmesg(store, &server_id, &dmesg_content, options)
+                {
+                    Ok(is_update) => {
+                        if is_update {
+                            result.add_updated();
+                        } else {
                             result.add_success();
                         }
-                        Err(e) => {
-                            result.add_error();
-                            if !continue_on_error {
-                                return Err(e);
-                            }
+                    }
+                    Err(e) => {
+                        result.add_error();
+                        if !continue_on_error {
+                            return Err(e);
                         }
                     }
                 }
@@ -579,21 +1566,21 @@ impl SmartInsertService {
     }
 
     // 私有辅助方法
-    fn handle_server_insert(conn: &mut SqliteConnection, server: NewServer) -> Result<bool> {
-        match get_server_by_id(conn, &server.server_id)? {
+    fn handle_server_insert<S: Store + ?Sized>(store: &mut S, server: NewServer) -> Result<bool> {
+        match store.get_server_by_id(&server.server_id)? {
             Some(_) => {
-                update_server_status(conn, &server.server_id, &server.server_status)?;
+                store.update_server_status(&server.server_id, &server.server_status)?;
                 Ok(true) // 是更新操作
             }
             None => {
-                create_server(conn, &server)?;
+                store.create_server(&server)?;
                 Ok(false) // 是新建操作
             }
         }
     }
 
-    fn handle_metric_insert(
-        conn: &mut SqliteConnection,
+    fn handle_metric_insert<S: Store + ?Sized>(
+        store: &mut S,
         metric: SmartSystemMetric,
     ) -> Result<bool> {
         let new_metric = NewSystemMetric {
@@ -608,35 +1595,34 @@ impl SmartInsertService {
             network_out: metric.network_out,
         };
 
-        match get_system_metric_by_timestamp(conn, &metric.server_id, metric.timestamp)? {
+        match store.get_system_metric_by_timestamp(&metric.server_id, metric.timestamp)? {
             Some(_) => {
-                update_system_metric(conn, &metric.server_id, metric.timestamp, &new_metric)?;
+                store.update_system_metric(&metric.server_id, metric.timestamp, &new_metric)?;
                 Ok(true) // 是更新操作
             }
             None => {
-                create_system_metric(conn, &new_metric)?;
+                store.create_system_metric(&new_metric)?;
                 Ok(false) // 是新建操作
             }
         }
     }
 
-    fn handle_process_insert(
-        conn: &mut SqliteConnection,
+    fn handle_process_insert<S: Store + ?Sized>(
+        store: &mut S,
         process_data: SmartProcessInsert,
         continue_on_error: bool,
     ) -> Result<bool> {
         // 验证服务器是否存在，如果不存在则尝试自动创建
-        Self::ensure_server_exists(conn, &process_data, continue_on_error)?;
+        Self::ensure_server_exists(store, &process_data, continue_on_error)?;
 
-        let is_update = match get_process_by_name_and_user(
-            conn,
+        let is_update = match store.get_process_by_name_and_user(
             &process_data.server_id,
             &process_data.name,
             &process_data.user_name,
         )? {
             Some(existing_process) => {
                 // 进程已存在，更新状态
-                update_process_status(conn, existing_process.id, &process_data.status)?;
+                store.update_process_status(existing_process.id, &process_data.status)?;
                 true
             }
             None => {
@@ -648,27 +1634,27 @@ impl SmartInsertService {
                     user_name: process_data.user_name.clone(),
                     status: process_data.status.clone(),
                 };
-                create_process(conn, &new_process)?;
+                store.create_process(&new_process)?;
                 false
             }
         };
 
         // 添加趋势数据和线程数据
-        Self::add_process_related_data(conn, &process_data)?;
+        Self::add_process_related_data(store, &process_data)?;
 
         Ok(is_update)
     }
 
-    fn handle_combined_process_insert(
-        conn: &mut SqliteConnection,
+    fn handle_combined_process_insert<S: Store + ?Sized>(
+        store: &mut S,
         process_data: CombinedProcessData,
         _continue_on_error: bool,
     ) -> Result<bool> {
         // 检查并创建服务器（如果不存在）
-        match get_server_by_id(conn, &process_data.server_id)? {
+        match store.get_server_by_id(&process_data.server_id)? {
             Some(_) => {
                 // 服务器存在，更新状态
-                update_server_status(conn, &process_data.server_id, &process_data.server_status)?;
+                store.update_server_status(&process_data.server_id, &process_data.server_status)?;
             }
             None => {
                 // 服务器不存在，创建新服务器
@@ -679,20 +1665,19 @@ impl SmartInsertService {
                     server_os: process_data.server_os.clone(),
                     server_status: process_data.server_status.clone(),
                 };
-                create_server(conn, &new_server)?;
+                store.create_server(&new_server)?;
             }
         }
 
         // 处理进程信息
-        let is_update = match get_process_by_name_and_user(
-            conn,
+        let is_update = match store.get_process_by_name_and_user(
             &process_data.server_id,
             &process_data.name,
             &process_data.user_name,
         )? {
             Some(existing_process) => {
                 // 进程存在，更新状态
-                update_process_status(conn, existing_process.id, &process_data.status)?;
+                store.update_process_status(existing_process.id, &process_data.status)?;
                 true
             }
             None => {
@@ -704,7 +1689,7 @@ impl SmartInsertService {
                     user_name: process_data.user_name.clone(),
                     status: process_data.status.clone(),
                 };
-                create_process(conn, &new_process)?;
+                store.create_process(&new_process)?;
                 false
             }
         };
@@ -719,11 +1704,11 @@ impl SmartInsertService {
                 memory_usage: trend.memory_usage,
                 thread_count: trend.thread_count,
             };
-            let _ = create_process_trend(conn, &new_trend);
+            let _ = store.create_process_trend(&new_trend);
         }
 
         // 删除旧的线程数据并添加新的线程数据
-        let _ = delete_threads_by_process(conn, &process_data.server_id, process_data.pid);
+        let _ = store.delete_threads_by_process(&process_data.server_id, process_data.pid);
 
         for thread in &process_data.threads {
             let new_thread = NewThread {
@@ -742,15 +1727,62 @@ impl SmartInsertService {
                 runtime: thread.runtime.clone(),
                 command: thread.command.clone(),
             };
-            let _ = create_thread(conn, &new_thread);
+            let _ = store.create_thread(&new_thread);
         }
 
         Ok(is_update)
     }
 
-    fn handle_crash_log_insert(
-        conn: &mut SqliteConnection,
+    fn handle_process_trend_point<S: Store + ?Sized>(
+        store: &mut S,
+        point: SmartProcessTrendPoint,
+    ) -> Result<bool> {
+        match store.get_process_by_name_and_user(&point.server_id, &point.name, &point.user_name)? {
+            Some(existing_process) => {
+                if let Some(status) = &point.status {
+                    store.update_process_status(existing_process.id, status)?;
+                }
+            }
+            None => {
+                let new_process = NewProcess {
+                    server_id: point.server_id.clone(),
+                    pid: point.pid,
+                    name: point.name.clone(),
+                    user_name: point.user_name.clone(),
+                    status: point
+                        .status
+                        .clone()
+                        .unwrap_or_else(|| ProcessState::Other("unknown".to_string())),
+                };
+                store.create_process(&new_process)?;
+            }
+        }
+
+        let new_trend = NewProcessTrend {
+            server_id: point.server_id.clone(),
+            pid: point.pid,
+            timestamp: point.timestamp,
+            cpu_usage: point.cpu_usage,
+            memory_usage: point.memory_usage,
+            thread_count: point.thread_count,
+        };
+
+        match store.get_process_trend_by_timestamp(&point.server_id, point.pid, point.timestamp)? {
+            Some(existing_trend) => {
+                store.update_process_trend(existing_trend.id, &new_trend)?;
+                Ok(true)
+            }
+            None => {
+                store.create_process_trend(&new_trend)?;
+                Ok(false)
+            }
+        }
+    }
+
+    fn handle_crash_log_insert<S: Store + ?Sized>(
+        store: &mut S,
         log_data: SmartCrashLog,
+        options: &InsertOptions,
     ) -> Result<bool> {
         let new_log = NewCrashLog {
             server_id: log_data.server_id.clone(),
@@ -766,24 +1798,73 @@ impl SmartInsertService {
             ai_analysis: log_data.ai_analysis.clone(),
         };
 
-        match get_crash_log_by_timestamp(conn, &log_data.server_id, log_data.timestamp)? {
+        let recommendations = match &log_data.recommendations {
+            Some(recommendations) => Some(crate::crash::numbered(0, recommendations)?),
+            None => None,
+        };
+
+        match store.get_crash_log_by_timestamp(&log_data.server_id, log_data.timestamp)? {
             Some(existing_log) => {
-                update_crash_log(conn, existing_log.id, &new_log)?;
+                let updated = analyze::keep_existing(&existing_log, new_log);
+                let needs_analysis = analyze::is_missing_analysis(updated.ai_summary.as_deref());
+                store.update_crash_log(existing_log.id, &updated)?;
+                if let Some(recommendations) = recommendations {
+                    Self::replace_recommendations(store, existing_log.id, recommendations)?;
+                }
+                if needs_analysis {
+                    Self::analyze_created_crash_log(
+                        store,
+                        &existing_log.server_id,
+                        existing_log.id,
+                        options,
+                        None,
+                    )?;
+                }
                 Ok(true) // 是更新操作
             }
             None => {
-                create_crash_log(conn, &new_log)?;
+                let crash_log_id = store.create_crash_log(&new_log)?;
+                if let Some(recommendations) = recommendations {
+                    Self::replace_recommendations(store, crash_log_id, recommendations)?;
+                }
+                // 采集端已经给出分析时保留原内容
+                if analyze::is_missing_analysis(new_log.ai_summary.as_deref()) {
+                    Self::analyze_created_crash_log(
+                        store,
+                        &new_log.server_id,
+                        crash_log_id,
+                        options,
+                        None,
+                    )?;
+                }
                 Ok(false) // 是新建操作
             }
         }
     }
 
-    fn ensure_server_exists(
-        conn: &mut SqliteConnection,
+    /// 用提交的建议替换崩溃日志已有的建议，优先级重新编号为 1..n
+    fn replace_recommendations<S: Store + ?Sized>(
+        store: &mut S,
+        crash_log_id: i32,
+        recommendations: Vec<NewAiRecommendation>,
+    ) -> Result<()> {
+        store.delete_recommendations_by_crash_log(crash_log_id)?;
+        for (index, recommendation) in recommendations.into_iter().enumerate() {
+            store.create_ai_recommendation(&NewAiRecommendation {
+                crash_log_id,
+                priority: index as i32 + 1,
+                ..recommendation
+            })?;
+        }
+        Ok(())
+    }
+
+    fn ensure_server_exists<S: Store + ?Sized>(
+        store: &mut S,
         process_data: &SmartProcessInsert,
         _continue_on_error: bool,
     ) -> Result<()> {
-        if get_server_by_id(conn, &process_data.server_id)?.is_none() {
+        if store.get_server_by_id(&process_data.server_id)?.is_none() {
             // 检查是否提供了服务器信息用于自动创建
             if let (Some(server_name), Some(server_ip), Some(server_os), Some(server_status)) = (
                 &process_data.server_name,
@@ -798,19 +1879,19 @@ impl SmartInsertService {
                     server_os: server_os.clone(),
                     server_status: server_status.clone(),
                 };
-                create_server(conn, &new_server)?;
+                store.create_server(&new_server)?;
             } else {
-                return Err(anyhow::anyhow!(
-                    "服务器 {} 不存在且未提供服务器信息用于自动创建",
-                    process_data.server_id
+                return Err(BlackBoxError::not_found(
+                    "服务器",
+                    process_data.server_id.clone(),
                 ));
             }
         }
         Ok(())
     }
 
-    fn add_process_related_data(
-        conn: &mut SqliteConnection,
+    fn add_process_related_data<S: Store + ?Sized>(
+        store: &mut S,
         process_data: &SmartProcessInsert,
     ) -> Result<()> {
         // 添加趋势数据
@@ -823,11 +1904,11 @@ impl SmartInsertService {
                 memory_usage: trend.memory_usage,
                 thread_count: trend.thread_count,
             };
-            let _ = create_process_trend(conn, &new_trend);
+            let _ = store.create_process_trend(&new_trend);
         }
 
         // 删除旧线程数据并添加新的线程数据
-        let _ = delete_threads_by_process(conn, &process_data.server_id, process_data.pid);
+        let _ = store.delete_threads_by_process(&process_data.server_id, process_data.pid);
 
         for thread in &process_data.threads {
             let new_thread = NewThread {
@@ -846,70 +1927,77 @@ impl SmartInsertService {
                 runtime: thread.runtime.clone(),
                 command: thread.command.clone(),
             };
-            let _ = create_thread(conn, &new_thread);
+            let _ = store.create_thread(&new_thread);
         }
 
         Ok(())
     }
 
     /// 检测 dmesg 内容是否包含系统崩溃信息
-    fn is_system_crash(dmesg_content: &str) -> bool {
-        let crash_indicators = [
-            "kernel BUG at",
-            "Internal error: Oops",
-            "segmentation fault",
-            "kernel panic",
-            "Call trace:",
-            "---[ end trace",
-            "BUG:",
-            "WARNING:",
-        ];
-
+    fn is_system_crash(dmesg_content: &str, crash_indicators: &[String]) -> bool {
         crash_indicators
             .iter()
-            .any(|indicator| dmesg_content.contains(indicator))
+            .any(|indicator| dmesg_content.contains(indicator.as_str()))
     }
 
-    /// 从 dmesg 内容创建崩溃日志
-    fn handle_crash_log_from_dmesg(
-        conn: &mut SqliteConnection,
+    /// 从 dmesg 内容创建崩溃日志，知识库没有匹配的特征时，摘要和分析留待 `crash analyze --pending` 补全
+    fn handle_crash_log_from_dmesg<S: Store + ?Sized>(
+        store: &mut S,
         server_id: &str,
         dmesg_content: &str,
-    ) -> Result<()> {
+        options: &InsertOptions,
+    ) -> Result<bool> {
         use chrono::Utc;
 
+        // 重复上报相同的 dmesg 时 log_id 相同，不再新建日志
+        let log_id = Self::dmesg_log_id(dmesg_content);
+        if store.get_crash_log_by_log_id(server_id, log_id)?.is_some() {
+            return Ok(true);
+        }
         let timestamp = Utc::now().timestamp_millis();
-        let log_id = timestamp; // 使用时间戳作为 log_id
 
         let new_crash_log = NewCrashLog {
             server_id: server_id.to_string(),
             log_id,
             timestamp,
-            crash_type: "kernel_exception".to_string(),
-            severity: "high".to_string(),
+            crash_type: CrashType::KernelException,
+            severity: Severity::High,
             title: "内核异常日志信息".to_string(),
-            message: "正在等待 AI 生成".to_string(),
+            message: PENDING_TEXT.to_string(),
             stack_trace: Some(dmesg_content.to_string()),
             resolved: false,
-            ai_summary: Some("正在等待 AI 生成".to_string()),
-            ai_analysis: Some("正在等待 AI 生成".to_string()),
+            ai_summary: Some(PENDING_TEXT.to_string()),
+            ai_analysis: Some(PENDING_TEXT.to_string()),
         };
 
-        create_crash_log(conn, &new_crash_log)?;
-        Ok(())
+        let crash_log_id = store.create_crash_log(&new_crash_log)?;
+        Self::analyze_created_crash_log(store, server_id, crash_log_id, options, None)?;
+        Ok(false)
+    }
+
+    /// dmesg 崩溃日志的 log_id：内容的 64 位 FNV-1a 哈希，取非负部分
+    ///
+    /// 不使用 [`std::hash::DefaultHasher`]，它的结果在不同 Rust 版本之间可能变化。
+    fn dmesg_log_id(dmesg_content: &str) -> i64 {
+        let hash = dmesg_content
+            .bytes()
+            .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+                (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+            });
+        (hash >> 1) as i64
     }
 
     /// 检测进程是否有线程数异常
-    fn has_thread_exception(process_data: &CombinedProcessData) -> bool {
+    fn has_thread_exception(process_data: &CombinedProcessData, threshold: i32) -> bool {
         // 检查进程趋势中的线程数
         for trend in &process_data.trend {
-            if trend.thread_count > THREAD_EXCEPTION_THRESHOLD {
+            if trend.thread_count > threshold {
                 return true;
             }
         }
 
         // 检查实际线程数量
-        if process_data.threads.len() as i32 > THREAD_EXCEPTION_THRESHOLD {
+        if process_data.threads.len() as i32 > threshold {
             return true;
         }
 
@@ -917,17 +2005,21 @@ impl SmartInsertService {
     }
 
     /// 处理线程数异常，创建崩溃日志
-    fn handle_thread_exception_crash_log(
-        conn: &mut SqliteConnection,
+    fn handle_thread_exception_crash_log<S: Store + ?Sized>(
+        store: &mut S,
         process_data: &CombinedProcessData,
+        options: &InsertOptions,
     ) -> Result<()> {
         use chrono::Utc;
 
         // 检查是否已经存在相同进程的线程异常崩溃日志，防止重复添加
 
         // 检查是否已存在相同的线程异常日志（通过 stack_trace 中的进程名称标记来识别）
-        if Self::thread_exception_crash_log_exists(conn, &process_data.server_id, &process_data.name)?
-        {
+        if Self::thread_exception_crash_log_exists(
+            store,
+            &process_data.server_id,
+            &process_data.name,
+        )? {
             return Ok(()); // 已存在，不重复添加
         }
 
@@ -941,38 +2033,74 @@ impl SmartInsertService {
             server_id: process_data.server_id.clone(),
             log_id,
             timestamp,
-            crash_type: "thread_exception".to_string(),
-            severity: "high".to_string(),
+            crash_type: CrashType::ThreadException,
+            severity: Severity::High,
             title: "Thread Exception".to_string(),
-            message: format!("Thread exception detected in process PID={} NAME={} Count={}", process_data.pid, process_data.name, process_data.trend.last().map_or(0, |t| t.thread_count)),
+            message: format!(
+                "Thread exception detected in process PID={} NAME={} Count={}",
+                process_data.pid,
+                process_data.name,
+                process_data.trend.last().map_or(0, |t| t.thread_count)
+            ),
             stack_trace: Some(stack_trace),
             resolved: false,
-            ai_summary: Some("线程数达到上限，建议增加线程限制并重启桌面服务".to_string()),
-            ai_analysis: Some("## 🔍 问题分析\n\nUKUI 3.0 桌面环境的 ukui-panel 进程因系统线程数达到上限（4096）而无法创建新的工作线程，导致桌面面板服务崩溃。\n\n### 📊 关键发现\n- **线程限制**: 当前系统线程限制为 4096，已达上限\n- **影响进程**: ukui-panel (PID: 1001) 桌面面板服务\n- **失败原因**: pthread_create 调用失败，资源暂时不可用\n\n---\n\n## 💡 解决方案\n\n### 1. 立即修复 `优先级: P1`\n\n增加系统线程限制：\n\n```bash\n# 临时增加线程限制\necho \"* soft nproc 8192\" >> /etc/security/limits.conf\necho \"* hard nproc 8192\" >> /etc/security/limits.conf\n\n# 重启桌面服务\nsystemctl --user restart ukui-panel.service\n```\n\n### 2. 长期优化 `优先级: P2`\n\n检查并优化 UKUI 桌面环境：\n\n```bash\n# 检查当前线程使用情况\nps -eLf | wc -l\n\n# 监控 ukui-panel 线程数\nwatch -n 1 \"ps -o pid,nlwp,comm -p 1001\"\n```\n\n> ⚠️ **注意**: 修改系统限制后需要重新登录或重启系统才能完全生效。".to_string()),
+            ai_summary: None,
+            ai_analysis: None,
         };
 
-        create_crash_log(conn, &new_crash_log)?;
-        Ok(())
+        let crash_log_id = store.create_crash_log(&new_crash_log)?;
+
+        // 线程异常日志的信息都在堆栈中，知识库没有匹配时直接用内置规则生成分析和建议
+        Self::analyze_created_crash_log(
+            store,
+            &process_data.server_id,
+            crash_log_id,
+            options,
+            Some(&RuleAnalyzer),
+        )
+    }
+
+    /// 为新建的崩溃日志写入分析：优先使用知识库中匹配的特征，否则使用 `fallback`，都没有时保持原样
+    fn analyze_created_crash_log<S: Store + ?Sized>(
+        store: &mut S,
+        server_id: &str,
+        crash_log_id: i32,
+        options: &InsertOptions,
+        fallback: Option<&dyn Analyzer>,
+    ) -> Result<()> {
+        if options.knowledge_base.is_none() && fallback.is_none() {
+            return Ok(());
+        }
+        let Some(crash_log) = store.get_crash_log_by_id(crash_log_id)? else {
+            return Ok(());
+        };
+
+        let analysis = match options
+            .knowledge_base
+            .as_ref()
+            .and_then(|knowledge_base| knowledge_base.analyze(&crash_log))
+        {
+            Some(analysis) => analysis,
+            None => match fallback {
+                Some(analyzer) => {
+                    analyzer.analyze(&crash_log, &AnalysisContext::load(store, server_id)?)?
+                }
+                None => return Ok(()),
+            },
+        };
+        analyze::apply(store, &crash_log, &analysis)
     }
 
     /// 检查是否已存在相同进程的线程异常崩溃日志（使用进程名称判断）
-    fn thread_exception_crash_log_exists(
-        conn: &mut SqliteConnection,
+    fn thread_exception_crash_log_exists<S: Store + ?Sized>(
+        store: &mut S,
         target_server_id: &str,
         process_name: &str,
     ) -> Result<bool> {
-        use crate::schema::crash_logs::dsl::*;
-
-        let process_marker = format!("PROCESS_NAME: {}", process_name);
-
-        let count: i64 = crash_logs
-            .filter(server_id.eq(target_server_id))
-            .filter(crash_type.eq("thread_exception"))
-            .filter(stack_trace.like(format!("%{}%", process_marker)))
-            .count()
-            .get_result(conn)?;
-
-        Ok(count > 0)
+        store.thread_exception_crash_log_exists(
+            target_server_id,
+            &format!("PROCESS_NAME: {}", process_name),
+        )
     }
 
     /// 构建线程异常的 stack_trace，包含进程信息
@@ -980,7 +2108,7 @@ impl SmartInsertService {
         let mut stack_trace = String::new();
 
         // 添加进程信息标记（使用 PROCESS_NAME 作为唯一标识，因为 PID 可能会变化）
-        stack_trace.push_str(&format!("THREAD_EXCEPTION_DETECTED\n"));
+        stack_trace.push_str("THREAD_EXCEPTION_DETECTED\n");
         stack_trace.push_str(&format!("PROCESS_NAME: {}\n", process_data.name));
         stack_trace.push_str(&format!(
             "PROCESS_INFO: PID={}, NAME={}, USER={}\n",
@@ -1034,26 +2162,121 @@ impl SmartInsertService {
     }
 }
 
+/// 幂等写入服务
+///
+/// 带幂等键的批次在写入后记入 ingest_ledger 表，同一个键再次提交时不再写入，
+/// 直接返回首次的插入结果。
+pub struct IdempotencyService;
+
+impl IdempotencyService {
+    /// 查找已记录的批次，返回其数据类型和首次的插入结果
+    pub fn find(conn: &mut SqliteConnection, key: &str) -> Result<Option<(String, InsertResult)>> {
+        use crate::schema::ingest_ledger;
+
+        let entry: Option<LedgerEntry> = ingest_ledger::table
+            .filter(ingest_ledger::record_id.eq(key))
+            .first(conn)
+            .optional()?;
+
+        entry
+            .map(|entry| {
+                let errors = match &entry.errors {
+                    Some(errors) => serde_json::from_str(errors)?,
+                    None => Vec::new(),
+                };
+                let result = InsertResult {
+                    success_count: entry.success_count as usize,
+                    updated_count: entry.updated_count as usize,
+                    error_count: entry.error_count as usize,
+                    errors,
+                };
+                Ok((entry.data_type, result))
+            })
+            .transpose()
+    }
+
+    pub fn record(
+        conn: &mut SqliteConnection,
+        key: &str,
+        data_type: &str,
+        result: &InsertResult,
+    ) -> Result<()> {
+        use crate::schema::ingest_ledger;
+
+        diesel::insert_into(ingest_ledger::table)
+            .values(&NewLedgerEntry {
+                record_id: key.to_string(),
+                data_type: data_type.to_string(),
+                success_count: result.success_count as i32,
+                updated_count: result.updated_count as i32,
+                error_count: result.error_count as i32,
+                errors: (!result.errors.is_empty())
+                    .then(|| serde_json::to_string(&result.errors))
+                    .transpose()?,
+            })
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    /// 按幂等键执行写入，调用方负责事务
+    ///
+    /// 键已记录时跳过写入，返回首次的结果和 `true`；同一个键不能用于不同类型的数据。
+    pub fn run(
+        conn: &mut SqliteConnection,
+        key: Option<&str>,
+        data_type: &str,
+        insert: impl FnOnce(&mut SqliteConnection) -> Result<InsertResult>,
+    ) -> Result<(InsertResult, bool)> {
+        let Some(key) = key else {
+            return Ok((insert(conn)?, false));
+        };
+
+        if let Some((recorded_type, result)) = Self::find(conn, key)? {
+            if recorded_type != data_type {
+                return Err(BlackBoxError::conflict(format!(
+                    "幂等键 {} 已用于 {} 数据，不能再用于 {} 数据",
+                    key, recorded_type, data_type
+                )));
+            }
+            return Ok((result, true));
+        }
+
+        let result = insert(conn)?;
+        Self::record(conn, key, data_type, &result)?;
+        Ok((result, false))
+    }
+}
+
 /// 数据清理服务
 pub struct DataCleanService;
 
 impl DataCleanService {
-    /// 清理旧数据（保留最近24小时的数据）
-    pub fn cleanup_old_data(conn: &mut SqliteConnection) -> Result<()> {
-        use crate::schema::{system_metrics, process_trends, crash_logs, threads, ai_recommendations};
+    /// 清理旧数据（保留最近 `retention.hours` 小时的数据）
+    pub fn cleanup_old_data(
+        conn: &mut SqliteConnection,
+        retention: &RetentionConfig,
+    ) -> Result<()> {
+        use crate::schema::{
+            ai_recommendations, crash_logs, ingest_ledger, process_trends, system_metrics, threads,
+        };
+        use chrono::{Duration, Utc};
         use diesel::prelude::*;
-        use chrono::{Utc, Duration};
 
-        let cutoff_timestamp = Utc::now().timestamp() - 24 * 3600;
-        let cutoff_datetime = Utc::now().naive_utc() - Duration::hours(24);
+        let cutoff_timestamp = Utc::now().timestamp() - retention.hours * 3600;
+        let cutoff_datetime = Utc::now().naive_utc() - Duration::hours(retention.hours);
 
         // 清理系统指标
-        diesel::delete(system_metrics::table.filter(system_metrics::timestamp.lt(cutoff_timestamp)))
-            .execute(conn)?;
+        diesel::delete(
+            system_metrics::table.filter(system_metrics::timestamp.lt(cutoff_timestamp)),
+        )
+        .execute(conn)?;
 
         // 清理进程趋势
-        diesel::delete(process_trends::table.filter(process_trends::timestamp.lt(cutoff_timestamp)))
-            .execute(conn)?;
+        diesel::delete(
+            process_trends::table.filter(process_trends::timestamp.lt(cutoff_timestamp)),
+        )
+        .execute(conn)?;
 
         // 清理没有趋势数据的进程（即不活跃超过24小时的进程）
         diesel::sql_query("DELETE FROM processes WHERE NOT EXISTS (SELECT 1 FROM process_trends WHERE process_trends.server_id = processes.server_id AND process_trends.pid = processes.pid)")
@@ -1063,16 +2286,32 @@ impl DataCleanService {
         diesel::sql_query("DELETE FROM threads WHERE NOT EXISTS (SELECT 1 FROM processes WHERE processes.server_id = threads.server_id AND processes.pid = threads.pid)")
             .execute(conn)?;
 
-        // 清理崩溃日志
+        // 清理崩溃日志（未迁移到 v1 的数据库没有级联删除，先删除其 AI 建议）
+        diesel::delete(
+            ai_recommendations::table.filter(
+                ai_recommendations::crash_log_id.eq_any(
+                    crash_logs::table
+                        .filter(crash_logs::timestamp.lt(cutoff_timestamp))
+                        .select(crash_logs::id),
+                ),
+            ),
+        )
+        .execute(conn)?;
         diesel::delete(crash_logs::table.filter(crash_logs::timestamp.lt(cutoff_timestamp)))
             .execute(conn)?;
-            
+
         // 清理线程快照
         diesel::delete(threads::table.filter(threads::created_at.lt(cutoff_datetime)))
             .execute(conn)?;
-            
+
         // 清理 AI 建议
-        diesel::delete(ai_recommendations::table.filter(ai_recommendations::created_at.lt(cutoff_datetime)))
+        diesel::delete(
+            ai_recommendations::table.filter(ai_recommendations::created_at.lt(cutoff_datetime)),
+        )
+        .execute(conn)?;
+
+        // 清理幂等台账，超过保留期的批次重复提交时会重新写入
+        diesel::delete(ingest_ledger::table.filter(ingest_ledger::received_at.lt(cutoff_datetime)))
             .execute(conn)?;
 
         Ok(())
@@ -1083,13 +2322,16 @@ impl DataCleanService {
         use crate::schema::*;
         use diesel::prelude::*;
 
+        diesel::delete(crash_events::table).execute(conn)?;
         diesel::delete(ai_recommendations::table).execute(conn)?;
         diesel::delete(crash_logs::table).execute(conn)?;
         diesel::delete(threads::table).execute(conn)?;
         diesel::delete(process_trends::table).execute(conn)?;
         diesel::delete(processes::table).execute(conn)?;
         diesel::delete(system_metrics::table).execute(conn)?;
+        diesel::delete(server_labels::table).execute(conn)?;
         diesel::delete(servers::table).execute(conn)?;
+        diesel::delete(ingest_ledger::table).execute(conn)?;
 
         Ok(())
     }
@@ -1100,9 +2342,13 @@ pub struct JsonImportService;
 
 impl JsonImportService {
     /// 导入 JSON 数据
-    pub fn import_json_data(conn: &mut SqliteConnection, json_data: JsonData) -> Result<()> {
+    pub fn import_json_data(
+        conn: &mut SqliteConnection,
+        json_data: JsonData,
+        retention: &RetentionConfig,
+    ) -> Result<()> {
         // 导入前清理旧数据
-        let _ = DataCleanService::cleanup_old_data(conn);
+        let _ = DataCleanService::cleanup_old_data(conn, retention);
 
         for json_server in json_data.servers {
             // 检查服务器是否已存在
@@ -1122,6 +2368,10 @@ impl JsonImportService {
                 }
             }
 
+            for (key, value) in json_server.labels.iter().flatten() {
+                set_server_label(conn, &json_server.server_id, key, value)?;
+            }
+
             // 导入系统指标数据
             for json_metric in json_server.system_metrics {
                 let new_metric = NewSystemMetric {
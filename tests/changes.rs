@@ -0,0 +1,124 @@
+use blackbox::changes::ExportCursor;
+use blackbox::*;
+use diesel::prelude::*;
+
+const SERVERS: &str = r#"[
+    {"serverId": "srv-01", "serverName": "web", "serverIp": "10.0.0.1", "serverOs": "Kylin", "serverStatus": "running"},
+    {"serverId": "srv-02", "serverName": "db", "serverIp": "10.0.0.2", "serverOs": "Kylin", "serverStatus": "running"}
+]"#;
+
+fn set_updated_at(conn: &mut SqliteConnection, server: &str, at: &str) {
+    diesel::sql_query(format!(
+        "UPDATE servers SET updated_at = '{at}' WHERE server_id = '{server}'"
+    ))
+    .execute(conn)
+    .unwrap();
+}
+
+/// 导出一次，返回变更的 (op, 服务器 id) 和新的游标
+fn export(blackbox: &BlackBox, since: &ExportCursor) -> (Vec<(String, String)>, ExportCursor) {
+    let mut output = Vec::new();
+    let summary = blackbox.export_changes(since, &mut output).unwrap();
+    let changes = String::from_utf8(output)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .filter(|record| record["type"] == "change" && record["table"] == "servers")
+        .map(|record| {
+            (
+                record["op"].as_str().unwrap().to_string(),
+                record["row"]["server_id"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect();
+    (changes, summary.cursor)
+}
+
+/// 首次全量导出，之后只导出游标之后新增和修改的行；游标可以保存到文件再读回
+#[test]
+fn export_changes_since_cursor() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("changes.db").to_string_lossy().to_string();
+    let blackbox = BlackBox::new(Some(path.clone()));
+    blackbox.init_database(true).unwrap();
+    blackbox
+        .smart_insert(SmartDataType::Servers, SERVERS, false)
+        .unwrap();
+
+    let (changes, cursor) = export(&blackbox, &ExportCursor::default());
+    assert_eq!(
+        changes,
+        vec![
+            ("insert".to_string(), "srv-01".to_string()),
+            ("insert".to_string(), "srv-02".to_string())
+        ]
+    );
+
+    let cursor_path = dir.path().join("cursor.json").to_string_lossy().to_string();
+    cursor.save(&cursor_path).unwrap();
+    let cursor = ExportCursor::load(&cursor_path).unwrap();
+    let (changes, cursor) = export(&blackbox, &cursor);
+    assert!(changes.is_empty());
+
+    let mut conn = SqliteConnection::establish(&path).unwrap();
+    set_updated_at(&mut conn, "srv-01", "2099-01-01 00:00:00");
+    blackbox
+        .smart_insert(
+            SmartDataType::Servers,
+            r#"[{"serverId": "srv-03", "serverName": "cache", "serverIp": "10.0.0.3", "serverOs": "Kylin", "serverStatus": "running"}]"#,
+            false,
+        )
+        .unwrap();
+
+    let (mut changes, cursor) = export(&blackbox, &cursor);
+    changes.sort();
+    assert_eq!(
+        changes,
+        vec![
+            ("insert".to_string(), "srv-03".to_string()),
+            ("update".to_string(), "srv-01".to_string())
+        ]
+    );
+    assert!(export(&blackbox, &cursor).0.is_empty());
+
+    assert!(
+        ExportCursor::load(&dir.path().join("missing.json").to_string_lossy())
+            .unwrap()
+            .tables
+            .is_empty()
+    );
+}
+
+/// 与游标同一毫秒修改的行按 id 区分，秒精度和毫秒精度的时间按同一格式比较
+#[test]
+fn cursor_breaks_updated_at_ties_by_id() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("changes.db").to_string_lossy().to_string();
+    let blackbox = BlackBox::new(Some(path.clone()));
+    blackbox.init_database(true).unwrap();
+    blackbox
+        .smart_insert(SmartDataType::Servers, SERVERS, false)
+        .unwrap();
+
+    let mut conn = SqliteConnection::establish(&path).unwrap();
+    set_updated_at(&mut conn, "srv-01", "2026-10-18 10:00:00.500");
+    set_updated_at(&mut conn, "srv-02", "2026-10-18 09:00:00");
+
+    let (changes, cursor) = export(&blackbox, &ExportCursor::default());
+    assert_eq!(changes.len(), 2);
+    let servers = &cursor.tables["servers"];
+    assert_eq!(servers.last_updated_id, 1);
+
+    // 游标之后提交、但 updated_at 与游标相同的修改
+    set_updated_at(&mut conn, "srv-02", "2026-10-18 10:00:00.500");
+    let (changes, cursor) = export(&blackbox, &cursor);
+    assert_eq!(changes, vec![("update".to_string(), "srv-02".to_string())]);
+
+    // CURRENT_TIMESTAMP 写入的秒精度时间
+    set_updated_at(&mut conn, "srv-01", "2026-10-18 10:00:01");
+    let (changes, cursor) = export(&blackbox, &cursor);
+    assert_eq!(changes, vec![("update".to_string(), "srv-01".to_string())]);
+
+    let (changes, _) = export(&blackbox, &cursor);
+    assert!(changes.is_empty());
+}
@@ -0,0 +1,84 @@
+use blackbox::*;
+
+const COMBINED: &str = r#"{
+    "process": [{
+        "serverId": "srv-01", "serverName": "web", "serverIp": "10.0.0.1", "serverOs": "Kylin", "serverStatus": "running",
+        "pid": 42, "name": "worker", "userName": "root", "status": "S", "timestamp": 1700000000000,
+        "trend": [{"cpuUsage": 1.0, "memoryUsage": 2.0, "threadCount": 150}],
+        "threads": []
+    }],
+    "metrics": [],
+    "dmesg": "[  1.0] custom watchdog: hard LOCKUP on cpu 3"
+}"#;
+
+/// 配置文件中未出现的项使用默认值，未知的项被拒绝
+#[test]
+fn config_file_merges_with_defaults() {
+    let config = BlackBoxConfig::from_toml("[retention]\nhours = 48\n").unwrap();
+    assert_eq!(config.retention.hours, 48);
+    assert_eq!(
+        config.retention.clean_days,
+        RetentionConfig::default().clean_days
+    );
+    assert_eq!(config.connection, ConnectionOptions::default());
+
+    assert_eq!(
+        BlackBoxConfig::from_toml(&config.to_toml().unwrap()).unwrap(),
+        config
+    );
+    assert!(BlackBoxConfig::from_toml("[retention]\nhourz = 48\n").is_err());
+}
+
+/// 无效的输出格式在读取配置时作为校验错误拒绝
+#[test]
+fn invalid_output_format_is_a_validation_error() {
+    assert_eq!(
+        BlackBoxConfig::from_toml("[output]\nformat = \"JSON\"\n")
+            .unwrap()
+            .output
+            .format,
+        "JSON"
+    );
+
+    let error = BlackBoxConfig::from_toml("[output]\nformat = \"xml\"\n").unwrap_err();
+    assert!(
+        matches!(error, BlackBoxError::Validation { .. }),
+        "{:?}",
+        error
+    );
+}
+
+/// 线程数阈值和崩溃特征字符串来自配置
+#[test]
+fn detection_rules_come_from_config() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.db").to_string_lossy().to_string();
+
+    let defaults = BlackBox::builder().db_path(path.clone()).build();
+    defaults.init_database(true).unwrap();
+    defaults
+        .smart_insert(SmartDataType::Combined, COMBINED, false)
+        .unwrap();
+    assert!(
+        defaults.query_servers(None, None).unwrap()[0]
+            .crashes
+            .is_empty()
+    );
+
+    let tuned = BlackBox::builder()
+        .db_path(path)
+        .thread_exception_threshold(100)
+        .crash_indicators(vec!["hard LOCKUP".to_string()])
+        .build();
+    tuned
+        .smart_insert(SmartDataType::Combined, COMBINED, false)
+        .unwrap();
+
+    let crash_types: Vec<CrashType> = tuned.query_servers(None, None).unwrap()[0]
+        .crashes
+        .iter()
+        .map(|detail| detail.crash_log.crash_type.clone())
+        .collect();
+    assert!(crash_types.contains(&CrashType::ThreadException));
+    assert!(crash_types.contains(&CrashType::KernelException));
+}
@@ -0,0 +1,188 @@
+use blackbox::*;
+use diesel::connection::SimpleConnection;
+use diesel::prelude::*;
+use diesel::sql_types::Text;
+use std::time::Duration;
+
+#[derive(QueryableByName)]
+struct Pragma {
+    #[diesel(sql_type = Text)]
+    value: String,
+}
+
+/// 读取 PRAGMA 的当前值
+fn pragma(conn: &mut SqliteConnection, name: &str) -> String {
+    diesel::sql_query(format!(
+        "SELECT CAST((SELECT * FROM pragma_{}()) AS TEXT) AS value",
+        name
+    ))
+    .get_result::<Pragma>(conn)
+    .unwrap()
+    .value
+}
+
+const SERVERS: &str = r#"[{"serverId": "srv-01", "serverName": "web", "serverIp": "10.0.0.1", "serverOs": "Kylin", "serverStatus": "running"}]"#;
+
+/// 新建的连接按连接参数设置日志模式、同步级别和等待时间
+#[test]
+fn connections_apply_pragmas() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("pragma.db").to_string_lossy().to_string();
+
+    let manager = DatabaseManager::new(Some(path.clone()));
+    let mut conn = manager.get_connection().unwrap();
+    assert_eq!(pragma(&mut conn, "journal_mode"), "wal");
+    // NORMAL
+    assert_eq!(pragma(&mut conn, "synchronous"), "1");
+    assert_eq!(pragma(&mut conn, "busy_timeout"), "5000");
+    assert_eq!(pragma(&mut conn, "cache_size"), "-8000");
+    drop(conn);
+
+    // 切换日志模式需要独占数据库，先关闭上面的连接
+    drop(manager);
+    let manager = DatabaseManager::with_options(
+        Some(path),
+        ConnectionOptions {
+            journal_mode: "DELETE".to_string(),
+            synchronous: "FULL".to_string(),
+            busy_timeout_ms: 250,
+            ..Default::default()
+        },
+    );
+    let mut conn = manager.get_connection().unwrap();
+    assert_eq!(pragma(&mut conn, "journal_mode"), "delete");
+    assert_eq!(pragma(&mut conn, "synchronous"), "2");
+    assert_eq!(pragma(&mut conn, "busy_timeout"), "250");
+    drop(conn);
+
+    // PRAGMA 不能绑定参数，非法取值直接拒绝
+    let manager = DatabaseManager::with_options(
+        Some(dir.path().join("invalid.db").to_string_lossy().to_string()),
+        ConnectionOptions {
+            journal_mode: "WAL; DROP TABLE servers".to_string(),
+            ..Default::default()
+        },
+    );
+    assert!(matches!(
+        manager.get_connection().err().unwrap(),
+        BlackBoxError::Validation { .. }
+    ));
+}
+
+/// 归还的连接被复用，超过空闲上限的连接直接关闭
+#[test]
+fn connections_are_reused() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("reuse.db").to_string_lossy().to_string();
+
+    // 临时表只在创建它的连接中可见
+    let marked =
+        |conn: &mut SqliteConnection| conn.batch_execute("SELECT * FROM temp.marker").is_ok();
+
+    let manager = DatabaseManager::new(Some(path.clone()));
+    let mut conn = manager.get_connection().unwrap();
+    conn.batch_execute("CREATE TEMP TABLE marker (x)").unwrap();
+    // 同时借出的是另一个连接
+    let mut other = manager.get_connection().unwrap();
+    assert!(!marked(&mut other));
+    drop(other);
+    drop(conn);
+    // 最后归还的连接最先借出
+    assert!(marked(&mut manager.get_connection().unwrap()));
+
+    // 关闭空闲连接后重新打开
+    manager.close_idle_connections();
+    assert!(!marked(&mut manager.get_connection().unwrap()));
+
+    let manager = DatabaseManager::with_options(
+        Some(path),
+        ConnectionOptions {
+            max_idle_connections: 0,
+            ..Default::default()
+        },
+    );
+    let mut conn = manager.get_connection().unwrap();
+    conn.batch_execute("CREATE TEMP TABLE marker (x)").unwrap();
+    drop(conn);
+    assert!(!marked(&mut manager.get_connection().unwrap()));
+}
+
+/// 只重试锁冲突，重试次数用完后返回 Busy
+#[test]
+fn busy_errors_are_retried_with_backoff() {
+    let options = ConnectionOptions {
+        busy_retries: 3,
+        retry_backoff_ms: 1,
+        ..Default::default()
+    };
+    let busy = || BlackBoxError::Busy {
+        message: "database is locked".to_string(),
+    };
+
+    let mut attempts = 0;
+    let result = with_busy_retry(&options, || {
+        attempts += 1;
+        if attempts < 3 {
+            Err(busy())
+        } else {
+            Ok(attempts)
+        }
+    });
+    assert_eq!(result.unwrap(), 3);
+
+    let mut attempts = 0;
+    let result: Result<()> = with_busy_retry(&options, || {
+        attempts += 1;
+        Err(busy())
+    });
+    assert!(result.unwrap_err().is_busy());
+    assert_eq!(attempts, 4);
+
+    let mut attempts = 0;
+    let result: Result<()> = with_busy_retry(&options, || {
+        attempts += 1;
+        Err(BlackBoxError::validation("x"))
+    });
+    assert!(result.is_err());
+    assert_eq!(attempts, 1);
+}
+
+/// 其他连接持有写锁时，智能插入等待锁释放后重试成功
+#[test]
+fn smart_insert_retries_while_database_is_locked() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("locked.db").to_string_lossy().to_string();
+    let build = |busy_retries: u32| {
+        BlackBox::builder()
+            .db_path(path.clone())
+            .connection_options(ConnectionOptions {
+                busy_timeout_ms: 0,
+                busy_retries,
+                retry_backoff_ms: 50,
+                ..Default::default()
+            })
+            .build()
+    };
+    build(0).init_database(true).unwrap();
+
+    let mut holder = SqliteConnection::establish(&path).unwrap();
+    holder.batch_execute("BEGIN IMMEDIATE").unwrap();
+
+    // 不重试时立即返回 Busy
+    let error = build(0)
+        .smart_insert(SmartDataType::Servers, SERVERS, false)
+        .unwrap_err();
+    assert!(error.is_busy(), "{:?}", error);
+
+    let release = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(150));
+        holder.batch_execute("COMMIT").unwrap();
+    });
+    let blackbox = build(5);
+    let result = blackbox
+        .smart_insert(SmartDataType::Servers, SERVERS, false)
+        .unwrap();
+    release.join().unwrap();
+    assert_eq!(result.success_count, 1);
+    assert_eq!(blackbox.query_servers(None, None).unwrap().len(), 1);
+}
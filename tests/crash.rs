@@ -0,0 +1,106 @@
+use blackbox::crash::CrashLogFilter;
+use blackbox::*;
+
+const SERVER: &str = r#"[{"serverId": "srv-01", "serverName": "web", "serverIp": "10.0.0.1", "serverOs": "Kylin", "serverStatus": "running"}]"#;
+const CRASH_LOG: &str = r#"[{"serverId": "srv-01", "logId": 1, "timestamp": 1700000000000, "crashType": "segfault", "severity": "high", "title": "nginx", "message": "SIGSEGV", "resolved": false}]"#;
+
+/// 解决、重新打开和备注按顺序记入历史，重复操作返回冲突且不留下记录
+#[test]
+fn crash_log_lifecycle_is_recorded() {
+    let dir = tempfile::tempdir().unwrap();
+    let blackbox = BlackBox::new(Some(
+        dir.path().join("crash.db").to_string_lossy().to_string(),
+    ));
+    blackbox.init_database(true).unwrap();
+    blackbox
+        .smart_insert(SmartDataType::Servers, SERVER, false)
+        .unwrap();
+    blackbox
+        .smart_insert(SmartDataType::CrashLogs, CRASH_LOG, false)
+        .unwrap();
+
+    let unresolved = CrashLogFilter {
+        resolved: Some(false),
+        ..Default::default()
+    };
+    let id = blackbox.list_crash_logs(&unresolved).unwrap()[0].id;
+
+    assert!(
+        blackbox
+            .resolve_crash_log(id, "alice", Some("重启修复"))
+            .unwrap()
+            .resolved
+    );
+    let error = blackbox.resolve_crash_log(id, "alice", None).unwrap_err();
+    assert!(matches!(error, BlackBoxError::Conflict { .. }));
+    assert!(blackbox.list_crash_logs(&unresolved).unwrap().is_empty());
+
+    blackbox.annotate_crash_log(id, "bob", "继续观察").unwrap();
+    assert!(!blackbox.reopen_crash_log(id, "bob", None).unwrap().resolved);
+    assert!(matches!(
+        blackbox.annotate_crash_log(id, "bob", " ").unwrap_err(),
+        BlackBoxError::Validation { .. }
+    ));
+
+    let history = blackbox.crash_log_history(id).unwrap();
+    let events: Vec<_> = history
+        .events
+        .iter()
+        .map(|event| {
+            (
+                event.action.clone(),
+                event.actor.as_str(),
+                event.note.as_deref(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        events,
+        vec![
+            (CrashAction::Resolved, "alice", Some("重启修复")),
+            (CrashAction::Note, "bob", Some("继续观察")),
+            (CrashAction::Reopened, "bob", None),
+        ]
+    );
+
+    assert!(matches!(
+        blackbox.crash_log_history(id + 1).unwrap_err(),
+        BlackBoxError::NotFound { .. }
+    ));
+}
+
+/// 采集端重复上报已解决的崩溃日志时更新内容，但不会悄悄重新打开
+#[test]
+fn resending_keeps_resolved_state() {
+    let dir = tempfile::tempdir().unwrap();
+    let blackbox = BlackBox::new(Some(
+        dir.path().join("crash.db").to_string_lossy().to_string(),
+    ));
+    blackbox.init_database(true).unwrap();
+    blackbox
+        .smart_insert(SmartDataType::Servers, SERVER, false)
+        .unwrap();
+    blackbox
+        .smart_insert(SmartDataType::CrashLogs, CRASH_LOG, false)
+        .unwrap();
+
+    let id = blackbox
+        .list_crash_logs(&CrashLogFilter::default())
+        .unwrap()[0]
+        .id;
+    blackbox.resolve_crash_log(id, "alice", None).unwrap();
+
+    let result = blackbox
+        .smart_insert(
+            SmartDataType::CrashLogs,
+            &CRASH_LOG.replace("SIGSEGV", "SIGSEGV again"),
+            false,
+        )
+        .unwrap();
+    assert_eq!(result.updated_count, 1);
+
+    let history = blackbox.crash_log_history(id).unwrap();
+    assert!(history.crash_log.resolved);
+    assert_eq!(history.crash_log.message, "SIGSEGV again");
+    assert_eq!(history.events.len(), 1);
+}
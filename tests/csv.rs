@@ -0,0 +1,241 @@
+use blackbox::csv_io::CsvTable;
+use blackbox::*;
+use std::path::Path;
+use std::process::Command;
+
+fn open(path: &Path) -> BlackBox {
+    let blackbox = BlackBox::new(Some(path.to_string_lossy().to_string()));
+    blackbox.init_database(true).unwrap();
+    blackbox
+}
+
+fn detail(blackbox: &BlackBox) -> ServerDetail {
+    blackbox
+        .query_servers(Some("srv-01"), None)
+        .unwrap()
+        .remove(0)
+}
+
+const SERVERS: &str =
+    "id,serverStatus,serverId,serverName,serverIp,serverOs\n9,running,srv-01,web,10.0.0.1,Kylin\n";
+
+/// 表头按 snake_case 或 camelCase 映射，列顺序任意，多余的列被忽略
+#[test]
+fn csv_insert_maps_columns_by_header() {
+    let dir = tempfile::tempdir().unwrap();
+    let blackbox = open(&dir.path().join("csv.db"));
+    let now = chrono::Utc::now().timestamp_millis();
+
+    let result = blackbox
+        .smart_insert_csv(SmartDataType::Servers, SERVERS, false)
+        .unwrap();
+    assert_eq!(result.success_count, 1);
+    let server = detail(&blackbox).server;
+    assert_eq!(server.server_name, "web");
+    assert_eq!(server.server_os, "Kylin");
+    assert_eq!(server.server_status, ServerStatus::Running);
+
+    let metrics = format!(
+        "server_id, timestamp ,cpu_usage,memory_usage,disk_usage,io_read,io_write,network_in,network_out,created_at\n\
+         srv-01,{now},10.5,20,30,1,2,3,4,2026-10-18T00:00:00\n"
+    );
+    let result = blackbox
+        .smart_insert_csv(SmartDataType::SystemMetrics, &metrics, false)
+        .unwrap();
+    assert_eq!(result.success_count, 1);
+    let metric = &detail(&blackbox).metrics[0];
+    assert_eq!(metric.timestamp, now);
+    assert_eq!(metric.cpu_usage, 10.5);
+    assert_eq!(metric.network_out, 4.0);
+
+    // 再次插入同一时间戳按智能插入规则更新
+    let result = blackbox
+        .smart_insert_csv(
+            SmartDataType::SystemMetrics,
+            &metrics.replace("10.5", "11.5"),
+            false,
+        )
+        .unwrap();
+    assert_eq!(result.updated_count, 1);
+    assert_eq!(detail(&blackbox).metrics[0].cpu_usage, 11.5);
+
+    let processes = format!(
+        "serverId,pid,name,userName,status,timestamp,cpuUsage,memoryUsage,threadCount\n\
+         srv-01,42,worker,root,Ss,{now},1.5,2.5,7\n"
+    );
+    blackbox
+        .smart_insert_csv(SmartDataType::Processes, &processes, false)
+        .unwrap();
+    let process = &detail(&blackbox).processes[0];
+    assert_eq!(process.process.name, "worker");
+    assert_eq!(process.process.status, ProcessState::Sleeping);
+    assert_eq!(process.trends.len(), 1);
+    assert_eq!(process.trends[0].thread_count, 7);
+    assert_eq!(process.trends[0].cpu_usage, 1.5);
+
+    let error = blackbox
+        .smart_insert_csv(SmartDataType::Combined, "process\n", false)
+        .unwrap_err();
+    assert!(matches!(error, BlackBoxError::Validation { .. }));
+}
+
+/// 类型错误带行号和列名；默认整体拒绝，continue_on_error 时跳过错误行
+#[test]
+fn csv_row_errors_report_line_and_column() {
+    let dir = tempfile::tempdir().unwrap();
+    let blackbox = open(&dir.path().join("csv.db"));
+    blackbox
+        .smart_insert_csv(SmartDataType::Servers, SERVERS, false)
+        .unwrap();
+
+    let now = chrono::Utc::now().timestamp_millis();
+    let metrics = format!(
+        "serverId,timestamp,cpuUsage,memoryUsage,diskUsage,ioRead,ioWrite,networkIn,networkOut\n\
+         srv-01,{},10,20,30,1,2,3,4\n\
+         srv-01,{},abc,20,30,1,2,3,4\n",
+        now,
+        now + 1000
+    );
+
+    match blackbox
+        .smart_insert_csv(SmartDataType::SystemMetrics, &metrics, false)
+        .unwrap_err()
+    {
+        BlackBoxError::Parse { format, message } => {
+            assert_eq!(format, "CSV");
+            assert!(message.contains("第 3 行 cpuUsage 列"), "{}", message);
+        }
+        error => panic!("{:?}", error),
+    }
+    assert!(detail(&blackbox).metrics.is_empty());
+
+    let result = blackbox
+        .smart_insert_csv(SmartDataType::SystemMetrics, &metrics, true)
+        .unwrap();
+    assert_eq!(result.success_count, 1);
+    assert_eq!(result.error_count, 1);
+    assert!(result.errors[0].contains("第 3 行 cpuUsage 列"));
+    assert_eq!(detail(&blackbox).metrics.len(), 1);
+}
+
+/// 按表导出的 CSV 带全部列，可以直接插入另一个数据库
+#[test]
+fn csv_export_per_table_round_trips() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("source.db");
+    let blackbox = open(&db);
+    let now = chrono::Utc::now().timestamp_millis();
+    blackbox
+        .smart_insert(
+            SmartDataType::Combined,
+            &format!(
+                r#"{{
+                    "process": [{{
+                        "serverId": "srv-01", "serverName": "web", "serverIp": "10.0.0.1", "serverOs": "Kylin", "serverStatus": "running",
+                        "pid": 42, "name": "worker", "userName": "root", "status": "S", "timestamp": {now},
+                        "trend": [{{"cpuUsage": 1.0, "memoryUsage": 2.0, "threadCount": 5}}],
+                        "threads": [{{"threadId": 43, "userName": "root", "priority": 20, "niceValue": 0, "virtualMemory": "1024", "residentMemory": "512",
+                                     "sharedMemory": "128", "status": "S", "cpuUsage": "0.5", "memoryUsage": "0.1", "runtime": "00:01", "command": "io"}}]
+                    }}],
+                    "metrics": [
+                        {{"serverId": "srv-01", "timestamp": {now}, "cpuUsage": 10.0, "memoryUsage": 20.0, "diskUsage": 30.0, "ioRead": 1.0, "ioWrite": 2.0, "networkIn": 3.0, "networkOut": 4.0}},
+                        {{"serverId": "srv-01", "timestamp": {}, "cpuUsage": 11.0, "memoryUsage": 21.0, "diskUsage": 31.0, "ioRead": 1.0, "ioWrite": 2.0, "networkIn": 3.0, "networkOut": 4.0}}
+                    ]
+                }}"#,
+                now + 1000
+            ),
+            false,
+        )
+        .unwrap();
+    blackbox
+        .smart_insert(
+            SmartDataType::CrashLogs,
+            &format!(
+                r#"[{{"serverId": "srv-01", "logId": 1, "timestamp": {now}, "crashType": "segfault", "severity": "high", "title": "nginx", "message": "SIGSEGV, core dumped", "resolved": false}}]"#
+            ),
+            false,
+        )
+        .unwrap();
+
+    // 单张表
+    let metrics_csv = dir.path().join("metrics.csv");
+    assert_eq!(
+        blackbox
+            .export_table_to_csv(CsvTable::SystemMetrics, metrics_csv.to_str().unwrap())
+            .unwrap(),
+        2
+    );
+    let content = std::fs::read_to_string(&metrics_csv).unwrap();
+    let mut reader = csv::Reader::from_reader(content.as_bytes());
+    assert_eq!(
+        reader.headers().unwrap().iter().collect::<Vec<_>>(),
+        [
+            "id",
+            "server_id",
+            "timestamp",
+            "cpu_usage",
+            "memory_usage",
+            "disk_usage",
+            "io_read",
+            "io_write",
+            "network_in",
+            "network_out",
+            "created_at",
+            "updated_at",
+        ]
+    );
+    assert_eq!(reader.records().count(), 2);
+
+    // 命令行导出全部表到目录，每张表一个文件
+    let out = dir.path().join("csv");
+    let status = Command::new(env!("CARGO_BIN_EXE_blackbox"))
+        .arg("--db")
+        .arg(&db)
+        .args(["export", "--format", "csv", "--file"])
+        .arg(format!("{}/", out.display()))
+        .env_remove("BLACKBOX_CONFIG")
+        .output()
+        .unwrap();
+    assert!(
+        status.status.success(),
+        "{}",
+        String::from_utf8_lossy(&status.stderr)
+    );
+    let rows = |table: CsvTable| {
+        let path = out.join(format!("{}.csv", table.table_name()));
+        csv::Reader::from_path(path).unwrap().records().count()
+    };
+    let counts: Vec<usize> = CsvTable::ALL.iter().map(|table| rows(*table)).collect();
+    // servers, system_metrics, processes, process_trends, threads, crash_logs, ai_recommendations
+    assert_eq!(counts, vec![1, 2, 1, 1, 1, 1, 0]);
+
+    let target = open(&dir.path().join("target.db"));
+    for (data_type, table) in [
+        (SmartDataType::Servers, CsvTable::Servers),
+        (SmartDataType::SystemMetrics, CsvTable::SystemMetrics),
+        (SmartDataType::CrashLogs, CsvTable::CrashLogs),
+    ] {
+        let content =
+            std::fs::read_to_string(out.join(format!("{}.csv", table.table_name()))).unwrap();
+        let result = target.smart_insert_csv(data_type, &content, false).unwrap();
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+    }
+
+    let source = detail(&blackbox);
+    let copied = detail(&target);
+    assert_eq!(copied.server.server_name, source.server.server_name);
+    let values = |detail: &ServerDetail| {
+        detail
+            .metrics
+            .iter()
+            .map(|metric| (metric.timestamp, metric.cpu_usage, metric.disk_usage))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(values(&copied), values(&source));
+    assert_eq!(copied.crashes.len(), 1);
+    assert_eq!(copied.crashes[0].crash_log.message, "SIGSEGV, core dumped");
+    assert_eq!(
+        copied.crashes[0].crash_log.crash_type,
+        CrashType::SegmentationFault
+    );
+}
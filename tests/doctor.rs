@@ -0,0 +1,229 @@
+use blackbox::{BlackBox, BlackBoxConfig, SmartDataType};
+use diesel::connection::SimpleConnection;
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Text};
+use std::path::Path;
+
+fn open_with_data(path: &str) -> BlackBox {
+    let data_json = Path::new(env!("CARGO_MANIFEST_DIR")).join("data.json");
+    let blackbox = BlackBox::new(Some(path.to_string()));
+    blackbox.init_database(true).unwrap();
+    blackbox
+        .import_json_data(data_json.to_str().unwrap(), false)
+        .unwrap();
+    blackbox
+}
+
+/// 关闭外键检查后写入一条重复指标和一条没有服务器的进程
+fn break_consistency(path: &str) {
+    SqliteConnection::establish(path)
+        .unwrap()
+        .batch_execute(
+            "PRAGMA foreign_keys = OFF;
+             INSERT INTO system_metrics (server_id, timestamp, cpu_usage, memory_usage, disk_usage, io_read, io_write, network_in, network_out)
+                 SELECT server_id, timestamp, cpu_usage, memory_usage, disk_usage, io_read, io_write, network_in, network_out
+                 FROM system_metrics LIMIT 1;
+             INSERT INTO processes (server_id, pid, name, user_name, status) VALUES ('gone', 1, 'x', 'root', 'S');",
+        )
+        .unwrap();
+}
+
+/// 诊断发现重复指标和孤儿数据，修复后数据库恢复健康
+#[test]
+fn diagnose_and_repair_logical_problems() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("doctor.db").to_string_lossy().to_string();
+    let blackbox = open_with_data(&path);
+
+    let report = blackbox.diagnose(false).unwrap();
+    assert!(report.is_healthy(), "{:?}", report);
+
+    break_consistency(&path);
+    let report = blackbox.diagnose(true).unwrap();
+    assert!(report.integrity_errors.is_empty());
+    assert!(report.schema_ok());
+    assert_eq!(report.duplicate_metrics, 1);
+    assert_eq!(report.orphans.count("processes"), 1);
+    assert!(!report.is_healthy());
+
+    let repaired = blackbox.repair().unwrap();
+    assert_eq!(repaired.duplicate_metrics_removed, 1);
+    assert_eq!(repaired.orphans_removed.total(), 1);
+    assert!(blackbox.diagnose(false).unwrap().is_healthy());
+
+    SqliteConnection::establish(&path)
+        .unwrap()
+        .batch_execute("DROP TABLE threads")
+        .unwrap();
+    let report = blackbox.diagnose(false).unwrap();
+    assert_eq!(report.missing_tables, vec!["threads".to_string()]);
+    assert!(!report.schema_ok());
+}
+
+/// 抢救到新数据库的数据跳过孤儿行和重复指标，结果可以直接使用
+#[test]
+fn salvage_skips_orphans_and_duplicates() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("damaged.db").to_string_lossy().to_string();
+    let blackbox = open_with_data(&path);
+    let expected = blackbox.get_statistics().unwrap();
+    break_consistency(&path);
+
+    let target_path = dir.path().join("rescued.db").to_string_lossy().to_string();
+    let report = blackbox.salvage_to(&target_path).unwrap();
+    assert!(report.tables.iter().all(|table| table.failed == 0));
+    let processes = report
+        .tables
+        .iter()
+        .find(|table| table.table == "processes")
+        .unwrap();
+    assert_eq!(
+        processes.copied,
+        expected
+            .servers
+            .iter()
+            .map(|s| s.processes_count)
+            .sum::<usize>()
+    );
+
+    let rescued = BlackBox::new(Some(target_path));
+    assert!(rescued.diagnose(false).unwrap().is_healthy());
+    let stats = rescued.get_statistics().unwrap();
+    assert_eq!(stats.server_count, expected.server_count);
+    for (actual, expected) in stats.servers.iter().zip(&expected.servers) {
+        assert_eq!(actual.metrics_count, expected.metrics_count);
+        assert_eq!(actual.crashes_count, expected.crashes_count);
+    }
+}
+
+#[derive(QueryableByName)]
+struct TableName {
+    #[diesel(sql_type = Text)]
+    name: String,
+}
+
+#[derive(QueryableByName)]
+struct RowCount {
+    #[diesel(sql_type = BigInt)]
+    count: i64,
+}
+
+fn row_counts(path: &str) -> Vec<(String, i64)> {
+    let mut conn = SqliteConnection::establish(path).unwrap();
+    let tables = diesel::sql_query(
+        "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' AND name NOT LIKE '__diesel%' ORDER BY name",
+    )
+    .load::<TableName>(&mut conn)
+    .unwrap();
+    tables
+        .into_iter()
+        .map(|table| {
+            let count = diesel::sql_query(format!("SELECT COUNT(*) AS count FROM {}", table.name))
+                .get_result::<RowCount>(&mut conn)
+                .unwrap()
+                .count;
+            (table.name, count)
+        })
+        .collect()
+}
+
+/// 抢救会拷贝每一张表，只丢弃孤儿数据；诊断能发现标签和处理历史中的孤儿数据
+#[test]
+fn salvage_copies_every_table_except_orphans() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_json = Path::new(env!("CARGO_MANIFEST_DIR")).join("data.json");
+    let source_path = dir.path().join("source.db").to_string_lossy().to_string();
+
+    let mut config = BlackBoxConfig::from_toml(
+        r#"
+        [forward]
+        enabled = true
+
+        [[notify.commands]]
+        name = "noop"
+        program = "true"
+        "#,
+    )
+    .unwrap();
+    config.database.path = Some(source_path.clone());
+    let source = BlackBox::from_config(config);
+    source.init_database(true).unwrap();
+
+    source
+        .import_json_data(data_json.to_str().unwrap(), false)
+        .unwrap();
+    source
+        .set_server_labels("ukui-server-01", &[("env".to_string(), "prod".to_string())])
+        .unwrap();
+    let crash_log_id = source.list_crash_logs(&Default::default()).unwrap()[0].id;
+    source
+        .annotate_crash_log(crash_log_id, "alice", "已通知负责人")
+        .unwrap();
+    source
+        .smart_insert(
+            SmartDataType::CrashLogs,
+            r#"[{"serverId": "ukui-server-01", "logId": 9001, "timestamp": 1765700000000, "crashType": "oom", "severity": "high", "title": "t", "message": "m", "resolved": false}]"#,
+            false,
+        )
+        .unwrap();
+    source
+        .ingest_forwarded(
+            SmartDataType::Servers,
+            r#"[{"serverId": "edge-01", "serverName": "edge", "serverIp": "10.0.0.5", "serverOs": "Kylin", "serverStatus": "online"}]"#,
+            Some("edge-record-1"),
+            false,
+        )
+        .unwrap();
+    source.flush_notifications();
+
+    // 关闭外键检查后写入孤儿数据
+    let mut conn = SqliteConnection::establish(&source_path).unwrap();
+    diesel::sql_query("PRAGMA foreign_keys = OFF")
+        .execute(&mut conn)
+        .unwrap();
+    diesel::sql_query("INSERT INTO server_labels (server_id, label_key, label_value) VALUES ('gone', 'env', 'dev')")
+        .execute(&mut conn)
+        .unwrap();
+    diesel::sql_query(
+        "INSERT INTO crash_events (crash_log_id, action, actor) VALUES (999999, 'note', 'bob')",
+    )
+    .execute(&mut conn)
+    .unwrap();
+
+    let report = source.diagnose(false).unwrap();
+    assert_eq!(report.orphans.count("server_labels"), 1);
+    assert_eq!(report.orphans.count("crash_events"), 1);
+
+    let target_path = dir.path().join("rescued.db").to_string_lossy().to_string();
+    let salvaged = source.salvage_to(&target_path).unwrap();
+    assert!(salvaged.tables.iter().all(|table| table.failed == 0));
+
+    // 表和孤儿规则来自结构中的外键：父表总在子表之前
+    let position = |tables: &[&str], table: &str| tables.iter().position(|t| *t == table).unwrap();
+    let salvage_order: Vec<&str> = salvaged.tables.iter().map(|t| t.table.as_str()).collect();
+    let orphan_order: Vec<&str> = report
+        .orphans
+        .tables
+        .iter()
+        .map(|(t, _)| t.as_str())
+        .collect();
+    assert!(position(&salvage_order, "servers") < position(&salvage_order, "crash_logs"));
+    assert!(position(&salvage_order, "crash_logs") < position(&salvage_order, "crash_events"));
+    assert!(position(&orphan_order, "crash_logs") < position(&orphan_order, "ai_recommendations"));
+    assert!(!orphan_order.contains(&"servers"));
+
+    let before = row_counts(&source_path);
+    let after = row_counts(&target_path);
+    assert_eq!(
+        before.iter().map(|(table, _)| table).collect::<Vec<_>>(),
+        after.iter().map(|(table, _)| table).collect::<Vec<_>>()
+    );
+    for ((table, expected), (_, actual)) in before.iter().zip(&after) {
+        assert!(*expected > 0, "{} 不应为空", table);
+        let orphans = match table.as_str() {
+            "server_labels" | "crash_events" => 1,
+            _ => 0,
+        };
+        assert_eq!(*actual, expected - orphans, "{} 的行数不一致", table);
+    }
+}
@@ -0,0 +1,148 @@
+use blackbox::*;
+use diesel::connection::SimpleConnection;
+use diesel::prelude::*;
+use std::path::Path;
+use std::process::Command;
+
+/// 运行命令行，返回退出码和标准错误输出
+fn run(db: &Path, args: &[&str]) -> (i32, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_blackbox"))
+        .arg("--db")
+        .arg(db)
+        .args(args)
+        .env_remove("BLACKBOX_CONFIG")
+        .output()
+        .unwrap();
+    (
+        output.status.code().unwrap(),
+        String::from_utf8_lossy(&output.stderr).to_string(),
+    )
+}
+
+/// 唯一约束冲突归为 Conflict，锁冲突按 SQLITE_BUSY 归为 Busy
+#[test]
+fn database_errors_are_classified_by_code() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("error.db").to_string_lossy().to_string();
+
+    let mut conn = SqliteConnection::establish(&path).unwrap();
+    conn.batch_execute("CREATE TABLE t (key TEXT NOT NULL UNIQUE); INSERT INTO t VALUES ('a');")
+        .unwrap();
+    let error: BlackBoxError = conn
+        .batch_execute("INSERT INTO t VALUES ('a')")
+        .unwrap_err()
+        .into();
+    assert!(
+        matches!(error, BlackBoxError::Conflict { .. }),
+        "{:?}",
+        error
+    );
+
+    conn.batch_execute("BEGIN IMMEDIATE").unwrap();
+    let mut other = SqliteConnection::establish(&path).unwrap();
+    other.batch_execute("PRAGMA busy_timeout = 0").unwrap();
+    let error: BlackBoxError = other.batch_execute("BEGIN IMMEDIATE").unwrap_err().into();
+    assert!(error.is_busy(), "{:?}", error);
+
+    // 消息中含有 busy 字样的其他错误不是锁冲突
+    let error: BlackBoxError = other
+        .batch_execute("SELECT * FROM busy")
+        .unwrap_err()
+        .into();
+    assert!(
+        matches!(error, BlackBoxError::Database { .. }),
+        "{:?}",
+        error
+    );
+}
+
+/// 读写文件失败时错误中带有文件路径
+#[test]
+fn io_errors_name_the_path() {
+    let dir = tempfile::tempdir().unwrap();
+    let blackbox = BlackBox::new(Some(dir.path().join("io.db").to_string_lossy().to_string()));
+    blackbox.init_database(true).unwrap();
+
+    let missing = dir
+        .path()
+        .join("missing.json")
+        .to_string_lossy()
+        .to_string();
+    match blackbox.import_json_data(&missing, false).unwrap_err() {
+        BlackBoxError::Io { target, .. } => assert_eq!(target, missing),
+        error => panic!("{:?}", error),
+    }
+
+    let unwritable = dir
+        .path()
+        .join("no-such-dir")
+        .join("export.json")
+        .to_string_lossy()
+        .to_string();
+    match blackbox
+        .export_to_json(&unwritable, false, &ExportFilter::default())
+        .unwrap_err()
+    {
+        BlackBoxError::Io { target, .. } => assert_eq!(target, unwritable),
+        error => panic!("{:?}", error),
+    }
+}
+
+/// 命令行按错误类别设置退出码
+#[test]
+fn cli_exit_codes_follow_error_category() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("cli.db");
+    assert_eq!(run(&db, &["init"]).0, 0);
+
+    // 参数组合不合法：4
+    let (code, stderr) = run(&db, &["export", "--format", "csv", "--server", "srv-01"]);
+    assert_eq!(code, 4, "{}", stderr);
+    let (code, stderr) = run(
+        &db,
+        &["export", "--format", "csv", "--since", "cursor.json"],
+    );
+    assert_eq!(code, 4, "{}", stderr);
+
+    // 记录不存在：3
+    assert_eq!(run(&db, &["crash", "show", "999"]).0, 3);
+
+    // 冲突：5
+    let blackbox = BlackBox::new(Some(db.to_string_lossy().to_string()));
+    blackbox
+        .smart_insert(
+            SmartDataType::Servers,
+            r#"[{"serverId": "srv-01", "serverName": "web", "serverIp": "10.0.0.1", "serverOs": "Kylin", "serverStatus": "running"}]"#,
+            false,
+        )
+        .unwrap();
+    let now = chrono::Utc::now().timestamp_millis();
+    blackbox
+        .smart_insert(
+            SmartDataType::CrashLogs,
+            &format!(
+                r#"[{{"serverId": "srv-01", "logId": 1, "timestamp": {now}, "crashType": "segfault", "severity": "high", "title": "nginx", "message": "SIGSEGV", "resolved": false}}]"#
+            ),
+            false,
+        )
+        .unwrap();
+    let id = blackbox.list_crash_logs(&Default::default()).unwrap()[0]
+        .id
+        .to_string();
+    let (code, stderr) = run(&db, &["crash", "reopen", &id, "--by", "alice"]);
+    assert_eq!(code, 5, "{}", stderr);
+
+    // 文件读写失败：8，错误中带有路径
+    let missing = dir.path().join("missing.json");
+    let (code, stderr) = run(&db, &["import", "--file", missing.to_str().unwrap()]);
+    assert_eq!(code, 8, "{}", stderr);
+    assert!(stderr.contains(missing.to_str().unwrap()), "{}", stderr);
+
+    // 内容无法解析：9
+    let invalid = dir.path().join("invalid.json");
+    std::fs::write(&invalid, "not json").unwrap();
+    assert_eq!(
+        run(&db, &["import", "--file", invalid.to_str().unwrap()]).0,
+        9
+    );
+}
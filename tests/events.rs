@@ -0,0 +1,103 @@
+use blackbox::*;
+use std::sync::{Arc, Mutex};
+
+const COMBINED: &str = r#"{
+    "batchId": "combined-1",
+    "process": [{
+        "serverId": "srv-01", "serverName": "web", "serverIp": "10.0.0.1", "serverOs": "Kylin", "serverStatus": "warning",
+        "pid": 42, "name": "worker", "userName": "root", "status": "S", "timestamp": 1700000000000,
+        "trend": [{"cpuUsage": 1.0, "memoryUsage": 2.0, "threadCount": 5000}],
+        "threads": []
+    }],
+    "metrics": [{"serverId": "srv-01", "timestamp": 1700000000000, "cpuUsage": 10.0, "memoryUsage": 20.0, "diskUsage": 30.0, "ioRead": 1.0, "ioWrite": 2.0, "networkIn": 3.0, "networkOut": 4.0}]
+}"#;
+
+/// 按顺序记录收到的事件
+#[derive(Default)]
+struct Recorder(Mutex<Vec<String>>);
+
+impl Subscriber for Recorder {
+    fn on_server_created(&self, server: &Server) {
+        self.0
+            .lock()
+            .unwrap()
+            .push(format!("created {}", server.server_id));
+    }
+
+    fn on_status_changed(&self, server: &Server, previous: &ServerStatus) {
+        self.0.lock().unwrap().push(format!(
+            "status {} {} -> {}",
+            server.server_id, previous, server.server_status
+        ));
+    }
+
+    fn on_crash_detected(&self, crash_log: &CrashLog) {
+        self.0.lock().unwrap().push(format!(
+            "crash {} {}",
+            crash_log.server_id, crash_log.crash_type
+        ));
+    }
+
+    fn on_metric_inserted(&self, metric: &NewSystemMetric) {
+        self.0
+            .lock()
+            .unwrap()
+            .push(format!("metric {} {}", metric.server_id, metric.timestamp));
+    }
+}
+
+impl Recorder {
+    fn take(&self) -> Vec<String> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+/// 提交后的写入按顺序通知订阅者，回滚和幂等重放不通知
+#[test]
+fn subscribers_see_committed_events() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("events.db").to_string_lossy().to_string();
+    let recorder = Arc::new(Recorder::default());
+    let blackbox = BlackBox::builder()
+        .db_path(path)
+        .subscriber(recorder.clone())
+        .build();
+    blackbox.init_database(true).unwrap();
+
+    let servers = r#"[{"serverId": "srv-01", "serverName": "web", "serverIp": "10.0.0.1", "serverOs": "Kylin", "serverStatus": "running"}]"#;
+    blackbox
+        .smart_insert(SmartDataType::Servers, servers, false)
+        .unwrap();
+    blackbox
+        .smart_insert(SmartDataType::Servers, servers, false)
+        .unwrap();
+    assert_eq!(recorder.take(), vec!["created srv-01"]);
+
+    blackbox
+        .smart_insert(SmartDataType::Combined, COMBINED, false)
+        .unwrap();
+    assert_eq!(
+        recorder.take(),
+        vec![
+            "status srv-01 running -> warning",
+            "crash srv-01 thread_exception",
+            "metric srv-01 1700000000000",
+        ]
+    );
+
+    blackbox
+        .smart_insert(SmartDataType::Combined, COMBINED, false)
+        .unwrap();
+    assert!(recorder.take().is_empty());
+
+    let failing = r#"[
+        {"serverId": "srv-01", "timestamp": 1700000060000, "cpuUsage": 1.0, "memoryUsage": 1.0, "diskUsage": 1.0, "ioRead": 1.0, "ioWrite": 1.0, "networkIn": 1.0, "networkOut": 1.0},
+        {"serverId": "missing", "timestamp": 1700000060000, "cpuUsage": 1.0, "memoryUsage": 1.0, "diskUsage": 1.0, "ioRead": 1.0, "ioWrite": 1.0, "networkIn": 1.0, "networkOut": 1.0}
+    ]"#;
+    assert!(
+        blackbox
+            .smart_insert(SmartDataType::SystemMetrics, failing, false)
+            .is_err()
+    );
+    assert!(recorder.take().is_empty());
+}
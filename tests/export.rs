@@ -0,0 +1,299 @@
+use blackbox::*;
+use serde_json::Value;
+use std::path::Path;
+use std::process::Command;
+
+const MINUTE: i64 = 60_000;
+
+/// 三台服务器：srv-01 (env=prod, role=web)、srv-02 (env=prod)、srv-03 (env=dev)，
+/// 每台在 t0、t0+1 分钟、t0+2 分钟各有一条指标和一条进程趋势，在 t0 和 t0+2 分钟各有一条崩溃日志
+fn populate(db: &Path) -> (BlackBox, i64) {
+    let blackbox = BlackBox::new(Some(db.to_string_lossy().to_string()));
+    blackbox.init_database(true).unwrap();
+    // 取整到秒，便于命令行以 UTC 时间指定范围
+    let t0 = (chrono::Utc::now().timestamp() - 600) * 1000;
+
+    for server in ["srv-01", "srv-02", "srv-03"] {
+        for minute in 0..3 {
+            let timestamp = t0 + minute * MINUTE;
+            blackbox
+                .smart_insert(
+                    SmartDataType::Combined,
+                    &format!(
+                        r#"{{
+                            "process": [{{
+                                "serverId": "{server}", "serverName": "{server}", "serverIp": "10.0.0.1", "serverOs": "Kylin", "serverStatus": "running",
+                                "pid": 42, "name": "worker", "userName": "root", "status": "S", "timestamp": {timestamp},
+                                "trend": [{{"cpuUsage": 1.0, "memoryUsage": 2.0, "threadCount": {minute}}}], "threads": []
+                            }}],
+                            "metrics": [{{"serverId": "{server}", "timestamp": {timestamp}, "cpuUsage": {minute}, "memoryUsage": 20.0, "diskUsage": 30.0,
+                                          "ioRead": 1.0, "ioWrite": 2.0, "networkIn": 3.0, "networkOut": 4.0}}]
+                        }}"#
+                    ),
+                    false,
+                )
+                .unwrap();
+        }
+        blackbox
+            .smart_insert(
+                SmartDataType::CrashLogs,
+                &format!(
+                    r#"[
+                        {{"serverId": "{server}", "logId": 1, "timestamp": {}, "crashType": "segfault", "severity": "high", "title": "nginx", "message": "SIGSEGV", "resolved": false}},
+                        {{"serverId": "{server}", "logId": 2, "timestamp": {}, "crashType": "oom", "severity": "high", "title": "java", "message": "oom", "resolved": false}}
+                    ]"#,
+                    t0,
+                    t0 + 2 * MINUTE
+                ),
+                false,
+            )
+            .unwrap();
+    }
+
+    let label = |key: &str, value: &str| (key.to_string(), value.to_string());
+    blackbox
+        .set_server_labels("srv-01", &[label("env", "prod"), label("role", "web")])
+        .unwrap();
+    blackbox
+        .set_server_labels("srv-02", &[label("env", "prod")])
+        .unwrap();
+    blackbox
+        .set_server_labels("srv-03", &[label("env", "dev")])
+        .unwrap();
+
+    (blackbox, t0)
+}
+
+/// 按筛选条件导出并读回 JSON
+fn export(blackbox: &BlackBox, dir: &Path, filter: &ExportFilter) -> Value {
+    let path = dir.join("export.json");
+    let count = blackbox
+        .export_to_json(path.to_str().unwrap(), false, filter)
+        .unwrap();
+    let value: Value = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+    assert_eq!(value["servers"].as_array().unwrap().len(), count);
+    value
+}
+
+fn server_ids(export: &Value) -> Vec<&str> {
+    export["servers"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|server| server["serverId"].as_str().unwrap())
+        .collect()
+}
+
+/// 某台服务器导出的指标、进程趋势和崩溃日志的时间戳
+fn timestamps(export: &Value, server_id: &str) -> (Vec<i64>, Vec<i64>, Vec<i64>) {
+    let server = export["servers"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|server| server["serverId"] == server_id)
+        .unwrap();
+    let collect = |items: &Value| {
+        let mut timestamps: Vec<i64> = items
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["timestamp"].as_i64().unwrap())
+            .collect();
+        timestamps.sort();
+        timestamps
+    };
+    let trends = server["processes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .flat_map(|process| collect(&process["trend"]))
+        .collect();
+    (
+        collect(&server["systemMetrics"]),
+        trends,
+        collect(&server["crashLogs"]),
+    )
+}
+
+/// 按服务器和标签筛选，多个标签需要同时具有
+#[test]
+fn export_filters_by_server_and_label() {
+    let dir = tempfile::tempdir().unwrap();
+    let (blackbox, _) = populate(&dir.path().join("export.db"));
+    let label = |key: &str, value: &str| (key.to_string(), value.to_string());
+
+    let all = export(&blackbox, dir.path(), &ExportFilter::default());
+    assert_eq!(server_ids(&all), ["srv-01", "srv-02", "srv-03"]);
+    assert_eq!(all["servers"][0]["labels"]["role"], "web");
+
+    let filter = ExportFilter {
+        server_ids: vec!["srv-03".to_string(), "srv-01".to_string()],
+        ..Default::default()
+    };
+    assert_eq!(
+        server_ids(&export(&blackbox, dir.path(), &filter)),
+        ["srv-01", "srv-03"]
+    );
+
+    let filter = ExportFilter {
+        labels: vec![label("env", "prod")],
+        ..Default::default()
+    };
+    assert_eq!(
+        server_ids(&export(&blackbox, dir.path(), &filter)),
+        ["srv-01", "srv-02"]
+    );
+
+    let filter = ExportFilter {
+        labels: vec![label("env", "prod"), label("role", "web")],
+        ..Default::default()
+    };
+    assert_eq!(
+        server_ids(&export(&blackbox, dir.path(), &filter)),
+        ["srv-01"]
+    );
+
+    // 服务器和标签条件同时生效
+    let filter = ExportFilter {
+        server_ids: vec!["srv-02".to_string(), "srv-03".to_string()],
+        labels: vec![label("env", "prod")],
+        ..Default::default()
+    };
+    assert_eq!(
+        server_ids(&export(&blackbox, dir.path(), &filter)),
+        ["srv-02"]
+    );
+
+    let filter = ExportFilter {
+        labels: vec![label("env", "staging")],
+        ..Default::default()
+    };
+    assert!(server_ids(&export(&blackbox, dir.path(), &filter)).is_empty());
+}
+
+/// 时间范围是闭区间，作用于指标、进程趋势和崩溃日志；范围内没有趋势的进程不导出
+#[test]
+fn export_filters_by_time_range_and_entity() {
+    let dir = tempfile::tempdir().unwrap();
+    let (blackbox, t0) = populate(&dir.path().join("export.db"));
+
+    let filter = ExportFilter {
+        from: Some(t0 + MINUTE),
+        to: Some(t0 + 2 * MINUTE),
+        ..Default::default()
+    };
+    let exported = export(&blackbox, dir.path(), &filter);
+    assert_eq!(
+        timestamps(&exported, "srv-01"),
+        (
+            vec![t0 + MINUTE, t0 + 2 * MINUTE],
+            vec![t0 + MINUTE, t0 + 2 * MINUTE],
+            vec![t0 + 2 * MINUTE],
+        )
+    );
+
+    let filter = ExportFilter {
+        to: Some(t0),
+        ..Default::default()
+    };
+    let exported = export(&blackbox, dir.path(), &filter);
+    assert_eq!(
+        timestamps(&exported, "srv-02"),
+        (vec![t0], vec![t0], vec![t0])
+    );
+
+    // 范围内没有趋势的进程不导出，服务器本身仍然导出
+    let filter = ExportFilter {
+        from: Some(t0 + 10 * MINUTE),
+        ..Default::default()
+    };
+    let exported = export(&blackbox, dir.path(), &filter);
+    assert_eq!(server_ids(&exported).len(), 3);
+    assert_eq!(timestamps(&exported, "srv-03"), (vec![], vec![], vec![]));
+    assert!(
+        exported["servers"][0]["processes"]
+            .as_array()
+            .unwrap()
+            .is_empty()
+    );
+
+    let filter = ExportFilter {
+        server_ids: vec!["srv-01".to_string()],
+        include: vec![ExportEntity::Metrics, ExportEntity::Crashes],
+        ..Default::default()
+    };
+    let exported = export(&blackbox, dir.path(), &filter);
+    let (metrics, trends, crashes) = timestamps(&exported, "srv-01");
+    assert_eq!((metrics.len(), trends.len(), crashes.len()), (3, 0, 2));
+
+    let filter = ExportFilter {
+        include: vec![ExportEntity::Processes],
+        from: Some(t0 + 2 * MINUTE),
+        ..Default::default()
+    };
+    let exported = export(&blackbox, dir.path(), &filter);
+    assert_eq!(
+        timestamps(&exported, "srv-02"),
+        (vec![], vec![t0 + 2 * MINUTE], vec![])
+    );
+}
+
+/// 命令行的 --server、--label、--from/--to 和 --include 对应同样的筛选
+#[test]
+fn export_cli_filters() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("export.db");
+    let (_, t0) = populate(&db);
+    let output = dir.path().join("cli.json");
+
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_blackbox"))
+            .arg("--db")
+            .arg(&db)
+            .arg("export")
+            .arg("--file")
+            .arg(&output)
+            .args(args)
+            .env_remove("BLACKBOX_CONFIG")
+            .output()
+            .unwrap()
+    };
+    let utc = |millis: i64| {
+        chrono::DateTime::from_timestamp_millis(millis)
+            .unwrap()
+            .format("%Y-%m-%dT%H:%M:%S")
+            .to_string()
+    };
+
+    let from = utc(t0 + MINUTE);
+    let to = (t0 + MINUTE).to_string();
+    let result = run(&[
+        "--label",
+        "env=prod",
+        "--server",
+        "srv-02",
+        "--server",
+        "srv-03",
+        "--from",
+        &from,
+        "--to",
+        &to,
+        "--include",
+        "metrics,crashes",
+    ]);
+    assert!(
+        result.status.success(),
+        "{}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+    let exported: Value = serde_json::from_str(&std::fs::read_to_string(&output).unwrap()).unwrap();
+    assert_eq!(server_ids(&exported), ["srv-02"]);
+    assert_eq!(
+        timestamps(&exported, "srv-02"),
+        (vec![t0 + MINUTE], vec![], vec![])
+    );
+
+    // 无法解析的标签和时间由参数解析拒绝
+    assert!(!run(&["--label", "env"]).status.success());
+    assert!(!run(&["--from", "yesterday"]).status.success());
+}
@@ -0,0 +1,300 @@
+use blackbox::forward::{self, ForwardOptions, PayloadKind};
+use blackbox::influx::Precision;
+use blackbox::{BlackBox, BlackBoxConfig, SmartDataType, SmartRecommendation};
+use diesel::prelude::*;
+use std::net::TcpListener;
+use std::path::Path;
+use std::time::Duration;
+
+fn open(dir: &Path, name: &str) -> BlackBox {
+    let path = dir.join(name).to_string_lossy().to_string();
+    let blackbox = BlackBox::new(Some(path));
+    blackbox.init_database(true).unwrap();
+    blackbox
+}
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+const SERVERS: &str = r#"[{"serverId": "edge-01", "serverName": "edge", "serverIp": "10.0.0.5", "serverOs": "Kylin", "serverStatus": "online"}]"#;
+
+/// 中心节点不可达时数据留在 outbox 中，恢复后按顺序投递，重复投递不会重复写入
+#[test]
+fn forward_buffers_until_central_is_reachable() {
+    let dir = tempfile::tempdir().unwrap();
+    let edge = open(dir.path(), "edge.db");
+    let (_, record_id) = edge
+        .smart_insert_and_forward(SmartDataType::Servers, SERVERS, false)
+        .unwrap();
+
+    let options = ForwardOptions {
+        initial_backoff: Duration::ZERO,
+        ..Default::default()
+    };
+    let endpoint = format!("http://127.0.0.1:{}", free_port());
+
+    let report = edge.forward_pending(&endpoint, &options).unwrap();
+    assert_eq!(report.delivered, 0);
+    assert_eq!(report.failure.unwrap().record_id, record_id);
+    assert_eq!(edge.outbox_status().unwrap().head.unwrap().attempts, 1);
+
+    let central = open(dir.path(), "central.db");
+    let central_path = central.get_db_path().clone();
+    let addr = endpoint.trim_start_matches("http://").to_string();
+    std::thread::spawn(move || blackbox::server::serve(&BlackBox::new(central_path), &addr));
+
+    let mut report = edge.forward_pending(&endpoint, &options).unwrap();
+    for _ in 0..50 {
+        if report.delivered > 0 {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+        report = edge.forward_pending(&endpoint, &options).unwrap();
+    }
+    assert_eq!(report.delivered, 1);
+    assert_eq!(report.pending, 0);
+
+    assert_eq!(central.query_servers(None, None).unwrap().len(), 1);
+
+    let ack = central
+        .ingest_forwarded(SmartDataType::Servers, SERVERS, Some(&record_id), false)
+        .unwrap();
+    assert!(ack.duplicate);
+    assert_eq!(ack.success_count, 1);
+}
+
+/// 边缘节点模式下所有写入路径都会入队；被中心节点拒绝的负载转入死信，不阻塞后面的负载
+#[test]
+fn every_write_path_is_forwarded_and_rejections_are_dead_lettered() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = BlackBoxConfig::default();
+    config.database.path = Some(dir.path().join("edge.db").to_string_lossy().to_string());
+    config.forward.enabled = true;
+    let edge = BlackBox::from_config(config.clone());
+    edge.init_database(true).unwrap();
+
+    // 本地无法写入、但已在队首的负载 (例如由旧版本入队)，中心节点会拒绝
+    let mut conn = SqliteConnection::establish(config.database.path.as_deref().unwrap()).unwrap();
+    forward::enqueue(
+        &mut conn,
+        &PayloadKind::Json(SmartDataType::Servers),
+        "not json",
+        false,
+    )
+    .unwrap();
+
+    edge.smart_insert(SmartDataType::Servers, SERVERS, false)
+        .unwrap();
+    let now = chrono::Utc::now().timestamp_millis();
+    edge.smart_insert_csv(
+        SmartDataType::Servers,
+        "serverId,serverName,serverIp,serverOs,serverStatus\nedge-02,edge2,10.0.0.6,Kylin,online\n",
+        false,
+    )
+    .unwrap();
+    let line = format!(
+        "system_metrics,server_id=edge-01 cpu_usage=45.2,memory_usage=67.8,disk_usage=23.1,io_read=1024,io_write=512,network_in=2048,network_out=1024 {now}"
+    );
+    edge.ingest_line_protocol(&line, Precision::Milliseconds, false)
+        .unwrap();
+    edge.set_server_labels("edge-01", &[("role".to_string(), "edge".to_string())])
+        .unwrap();
+
+    let source = open(dir.path(), "source.db");
+    source
+        .smart_insert(
+            SmartDataType::Servers,
+            r#"[{"serverId": "edge-03", "serverName": "merged", "serverIp": "10.0.0.7", "serverOs": "Kylin", "serverStatus": "online"}]"#,
+            false,
+        )
+        .unwrap();
+    edge.merge_database(source.get_db_path().as_deref().unwrap())
+        .unwrap();
+
+    assert_eq!(edge.outbox_status().unwrap().pending, 6);
+
+    let central = open(dir.path(), "central.db");
+    let central_path = central.get_db_path().clone();
+    let endpoint = format!("http://127.0.0.1:{}", free_port());
+    let addr = endpoint.trim_start_matches("http://").to_string();
+    std::thread::spawn(move || blackbox::server::serve(&BlackBox::new(central_path), &addr));
+
+    let options = ForwardOptions {
+        initial_backoff: Duration::ZERO,
+        ..Default::default()
+    };
+    let mut report = edge.forward_pending(&endpoint, &options).unwrap();
+    for _ in 0..50 {
+        if report.pending == 0 {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+        report = edge.forward_pending(&endpoint, &options).unwrap();
+    }
+    assert_eq!(report.pending, 0);
+
+    let status = edge.outbox_status().unwrap();
+    assert_eq!(status.dead, 1);
+    let dead = edge.dead_letters().unwrap();
+    assert_eq!(dead[0].payload, "not json");
+
+    let servers: Vec<String> = central
+        .query_servers(None, None)
+        .unwrap()
+        .into_iter()
+        .map(|detail| detail.server.server_id)
+        .collect();
+    assert_eq!(servers.len(), 3);
+    assert!(servers.contains(&"edge-03".to_string()));
+    let labels = central.get_server_labels(Some("edge-01")).unwrap();
+    assert_eq!(labels[0].label_value, "edge");
+    let stats = central.get_statistics().unwrap();
+    let edge_01 = stats
+        .servers
+        .iter()
+        .find(|s| s.server.server_id == "edge-01")
+        .unwrap();
+    assert_eq!(edge_01.metrics_count, 1);
+
+    // 重新入队后仍会被拒绝，再次转入死信
+    assert_eq!(edge.requeue_outbox(&dead[0].record_id).unwrap(), 1);
+    let report = edge.forward_pending(&endpoint, &options).unwrap();
+    assert_eq!(report.dead_lettered.len(), 1);
+    assert_eq!(edge.discard_outbox(&dead[0].record_id).unwrap(), 1);
+    assert_eq!(edge.outbox_status().unwrap().dead, 0);
+}
+
+/// 导入、崩溃日志的处理、建议的调整、分析和清理都会入队；
+/// 中心节点按服务器 + 时间戳找到同一条崩溃日志，得到与边缘节点相同的状态
+#[test]
+fn import_crash_handling_and_cleanup_are_forwarded() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = BlackBoxConfig::default();
+    config.database.path = Some(dir.path().join("edge.db").to_string_lossy().to_string());
+    config.forward.enabled = true;
+    let edge = BlackBox::from_config(config);
+    edge.init_database(true).unwrap();
+    let pending = || edge.outbox_status().unwrap().pending;
+
+    let now = chrono::Utc::now().timestamp_millis();
+    let old = now - 10 * 24 * 3600 * 1000;
+    let import = dir.path().join("import.json");
+    std::fs::write(
+        &import,
+        format!(
+            r#"{{"servers": [{{"serverId": "edge-01", "serverName": "edge", "serverIp": "10.0.0.5", "serverOs": "Kylin", "serverStatus": "running",
+                "systemMetrics": [
+                    {{"timestamp": {old}, "cpuUsage": 1, "memoryUsage": 2, "diskUsage": 3, "ioRead": 4, "ioWrite": 5, "networkIn": 6, "networkOut": 7}},
+                    {{"timestamp": {now}, "cpuUsage": 1, "memoryUsage": 2, "diskUsage": 3, "ioRead": 4, "ioWrite": 5, "networkIn": 6, "networkOut": 7}}],
+                "crashLogs": [{{"id": 1, "timestamp": {now}, "crashType": "oom", "severity": "high", "title": "java", "message": "Out of memory",
+                    "stackTrace": "", "resolved": false}}]}}]}}"#
+        ),
+    )
+    .unwrap();
+    edge.import_json_data(import.to_str().unwrap(), false)
+        .unwrap();
+    assert_eq!(pending(), 1);
+
+    let crash_log_id = edge.list_crash_logs(&Default::default()).unwrap()[0].id;
+    edge.resolve_crash_log(crash_log_id, "alice", Some("已扩容"))
+        .unwrap();
+    edge.reopen_crash_log(crash_log_id, "bob", None).unwrap();
+    edge.annotate_crash_log(crash_log_id, "bob", "仍在观察")
+        .unwrap();
+    assert_eq!(pending(), 4);
+
+    edge.analyze_crash_log(&*edge.analyzer(), crash_log_id)
+        .unwrap();
+    let recommendation = |action: &str| SmartRecommendation {
+        priority: None,
+        action: action.to_string(),
+        command: "true".to_string(),
+    };
+    edge.replace_crash_recommendations(crash_log_id, &[recommendation("a"), recommendation("b")])
+        .unwrap();
+    let added = edge
+        .add_crash_recommendation(crash_log_id, &recommendation("c"))
+        .unwrap();
+    let mut ids: Vec<i32> = edge
+        .crash_recommendations(crash_log_id)
+        .unwrap()
+        .iter()
+        .map(|r| r.id)
+        .collect();
+    ids.reverse();
+    edge.reorder_crash_recommendations(crash_log_id, &ids)
+        .unwrap();
+    edge.mark_recommendation_applied(added.id, true, Some("已执行"))
+        .unwrap();
+    let removed = edge
+        .remove_crash_recommendation(*ids.last().unwrap())
+        .unwrap();
+    assert_eq!(pending(), 10);
+
+    assert_eq!(edge.clean_old_data(1).unwrap(), 1);
+    assert_eq!(pending(), 11);
+
+    let central = open(dir.path(), "central.db");
+    let central_path = central.get_db_path().clone();
+    let endpoint = format!("http://127.0.0.1:{}", free_port());
+    let addr = endpoint.trim_start_matches("http://").to_string();
+    std::thread::spawn(move || blackbox::server::serve(&BlackBox::new(central_path), &addr));
+
+    let options = ForwardOptions {
+        initial_backoff: Duration::ZERO,
+        ..Default::default()
+    };
+    let mut report = edge.forward_pending(&endpoint, &options).unwrap();
+    for _ in 0..50 {
+        if report.pending == 0 {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+        report = edge.forward_pending(&endpoint, &options).unwrap();
+    }
+    assert_eq!(report.pending, 0);
+    assert_eq!(edge.outbox_status().unwrap().dead, 0);
+
+    let local = edge.crash_log_history(crash_log_id).unwrap();
+    let central_log = central.list_crash_logs(&Default::default()).unwrap();
+    assert_eq!(central_log.len(), 1);
+    let forwarded = central.crash_log_history(central_log[0].id).unwrap();
+    assert!(!forwarded.crash_log.resolved);
+    assert_eq!(forwarded.crash_log.ai_summary, local.crash_log.ai_summary);
+    assert_eq!(forwarded.crash_log.ai_analysis, local.crash_log.ai_analysis);
+
+    let actions = |history: &blackbox::crash::CrashLogHistory| -> Vec<(String, bool)> {
+        history
+            .recommendations
+            .iter()
+            .map(|r| (r.action.clone(), r.applied))
+            .collect()
+    };
+    assert_eq!(actions(&forwarded), actions(&local));
+    assert!(
+        !actions(&forwarded)
+            .iter()
+            .any(|(action, _)| *action == removed.action)
+    );
+    let events: Vec<(String, String)> = forwarded
+        .events
+        .iter()
+        .map(|e| (e.action.as_str().to_string(), e.actor.clone()))
+        .collect();
+    assert_eq!(
+        events,
+        vec![
+            ("resolved".to_string(), "alice".to_string()),
+            ("reopened".to_string(), "bob".to_string()),
+            ("note".to_string(), "bob".to_string()),
+        ]
+    );
+
+    let stats = central.get_statistics().unwrap();
+    assert_eq!(stats.servers[0].metrics_count, 1);
+}
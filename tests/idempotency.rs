@@ -0,0 +1,50 @@
+use blackbox::*;
+
+const COMBINED: &str = r#"{
+    "process": [{
+        "serverId": "srv-01", "serverName": "web", "serverIp": "10.0.0.1", "serverOs": "Kylin", "serverStatus": "running",
+        "pid": 42, "name": "worker", "userName": "root", "status": "S", "timestamp": 1700000000000,
+        "trend": [], "threads": []
+    }],
+    "metrics": [],
+    "dmesg": "[12.5] kernel BUG at mm/slub.c:3952!"
+}"#;
+
+/// 不带 batchId 重复上报相同的 dmesg 时不重复生成崩溃日志
+#[test]
+fn resending_dmesg_does_not_duplicate_crash_logs() {
+    let dir = tempfile::tempdir().unwrap();
+    let blackbox = BlackBox::new(Some(
+        dir.path()
+            .join("idempotency.db")
+            .to_string_lossy()
+            .to_string(),
+    ));
+    blackbox.init_database(true).unwrap();
+
+    let first = blackbox
+        .smart_insert(SmartDataType::Combined, COMBINED, false)
+        .unwrap();
+    let crash_logs = blackbox.list_crash_logs(&Default::default()).unwrap();
+    assert_eq!(crash_logs.len(), 1);
+    assert_eq!(crash_logs[0].crash_type, CrashType::KernelException);
+
+    let resent = blackbox
+        .smart_insert(SmartDataType::Combined, COMBINED, false)
+        .unwrap();
+    // 已有的日志计为更新
+    assert_eq!(resent.success_count, first.success_count - 1);
+    assert_eq!(resent.updated_count, first.updated_count + 1);
+    let resent_logs = blackbox.list_crash_logs(&Default::default()).unwrap();
+    assert_eq!(resent_logs.len(), 1);
+    assert_eq!(resent_logs[0].id, crash_logs[0].id);
+
+    // 内容不同的 dmesg 生成新的日志
+    let other = COMBINED.replace("[12.5]", "[98.1]");
+    blackbox
+        .smart_insert(SmartDataType::Combined, &other, false)
+        .unwrap();
+    let crash_logs = blackbox.list_crash_logs(&Default::default()).unwrap();
+    assert_eq!(crash_logs.len(), 2);
+    assert_ne!(crash_logs[0].log_id, crash_logs[1].log_id);
+}
@@ -0,0 +1,237 @@
+use blackbox::influx::{self, FieldValue, Precision};
+use blackbox::*;
+
+/// tag 和字段键值中转义的逗号、空格和等号按字面值解析
+#[test]
+fn escaped_commas_spaces_and_equals_signs() {
+    let point = influx::parse_line(
+        r#"process\ trends,server_id=srv\,01,name=my\ worker,user=a\=b cpu\ usage=1.5,note="x, y=z" 1700000000000"#,
+    )
+    .unwrap();
+    assert_eq!(point.measurement, "process trends");
+    assert_eq!(
+        point.tags,
+        vec![
+            ("server_id".to_string(), "srv,01".to_string()),
+            ("name".to_string(), "my worker".to_string()),
+            ("user".to_string(), "a=b".to_string()),
+        ]
+    );
+    assert_eq!(
+        point.fields,
+        vec![
+            ("cpu usage".to_string(), FieldValue::Float(1.5)),
+            ("note".to_string(), FieldValue::String("x, y=z".to_string())),
+        ]
+    );
+    assert_eq!(point.timestamp, Some(1_700_000_000_000));
+}
+
+/// 字符串字段可以包含空格、逗号、等号和转义的双引号
+#[test]
+fn quoted_string_fields() {
+    let point =
+        influx::parse_line(r#"m,t=1 a="hello world",b="say \"hi\"",c="",d="x=1,y=2" 1"#).unwrap();
+    assert_eq!(
+        point.fields,
+        vec![
+            (
+                "a".to_string(),
+                FieldValue::String("hello world".to_string())
+            ),
+            (
+                "b".to_string(),
+                FieldValue::String(r#"say "hi""#.to_string())
+            ),
+            ("c".to_string(), FieldValue::String(String::new())),
+            ("d".to_string(), FieldValue::String("x=1,y=2".to_string())),
+        ]
+    );
+
+    // 进程状态可以作为字符串字段提供
+    let parsed = influx::parse(
+        r#"process_trends,server_id=srv-01,pid=42,name=worker,user=root cpu_usage=1,memory_usage=2,thread_count=3i,status="Ss" 1700000000000"#,
+        Precision::Milliseconds,
+    );
+    assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+    assert_eq!(
+        parsed.process_trends[0].status,
+        Some(ProcessState::Sleeping)
+    );
+}
+
+/// 整数字段带 i (或 u) 后缀，带后缀的小数是错误
+#[test]
+fn integer_fields_use_the_i_suffix() {
+    let point = influx::parse_line("m a=5i,b=-3i,c=7u,d=5,e=t,f=FALSE 1").unwrap();
+    assert_eq!(
+        point.fields,
+        vec![
+            ("a".to_string(), FieldValue::Integer(5)),
+            ("b".to_string(), FieldValue::Integer(-3)),
+            ("c".to_string(), FieldValue::Integer(7)),
+            ("d".to_string(), FieldValue::Float(5.0)),
+            ("e".to_string(), FieldValue::Boolean(true)),
+            ("f".to_string(), FieldValue::Boolean(false)),
+        ]
+    );
+    assert!(influx::parse_line("m a=1.5i 1").is_err());
+
+    // 整数和浮点数字段都可以写入数值列
+    let parsed = influx::parse(
+        "process_trends,server_id=srv-01,pid=42,name=worker,user=root cpu_usage=1i,memory_usage=2.5,thread_count=300i 1700000000000",
+        Precision::Milliseconds,
+    );
+    assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+    let trend = &parsed.process_trends[0];
+    assert_eq!(trend.cpu_usage, 1.0);
+    assert_eq!(trend.memory_usage, 2.5);
+    assert_eq!(trend.thread_count, 300);
+}
+
+/// 没有时间戳时使用写入时刻，有时间戳时按精度换算为毫秒
+#[test]
+fn missing_timestamp_uses_the_current_time() {
+    let line = "system_metrics,server_id=srv-01 cpu_usage=1,memory_usage=2,disk_usage=3";
+    assert_eq!(influx::parse_line(line).unwrap().timestamp, None);
+
+    let before = chrono::Utc::now().timestamp_millis();
+    let parsed = influx::parse(line, Precision::Nanoseconds);
+    let after = chrono::Utc::now().timestamp_millis();
+    assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+    let metric = &parsed.system_metrics[0];
+    assert!((before..=after).contains(&metric.timestamp));
+    // IO 和网络字段缺省为 0
+    assert_eq!(metric.io_read, 0.0);
+    assert_eq!(metric.network_out, 0.0);
+
+    for (precision, timestamp) in [
+        (Precision::Nanoseconds, "1700000000123000000"),
+        (Precision::Microseconds, "1700000000123000"),
+        (Precision::Milliseconds, "1700000000123"),
+    ] {
+        let parsed = influx::parse(&format!("{} {}", line, timestamp), precision);
+        assert_eq!(parsed.system_metrics[0].timestamp, 1_700_000_000_123);
+    }
+    let parsed = influx::parse(&format!("{} 1700000000", line), Precision::Seconds);
+    assert_eq!(parsed.system_metrics[0].timestamp, 1_700_000_000_000);
+}
+
+/// 无法解析的行按行号记录，其余行照常解析
+#[test]
+fn malformed_lines_are_reported_by_line() {
+    let content = "\
+# 注释行和空行被跳过
+
+system_metrics,server_id=srv-01 cpu_usage=1,memory_usage=2,disk_usage=3 1700000000000
+system_metrics,server_id=srv-01
+system_metrics,server_id=srv-01 cpu_usage=abc,memory_usage=2,disk_usage=3
+system_metrics,server_id=srv-01 cpu_usage=1,memory_usage=2,disk_usage=3 yesterday
+system_metrics,server_id cpu_usage=1,memory_usage=2,disk_usage=3
+system_metrics,server_id=srv-01 cpu_usage=1,memory_usage=2
+system_metrics,server_id=srv-01 cpu_usage=\"1\",memory_usage=2,disk_usage=3
+system_metrics,server_id=srv-01 note=\"unterminated
+cpu,host=a usage=1
+process_trends,server_id=srv-01,pid=abc,name=w,user=root cpu_usage=1,memory_usage=2,thread_count=3i
+";
+    let parsed = influx::parse(content, Precision::Milliseconds);
+    assert_eq!(parsed.system_metrics.len(), 1);
+    assert!(parsed.process_trends.is_empty());
+
+    let lines: Vec<&str> = parsed
+        .errors
+        .iter()
+        .map(|error| error.split(':').next().unwrap())
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            "第 4 行",
+            "第 5 行",
+            "第 6 行",
+            "第 7 行",
+            "第 8 行",
+            "第 9 行",
+            "第 10 行",
+            "第 11 行",
+            "第 12 行",
+        ]
+    );
+    assert!(
+        parsed.errors[4].contains("缺少字段 disk_usage"),
+        "{}",
+        parsed.errors[4]
+    );
+    assert!(
+        parsed.errors[5].contains("不是数值"),
+        "{}",
+        parsed.errors[5]
+    );
+    assert!(
+        parsed.errors[7].contains("不支持的 measurement: cpu"),
+        "{}",
+        parsed.errors[7]
+    );
+    assert!(
+        parsed.errors[8].contains("无效的 pid"),
+        "{}",
+        parsed.errors[8]
+    );
+}
+
+/// 写入时任一行无法解析即整体拒绝，continue_on_error 时跳过错误行
+#[test]
+fn ingest_rejects_malformed_lines_unless_continuing() {
+    let dir = tempfile::tempdir().unwrap();
+    let blackbox = BlackBox::new(Some(
+        dir.path().join("influx.db").to_string_lossy().to_string(),
+    ));
+    blackbox.init_database(true).unwrap();
+    blackbox
+        .smart_insert(
+            SmartDataType::Servers,
+            r#"[{"serverId": "srv 01,a", "serverName": "web", "serverIp": "10.0.0.1", "serverOs": "Kylin", "serverStatus": "running"}]"#,
+            false,
+        )
+        .unwrap();
+
+    let now = chrono::Utc::now().timestamp_millis();
+    let content = format!(
+        "system_metrics,server_id=srv\\ 01\\,a cpu_usage=12.5,memory_usage=2,disk_usage=3i {now}\n\
+         system_metrics,server_id=srv\\ 01\\,a cpu_usage=oops {now}\n"
+    );
+
+    match blackbox
+        .ingest_line_protocol(&content, Precision::Milliseconds, false)
+        .unwrap_err()
+    {
+        BlackBoxError::Parse { message, .. } => assert!(message.contains("第 2 行"), "{}", message),
+        error => panic!("{:?}", error),
+    }
+    let metrics = |blackbox: &BlackBox| {
+        blackbox.query_servers(Some("srv 01,a"), None).unwrap()[0]
+            .metrics
+            .clone()
+    };
+    assert!(metrics(&blackbox).is_empty());
+
+    let result = blackbox
+        .ingest_line_protocol(&content, Precision::Milliseconds, true)
+        .unwrap();
+    assert_eq!(result.success_count, 1);
+    assert_eq!(result.error_count, 1);
+    let stored = metrics(&blackbox);
+    assert_eq!(stored.len(), 1);
+    assert_eq!(stored[0].timestamp, now);
+    assert_eq!(stored[0].cpu_usage, 12.5);
+    assert_eq!(stored[0].disk_usage, 3.0);
+
+    // 导出的行协议按同样的规则转义，可以原样解析回来
+    let line = influx::format_system_metric(&stored[0]);
+    let point = influx::parse_line(&line).unwrap();
+    assert_eq!(
+        point.tags,
+        vec![("server_id".to_string(), "srv 01,a".to_string())]
+    );
+    assert_eq!(point.timestamp, Some(now));
+}
@@ -0,0 +1,201 @@
+use blackbox::knowledge::KnowledgeBase;
+use blackbox::*;
+use std::path::Path;
+
+const SERVER: &str = r#"[{"serverId": "srv-01", "serverName": "web", "serverIp": "10.0.0.1", "serverOs": "Kylin", "serverStatus": "running"}]"#;
+
+const CRASH_LOGS: &str = r#"[
+    {"serverId": "srv-01", "logId": 1, "timestamp": 1700000000000, "crashType": "other", "severity": "high", "title": "java", "message": "abort",
+     "stackTrace": "pthread_create failed: Resource temporarily unavailable", "resolved": false},
+    {"serverId": "srv-01", "logId": 2, "timestamp": 1700000060000, "crashType": "other", "severity": "high", "title": "java", "message": "abort",
+     "stackTrace": "pthread_create failed: Resource temporarily unavailable", "resolved": false, "aiSummary": "采集端的分析"},
+    {"serverId": "srv-01", "logId": 3, "timestamp": 1700000120000, "crashType": "other", "severity": "low", "title": "nginx", "message": "exit",
+     "resolved": false}
+]"#;
+
+const COMBINED: &str = r#"{
+    "process": [{
+        "serverId": "srv-01", "serverName": "web", "serverIp": "10.0.0.1", "serverOs": "Kylin", "serverStatus": "running",
+        "pid": 42, "name": "worker", "userName": "root", "status": "S", "timestamp": 1700000000000,
+        "trend": [], "threads": []
+    }],
+    "metrics": [],
+    "dmesg": "[12.5] watchdog: BUG: soft lockup - CPU#3 stuck for 22s! [kworker/3:1:123]"
+}"#;
+
+/// 示例知识库在新建崩溃日志时自动写入分析和建议，不覆盖采集端给出的分析
+#[test]
+fn knowledge_base_analyzes_new_crash_logs() {
+    let dir = tempfile::tempdir().unwrap();
+    let example = Path::new(env!("CARGO_MANIFEST_DIR")).join("crash_knowledge.toml");
+    let knowledge_base = KnowledgeBase::load(example.to_str().unwrap()).unwrap();
+    let blackbox = BlackBox::builder()
+        .db_path(
+            dir.path()
+                .join("knowledge.db")
+                .to_string_lossy()
+                .to_string(),
+        )
+        .knowledge_base(knowledge_base)
+        .build();
+    blackbox.init_database(true).unwrap();
+    blackbox
+        .smart_insert(SmartDataType::Servers, SERVER, false)
+        .unwrap();
+    blackbox
+        .smart_insert(SmartDataType::CrashLogs, CRASH_LOGS, false)
+        .unwrap();
+    blackbox
+        .smart_insert(SmartDataType::Combined, COMBINED, false)
+        .unwrap();
+
+    let crash_logs = blackbox.list_crash_logs(&Default::default()).unwrap();
+    let history = |matches: &dyn Fn(&CrashLog) -> bool| {
+        let crash_log = crash_logs.iter().find(|log| matches(log)).unwrap();
+        blackbox.crash_log_history(crash_log.id).unwrap()
+    };
+
+    let thread_limit = history(&|log| log.log_id == 1);
+    assert!(
+        thread_limit
+            .crash_log
+            .ai_summary
+            .unwrap()
+            .contains("pthread_create")
+    );
+    let priorities: Vec<i32> = thread_limit
+        .recommendations
+        .iter()
+        .map(|rec| rec.priority)
+        .collect();
+    assert_eq!(priorities, vec![1, 2, 3]);
+
+    let collected = history(&|log| log.log_id == 2);
+    assert_eq!(
+        collected.crash_log.ai_summary.as_deref(),
+        Some("采集端的分析")
+    );
+    assert!(collected.recommendations.is_empty());
+
+    assert!(
+        history(&|log| log.log_id == 3)
+            .crash_log
+            .ai_summary
+            .is_none()
+    );
+
+    let lockup = history(&|log| log.crash_type == CrashType::KernelException);
+    assert!(lockup.crash_log.ai_summary.unwrap().contains("软锁死"));
+    assert!(lockup.crash_log.message.contains("软锁死"));
+    assert!(blackbox.pending_crash_logs().unwrap().is_empty());
+
+    let invalid = dir.path().join("invalid.json");
+    std::fs::write(
+        &invalid,
+        r#"{"signatures": [{"name": "bad", "title": "(", "summary": "x"}]}"#,
+    )
+    .unwrap();
+    let error = KnowledgeBase::load(invalid.to_str().unwrap()).unwrap_err();
+    assert!(matches!(error, BlackBoxError::Validation { .. }));
+}
+
+/// 重复上报不带分析的崩溃日志时，保留知识库和 crash analyze 已写入的分析
+#[test]
+fn resending_keeps_generated_analysis() {
+    let dir = tempfile::tempdir().unwrap();
+    let example = Path::new(env!("CARGO_MANIFEST_DIR")).join("crash_knowledge.toml");
+    let blackbox = BlackBox::builder()
+        .db_path(
+            dir.path()
+                .join("knowledge.db")
+                .to_string_lossy()
+                .to_string(),
+        )
+        .knowledge_base(KnowledgeBase::load(example.to_str().unwrap()).unwrap())
+        .build();
+    blackbox.init_database(true).unwrap();
+    blackbox
+        .smart_insert(SmartDataType::Servers, SERVER, false)
+        .unwrap();
+    blackbox
+        .smart_insert(SmartDataType::CrashLogs, CRASH_LOGS, false)
+        .unwrap();
+
+    let find = |log_id: i64| {
+        blackbox
+            .list_crash_logs(&Default::default())
+            .unwrap()
+            .into_iter()
+            .find(|log| log.log_id == log_id)
+            .unwrap()
+    };
+    let nginx = find(3);
+    blackbox
+        .analyze_crash_log(blackbox.analyzer().as_ref(), nginx.id)
+        .unwrap();
+    let analyzed = find(3).ai_summary;
+    assert!(analyzed.is_some());
+
+    let result = blackbox
+        .smart_insert(SmartDataType::CrashLogs, CRASH_LOGS, false)
+        .unwrap();
+    assert_eq!(result.updated_count, 3);
+    assert!(find(1).ai_summary.unwrap().contains("pthread_create"));
+    assert_eq!(find(2).ai_summary.as_deref(), Some("采集端的分析"));
+    assert_eq!(find(3).ai_summary, analyzed);
+
+    // 采集端给出新的分析时仍以采集端为准
+    let collected = CRASH_LOGS.replace(
+        r#""aiSummary": "采集端的分析""#,
+        r#""aiSummary": "新的分析""#,
+    );
+    blackbox
+        .smart_insert(SmartDataType::CrashLogs, &collected, false)
+        .unwrap();
+    assert_eq!(find(2).ai_summary.as_deref(), Some("新的分析"));
+}
+
+/// 配置的知识库文件缺失或无效时，插入不受影响，改用内置规则分析
+#[test]
+fn invalid_knowledge_base_falls_back_to_rules() {
+    let dir = tempfile::tempdir().unwrap();
+    let invalid = dir.path().join("invalid.toml");
+    std::fs::write(
+        &invalid,
+        "[[signatures]]\nname = \"bad\"\ntitle = \"(\"\nsummary = \"x\"\n",
+    )
+    .unwrap();
+    let missing = dir.path().join("missing.toml");
+    let example = Path::new(env!("CARGO_MANIFEST_DIR")).join("crash_knowledge.toml");
+
+    let build = |name: &str, knowledge_base: &Path| {
+        let mut config = BlackBoxConfig::default();
+        config.database.path = Some(dir.path().join(name).to_string_lossy().to_string());
+        config.analyzer.knowledge_base = Some(knowledge_base.to_string_lossy().to_string());
+        BlackBox::from_config(config)
+    };
+    assert!(build("example.db", &example).knowledge_base().is_some());
+
+    for (name, knowledge_base) in [("invalid.db", &invalid), ("missing.db", &missing)] {
+        let blackbox = build(name, knowledge_base);
+        assert!(blackbox.knowledge_base().is_none());
+        blackbox.init_database(true).unwrap();
+        blackbox
+            .smart_insert(SmartDataType::Servers, SERVER, false)
+            .unwrap();
+        let result = blackbox
+            .smart_insert(SmartDataType::CrashLogs, CRASH_LOGS, false)
+            .unwrap();
+        assert_eq!(result.success_count, 3);
+        blackbox
+            .smart_insert(SmartDataType::Combined, COMBINED, false)
+            .unwrap();
+
+        let crash_logs = blackbox.list_crash_logs(&Default::default()).unwrap();
+        assert_eq!(crash_logs.len(), 4);
+        // 没有知识库时 dmesg 日志留给 crash analyze --pending
+        let pending = blackbox.pending_crash_logs().unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].crash_type, CrashType::KernelException);
+    }
+}
@@ -0,0 +1,274 @@
+use blackbox::crash::CrashLogFilter;
+use blackbox::*;
+use std::path::Path;
+
+fn open(dir: &Path, name: &str) -> BlackBox {
+    let path = dir.join(name).to_string_lossy().to_string();
+    let blackbox = BlackBox::new(Some(path));
+    blackbox.init_database(true).unwrap();
+    blackbox
+}
+
+fn server(server_id: &str, name: &str, status: &str) -> String {
+    format!(
+        r#"[{{"serverId": "{server_id}", "serverName": "{name}", "serverIp": "10.0.0.1", "serverOs": "Kylin", "serverStatus": "{status}"}}]"#
+    )
+}
+
+fn process(server_id: &str, name: &str, pid: i32) -> String {
+    format!(
+        r#"[{{"serverId": "{server_id}", "serverName": "{server_id}", "serverIp": "10.0.0.1", "serverOs": "Kylin", "serverStatus": "running",
+            "pid": {pid}, "name": "{name}", "userName": "root", "status": "S", "timestamp": 1700000000000,
+            "trend": [{{"cpuUsage": 1.0, "memoryUsage": 2.0, "threadCount": 1}}],
+            "threads": [{{"threadId": {pid}, "userName": "root", "priority": 20, "niceValue": 0, "virtualMemory": "1G",
+                "residentMemory": "1M", "sharedMemory": "1M", "status": "S", "cpuUsage": "0.1", "memoryUsage": "0.1",
+                "runtime": "00:00:01", "command": "{name}"}}]}}]"#
+    )
+}
+
+fn crash_log(
+    server_id: &str,
+    timestamp: i64,
+    message: &str,
+    ai_summary: Option<&str>,
+    actions: &[&str],
+) -> String {
+    let recommendations: Vec<String> = actions
+        .iter()
+        .map(|action| format!(r#"{{"action": "{action}", "command": "true"}}"#))
+        .collect();
+    let ai_summary = ai_summary.map_or("null".to_string(), |summary| format!(r#""{summary}""#));
+    format!(
+        r#"[{{"serverId": "{server_id}", "logId": {timestamp}, "timestamp": {timestamp}, "crashType": "segfault", "severity": "high",
+            "title": "nginx", "message": "{message}", "resolved": false, "aiSummary": {ai_summary},
+            "recommendations": [{}]}}]"#,
+        recommendations.join(", ")
+    )
+}
+
+/// 崩溃日志的时间戳 (秒)，早于保留期的数据会在写入后被清理
+fn now() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+fn crash_log_of(blackbox: &BlackBox, server_id: &str) -> CrashLog {
+    let filter = CrashLogFilter {
+        server_id: Some(server_id.to_string()),
+        ..Default::default()
+    };
+    let mut crash_logs = blackbox.list_crash_logs(&filter).unwrap();
+    assert_eq!(crash_logs.len(), 1);
+    crash_logs.remove(0)
+}
+
+fn actions(blackbox: &BlackBox, crash_log_id: i32) -> Vec<String> {
+    let mut actions: Vec<String> = blackbox
+        .crash_recommendations(crash_log_id)
+        .unwrap()
+        .into_iter()
+        .map(|recommendation| recommendation.action)
+        .collect();
+    actions.sort();
+    actions
+}
+
+/// 源数据库的行按目标库的新 id 写入，冲突按规则取值，AI 建议取并集；
+/// 过期的副本不会重新打开已解决的崩溃日志，也不会清空已生成的分析
+#[test]
+fn merge_remaps_ids_and_keeps_crash_log_state() {
+    let dir = tempfile::tempdir().unwrap();
+    let now = now();
+
+    let target = open(dir.path(), "target.db");
+    target
+        .smart_insert(
+            SmartDataType::Processes,
+            &process("srv-00", "sshd", 10),
+            false,
+        )
+        .unwrap();
+    target
+        .smart_insert(
+            SmartDataType::Servers,
+            &server("srv-01", "web-target", "running"),
+            false,
+        )
+        .unwrap();
+    target
+        .smart_insert(
+            SmartDataType::CrashLogs,
+            &crash_log("srv-00", now, "boot", None, &[]),
+            false,
+        )
+        .unwrap();
+    target
+        .smart_insert(
+            SmartDataType::CrashLogs,
+            &crash_log(
+                "srv-01",
+                now + 1,
+                "old",
+                Some("target analysis"),
+                &["restart"],
+            ),
+            false,
+        )
+        .unwrap();
+    let shared = crash_log_of(&target, "srv-01");
+    target.resolve_crash_log(shared.id, "alice", None).unwrap();
+
+    let source = open(dir.path(), "source.db");
+    source
+        .smart_insert(
+            SmartDataType::Servers,
+            &server("srv-01", "web-source", "offline"),
+            false,
+        )
+        .unwrap();
+    source
+        .smart_insert(
+            SmartDataType::Processes,
+            &process("srv-02", "nginx", 20),
+            false,
+        )
+        .unwrap();
+    source
+        .smart_insert(
+            SmartDataType::CrashLogs,
+            &crash_log("srv-01", now + 1, "new", None, &["restart", "clear cache"]),
+            false,
+        )
+        .unwrap();
+
+    source
+        .smart_insert(
+            SmartDataType::CrashLogs,
+            &crash_log("srv-02", now + 2, "oops", None, &["reload"]),
+            false,
+        )
+        .unwrap();
+    let source_only = crash_log_of(&source, "srv-02");
+    source
+        .resolve_crash_log(source_only.id, "bob", Some("已修复"))
+        .unwrap();
+
+    let report = target
+        .merge_database(source.get_db_path().as_deref().unwrap())
+        .unwrap();
+
+    let mut conflicts: Vec<(&str, &str)> = report
+        .conflicts
+        .iter()
+        .map(|conflict| (conflict.table, conflict.key.as_str()))
+        .collect();
+    conflicts.sort();
+    assert_eq!(
+        conflicts,
+        vec![
+            ("crash_logs", format!("srv-01@{}", now + 1).as_str()),
+            ("servers", "srv-01")
+        ]
+    );
+    let crash_stats = report
+        .tables
+        .iter()
+        .find(|stats| stats.table == "crash_logs")
+        .unwrap();
+    assert_eq!((crash_stats.inserted, crash_stats.updated), (1, 1));
+
+    // 服务器保留目标库的名称，只更新状态；源库的进程和线程写到新的 id 下
+    let servers = target.query_servers(None, None).unwrap();
+    assert_eq!(servers.len(), 3);
+    let web = servers
+        .iter()
+        .find(|detail| detail.server.server_id == "srv-01")
+        .unwrap();
+    assert_eq!(web.server.server_name, "web-target");
+    assert_eq!(web.server.server_status, ServerStatus::Offline);
+    let nginx = servers
+        .iter()
+        .find(|detail| detail.server.server_id == "srv-02")
+        .unwrap();
+    assert_eq!(nginx.processes.len(), 1);
+    assert_eq!(nginx.processes[0].process.name, "nginx");
+    assert_ne!(
+        nginx.processes[0].process.id,
+        source.query_servers(Some("srv-02"), None).unwrap()[0].processes[0]
+            .process
+            .id
+    );
+    assert_eq!(nginx.processes[0].threads.len(), 1);
+
+    // 只在源库中的崩溃日志：新的 id 下带着建议、处理历史和解决状态
+    let merged = crash_log_of(&target, "srv-02");
+    assert_ne!(merged.id, source_only.id);
+    assert!(merged.resolved);
+    assert_eq!(actions(&target, merged.id), vec!["reload"]);
+    let history = target.crash_log_history(merged.id).unwrap();
+    assert_eq!(history.events.len(), 1);
+    assert_eq!(history.events[0].actor, "bob");
+
+    // 两边都有的崩溃日志：内容采用源库，解决状态和分析保留目标库，建议取并集
+    let shared = crash_log_of(&target, "srv-01");
+    assert_eq!(shared.message, "new");
+    assert!(shared.resolved);
+    assert_eq!(shared.ai_summary.as_deref(), Some("target analysis"));
+    assert_eq!(actions(&target, shared.id), vec!["clear cache", "restart"]);
+    assert_eq!(target.crash_log_history(shared.id).unwrap().events.len(), 1);
+
+    // 再次合并不会产生新的写入
+    let report = target
+        .merge_database(source.get_db_path().as_deref().unwrap())
+        .unwrap();
+    assert!(report.tables.iter().all(|stats| stats.inserted == 0));
+    assert!(
+        report
+            .conflicts
+            .iter()
+            .all(|conflict| conflict.table == "servers")
+    );
+}
+
+/// 合并进来的解决和重新打开记录按时间先后决定解决状态
+#[test]
+fn merged_crash_events_decide_resolved_state() {
+    let dir = tempfile::tempdir().unwrap();
+    let target = open(dir.path(), "target.db");
+    let source = open(dir.path(), "source.db");
+    for blackbox in [&target, &source] {
+        blackbox
+            .smart_insert(
+                SmartDataType::Servers,
+                &server("srv-01", "web", "running"),
+                false,
+            )
+            .unwrap();
+        blackbox
+            .smart_insert(
+                SmartDataType::CrashLogs,
+                &crash_log("srv-01", now(), "m", None, &[]),
+                false,
+            )
+            .unwrap();
+    }
+
+    let target_log = crash_log_of(&target, "srv-01");
+    target
+        .resolve_crash_log(target_log.id, "alice", None)
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    let source_log = crash_log_of(&source, "srv-01");
+    source
+        .resolve_crash_log(source_log.id, "bob", None)
+        .unwrap();
+    source
+        .reopen_crash_log(source_log.id, "bob", Some("又出现了"))
+        .unwrap();
+
+    target
+        .merge_database(source.get_db_path().as_deref().unwrap())
+        .unwrap();
+    let merged = target.crash_log_history(target_log.id).unwrap();
+    assert!(!merged.crash_log.resolved);
+    assert_eq!(merged.events.len(), 3);
+}
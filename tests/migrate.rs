@@ -0,0 +1,244 @@
+use blackbox::*;
+use diesel::connection::SimpleConnection;
+use diesel::prelude::*;
+use diesel::sql_types::BigInt;
+use std::path::Path;
+
+/// 升级前 (v0) 的表结构：外键没有级联删除，也没有开启外键检查
+const LEGACY_SCHEMA: &str = r#"
+PRAGMA foreign_keys = OFF;
+CREATE TABLE servers (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    server_id TEXT NOT NULL UNIQUE,
+    server_name TEXT NOT NULL,
+    server_ip TEXT NOT NULL,
+    server_os TEXT NOT NULL,
+    server_status TEXT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+CREATE TABLE system_metrics (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    server_id TEXT NOT NULL,
+    timestamp BIGINT NOT NULL,
+    cpu_usage REAL NOT NULL,
+    memory_usage REAL NOT NULL,
+    disk_usage REAL NOT NULL,
+    io_read REAL NOT NULL,
+    io_write REAL NOT NULL,
+    network_in REAL NOT NULL,
+    network_out REAL NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (server_id) REFERENCES servers(server_id)
+);
+CREATE TABLE processes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    server_id TEXT NOT NULL,
+    pid INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    user_name TEXT NOT NULL,
+    status TEXT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (server_id) REFERENCES servers(server_id)
+);
+CREATE TABLE process_trends (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    server_id TEXT NOT NULL,
+    pid INTEGER NOT NULL,
+    timestamp BIGINT NOT NULL,
+    cpu_usage REAL NOT NULL,
+    memory_usage REAL NOT NULL,
+    thread_count INTEGER NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (server_id) REFERENCES servers(server_id)
+);
+CREATE TABLE threads (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    server_id TEXT NOT NULL,
+    pid INTEGER NOT NULL,
+    thread_id INTEGER NOT NULL,
+    user_name TEXT NOT NULL,
+    priority INTEGER NOT NULL,
+    nice_value INTEGER NOT NULL,
+    virtual_memory TEXT NOT NULL,
+    resident_memory TEXT NOT NULL,
+    shared_memory TEXT NOT NULL,
+    status TEXT NOT NULL,
+    cpu_usage TEXT NOT NULL,
+    memory_usage TEXT NOT NULL,
+    runtime TEXT NOT NULL,
+    command TEXT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (server_id) REFERENCES servers(server_id)
+);
+CREATE TABLE crash_logs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    server_id TEXT NOT NULL,
+    log_id BIGINT NOT NULL,
+    timestamp BIGINT NOT NULL,
+    crash_type TEXT NOT NULL,
+    severity TEXT NOT NULL,
+    title TEXT NOT NULL,
+    message TEXT NOT NULL,
+    stack_trace TEXT,
+    resolved BOOLEAN NOT NULL DEFAULT 0,
+    ai_summary TEXT,
+    ai_analysis TEXT,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (server_id) REFERENCES servers(server_id)
+);
+CREATE TABLE ai_recommendations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    crash_log_id INTEGER NOT NULL,
+    priority INTEGER NOT NULL,
+    action TEXT NOT NULL,
+    command TEXT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (crash_log_id) REFERENCES crash_logs(id)
+);
+
+INSERT INTO servers (server_id, server_name, server_ip, server_os, server_status)
+    VALUES ('srv-01', 'web', '10.0.0.1', 'Kylin', 'running');
+INSERT INTO system_metrics (server_id, timestamp, cpu_usage, memory_usage, disk_usage, io_read, io_write, network_in, network_out)
+    VALUES ('srv-01', 1700000000000, 1, 2, 3, 4, 5, 6, 7), ('gone', 1700000000000, 1, 2, 3, 4, 5, 6, 7);
+INSERT INTO crash_logs (server_id, log_id, timestamp, crash_type, severity, title, message)
+    VALUES ('srv-01', 1, 1700000000000, 'oom', 'high', 't', 'm');
+INSERT INTO ai_recommendations (crash_log_id, priority, action, command)
+    VALUES (1, 1, 'a', 'c'), (99, 1, 'a', 'c');
+"#;
+
+#[derive(QueryableByName)]
+struct RowCount {
+    #[diesel(sql_type = BigInt)]
+    count: i64,
+}
+
+fn count(conn: &mut SqliteConnection, table: &str) -> i64 {
+    diesel::sql_query(format!("SELECT COUNT(*) AS count FROM {table}"))
+        .get_result::<RowCount>(conn)
+        .unwrap()
+        .count
+}
+
+/// 旧数据库报告孤儿数据，升级后孤儿数据被丢弃，其余数据保留
+#[test]
+fn legacy_database_is_migrated_without_orphans() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("legacy.db").to_string_lossy().to_string();
+    SqliteConnection::establish(&path)
+        .unwrap()
+        .batch_execute(LEGACY_SCHEMA)
+        .unwrap();
+
+    let blackbox = BlackBox::new(Some(path.clone()));
+    assert_eq!(blackbox.get_schema_version().unwrap(), 0);
+    let orphans = blackbox.check_orphans().unwrap();
+    assert_eq!(orphans.count("system_metrics"), 1);
+    assert_eq!(orphans.count("ai_recommendations"), 1);
+    assert_eq!(orphans.total(), 2);
+
+    assert_eq!(blackbox.migrate_database().unwrap(), 0);
+    assert_eq!(blackbox.get_schema_version().unwrap(), SCHEMA_VERSION);
+    assert_eq!(blackbox.check_orphans().unwrap().total(), 0);
+    assert_eq!(blackbox.migrate_database().unwrap(), SCHEMA_VERSION);
+
+    let mut conn = SqliteConnection::establish(&path).unwrap();
+    assert_eq!(count(&mut conn, "servers"), 1);
+    assert_eq!(count(&mut conn, "system_metrics"), 1);
+    assert_eq!(count(&mut conn, "crash_logs"), 1);
+    assert_eq!(count(&mut conn, "ai_recommendations"), 1);
+}
+
+/// 删除服务器时关联的指标、进程、趋势、线程、崩溃日志和 AI 建议级联删除
+#[test]
+fn deleting_a_server_cascades() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("cascade.db").to_string_lossy().to_string();
+    let data_json = Path::new(env!("CARGO_MANIFEST_DIR")).join("data.json");
+    let blackbox = BlackBox::new(Some(path.clone()));
+    blackbox.init_database(true).unwrap();
+    blackbox
+        .import_json_data(data_json.to_str().unwrap(), false)
+        .unwrap();
+
+    let mut conn = SqliteConnection::establish(&path).unwrap();
+    let tables = [
+        "system_metrics",
+        "processes",
+        "process_trends",
+        "threads",
+        "crash_logs",
+        "ai_recommendations",
+    ];
+    for table in tables {
+        assert!(count(&mut conn, table) > 0, "{} 不应为空", table);
+    }
+
+    conn.batch_execute("PRAGMA foreign_keys = ON; DELETE FROM servers;")
+        .unwrap();
+    for table in tables {
+        assert_eq!(count(&mut conn, table), 0, "{} 应被级联删除", table);
+    }
+}
+
+/// 旧版本写入的大小写和别名写法在 v11 中改写为规范值，统计时合并为同一序列
+#[test]
+fn legacy_enum_spellings_are_rewritten() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir
+        .path()
+        .join("spellings.db")
+        .to_string_lossy()
+        .to_string();
+    let blackbox = BlackBox::new(Some(path.clone()));
+    blackbox.init_database(true).unwrap();
+
+    let mut conn = SqliteConnection::establish(&path).unwrap();
+    conn.batch_execute(
+        "INSERT INTO servers (server_id, server_name, server_ip, server_os, server_status)
+             VALUES ('s1', 'web', '10.0.0.1', 'Kylin', 'Online');
+         INSERT INTO processes (server_id, pid, name, user_name, status) VALUES ('s1', 1, 'init', 'root', 'Ss');
+         INSERT INTO crash_logs (server_id, log_id, timestamp, crash_type, severity, title, message) VALUES
+             ('s1', 1, 1700000000000, 'oom', 'HIGH', 't', 'm'),
+             ('s1', 2, 1700000000000, 'oom', 'high', 't', 'm'),
+             ('s1', 3, 1700000000000, 'OOM', 'high', 't', 'm');
+         PRAGMA user_version = 10;",
+    )
+    .unwrap();
+
+    assert_eq!(blackbox.migrate_database().unwrap(), 10);
+
+    let metrics = blackbox.render_metrics(false).unwrap();
+    let series: Vec<&str> = metrics
+        .lines()
+        .filter(|line| line.starts_with("blackbox_crash_logs_total{"))
+        .collect();
+    assert_eq!(
+        series,
+        vec![
+            r#"blackbox_crash_logs_total{server_id="s1",server_name="web",crash_type="oom",severity="high",resolved="false"} 3"#
+        ]
+    );
+
+    let stored = |sql: &str| {
+        diesel::sql_query(sql)
+            .get_result::<RowCount>(&mut SqliteConnection::establish(&path).unwrap())
+            .unwrap()
+            .count
+    };
+    assert_eq!(
+        stored("SELECT COUNT(*) AS count FROM servers WHERE server_status = 'running'"),
+        1
+    );
+    assert_eq!(
+        stored("SELECT COUNT(*) AS count FROM processes WHERE status = 'S'"),
+        1
+    );
+    assert_eq!(
+        stored(
+            "SELECT COUNT(*) AS count FROM crash_logs WHERE crash_type = 'oom' AND severity = 'high'"
+        ),
+        3
+    );
+}
@@ -0,0 +1,176 @@
+use blackbox::notify::{STATUS_DELIVERED, STATUS_PENDING, STATUS_RATE_LIMITED};
+use blackbox::*;
+use std::sync::mpsc;
+
+const SERVER: &str = r#"[{"serverId": "srv-01", "serverName": "web", "serverIp": "10.0.0.1", "serverOs": "Kylin", "serverStatus": "running"}]"#;
+
+fn crash_log(timestamp: i64) -> String {
+    format!(
+        r#"[{{"serverId": "srv-01", "logId": {timestamp}, "timestamp": {timestamp}, "crashType": "segfault", "severity": "high", "title": "nginx", "message": "SIGSEGV", "resolved": false}}]"#
+    )
+}
+
+/// 本地 webhook：第一次请求返回 500，之后返回 200，收到的请求体发到通道中
+fn start_webhook() -> (String, mpsc::Receiver<serde_json::Value>) {
+    let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hook", server.server_addr().to_ip().unwrap());
+    let (sender, receiver) = mpsc::channel();
+
+    std::thread::spawn(move || {
+        for (index, mut request) in server.incoming_requests().enumerate() {
+            let mut body = String::new();
+            request.as_reader().read_to_string(&mut body).unwrap();
+            let code = if index == 0 { 500 } else { 200 };
+            request.respond(tiny_http::Response::empty(code)).unwrap();
+            if code == 200 {
+                sender.send(serde_json::from_str(&body).unwrap()).unwrap();
+            }
+        }
+    });
+
+    (url, receiver)
+}
+
+/// 崩溃和告警通知经过重试送达，限流窗口内的重复事件只记录不投递
+#[test]
+fn notifications_retry_rate_limit_and_log() {
+    let dir = tempfile::tempdir().unwrap();
+    let (url, received) = start_webhook();
+    let command_output = dir.path().join("command.log");
+
+    let mut config = BlackBoxConfig::from_toml(&format!(
+        r#"
+        [notify]
+        retry_backoff_ms = 10
+
+        [[notify.webhooks]]
+        name = "ops"
+        url = "{url}"
+
+        [[notify.commands]]
+        name = "script"
+        program = "sh"
+        args = ["-c", "echo \"$BLACKBOX_EVENT $BLACKBOX_SERVER_ID $BLACKBOX_SEVERITY\" >> {}"]
+        events = ["crash"]
+        "#,
+        command_output.display()
+    ))
+    .unwrap();
+    config.database.path = Some(dir.path().join("notify.db").to_string_lossy().to_string());
+    let blackbox = BlackBox::from_config(config);
+    blackbox.init_database(true).unwrap();
+
+    blackbox
+        .smart_insert(SmartDataType::Servers, SERVER, false)
+        .unwrap();
+    blackbox
+        .smart_insert(SmartDataType::CrashLogs, &crash_log(1700000000000), false)
+        .unwrap();
+    blackbox
+        .smart_insert(SmartDataType::CrashLogs, &crash_log(1700000060000), false)
+        .unwrap();
+    blackbox
+        .smart_insert(
+            SmartDataType::Servers,
+            &SERVER.replace("running", "offline"),
+            false,
+        )
+        .unwrap();
+
+    // 崩溃通知第一次投递失败后在后台重试，不阻塞之后的告警通知，送达顺序不固定
+    let mut notifications = [received.recv().unwrap(), received.recv().unwrap()];
+    notifications.sort_by_key(|notification| notification["event"].as_str().unwrap().to_string());
+    let [alert, crash] = notifications;
+    assert_eq!(crash["event"], "crash");
+    assert_eq!(crash["title"], "nginx");
+    assert_eq!(crash["severity"], "high");
+    assert_eq!(alert["event"], "alert");
+    assert_eq!(alert["previousStatus"], "running");
+    assert_eq!(alert["status"], "offline");
+
+    blackbox.flush_notifications();
+
+    assert_eq!(
+        std::fs::read_to_string(&command_output).unwrap(),
+        "crash srv-01 high\n"
+    );
+
+    let mut log: Vec<_> = blackbox
+        .notification_log(10)
+        .unwrap()
+        .into_iter()
+        .map(|delivery| {
+            (
+                delivery.notifier,
+                delivery.event,
+                delivery.status,
+                delivery.attempts,
+            )
+        })
+        .collect();
+    log.sort();
+    let entry = |notifier: &str, event: &str, status: &str, attempts| {
+        (
+            notifier.to_string(),
+            event.to_string(),
+            status.to_string(),
+            attempts,
+        )
+    };
+    assert_eq!(
+        log,
+        vec![
+            entry("ops", "alert", STATUS_DELIVERED, 1),
+            entry("ops", "crash", STATUS_DELIVERED, 2),
+            entry("ops", "crash", STATUS_RATE_LIMITED, 0),
+            entry("script", "crash", STATUS_DELIVERED, 1),
+            entry("script", "crash", STATUS_RATE_LIMITED, 0),
+        ]
+    );
+}
+
+/// 通知在后台投递：webhook 响应缓慢时写入立即返回，通知先以 pending 记录入队
+#[test]
+fn slow_targets_do_not_block_inserts() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hook", server.server_addr().to_ip().unwrap());
+    std::thread::spawn(move || {
+        for request in server.incoming_requests() {
+            std::thread::sleep(std::time::Duration::from_millis(1500));
+            let _ = request.respond(tiny_http::Response::empty(200));
+        }
+    });
+
+    let mut config = BlackBoxConfig::from_toml(&format!(
+        "[[notify.webhooks]]\nname = \"slow\"\nurl = \"{url}\"\n"
+    ))
+    .unwrap();
+    config.database.path = Some(dir.path().join("notify.db").to_string_lossy().to_string());
+    let blackbox = BlackBox::from_config(config);
+    blackbox.init_database(true).unwrap();
+    blackbox
+        .smart_insert(SmartDataType::Servers, SERVER, false)
+        .unwrap();
+
+    let started = std::time::Instant::now();
+    blackbox
+        .smart_insert(SmartDataType::CrashLogs, &crash_log(1700000000000), false)
+        .unwrap();
+    assert!(started.elapsed() < std::time::Duration::from_secs(1));
+    assert_eq!(
+        blackbox.notification_log(1).unwrap()[0].status,
+        STATUS_PENDING
+    );
+
+    blackbox.flush_notifications();
+    let delivery = &blackbox.notification_log(1).unwrap()[0];
+    assert_eq!(
+        (
+            delivery.status.as_str(),
+            delivery.attempts,
+            delivery.next_attempt_at
+        ),
+        (STATUS_DELIVERED, 1, None)
+    );
+}
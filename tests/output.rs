@@ -0,0 +1,202 @@
+use blackbox::*;
+use std::path::Path;
+use std::process::Command;
+
+/// 运行命令行，返回标准输出
+fn run(db: &Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_blackbox"))
+        .arg("--db")
+        .arg(db)
+        .args(args)
+        .env_remove("BLACKBOX_CONFIG")
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// 写入一台服务器、一条指标、一个进程和一条未解决的崩溃日志
+fn populate(db: &Path) -> i64 {
+    let now = chrono::Utc::now().timestamp_millis();
+    let blackbox = BlackBox::new(Some(db.to_string_lossy().to_string()));
+    blackbox.init_database(true).unwrap();
+    blackbox
+        .smart_insert(
+            SmartDataType::Combined,
+            &format!(
+                r#"{{
+                    "process": [{{
+                        "serverId": "srv-01", "serverName": "web", "serverIp": "10.0.0.1", "serverOs": "Kylin", "serverStatus": "running",
+                        "pid": 42, "name": "worker", "userName": "root", "status": "S", "timestamp": {now},
+                        "trend": [{{"cpuUsage": 1.0, "memoryUsage": 2.0, "threadCount": 5}}], "threads": []
+                    }}],
+                    "metrics": [{{"serverId": "srv-01", "timestamp": {now}, "cpuUsage": 10.0, "memoryUsage": 20.0, "diskUsage": 30.0,
+                                  "ioRead": 1.0, "ioWrite": 2.0, "networkIn": 3.0, "networkOut": 4.0}}]
+                }}"#
+            ),
+            false,
+        )
+        .unwrap();
+    blackbox
+        .smart_insert(
+            SmartDataType::CrashLogs,
+            &format!(
+                r#"[{{"serverId": "srv-01", "logId": 1, "timestamp": {now}, "crashType": "segfault", "severity": "high", "title": "nginx", "message": "SIGSEGV", "resolved": false}}]"#
+            ),
+            false,
+        )
+        .unwrap();
+    now
+}
+
+/// CSV 输出解析为表头和各行
+fn csv_rows(output: &str) -> (Vec<String>, Vec<Vec<String>>) {
+    let mut reader = csv::Reader::from_reader(output.as_bytes());
+    let headers = reader.headers().unwrap().iter().map(String::from).collect();
+    let rows = reader
+        .records()
+        .map(|record| record.unwrap().iter().map(String::from).collect())
+        .collect();
+    (headers, rows)
+}
+
+/// query 按 --output 输出 JSON、YAML、CSV 或表格，YAML 与 JSON 内容相同
+#[test]
+fn query_output_formats() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("output.db");
+    let now = populate(&db);
+
+    let json: serde_json::Value =
+        serde_json::from_str(&run(&db, &["query", "-o", "json"])).unwrap();
+    let servers = json.as_array().unwrap();
+    assert_eq!(servers.len(), 1);
+    assert_eq!(servers[0]["server"]["server_id"], "srv-01");
+    assert_eq!(servers[0]["metrics"][0]["timestamp"], now);
+    assert_eq!(servers[0]["processes"][0]["process"]["name"], "worker");
+    assert_eq!(servers[0]["processes"][0]["trends"][0]["thread_count"], 5);
+    assert_eq!(servers[0]["crashes"][0]["crash_log"]["title"], "nginx");
+
+    let yaml: serde_json::Value =
+        serde_yaml::from_str(&run(&db, &["query", "--output", "yaml"])).unwrap();
+    assert_eq!(yaml, json);
+
+    let (headers, rows) = csv_rows(&run(&db, &["query", "-o", "csv"]));
+    assert_eq!(
+        headers,
+        [
+            "server_id",
+            "server_name",
+            "server_ip",
+            "server_status",
+            "metrics_count",
+            "latest_metric_time",
+            "avg_cpu_usage",
+            "avg_memory_usage",
+            "avg_disk_usage",
+            "processes_count",
+            "crashes_count",
+            "unresolved_crashes_count",
+        ]
+    );
+    assert_eq!(
+        rows,
+        vec![vec![
+            "srv-01".to_string(),
+            "web".to_string(),
+            "10.0.0.1".to_string(),
+            "running".to_string(),
+            "1".to_string(),
+            now.to_string(),
+            "10.0".to_string(),
+            "20.0".to_string(),
+            "30.0".to_string(),
+            "1".to_string(),
+            "1".to_string(),
+            "1".to_string(),
+        ]]
+    );
+
+    // 筛选不到服务器时机器可读格式输出空结果
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&run(
+            &db,
+            &["query", "-s", "missing", "-o", "json"]
+        ))
+        .unwrap(),
+        serde_json::json!([])
+    );
+
+    let table = run(&db, &["query", "-o", "table"]);
+    assert!(table.contains("web (10.0.0.1)"), "{}", table);
+    assert!(serde_json::from_str::<serde_json::Value>(&table).is_err());
+}
+
+/// stats 按 --output 输出，未指定时使用配置的 output.format
+#[test]
+fn stats_output_formats() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("output.db");
+    let now = populate(&db);
+
+    let json: serde_json::Value =
+        serde_json::from_str(&run(&db, &["stats", "-o", "json"])).unwrap();
+    assert_eq!(json["server_count"], 1);
+    let server = &json["servers"][0];
+    assert_eq!(server["server"]["server_id"], "srv-01");
+    assert_eq!(server["metrics_count"], 1);
+    assert_eq!(server["processes_count"], 1);
+    assert_eq!(server["crashes_count"], 1);
+    assert_eq!(server["latest_metric_time"], now);
+
+    let yaml: serde_json::Value =
+        serde_yaml::from_str(&run(&db, &["stats", "-o", "yaml"])).unwrap();
+    assert_eq!(yaml, json);
+
+    let (headers, rows) = csv_rows(&run(&db, &["stats", "-o", "csv"]));
+    assert_eq!(
+        headers,
+        [
+            "server_id",
+            "server_name",
+            "server_status",
+            "metrics_count",
+            "processes_count",
+            "crashes_count",
+            "latest_metric_time",
+        ]
+    );
+    assert_eq!(
+        rows,
+        vec![vec![
+            "srv-01".to_string(),
+            "web".to_string(),
+            "running".to_string(),
+            "1".to_string(),
+            "1".to_string(),
+            "1".to_string(),
+            now.to_string(),
+        ]]
+    );
+
+    let table = run(&db, &["stats"]);
+    assert!(table.contains("数据库统计信息"), "{}", table);
+
+    let config = dir.path().join("blackbox.toml");
+    std::fs::write(&config, "[output]\nformat = \"json\"\n").unwrap();
+    let configured: serde_json::Value =
+        serde_json::from_str(&run(&db, &["--config", config.to_str().unwrap(), "stats"])).unwrap();
+    assert_eq!(configured, json);
+    // 命令行参数优先于配置
+    assert!(
+        run(
+            &db,
+            &["--config", config.to_str().unwrap(), "stats", "-o", "table"]
+        )
+        .contains("数据库统计信息")
+    );
+}
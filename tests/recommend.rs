@@ -0,0 +1,102 @@
+use blackbox::*;
+
+const SERVER: &str = r#"[{"serverId": "srv-01", "serverName": "web", "serverIp": "10.0.0.1", "serverOs": "Kylin", "serverStatus": "running"}]"#;
+
+const CRASH_LOG: &str = r#"[{"serverId": "srv-01", "logId": 1, "timestamp": 1700000000000, "crashType": "segmentation_fault", "severity": "high",
+    "title": "nginx", "message": "segfault", "resolved": false, "aiSummary": "采集端的分析",
+    "recommendations": [
+        {"priority": 2, "action": "查看 core dump", "command": "coredumpctl info nginx"},
+        {"priority": 1, "action": "检查配置", "command": "nginx -t"}
+    ]}]"#;
+
+const UPDATED_CRASH_LOG: &str = r#"[{"serverId": "srv-01", "logId": 1, "timestamp": 1700000000000, "crashType": "segmentation_fault", "severity": "high",
+    "title": "nginx", "message": "segfault", "resolved": false, "aiSummary": "采集端的分析",
+    "recommendations": [{"action": "升级 nginx"}, {"action": "检查配置", "command": "nginx -t"}]}]"#;
+
+fn actions(recommendations: &[AiRecommendation]) -> Vec<(i32, &str)> {
+    recommendations
+        .iter()
+        .map(|rec| (rec.priority, rec.action.as_str()))
+        .collect()
+}
+
+/// 插入时写入和替换建议，增删、排序后重新编号，标记执行结果
+#[test]
+fn recommendations_are_managed_in_priority_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let blackbox = BlackBox::new(Some(
+        dir.path()
+            .join("recommend.db")
+            .to_string_lossy()
+            .to_string(),
+    ));
+    blackbox.init_database(true).unwrap();
+    blackbox
+        .smart_insert(SmartDataType::Servers, SERVER, false)
+        .unwrap();
+    blackbox
+        .smart_insert(SmartDataType::CrashLogs, CRASH_LOG, false)
+        .unwrap();
+
+    let crash_log_id = blackbox.list_crash_logs(&Default::default()).unwrap()[0].id;
+    let inserted = blackbox.crash_recommendations(crash_log_id).unwrap();
+    assert_eq!(
+        actions(&inserted),
+        vec![(1, "检查配置"), (2, "查看 core dump")]
+    );
+
+    blackbox
+        .smart_insert(SmartDataType::CrashLogs, UPDATED_CRASH_LOG, false)
+        .unwrap();
+    let replaced = blackbox.crash_recommendations(crash_log_id).unwrap();
+    assert_eq!(actions(&replaced), vec![(1, "升级 nginx"), (2, "检查配置")]);
+
+    let restart = SmartRecommendation {
+        priority: Some(1),
+        action: "重启 nginx".to_string(),
+        command: "systemctl restart nginx".to_string(),
+    };
+    let added = blackbox
+        .add_crash_recommendation(crash_log_id, &restart)
+        .unwrap();
+    assert_eq!(added.priority, 1);
+    let current = blackbox.crash_recommendations(crash_log_id).unwrap();
+    assert_eq!(
+        actions(&current),
+        vec![(1, "重启 nginx"), (2, "升级 nginx"), (3, "检查配置")]
+    );
+
+    let ids: Vec<i32> = current.iter().rev().map(|rec| rec.id).collect();
+    let reordered = blackbox
+        .reorder_crash_recommendations(crash_log_id, &ids)
+        .unwrap();
+    assert_eq!(
+        actions(&reordered),
+        vec![(1, "检查配置"), (2, "升级 nginx"), (3, "重启 nginx")]
+    );
+    let error = blackbox
+        .reorder_crash_recommendations(crash_log_id, &ids[..2])
+        .unwrap_err();
+    assert!(matches!(error, BlackBoxError::Validation { .. }));
+
+    let applied = blackbox
+        .mark_recommendation_applied(ids[0], true, Some("重启后恢复"))
+        .unwrap();
+    assert!(applied.applied && applied.applied_at.is_some());
+    assert_eq!(applied.outcome.as_deref(), Some("重启后恢复"));
+    let unapplied = blackbox
+        .mark_recommendation_applied(ids[0], false, None)
+        .unwrap();
+    assert!(!unapplied.applied && unapplied.applied_at.is_none() && unapplied.outcome.is_none());
+
+    blackbox.remove_crash_recommendation(ids[1]).unwrap();
+    let remaining = blackbox.crash_recommendations(crash_log_id).unwrap();
+    assert_eq!(
+        actions(&remaining),
+        vec![(1, "检查配置"), (2, "重启 nginx")]
+    );
+    assert!(matches!(
+        blackbox.remove_crash_recommendation(ids[1]).unwrap_err(),
+        BlackBoxError::NotFound { .. }
+    ));
+}
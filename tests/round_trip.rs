@@ -0,0 +1,114 @@
+use blackbox::{BlackBox, BlackBoxError, SmartDataType};
+use std::path::Path;
+
+fn open(dir: &Path, name: &str) -> BlackBox {
+    let path = dir.join(name).to_string_lossy().to_string();
+    let blackbox = BlackBox::new(Some(path));
+    blackbox.init_database(true).unwrap();
+    blackbox
+}
+
+fn read_json(path: &Path) -> serde_json::Value {
+    serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+}
+
+/// data.json 导入后无损导出，再导入到新数据库，两边的每一行都应完全一致
+#[test]
+fn lossless_export_import_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_json = Path::new(env!("CARGO_MANIFEST_DIR")).join("data.json");
+
+    let source = open(dir.path(), "source.db");
+    source
+        .import_json_data(data_json.to_str().unwrap(), false)
+        .unwrap();
+    source
+        .set_server_labels("ukui-server-01", &[("env".to_string(), "prod".to_string())])
+        .unwrap();
+    let crash_log_id = source.list_crash_logs(&Default::default()).unwrap()[0].id;
+    source
+        .annotate_crash_log(crash_log_id, "alice", "已通知负责人")
+        .unwrap();
+
+    // 覆盖旧格式会丢失的内容：空的堆栈和 AI 字段，以及被更新过的行 (updated_at)
+    let crash_log = r#"[{
+        "serverId": "ukui-server-01", "logId": 9001, "timestamp": 1765700000000,
+        "crashType": "oom", "severity": "low", "title": "t", "message": "m",
+        "stackTrace": null, "resolved": false, "aiSummary": null, "aiAnalysis": null
+    }]"#;
+    source
+        .smart_insert(SmartDataType::CrashLogs, crash_log, false)
+        .unwrap();
+    let updated = crash_log.replace(r#""message": "m""#, r#""message": "m2""#);
+    source
+        .smart_insert(SmartDataType::CrashLogs, &updated, false)
+        .unwrap();
+
+    let first = dir.path().join("first.json");
+    source
+        .export_lossless(first.to_str().unwrap(), true)
+        .unwrap();
+
+    let target = open(dir.path(), "target.db");
+    target
+        .import_json_data(first.to_str().unwrap(), false)
+        .unwrap();
+
+    let second = dir.path().join("second.json");
+    target
+        .export_lossless(second.to_str().unwrap(), true)
+        .unwrap();
+
+    let exported = read_json(&first);
+    assert_eq!(exported, read_json(&second));
+
+    for table in [
+        "servers",
+        "serverLabels",
+        "systemMetrics",
+        "processes",
+        "processTrends",
+        "threads",
+        "crashLogs",
+        "aiRecommendations",
+        "crashEvents",
+    ] {
+        assert!(
+            !exported[table].as_array().unwrap().is_empty(),
+            "{} 不应为空",
+            table
+        );
+    }
+
+    let crash_logs = exported["crashLogs"].as_array().unwrap();
+    let oom = crash_logs.iter().find(|log| log["log_id"] == 9001).unwrap();
+    assert!(oom["stack_trace"].is_null());
+    assert!(oom["ai_summary"].is_null());
+    assert!(oom["updated_at"].is_string());
+    assert_eq!(oom["message"], "m2");
+}
+
+/// 无损导入按原 id 写回，不能导入到已有数据的数据库
+#[test]
+fn lossless_import_requires_empty_database() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_json = Path::new(env!("CARGO_MANIFEST_DIR")).join("data.json");
+
+    let blackbox = open(dir.path(), "source.db");
+    blackbox
+        .import_json_data(data_json.to_str().unwrap(), false)
+        .unwrap();
+
+    let export = dir.path().join("export.json");
+    blackbox
+        .export_lossless(export.to_str().unwrap(), false)
+        .unwrap();
+
+    let error = blackbox
+        .import_json_data(export.to_str().unwrap(), false)
+        .unwrap_err();
+    assert!(matches!(error, BlackBoxError::Conflict { .. }));
+    blackbox
+        .import_json_data(export.to_str().unwrap(), true)
+        .unwrap();
+}
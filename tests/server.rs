@@ -0,0 +1,178 @@
+use blackbox::server::MAX_BODY;
+use blackbox::{BlackBox, SmartDataType};
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+/// 发送一个只有请求头的 POST，返回应答的状态行
+fn post_status(addr: &str, path: &str, content_length: u64) -> String {
+    for _ in 0..50 {
+        if let Ok(mut stream) = TcpStream::connect(addr) {
+            write!(stream, "POST {path} HTTP/1.1\r\nHost: {addr}\r\nContent-Length: {content_length}\r\nConnection: close\r\n\r\n")
+                .unwrap();
+            let mut response = String::new();
+            let _ = stream.read_to_string(&mut response);
+            return response.lines().next().unwrap_or_default().to_string();
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    panic!("服务没有启动");
+}
+
+/// 发送 GET 请求，返回应答的状态行、Content-Type 和正文
+fn get(addr: &str, path: &str, accept: Option<&str>) -> (String, String, String) {
+    for _ in 0..50 {
+        if let Ok(mut stream) = TcpStream::connect(addr) {
+            let accept = accept
+                .map(|accept| format!("Accept: {accept}\r\n"))
+                .unwrap_or_default();
+            write!(
+                stream,
+                "GET {path} HTTP/1.1\r\nHost: {addr}\r\n{accept}Connection: close\r\n\r\n"
+            )
+            .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            let (head, body) = response.split_once("\r\n\r\n").unwrap();
+            let content_type = head
+                .lines()
+                .find_map(|line| line.strip_prefix("Content-Type: "))
+                .unwrap_or_default();
+            return (
+                head.lines().next().unwrap().to_string(),
+                content_type.to_string(),
+                body.to_string(),
+            );
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    panic!("服务没有启动");
+}
+
+/// 在空闲端口上启动服务，返回地址
+fn start(path: String) -> String {
+    let addr = format!("127.0.0.1:{}", free_port());
+    let serve_addr = addr.clone();
+    std::thread::spawn(move || blackbox::server::serve(&BlackBox::new(Some(path)), &serve_addr));
+    addr
+}
+
+/// /metrics 输出各服务器最新的系统指标、进程趋势和分组的崩溃日志数量
+#[test]
+fn metrics_endpoint_exposes_latest_snapshot() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("metrics.db").to_string_lossy().to_string();
+    let blackbox = BlackBox::new(Some(path.clone()));
+    blackbox.init_database(true).unwrap();
+
+    let now = chrono::Utc::now().timestamp_millis();
+    blackbox
+        .smart_insert(
+            SmartDataType::Combined,
+            &format!(
+                r#"{{
+                    "process": [{{
+                        "serverId": "srv-01", "serverName": "web \"a\"", "serverIp": "10.0.0.1", "serverOs": "Kylin", "serverStatus": "running",
+                        "pid": 42, "name": "worker", "userName": "root", "status": "S", "timestamp": {now},
+                        "trend": [{{"cpuUsage": 1.5, "memoryUsage": 2.5, "threadCount": 7}}], "threads": []
+                    }}],
+                    "metrics": [
+                        {{"serverId": "srv-01", "timestamp": {}, "cpuUsage": 90.0, "memoryUsage": 20.0, "diskUsage": 30.0, "ioRead": 1.0, "ioWrite": 2.0, "networkIn": 3.0, "networkOut": 4.0}},
+                        {{"serverId": "srv-01", "timestamp": {now}, "cpuUsage": 12.5, "memoryUsage": 20.0, "diskUsage": 30.0, "ioRead": 1.0, "ioWrite": 2.0, "networkIn": 3.0, "networkOut": 4.0}}
+                    ]
+                }}"#,
+                now - 60_000
+            ),
+            false,
+        )
+        .unwrap();
+    blackbox
+        .smart_insert(
+            SmartDataType::CrashLogs,
+            &format!(
+                r#"[
+                    {{"serverId": "srv-01", "logId": 1, "timestamp": {now}, "crashType": "segfault", "severity": "high", "title": "nginx", "message": "SIGSEGV", "resolved": false}},
+                    {{"serverId": "srv-01", "logId": 2, "timestamp": {}, "crashType": "segfault", "severity": "high", "title": "nginx", "message": "SIGSEGV", "resolved": false}},
+                    {{"serverId": "srv-01", "logId": 3, "timestamp": {}, "crashType": "oom", "severity": "critical", "title": "java", "message": "oom", "resolved": true}}
+                ]"#,
+                now + 1,
+                now + 2
+            ),
+            false,
+        )
+        .unwrap();
+
+    let addr = start(path);
+    let (status, content_type, body) = get(&addr, "/metrics", None);
+    assert!(status.contains("200"), "{}", status);
+    assert_eq!(content_type, "text/plain; version=0.0.4; charset=utf-8");
+    let server = r#"server_id="srv-01",server_name="web \"a\"""#;
+    for line in [
+        "# TYPE blackbox_server_info gauge".to_string(),
+        format!(
+            r#"blackbox_server_info{{{server},server_ip="10.0.0.1",server_os="Kylin",status="running"}} 1"#
+        ),
+        "# TYPE blackbox_system_cpu_usage_percent gauge".to_string(),
+        format!("blackbox_system_cpu_usage_percent{{{server}}} 12.5"),
+        format!(
+            "blackbox_system_metric_timestamp_seconds{{{server}}} {}",
+            now as f64 / 1000.0
+        ),
+        format!(r#"blackbox_process_threads{{{server},pid="42",process="worker",user="root"}} 7"#),
+        "# TYPE blackbox_crash_logs_total counter".to_string(),
+        format!(
+            r#"blackbox_crash_logs_total{{{server},crash_type="segmentation_fault",severity="high",resolved="false"}} 2"#
+        ),
+        format!(
+            r#"blackbox_crash_logs_total{{{server},crash_type="oom",severity="critical",resolved="true"}} 1"#
+        ),
+    ] {
+        assert!(body.lines().any(|l| l == line), "缺少 {}\n{}", line, body);
+    }
+    // 只输出最新一条系统指标
+    assert_eq!(
+        body.lines()
+            .filter(|l| l.starts_with("blackbox_system_cpu_usage_percent{"))
+            .count(),
+        1
+    );
+    assert!(!body.contains("# EOF"));
+
+    let (_, content_type, body) = get(
+        &addr,
+        "/metrics",
+        Some("application/openmetrics-text; version=1.0.0"),
+    );
+    assert_eq!(
+        content_type,
+        "application/openmetrics-text; version=1.0.0; charset=utf-8"
+    );
+    assert!(body.contains("# TYPE blackbox_server info\n"));
+    assert!(body.contains("# TYPE blackbox_crash_logs counter\n"));
+    assert!(body.contains("\nblackbox_crash_logs_total{"));
+    assert!(body.ends_with("# EOF\n"));
+
+    assert!(get(&addr, "/missing", None).0.contains("404"));
+}
+
+/// 声明的请求体超过上限时直接返回 413，不读取请求体
+#[test]
+fn oversized_bodies_are_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("serve.db").to_string_lossy().to_string();
+    BlackBox::new(Some(path.clone()))
+        .init_database(true)
+        .unwrap();
+
+    let addr = start(path);
+    assert!(post_status(&addr, "/write", MAX_BODY + 1).contains("413"));
+    assert!(post_status(&addr, "/ingest/servers", MAX_BODY + 1).contains("413"));
+}
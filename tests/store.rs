@@ -0,0 +1,152 @@
+use blackbox::*;
+
+const SERVERS: &str = r#"[{"serverId": "srv-01", "serverName": "web", "serverIp": "10.0.0.1", "serverOs": "Kylin", "serverStatus": "online"}]"#;
+
+const METRICS: &str = r#"[
+    {"serverId": "srv-01", "timestamp": 1700000000000, "cpuUsage": 10.0, "memoryUsage": 20.0, "diskUsage": 30.0, "ioRead": 1.0, "ioWrite": 2.0, "networkIn": 3.0, "networkOut": 4.0},
+    {"serverId": "srv-01", "timestamp": 1700000060000, "cpuUsage": 11.0, "memoryUsage": 21.0, "diskUsage": 31.0, "ioRead": 1.0, "ioWrite": 2.0, "networkIn": 3.0, "networkOut": 4.0},
+    {"serverId": "srv-01", "timestamp": 1700000000000, "cpuUsage": 55.0, "memoryUsage": 20.0, "diskUsage": 30.0, "ioRead": 1.0, "ioWrite": 2.0, "networkIn": 3.0, "networkOut": 4.0},
+    {"serverId": "missing", "timestamp": 1700000000000, "cpuUsage": 1.0, "memoryUsage": 1.0, "diskUsage": 1.0, "ioRead": 1.0, "ioWrite": 1.0, "networkIn": 1.0, "networkOut": 1.0}
+]"#;
+
+const CRASH_LOGS: &str = r#"[{"serverId": "srv-01", "logId": 1, "timestamp": 1700000000000, "crashType": "segfault", "severity": "HIGH", "title": "nginx", "message": "SIGSEGV", "resolved": false}]"#;
+
+/// 对同一个存储依次写入服务器、指标和崩溃日志，返回各批的插入结果
+fn insert_all<S: Store + ?Sized>(store: &mut S) -> Vec<InsertResult> {
+    vec![
+        SmartInsertService::insert_servers(
+            store,
+            serde_json::from_str(SERVERS).unwrap(),
+            &InsertOptions::new(false),
+        )
+        .unwrap(),
+        SmartInsertService::insert_system_metrics(
+            store,
+            serde_json::from_str(METRICS).unwrap(),
+            &InsertOptions::new(true),
+        )
+        .unwrap(),
+        SmartInsertService::insert_crash_logs(
+            store,
+            serde_json::from_str(CRASH_LOGS).unwrap(),
+            &InsertOptions::new(false),
+        )
+        .unwrap(),
+        SmartInsertService::insert_servers(
+            store,
+            serde_json::from_str(SERVERS).unwrap(),
+            &InsertOptions::new(false),
+        )
+        .unwrap(),
+    ]
+}
+
+/// 内存存储与 SQLite 对同样的输入给出相同的插入结果和查询结果
+#[test]
+fn memory_store_matches_sqlite() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("store.db").to_string_lossy().to_string();
+    BlackBox::new(Some(path.clone()))
+        .init_database(true)
+        .unwrap();
+    let mut sqlite = establish_connection_with_url(Some(&path)).unwrap();
+    let mut memory = MemoryStore::new();
+
+    assert_eq!(insert_all(&mut memory), insert_all(&mut sqlite));
+
+    let stores: [&mut dyn Store; 2] = [&mut memory, &mut sqlite];
+    let snapshots: Vec<_> = stores
+        .into_iter()
+        .map(|store| {
+            let metrics = store.get_metrics_by_server("srv-01", None).unwrap();
+            let crash_logs = store.get_crash_logs_by_server("srv-01").unwrap();
+            let server = store.get_server_by_id("srv-01").unwrap().unwrap();
+            (
+                server.server_status,
+                metrics
+                    .iter()
+                    .map(|m| (m.timestamp, m.cpu_usage))
+                    .collect::<Vec<_>>(),
+                crash_logs
+                    .iter()
+                    .map(|l| (l.crash_type.clone(), l.severity.clone()))
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .collect();
+
+    assert_eq!(snapshots[0], snapshots[1]);
+    assert_eq!(
+        snapshots[0].1,
+        vec![(1700000060000, 11.0), (1700000000000, 55.0)]
+    );
+}
+
+/// 与外键约束一致：服务器不存在时不能写入子表数据
+#[test]
+fn memory_store_requires_server() {
+    let mut memory = MemoryStore::new();
+    let metrics: Vec<SmartSystemMetric> = serde_json::from_str(METRICS).unwrap();
+
+    let error =
+        SmartInsertService::insert_system_metrics(&mut memory, metrics, &InsertOptions::new(false))
+            .unwrap_err();
+    assert!(matches!(error, BlackBoxError::NotFound { .. }));
+}
+
+/// 按 id 查询崩溃日志，按 stack_trace 标记检查线程异常：两种存储结果一致，标记区分大小写
+#[test]
+fn crash_log_lookups_match_sqlite() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("store.db").to_string_lossy().to_string();
+    BlackBox::new(Some(path.clone()))
+        .init_database(true)
+        .unwrap();
+    let mut sqlite = establish_connection_with_url(Some(&path)).unwrap();
+    let mut memory = MemoryStore::new();
+
+    let thread_exception = r#"[{"serverId": "srv-01", "logId": 2, "timestamp": 1700000100000, "crashType": "thread_exception", "severity": "HIGH", "title": "java", "message": "too many threads", "stackTrace": "PROCESS_NAME: java_%\n", "resolved": false}]"#;
+    let stores: [&mut dyn Store; 2] = [&mut memory, &mut sqlite];
+    for store in stores {
+        insert_all(store);
+        SmartInsertService::insert_crash_logs(
+            store,
+            serde_json::from_str(thread_exception).unwrap(),
+            &InsertOptions::new(false),
+        )
+        .unwrap();
+
+        let latest = &store.get_crash_logs_by_server("srv-01").unwrap()[0];
+        assert_eq!(
+            store.get_crash_log_by_id(latest.id).unwrap().unwrap().title,
+            "java"
+        );
+        assert!(
+            store
+                .get_crash_log_by_id(latest.id + 100)
+                .unwrap()
+                .is_none()
+        );
+
+        assert!(
+            store
+                .thread_exception_crash_log_exists("srv-01", "PROCESS_NAME: java_%")
+                .unwrap()
+        );
+        assert!(
+            !store
+                .thread_exception_crash_log_exists("srv-01", "PROCESS_NAME: JAVA_%")
+                .unwrap()
+        );
+        assert!(
+            !store
+                .thread_exception_crash_log_exists("srv-01", "PROCESS_NAME: javax")
+                .unwrap()
+        );
+        assert!(
+            !store
+                .thread_exception_crash_log_exists("srv-02", "PROCESS_NAME: java_%")
+                .unwrap()
+        );
+    }
+}